@@ -20,10 +20,12 @@ use arroyo_connectors::{connector_for_type, ErasedConnector};
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_rpc::types::{
     ConfluentSchema, ConfluentSchemaQueryParams, ConnectionProfile, ConnectionSchema,
-    ConnectionTable, ConnectionTableCollection, ConnectionTablePost, PaginationQueryParams,
-    SchemaDefinition,
+    ConnectionTable, ConnectionTableCollection, ConnectionTablePost, ConnectionType, Format,
+    InferredSchema, JsonFormat, PaginationQueryParams, SchemaDefinition, SchemaInferencePost,
 };
+use arroyo_sql::avro::convert_avro_schema;
 use arroyo_sql::json_schema::convert_json_schema;
+use arroyo_sql::protobuf::{compile_proto_schema, convert_protobuf_schema};
 use arroyo_sql::types::{StructField, TypeDef};
 
 use crate::rest::AppState;
@@ -31,6 +33,7 @@ use crate::rest_utils::{
     authenticate, bad_request, client, log_and_map, not_found, paginate_results, required_field,
     validate_pagination_params, ApiError, BearerAuth, ErrorResp,
 };
+use crate::schema_inference::infer_json_schema;
 use crate::{
     handle_db_error, handle_delete,
     queries::api_queries::{self, DbConnectionTable},
@@ -166,6 +169,89 @@ pub(crate) async fn test_connection_table(
     Ok(Sse::new(ReceiverStream::new(rx)))
 }
 
+const DEFAULT_INFERENCE_SAMPLES: u32 = 100;
+const MAX_INFERENCE_SAMPLES: u32 = 1000;
+
+/// Infer a Connection Schema by sampling messages from a source table
+#[utoipa::path(
+    post,
+    path = "/v1/connection_tables/schemas/infer",
+    tag = "connection_tables",
+    request_body = SchemaInferencePost,
+    responses(
+        (status = 200, description = "Inferred schema", body = InferredSchema),
+    ),
+)]
+pub(crate) async fn infer_schema(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    WithRejection(Json(req), _): WithRejection<Json<SchemaInferencePost>, ApiError>,
+) -> Result<Json<InferredSchema>, ErrorResp> {
+    let client = client(&state.pool).await?;
+    let auth_data = authenticate(&state.pool, bearer_auth).await?;
+
+    let (connector, _, profile, schema) =
+        get_and_validate_connector(&req.table, &auth_data, &client).await?;
+
+    let table_type = connector
+        .table_type(&profile, &req.table.config)
+        .map_err(|e| bad_request(format!("Failed to parse config: {:?}", e)))?;
+
+    if !matches!(table_type, ConnectionType::Source) {
+        return Err(bad_request(
+            "Schemas can only be inferred for source tables".to_string(),
+        ));
+    }
+
+    let format = match schema.as_ref().and_then(|s| s.format.as_ref()) {
+        None => JsonFormat::default(),
+        Some(Format::Json(json)) if !json.unstructured => json.clone(),
+        Some(_) => {
+            return Err(bad_request(
+                "Schema inference is only supported for structured JSON tables".to_string(),
+            ))
+        }
+    };
+
+    let count = req
+        .samples
+        .unwrap_or(DEFAULT_INFERENCE_SAMPLES)
+        .clamp(1, MAX_INFERENCE_SAMPLES) as usize;
+
+    let (tx, mut rx) = channel(8);
+
+    connector
+        .sample(&profile, &req.table.config, count, tx)
+        .map_err(|e| bad_request(format!("Failed to sample messages: {}", e)))?;
+
+    let mut messages = vec![];
+    while let Some(msg) = rx.recv().await {
+        messages.push(msg.map_err(|e| bad_request(format!("Failed to sample messages: {}", e)))?);
+    }
+
+    let inference = infer_json_schema(&format, &messages).map_err(bad_request)?;
+
+    let format = JsonFormat {
+        timestamp_format: inference
+            .timestamp_format
+            .unwrap_or(format.timestamp_format),
+        ..format
+    };
+
+    Ok(Json(InferredSchema {
+        schema: ConnectionSchema {
+            format: Some(Format::Json(format)),
+            struct_name: None,
+            fields: inference.fields,
+            definition: None,
+            bad_data: schema.and_then(|s| s.bad_data),
+        },
+        samples: inference.samples as u32,
+        conflicts: inference.conflicts,
+        warnings: inference.warnings,
+    }))
+}
+
 fn get_connection_profile(
     c: &DbConnectionTable,
     connector: &dyn ErasedConnector,
@@ -423,15 +509,31 @@ pub(crate) fn expand_schema(
         let fields = match d {
             SchemaDefinition::JsonSchema(json) => convert_json_schema(name, &json)
                 .map_err(|e| bad_request(format!("Invalid json-schema: {}", e)))?,
-            SchemaDefinition::ProtobufSchema(_) => {
-                return Err(bad_request(
-                    "Protobuf schemas are not yet supported".to_string(),
-                ))
+            SchemaDefinition::ProtobufSchema(proto) => {
+                let Some(Format::Protobuf(format)) = &mut schema.format else {
+                    return Err(bad_request(
+                        "Protobuf schemas can only be used with the protobuf format".to_string(),
+                    ));
+                };
+
+                // the compiled schema is needed at runtime to decode messages
+                let compiled = compile_proto_schema(&proto).map_err(bad_request)?;
+                let fields = convert_protobuf_schema(&compiled, format.message_name.as_deref())
+                    .map_err(|e| bad_request(format!("Invalid protobuf schema: {}", e)))?;
+                format.compiled_schema = Some(compiled);
+
+                fields
             }
-            SchemaDefinition::AvroSchema(_) => {
-                return Err(bad_request(
-                    "Avro schemas are not yet supported".to_string(),
-                ))
+            SchemaDefinition::AvroSchema(avro) => {
+                let fields = convert_avro_schema(name, &avro)
+                    .map_err(|e| bad_request(format!("Invalid avro schema: {}", e)))?;
+
+                // the avro schema is also needed at runtime to decode messages
+                if let Some(Format::Avro(format)) = &mut schema.format {
+                    format.schema = Some(avro.clone());
+                }
+
+                fields
             }
             SchemaDefinition::RawSchema(_) => vec![StructField::new(
                 "value".to_string(),
@@ -474,6 +576,20 @@ pub(crate) async fn test_schema(
                 Ok(())
             }
         }
+        SchemaDefinition::ProtobufSchema(schema) => {
+            if let Err(e) = compile_proto_schema(&schema) {
+                Err(bad_request(e))
+            } else {
+                Ok(())
+            }
+        }
+        SchemaDefinition::AvroSchema(schema) => {
+            if let Err(e) = convert_avro_schema(&"test", &schema) {
+                Err(bad_request(e))
+            } else {
+                Ok(())
+            }
+        }
         _ => {
             // TODO: add testing for other schema types
             Ok(())
@@ -544,27 +660,14 @@ pub(crate) async fn get_confluent_schema(
         ))
     })?;
 
-    let schema_type = value
-        .get("schemaType")
+    let schema = value
+        .get("schema")
         .ok_or_else(|| {
             bad_request(
                 "The JSON returned from this endpoint was unexpected. Please confirm that the URL is correct."
                     .to_string(),
             )
         })?
-        .as_str();
-
-    if schema_type != Some("JSON") {
-        return Err(bad_request(
-            "Only JSON schema types are supported currently".to_string(),
-        ));
-    }
-
-    let schema = value
-        .get("schema")
-        .ok_or_else(|| {
-            return bad_request("Missing 'schema' field in schema registry response".to_string());
-        })?
         .as_str()
         .ok_or_else(|| {
             return bad_request(
@@ -572,7 +675,19 @@ pub(crate) async fn get_confluent_schema(
             );
         })?;
 
-    if let Err(e) = convert_json_schema(&query_params.topic, schema) {
+    // the schema registry omits the schema type for avro schemas
+    let result = match value.get("schemaType").and_then(|t| t.as_str()) {
+        Some("JSON") => convert_json_schema(&query_params.topic, schema),
+        None | Some("AVRO") => convert_avro_schema(&query_params.topic, schema),
+        Some(t) => {
+            return Err(bad_request(format!(
+                "Unsupported schema type '{}'; only JSON and Avro schemas are supported",
+                t
+            )));
+        }
+    };
+
+    if let Err(e) = result {
         warn!(
             "Schema from schema registry is not valid: '{}': {}",
             schema, e
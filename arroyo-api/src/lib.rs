@@ -5,17 +5,18 @@ use tracing::warn;
 use utoipa::OpenApi;
 
 use arroyo_rpc::types::{
-    AvroFormat, Checkpoint, CheckpointCollection, CheckpointEventSpan, CheckpointSpanType,
+    AvroFormat, BadData, Checkpoint, CheckpointCollection, CheckpointEventSpan, CheckpointSpanType,
     ConfluentSchema, ConnectionProfile, ConnectionProfileCollection, ConnectionProfilePost,
     ConnectionSchema, ConnectionTable, ConnectionTableCollection, ConnectionTablePost,
-    ConnectionType, Connector, ConnectorCollection, FieldType, Format, Job, JobCollection,
-    JobLogLevel, JobLogMessage, JobLogMessageCollection, JsonFormat, Metric, MetricGroup,
-    MetricNames, OperatorCheckpointGroup, OperatorCheckpointGroupCollection, OperatorMetricGroup,
-    OutputData, PaginationQueryParams, ParquetFormat, Pipeline, PipelineCollection, PipelineEdge,
-    PipelineGraph, PipelineNode, PipelinePatch, PipelinePost, PrimitiveType, RawStringFormat,
-    SchemaDefinition, SourceField, SourceFieldType, StopType as StopTypeRest, StructType,
-    SubtaskCheckpointGroup, SubtaskMetrics, TestSourceMessage, TimestampFormat, Udf, UdfLanguage,
-    ValidatePipelinePost,
+    ConnectionType, Connector, ConnectorCollection, CsvFormat, FieldType, Format, InferredSchema,
+    Job, JobCollection, JobLogLevel, JobLogMessage, JobLogMessageCollection, JsonEnvelope,
+    JsonFormat, Metric, MetricGroup, MetricNames, OperatorCheckpointGroup,
+    OperatorCheckpointGroupCollection, OperatorMetricGroup, OutputData, PaginationQueryParams,
+    ParquetFormat, Pipeline, PipelineCollection, PipelineEdge, PipelineGraph, PipelineNode,
+    PipelinePatch, PipelinePost, PrimitiveType, ProtobufFormat, RawBytesFormat, RawStringFormat,
+    SchemaDefinition, SchemaInferencePost, SourceField, SourceFieldType, StopType as StopTypeRest,
+    StructType, SubtaskCheckpointGroup, SubtaskMetrics, TestSourceMessage, TimestampFormat, Udf,
+    UdfLanguage, ValidatePipelinePost,
 };
 
 use crate::connection_profiles::{
@@ -23,7 +24,8 @@ use crate::connection_profiles::{
 };
 use crate::connection_tables::{
     __path_create_connection_table, __path_delete_connection_table, __path_get_confluent_schema,
-    __path_get_connection_tables, __path_test_connection_table, __path_test_schema,
+    __path_get_connection_tables, __path_infer_schema, __path_test_connection_table,
+    __path_test_schema,
 };
 use crate::connectors::__path_get_connectors;
 use crate::jobs::{
@@ -50,6 +52,7 @@ mod optimizations;
 mod pipelines;
 pub mod rest;
 mod rest_utils;
+mod schema_inference;
 
 include!(concat!(env!("OUT_DIR"), "/api-sql.rs"));
 
@@ -159,6 +162,7 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
         delete_connection_table,
         test_connection_table,
         test_schema,
+        infer_schema,
         get_confluent_schema,
         get_checkpoint_details,
     ),
@@ -196,6 +200,7 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
         ConnectionTablePost,
         ConnectionTableCollection,
         ConnectionSchema,
+        BadData,
         ConnectionType,
         SourceField,
         Format,
@@ -206,10 +211,16 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
         SchemaDefinition,
         TestSourceMessage,
         ConfluentSchema,
+        SchemaInferencePost,
+        InferredSchema,
         JsonFormat,
+        JsonEnvelope,
         AvroFormat,
+        ProtobufFormat,
+        CsvFormat,
         ParquetFormat,
         RawStringFormat,
+        RawBytesFormat,
         TimestampFormat,
         PaginationQueryParams,
         CheckpointEventSpan,
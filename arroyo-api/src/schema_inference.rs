@@ -0,0 +1,594 @@
+use std::collections::BTreeSet;
+
+use arroyo_rpc::primitive_to_sql;
+use arroyo_rpc::types::{
+    FieldType, JsonEnvelope, JsonFormat, PrimitiveType, SourceField, SourceFieldType, StructType,
+    TimestampFormat,
+};
+use chrono::{DateTime, NaiveDateTime};
+use serde_json::{Map, Value};
+
+/// The result of inferring a schema from a set of JSON messages
+#[derive(Debug)]
+pub(crate) struct Inference {
+    pub fields: Vec<SourceField>,
+    /// Set if any string fields were detected as timestamps
+    pub timestamp_format: Option<TimestampFormat>,
+    pub samples: usize,
+    pub conflicts: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+// timestamp formats we try to detect in string fields, in order of preference
+fn timestamp_formats() -> Vec<TimestampFormat> {
+    vec![
+        TimestampFormat::RFC3339,
+        TimestampFormat::ISO8601,
+        TimestampFormat::Pattern {
+            pattern: "%Y/%m/%d %H:%M:%S".to_string(),
+            timezone: None,
+        },
+        // the common log format used by web servers
+        TimestampFormat::Pattern {
+            pattern: "%d/%b/%Y:%H:%M:%S %z".to_string(),
+            timezone: None,
+        },
+    ]
+}
+
+fn is_timestamp(format: &TimestampFormat, s: &str) -> bool {
+    match format {
+        TimestampFormat::RFC3339 => DateTime::parse_from_rfc3339(s).is_ok(),
+        TimestampFormat::ISO8601 => {
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+                || NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").is_ok()
+        }
+        TimestampFormat::Pattern { pattern, .. } => {
+            DateTime::parse_from_str(s, pattern).is_ok()
+                || NaiveDateTime::parse_from_str(s, pattern).is_ok()
+        }
+        // numeric timestamps can't be distinguished from other numbers
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum InferredType {
+    Bool,
+    Int,
+    UInt,
+    Float,
+    /// Holds the timestamp formats that every value of the field has matched
+    String(Vec<TimestampFormat>),
+    Struct(InferredStruct),
+    Array,
+    Conflict(BTreeSet<&'static str>),
+}
+
+impl InferredType {
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::Null => return None,
+            Value::Bool(_) => InferredType::Bool,
+            Value::Number(n) if n.is_i64() => InferredType::Int,
+            Value::Number(n) if n.is_u64() => InferredType::UInt,
+            Value::Number(_) => InferredType::Float,
+            Value::String(s) => InferredType::String(
+                timestamp_formats()
+                    .into_iter()
+                    .filter(|f| is_timestamp(f, s))
+                    .collect(),
+            ),
+            Value::Array(_) => InferredType::Array,
+            Value::Object(o) => {
+                let mut s = InferredStruct::default();
+                s.observe(o);
+                InferredType::Struct(s)
+            }
+        })
+    }
+
+    fn names(&self) -> BTreeSet<&'static str> {
+        let name = match self {
+            InferredType::Bool => "BOOLEAN",
+            InferredType::Int => "BIGINT",
+            InferredType::UInt => "BIGINT UNSIGNED",
+            InferredType::Float => "DOUBLE",
+            InferredType::String(_) => "TEXT",
+            InferredType::Struct(_) => "STRUCT",
+            InferredType::Array => "ARRAY",
+            InferredType::Conflict(names) => return names.clone(),
+        };
+        [name].into_iter().collect()
+    }
+
+    fn merge(self, other: InferredType) -> InferredType {
+        use InferredType::*;
+        match (self, other) {
+            (Bool, Bool) => Bool,
+            (Int, Int) => Int,
+            (UInt, UInt) => UInt,
+            // UInt values are above i64::MAX, so a mix of them and (possibly negative) ints only
+            // fits in a float
+            (Int | UInt | Float, Int | UInt | Float) => Float,
+            (String(mut a), String(b)) => {
+                a.retain(|f| b.contains(f));
+                String(a)
+            }
+            (Struct(mut a), Struct(b)) => {
+                a.merge(b);
+                Struct(a)
+            }
+            (Array, Array) => Array,
+            (a, b) => {
+                let mut names = a.names();
+                names.extend(b.names());
+                Conflict(names)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct InferredField {
+    typ: Option<InferredType>,
+    present: usize,
+    has_null: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct InferredStruct {
+    samples: usize,
+    // fields are kept in the order they were first seen
+    fields: Vec<(String, InferredField)>,
+}
+
+impl InferredStruct {
+    fn field_mut(&mut self, name: &str) -> &mut InferredField {
+        let idx = match self.fields.iter().position(|(n, _)| n == name) {
+            Some(idx) => idx,
+            None => {
+                self.fields
+                    .push((name.to_string(), InferredField::default()));
+                self.fields.len() - 1
+            }
+        };
+        &mut self.fields[idx].1
+    }
+
+    fn observe(&mut self, object: &Map<String, Value>) {
+        self.samples += 1;
+        for (name, value) in object {
+            let field = self.field_mut(name);
+            field.present += 1;
+            match InferredType::from_value(value) {
+                Some(t) => {
+                    field.typ = Some(match field.typ.take() {
+                        Some(existing) => existing.merge(t),
+                        None => t,
+                    });
+                }
+                None => field.has_null = true,
+            }
+        }
+    }
+
+    fn merge(&mut self, other: InferredStruct) {
+        self.samples += other.samples;
+        for (name, other) in other.fields {
+            let field = self.field_mut(&name);
+            field.present += other.present;
+            field.has_null |= other.has_null;
+            field.typ = match (field.typ.take(), other.typ) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            };
+        }
+    }
+
+    fn timestamp_candidates<'a>(&'a self, candidates: &mut Vec<&'a Vec<TimestampFormat>>) {
+        for (_, field) in &self.fields {
+            match &field.typ {
+                Some(InferredType::String(formats)) if !formats.is_empty() => {
+                    candidates.push(formats)
+                }
+                Some(InferredType::Struct(s)) => s.timestamp_candidates(candidates),
+                _ => {}
+            }
+        }
+    }
+}
+
+struct Finalizer {
+    timestamp_format: Option<TimestampFormat>,
+    conflicts: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Finalizer {
+    fn fields(&mut self, path: &str, s: InferredStruct) -> Vec<SourceField> {
+        let samples = s.samples;
+        s.fields
+            .into_iter()
+            .filter_map(|(name, field)| {
+                let path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", path, name)
+                };
+
+                let nullable = field.has_null || field.present < samples;
+                let field_type = self.field_type(&path, field.typ)?;
+
+                Some(SourceField {
+                    field_name: name,
+                    field_type,
+                    nullable,
+                })
+            })
+            .collect()
+    }
+
+    fn field_type(&mut self, path: &str, typ: Option<InferredType>) -> Option<SourceFieldType> {
+        let primitive = match typ {
+            None => {
+                self.warnings.push(format!(
+                    "field '{}' was null in every sample, so its type is assumed to be TEXT",
+                    path
+                ));
+                PrimitiveType::String
+            }
+            Some(InferredType::Bool) => PrimitiveType::Bool,
+            Some(InferredType::Int) => PrimitiveType::Int64,
+            Some(InferredType::UInt) => PrimitiveType::UInt64,
+            Some(InferredType::Float) => PrimitiveType::F64,
+            Some(InferredType::String(formats)) => match &self.timestamp_format {
+                Some(f) if formats.contains(f) => PrimitiveType::DateTime,
+                _ => {
+                    if !formats.is_empty() {
+                        self.warnings.push(format!(
+                                "field '{}' looks like a timestamp, but in a different format than other timestamp fields, so it has been typed as TEXT",
+                                path
+                            ));
+                    }
+                    PrimitiveType::String
+                }
+            },
+            Some(InferredType::Struct(s)) => {
+                let fields = self.fields(path, s);
+                if fields.is_empty() {
+                    self.warnings.push(format!(
+                        "field '{}' is an object with no usable fields and has been left out of the schema",
+                        path
+                    ));
+                    return None;
+                }
+
+                return Some(SourceFieldType {
+                    r#type: FieldType::Struct(StructType { name: None, fields }),
+                    sql_name: None,
+                });
+            }
+            Some(InferredType::Array) => {
+                self.warnings.push(format!(
+                    "field '{}' is an array, which is not supported, and has been left out of the schema",
+                    path
+                ));
+                return None;
+            }
+            Some(InferredType::Conflict(names)) => {
+                self.conflicts.push(format!(
+                    "field '{}' has conflicting types across samples ({}) and has been left out of the schema",
+                    path,
+                    names.into_iter().collect::<Vec<_>>().join(", ")
+                ));
+                return None;
+            }
+        };
+
+        Some(SourceFieldType {
+            r#type: FieldType::Primitive(primitive.clone()),
+            sql_name: Some(primitive_to_sql(primitive).to_string()),
+        })
+    }
+}
+
+/// Extracts the rows described by a message, unwrapping any change data capture envelope
+fn rows(format: &JsonFormat, msg: Value) -> Result<Vec<Map<String, Value>>, String> {
+    let values = if format.debezium {
+        let Value::Object(mut msg) = msg else {
+            return Err("expected a Debezium message to be a JSON object".to_string());
+        };
+        vec![
+            msg.remove("before").unwrap_or_default(),
+            msg.remove("after").unwrap_or_default(),
+        ]
+        .into_iter()
+        .filter(|v| !v.is_null())
+        .collect()
+    } else {
+        match format.envelope {
+            Some(JsonEnvelope::Maxwell) => match msg {
+                Value::Object(mut msg) => msg.remove("data").into_iter().collect(),
+                _ => return Err("expected a Maxwell message to be a JSON object".to_string()),
+            },
+            Some(JsonEnvelope::Canal) => match msg {
+                Value::Object(mut msg) => {
+                    let mysql_types = msg.remove("mysqlType");
+                    match msg.remove("data") {
+                        Some(Value::Array(data)) => data
+                            .into_iter()
+                            .map(|row| coerce_canal_row(row, mysql_types.as_ref()))
+                            .collect(),
+                        _ => vec![],
+                    }
+                }
+                _ => return Err("expected a Canal message to be a JSON object".to_string()),
+            },
+            None => vec![msg],
+        }
+    };
+
+    values
+        .into_iter()
+        .map(|v| match v {
+            Value::Object(o) => Ok(o),
+            v => Err(format!("expected a JSON object, but found '{}'", v)),
+        })
+        .collect()
+}
+
+/// Canal writes every value as a string, and numeric columns are converted back into numbers
+/// using the MySQL column types when they are read
+fn coerce_canal_row(row: Value, mysql_types: Option<&Value>) -> Value {
+    match (row, mysql_types) {
+        (Value::Object(row), Some(Value::Object(mysql_types))) => Value::Object(
+            row.into_iter()
+                .map(|(k, v)| {
+                    let v = coerce_canal_value(v, mysql_types.get(&k).and_then(|t| t.as_str()));
+                    (k, v)
+                })
+                .collect(),
+        ),
+        (row, _) => row,
+    }
+}
+
+fn coerce_canal_value(value: Value, mysql_type: Option<&str>) -> Value {
+    let (Value::String(s), Some(mysql_type)) = (&value, mysql_type) else {
+        return value;
+    };
+
+    let mysql_type = mysql_type.to_lowercase();
+    let coerced = match mysql_type.split(['(', ' ']).next().unwrap_or_default() {
+        "tinyint" | "smallint" | "mediumint" | "int" | "integer" | "bigint" => {
+            s.parse::<i64>().ok().map(Value::from)
+        }
+        "float" | "double" | "real" | "decimal" | "numeric" => {
+            s.parse::<f64>().ok().map(Value::from)
+        }
+        _ => None,
+    };
+
+    coerced.unwrap_or(value)
+}
+
+/// Infers a schema from sampled JSON messages. Nested objects become structs, fields that are
+/// missing or null in any sample are nullable, and strings that consistently match one of the
+/// supported timestamp formats become timestamps.
+pub(crate) fn infer_json_schema(
+    format: &JsonFormat,
+    messages: &[Vec<u8>],
+) -> Result<Inference, String> {
+    let mut root = InferredStruct::default();
+    let mut invalid = 0;
+    let mut last_error = None;
+
+    for msg in messages {
+        // messages written with the schema registry are prefixed by a magic byte and schema id
+        let msg = if format.confluent_schema_registry && msg.len() >= 5 {
+            &msg[5..]
+        } else {
+            &msg[..]
+        };
+
+        let result = serde_json::from_slice(msg)
+            .map_err(|e| format!("invalid JSON: {}", e))
+            .and_then(|v| rows(format, v));
+
+        match result {
+            Ok(rows) => {
+                for row in rows {
+                    root.observe(&row);
+                }
+            }
+            Err(e) => {
+                invalid += 1;
+                last_error = Some(e);
+            }
+        }
+    }
+
+    if root.samples == 0 {
+        return Err(match last_error {
+            Some(e) => format!("None of the sampled messages could be read: {}", e),
+            None => "No rows were found in the sampled messages".to_string(),
+        });
+    }
+
+    // the timestamp format applies to the whole table, so we pick the one that the most
+    // timestamp-like fields agree on
+    let mut candidates = vec![];
+    root.timestamp_candidates(&mut candidates);
+    let timestamp_format = timestamp_formats()
+        .into_iter()
+        .map(|f| {
+            let count = candidates.iter().filter(|c| c.contains(&f)).count();
+            (f, count)
+        })
+        .filter(|(_, count)| *count > 0)
+        .fold(
+            None,
+            |best: Option<(TimestampFormat, usize)>, (f, count)| match best {
+                Some((_, best_count)) if best_count >= count => best,
+                _ => Some((f, count)),
+            },
+        )
+        .map(|(f, _)| f);
+
+    let mut finalizer = Finalizer {
+        timestamp_format: timestamp_format.clone(),
+        conflicts: vec![],
+        warnings: vec![],
+    };
+
+    if invalid > 0 {
+        finalizer.warnings.push(format!(
+            "{} of {} sampled messages could not be read and were ignored (last error: {})",
+            invalid,
+            messages.len(),
+            last_error.unwrap_or_default()
+        ));
+    }
+
+    let fields = finalizer.fields("", root);
+
+    Ok(Inference {
+        fields,
+        timestamp_format,
+        samples: messages.len() - invalid,
+        conflicts: finalizer.conflicts,
+        warnings: finalizer.warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use arroyo_rpc::types::{FieldType, JsonFormat, PrimitiveType, SourceField, TimestampFormat};
+    use serde_json::json;
+
+    use super::infer_json_schema;
+
+    fn samples(values: Vec<serde_json::Value>) -> Vec<Vec<u8>> {
+        values
+            .into_iter()
+            .map(|v| serde_json::to_vec(&v).unwrap())
+            .collect()
+    }
+
+    fn field<'a>(fields: &'a [SourceField], name: &str) -> &'a SourceField {
+        fields.iter().find(|f| f.field_name == name).unwrap()
+    }
+
+    fn primitive(f: &SourceField) -> PrimitiveType {
+        match &f.field_type.r#type {
+            FieldType::Primitive(p) => p.clone(),
+            t => panic!("expected a primitive, found {:?}", t),
+        }
+    }
+
+    #[test]
+    fn test_types_and_nullability() {
+        let messages = samples(vec![
+            json!({"id": 1, "price": 3, "name": "a", "user": {"id": 5, "admin": true}}),
+            json!({"id": 2, "price": 4.5, "name": null, "user": {"id": 6}}),
+            json!({"id": 3, "price": 1, "user": {"id": 7, "admin": false}}),
+        ]);
+
+        let inference = infer_json_schema(&JsonFormat::default(), &messages).unwrap();
+        assert_eq!(inference.samples, 3);
+        assert!(inference.conflicts.is_empty());
+
+        let fields = &inference.fields;
+        assert_eq!(primitive(field(fields, "id")), PrimitiveType::Int64);
+        assert!(!field(fields, "id").nullable);
+        assert_eq!(primitive(field(fields, "price")), PrimitiveType::F64);
+        assert_eq!(primitive(field(fields, "name")), PrimitiveType::String);
+        assert!(field(fields, "name").nullable);
+
+        let FieldType::Struct(user) = &field(fields, "user").field_type.r#type else {
+            panic!("expected user to be a struct");
+        };
+        assert_eq!(primitive(field(&user.fields, "id")), PrimitiveType::Int64);
+        assert_eq!(primitive(field(&user.fields, "admin")), PrimitiveType::Bool);
+        assert!(field(&user.fields, "admin").nullable);
+    }
+
+    #[test]
+    fn test_integers() {
+        let messages = samples(vec![
+            json!({"small": -1, "large": 18446744073709551615u64, "mixed": -5}),
+            json!({"small": 2, "large": 9223372036854775808u64, "mixed": 18446744073709551615u64}),
+        ]);
+
+        let inference = infer_json_schema(&JsonFormat::default(), &messages).unwrap();
+        assert!(inference.conflicts.is_empty());
+
+        let fields = &inference.fields;
+        assert_eq!(primitive(field(fields, "small")), PrimitiveType::Int64);
+        assert_eq!(primitive(field(fields, "large")), PrimitiveType::UInt64);
+        assert_eq!(primitive(field(fields, "mixed")), PrimitiveType::F64);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let messages = samples(vec![
+            json!({"created": "2023-10-01 12:00:00", "updated": "2023-10-01T12:00:00.123", "label": "x"}),
+            json!({"created": "2023-10-02 08:30:00", "updated": "2023-10-02T08:30:00", "label": "2023-10-02 08:30:00"}),
+        ]);
+
+        let inference = infer_json_schema(&JsonFormat::default(), &messages).unwrap();
+        assert_eq!(inference.timestamp_format, Some(TimestampFormat::ISO8601));
+        assert_eq!(
+            primitive(field(&inference.fields, "created")),
+            PrimitiveType::DateTime
+        );
+        assert_eq!(
+            primitive(field(&inference.fields, "updated")),
+            PrimitiveType::DateTime
+        );
+        assert_eq!(
+            primitive(field(&inference.fields, "label")),
+            PrimitiveType::String
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut messages = samples(vec![
+            json!({"id": 1, "value": 5, "tags": ["a"]}),
+            json!({"id": 2, "value": "five", "tags": []}),
+        ]);
+        messages.push(b"not json".to_vec());
+
+        let inference = infer_json_schema(&JsonFormat::default(), &messages).unwrap();
+        assert_eq!(inference.samples, 2);
+        assert_eq!(inference.fields.len(), 1);
+        assert_eq!(inference.conflicts.len(), 1);
+        assert!(inference.conflicts[0].contains("'value'"));
+        assert!(inference.conflicts[0].contains("BIGINT, TEXT"));
+        assert_eq!(inference.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_debezium() {
+        let messages = samples(vec![
+            json!({"before": null, "after": {"id": 1, "name": "a"}, "op": "c"}),
+            json!({"before": {"id": 1, "name": "a"}, "after": null, "op": "d"}),
+        ]);
+
+        let format = JsonFormat {
+            debezium: true,
+            ..Default::default()
+        };
+
+        let inference = infer_json_schema(&format, &messages).unwrap();
+        let names: Vec<_> = inference
+            .fields
+            .iter()
+            .map(|f| f.field_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["id", "name"]);
+        assert!(inference.fields.iter().all(|f| !f.nullable));
+    }
+}
@@ -91,6 +91,9 @@ impl Connector for BlackholeConnector {
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: None,
+            bad_data: None,
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -99,6 +102,7 @@ impl Connector for BlackholeConnector {
             connection_type: ConnectionType::Sink,
             schema: s.cloned().unwrap_or_else(|| ConnectionSchema {
                 format: None,
+                bad_data: None,
                 struct_name: None,
                 fields: vec![],
                 definition: None,
@@ -0,0 +1,144 @@
+use anyhow::{anyhow, bail};
+use axum::response::sse::Event;
+use std::convert::Infallible;
+
+use arroyo_rpc::types::{ConnectionSchema, ConnectionType, Format, TestSourceMessage};
+use arroyo_rpc::OperatorConfig;
+
+use crate::filesystem::{
+    is_s3_destination, sink_table_from_options, FileSystemTable, FormatSettings,
+};
+use crate::{Connection, EmptyConfig};
+
+use super::Connector;
+
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/filesystem/table.json");
+
+pub struct DeltaLakeConnector {}
+
+impl Connector for DeltaLakeConnector {
+    type ProfileT = EmptyConfig;
+
+    type TableT = FileSystemTable;
+
+    fn name(&self) -> &'static str {
+        "delta"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::types::Connector {
+        arroyo_rpc::types::Connector {
+            id: "delta".to_string(),
+            name: "Delta Lake".to_string(),
+            icon: "".to_string(),
+            description: "Write to a Delta Lake table".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: false,
+            hidden: true,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = TestSourceMessage {
+                error: false,
+                done: true,
+                message: "Successfully validated connection".to_string(),
+            };
+            tx.send(Ok(Event::default().json_data(message).unwrap()))
+                .await
+                .unwrap();
+        });
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<crate::Connection> {
+        if table.source.is_some() {
+            bail!("Delta Lake tables can only be used as sinks");
+        }
+        let Some(write_target) = &table.write_target else {
+            bail!("a write_target must be configured for Delta Lake tables");
+        };
+        // commits are made by atomically creating the next version of the log, which S3 can't do
+        // without an external locking provider
+        if is_s3_destination(write_target) {
+            bail!("Delta Lake tables cannot be written to S3, which does not support atomic commits to the delta log");
+        }
+        if table.iceberg.is_some() {
+            bail!("iceberg settings can only be used with the iceberg connector");
+        }
+        if table.partitioning.is_some() {
+            bail!("partitioning is not yet supported for Delta Lake tables");
+        }
+        let Some(FormatSettings::Parquet { .. }) = &table.format_settings else {
+            bail!("Delta Lake tables must use the parquet format");
+        };
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Delta Lake connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for Delta Lake connection"))?;
+
+        if !matches!(format, Format::Parquet(_)) {
+            bail!("Delta Lake tables must use the parquet format");
+        }
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            operator: "connectors::filesystem::delta::DeltaLakeSink::<#in_k, #in_t, #in_tRecordBatchBuilder>"
+                .to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description: "DeltaLake<Parquet>".to_string(),
+        })
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        opts: &mut std::collections::HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<crate::Connection> {
+        let table = sink_table_from_options(opts, schema)?;
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+}
@@ -1,13 +1,15 @@
 use anyhow::{anyhow, bail, Result};
 use axum::response::sse::Event;
+use chrono::format::{Item, StrftimeItems};
 use std::convert::Infallible;
 use typify::import_types;
 
-use arroyo_rpc::types::{ConnectionSchema, ConnectionType, Format, TestSourceMessage};
+use arroyo_rpc::types::{ConnectionSchema, ConnectionType, CsvFormat, Format, TestSourceMessage};
 use arroyo_rpc::OperatorConfig;
+use arroyo_storage::split_glob;
 use serde::{Deserialize, Serialize};
 
-use crate::{pull_option_to_i64, Connection, EmptyConfig};
+use crate::{pull_opt, pull_option_to_i64, Connection, EmptyConfig};
 
 use super::Connector;
 
@@ -29,11 +31,11 @@ impl Connector for FileSystemConnector {
     fn metadata(&self) -> arroyo_rpc::types::Connector {
         arroyo_rpc::types::Connector {
             id: "filesystem".to_string(),
-            name: "FileSystem Sink".to_string(),
+            name: "FileSystem".to_string(),
             icon: "".to_string(),
-            description: "Write to a filesystem (like S3)".to_string(),
+            description: "Read from or write to a filesystem (like S3)".to_string(),
             enabled: true,
-            source: false,
+            source: true,
             sink: true,
             testing: false,
             hidden: true,
@@ -63,8 +65,12 @@ impl Connector for FileSystemConnector {
         });
     }
 
-    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
-        return ConnectionType::Source;
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        if table.source.is_some() {
+            ConnectionType::Source
+        } else {
+            ConnectionType::Sink
+        }
     }
 
     fn from_config(
@@ -75,10 +81,18 @@ impl Connector for FileSystemConnector {
         table: Self::TableT,
         schema: Option<&ConnectionSchema>,
     ) -> anyhow::Result<crate::Connection> {
+        if table.source.is_some() {
+            return self.source_from_config(id, name, config, table, schema);
+        }
+        if table.iceberg.is_some() {
+            bail!("iceberg settings can only be used with the iceberg connector");
+        }
+
         let is_local = match &table.write_target {
-            Destination::FolderUri { path } => path.starts_with("file:/"),
-            Destination::S3Bucket { .. } => false,
-            Destination::LocalFilesystem { .. } => true,
+            Some(Destination::FolderUri { path }) => path.starts_with("file:/"),
+            Some(Destination::S3Bucket { .. }) => false,
+            Some(Destination::LocalFilesystem { .. }) => true,
+            None => bail!("a write_target or source must be configured for FileSystem tables"),
         };
         let (description, operator) = match (&table.format_settings, is_local) {
             (Some(FormatSettings::Parquet { .. }), true) => (
@@ -89,17 +103,51 @@ impl Connector for FileSystemConnector {
                 "FileSystem<Parquet>".to_string(),
                 "connectors::filesystem::ParquetFileSystemSink::<#in_k, #in_t, #in_tRecordBatchBuilder>"
             ),
-            (Some(FormatSettings::Json {  }), true) => (
+            (Some(FormatSettings::Json { .. }), true) => (
                 "LocalFileSystem<JSON>".to_string(),
                 "connectors::filesystem::LocalJsonFileSystemSink::<#in_k, #in_t>"
             ),
-            (Some(FormatSettings::Json {  }), false) => (
+            (Some(FormatSettings::Json { .. }), false) => (
                 "FileSystem<JSON>".to_string(),
                 "connectors::filesystem::JsonFileSystemSink::<#in_k, #in_t>"
             ),
+            (Some(FormatSettings::Csv { .. }), true) => (
+                "LocalFileSystem<CSV>".to_string(),
+                "connectors::filesystem::LocalCsvFileSystemSink::<#in_k, #in_t>"
+            ),
+            (Some(FormatSettings::Csv { .. }), false) => (
+                "FileSystem<CSV>".to_string(),
+                "connectors::filesystem::CsvFileSystemSink::<#in_k, #in_t>"
+            ),
+            (Some(FormatSettings::Avro { .. }), true) => (
+                "LocalFileSystem<Avro>".to_string(),
+                "connectors::filesystem::LocalAvroFileSystemSink::<#in_k, #in_t>"
+            ),
+            (Some(FormatSettings::Avro { .. }), false) => (
+                "FileSystem<Avro>".to_string(),
+                "connectors::filesystem::AvroFileSystemSink::<#in_k, #in_t>"
+            ),
             (None, _) => bail!("have to have some format settings"),
         };
 
+        if let Some(FormatSettings::Csv {
+            delimiter,
+            quote,
+            escape,
+            include_header,
+            null_value,
+        }) = &table.format_settings
+        {
+            CsvFormat::new(
+                Some(delimiter.as_str()),
+                quote.as_deref(),
+                escape.as_deref(),
+                include_header.unwrap_or_default(),
+                null_value.clone(),
+            )
+            .map_err(|e| anyhow!(e))?;
+        }
+
         let schema = schema
             .map(|s| s.to_owned())
             .ok_or_else(|| anyhow!("no schema defined for FileSystem connection"))?;
@@ -110,11 +158,18 @@ impl Connector for FileSystemConnector {
             .map(|t| t.to_owned())
             .ok_or_else(|| anyhow!("'format' must be set for FileSystem connection"))?;
 
+        if let Some(partitioning) = &table.partitioning {
+            validate_partitioning(partitioning, &schema)?;
+        }
+
         let config = OperatorConfig {
             connection: serde_json::to_value(config).unwrap(),
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -134,36 +189,116 @@ impl Connector for FileSystemConnector {
         opts: &mut std::collections::HashMap<String, String>,
         schema: Option<&ConnectionSchema>,
     ) -> anyhow::Result<crate::Connection> {
-        let write_target = if let Some(path) = opts.remove("path") {
-            Destination::FolderUri { path }
-        } else if let (Some(s3_bucket), Some(s3_directory), Some(aws_region)) = (
-            opts.remove("s3_bucket"),
-            opts.remove("s3_directory"),
-            opts.remove("aws_region"),
-        ) {
-            Destination::S3Bucket {
-                s3_bucket,
-                s3_directory,
-                aws_region,
+        match opts.remove("type").as_deref() {
+            None | Some("sink") => {}
+            Some("source") => {
+                let source = SourceSettings {
+                    path: pull_opt("path", opts)?,
+                    watch_interval_seconds: pull_option_to_i64("watch_interval_seconds", opts)?,
+                };
+
+                return self.from_config(
+                    None,
+                    name,
+                    EmptyConfig {},
+                    FileSystemTable {
+                        source: Some(source),
+                        write_target: None,
+                        file_settings: None,
+                        format_settings: None,
+                        partitioning: None,
+                        iceberg: None,
+                    },
+                    schema,
+                );
             }
-        } else {
-            bail!("Target for filesystem connector incorrectly specified. Should be a URI path or a triple of s3_bucket, s3_directory, and aws_region");
-        };
+            Some(other) => bail!("type must be one of 'source' or 'sink', not '{}'", other),
+        }
 
-        let inactivity_rollover_seconds = pull_option_to_i64("inactivity_rollover_seconds", opts)?;
-        let max_parts = pull_option_to_i64("max_parts", opts)?;
-        let rollover_seconds = pull_option_to_i64("rollover_seconds", opts)?;
-        let target_file_size = pull_option_to_i64("target_file_size", opts)?;
-        let target_part_size = pull_option_to_i64("target_part_size", opts)?;
-
-        let file_settings = Some(FileSettings {
-            inactivity_rollover_seconds,
-            max_parts,
-            rollover_seconds,
-            target_file_size,
-            target_part_size,
-        });
-        let format_settings = match schema
+        let table = sink_table_from_options(opts, schema)?;
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+}
+
+/// Builds the table config of a filesystem sink from SQL WITH options
+pub(crate) fn sink_table_from_options(
+    opts: &mut std::collections::HashMap<String, String>,
+    schema: Option<&ConnectionSchema>,
+) -> Result<FileSystemTable> {
+    let write_target = if let Some(path) = opts.remove("path") {
+        Destination::FolderUri { path }
+    } else if let (Some(s3_bucket), Some(s3_directory), Some(aws_region)) = (
+        opts.remove("s3_bucket"),
+        opts.remove("s3_directory"),
+        opts.remove("aws_region"),
+    ) {
+        Destination::S3Bucket {
+            s3_bucket,
+            s3_directory,
+            aws_region,
+        }
+    } else {
+        bail!("Target for filesystem connector incorrectly specified. Should be a URI path or a triple of s3_bucket, s3_directory, and aws_region");
+    };
+
+    let file_settings = Some(file_settings_from_options(opts)?);
+    let format_settings = Some(format_settings_from_options(opts, schema)?);
+
+    let time_pattern = opts.remove("time_partition_pattern");
+    let partition_fields = opts.remove("partition_fields");
+    let partitioning = if time_pattern.is_some() || partition_fields.is_some() {
+        Some(Partitioning {
+            time_pattern,
+            partition_fields,
+        })
+    } else {
+        None
+    };
+
+    Ok(FileSystemTable {
+        source: None,
+        write_target: Some(write_target),
+        file_settings,
+        format_settings,
+        partitioning,
+        iceberg: None,
+    })
+}
+
+/// Whether a storage URL points at S3, which doesn't support the atomic rename-if-not-exists that
+/// table formats rely on to commit new versions of their metadata
+pub(crate) fn is_s3_url(url: &str) -> bool {
+    let url = url.to_lowercase();
+    url.starts_with("s3") || (url.starts_with("https://") && url.contains(".amazonaws.com"))
+}
+
+pub(crate) fn is_s3_destination(destination: &Destination) -> bool {
+    match destination {
+        Destination::FolderUri { path } => is_s3_url(path),
+        Destination::S3Bucket { .. } => true,
+        Destination::LocalFilesystem { .. } => false,
+    }
+}
+
+pub(crate) fn file_settings_from_options(
+    opts: &mut std::collections::HashMap<String, String>,
+) -> Result<FileSettings> {
+    Ok(FileSettings {
+        inactivity_rollover_seconds: pull_option_to_i64("inactivity_rollover_seconds", opts)?,
+        max_parts: pull_option_to_i64("max_parts", opts)?,
+        rollover_seconds: pull_option_to_i64("rollover_seconds", opts)?,
+        target_file_size: pull_option_to_i64("target_file_size", opts)?,
+        target_part_size: pull_option_to_i64("target_part_size", opts)?,
+    })
+}
+
+pub(crate) fn format_settings_from_options(
+    opts: &mut std::collections::HashMap<String, String>,
+    schema: Option<&ConnectionSchema>,
+) -> Result<FormatSettings> {
+    Ok(
+        match schema
             .ok_or(anyhow!("require schema"))?
             .format
             .as_ref()
@@ -180,26 +315,122 @@ impl Connector for FileSystemConnector {
                     .transpose()?;
                 let row_batch_size = pull_option_to_i64("parquet_row_batch_size", opts)?;
                 let row_group_size = pull_option_to_i64("parquet_row_group_size", opts)?;
-                Some(FormatSettings::Parquet {
+                FormatSettings::Parquet {
                     compression,
                     row_batch_size,
                     row_group_size,
-                })
+                }
+            }
+            Format::Json(..) => {
+                let compression = opts
+                    .remove("json_compression")
+                    .map(|value| {
+                        JsonCompression::try_from(&value).map_err(|_err| {
+                            anyhow!("{} is not a valid json_compression argument", value)
+                        })
+                    })
+                    .transpose()?;
+                FormatSettings::Json { compression }
+            }
+            Format::Csv(csv) => FormatSettings::Csv {
+                delimiter: csv.delimiter.to_string(),
+                quote: Some(csv.quote.to_string()),
+                escape: csv.escape.map(|c| c.to_string()),
+                include_header: Some(csv.header),
+                null_value: Some(csv.null_value.clone()),
+            },
+            Format::Avro(..) => {
+                let compression = opts
+                    .remove("avro_compression")
+                    .map(|value| {
+                        AvroCompression::try_from(&value).map_err(|_err| {
+                            anyhow!("{} is not a valid avro_compression argument", value)
+                        })
+                    })
+                    .transpose()?;
+                FormatSettings::Avro { compression }
             }
-            Format::Json(..) => Some(FormatSettings::Json {}),
             other => bail!("Unsupported format: {:?}", other),
+        },
+    )
+}
+
+impl FileSystemConnector {
+    fn source_from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: EmptyConfig,
+        table: FileSystemTable,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<crate::Connection> {
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for FileSystem connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for FileSystem connection"))?;
+
+        let path = &table
+            .source
+            .as_ref()
+            .ok_or_else(|| anyhow!("no source config defined for FileSystem source"))?
+            .path;
+        split_glob(path).map_err(|e| anyhow!("invalid source path: {}", e))?;
+
+        let description = match &format {
+            Format::Parquet(_) => "FileSystemSource<Parquet>",
+            Format::Json(_) | Format::RawString(_) => "FileSystemSource<JSON>",
+            other => bail!(
+                "FileSystem sources support parquet and line-delimited JSON files, not {:?}",
+                other
+            ),
         };
 
-        self.from_config(
-            None,
-            name,
-            EmptyConfig {},
-            FileSystemTable {
-                write_target,
-                file_settings,
-                format_settings,
-            },
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
             schema,
-        )
+            operator: "connectors::filesystem::source::FileSystemSourceFunc".to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description: description.to_string(),
+        })
     }
 }
+
+fn validate_partitioning(partitioning: &Partitioning, schema: &ConnectionSchema) -> Result<()> {
+    if let Some(pattern) = &partitioning.time_pattern {
+        if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+            bail!("'{}' is not a valid time partition pattern", pattern);
+        }
+    }
+
+    for field in partitioning
+        .partition_fields
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+    {
+        if !schema.fields.iter().any(|f| f.field_name == field) {
+            bail!("partition field '{}' is not in the schema", field);
+        }
+    }
+
+    Ok(())
+}
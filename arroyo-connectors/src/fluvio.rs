@@ -1,9 +1,16 @@
+use ::fluvio::metadata::objects::Metadata;
+use ::fluvio::metadata::topic::TopicSpec;
+use ::fluvio::{Fluvio, FluvioConfig, Offset};
 use anyhow::{anyhow, bail};
 use arroyo_rpc::types::{ConnectionSchema, TestSourceMessage};
 use arroyo_rpc::OperatorConfig;
 use axum::response::sse::Event;
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tracing::warn;
 use typify::import_types;
 
 use crate::{pull_opt, Connection, ConnectionType, Connector, EmptyConfig};
@@ -76,6 +83,22 @@ impl Connector for FluvioConnector {
         });
     }
 
+    fn sample(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        count: usize,
+        tx: Sender<Result<Vec<u8>, String>>,
+    ) -> anyhow::Result<()> {
+        tokio::spawn(async move {
+            if let Err(e) = sample_topic(&table, count, &tx).await {
+                let _ = tx.send(Err(format!("{:?}", e))).await;
+            }
+        });
+
+        Ok(())
+    }
+
     fn from_options(
         &self,
         name: &str,
@@ -148,6 +171,9 @@ impl Connector for FluvioConnector {
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -161,3 +187,136 @@ impl Connector for FluvioConnector {
         })
     }
 }
+
+async fn sample_topic(
+    table: &FluvioTable,
+    count: usize,
+    tx: &Sender<Result<Vec<u8>, String>>,
+) -> anyhow::Result<()> {
+    let client = if let Some(endpoint) = &table.endpoint {
+        Fluvio::connect_with_config(&FluvioConfig::new(endpoint)).await?
+    } else {
+        Fluvio::connect().await?
+    };
+
+    let metadata: Metadata<TopicSpec> = client
+        .admin()
+        .await
+        .list(vec![table.topic.clone()])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            anyhow!(
+                "Could not fetch metadata for topic {}; may not exist",
+                table.topic
+            )
+        })?;
+
+    let mut sampled = 0;
+    for partition in 0..metadata.spec.partitions() as u32 {
+        let consumer = client
+            .partition_consumer(table.topic.clone(), partition)
+            .await?;
+        let stream = consumer
+            .stream(Offset::beginning())
+            .await?
+            .map(|r| r.map(|record| record.value().to_vec()));
+
+        if !sample_partition(stream, IDLE_TIMEOUT, count, &mut sampled, tx).await? {
+            return Ok(());
+        }
+    }
+
+    if sampled == 0 {
+        bail!("Topic {} does not contain any messages", table.topic);
+    }
+
+    Ok(())
+}
+
+// when no message arrives for this long, we've read everything currently in a partition
+const IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sends messages from a partition until `count` have been sampled in total or the partition is
+/// idle, returning false if sampling should stop
+async fn sample_partition<S, E>(
+    mut stream: S,
+    idle_timeout: Duration,
+    count: usize,
+    sampled: &mut usize,
+    tx: &Sender<Result<Vec<u8>, String>>,
+) -> anyhow::Result<bool>
+where
+    S: Stream<Item = Result<Vec<u8>, E>> + Unpin,
+    E: Into<anyhow::Error>,
+{
+    while *sampled < count {
+        let value = match tokio::time::timeout(idle_timeout, stream.next()).await {
+            Ok(Some(value)) => value.map_err(Into::into)?,
+            Ok(None) | Err(_) => break,
+        };
+
+        if tx.send(Ok(value)).await.is_err() {
+            warn!("Sample rx closed while sending message");
+            return Ok(false);
+        }
+        *sampled += 1;
+    }
+
+    Ok(*sampled < count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::stream;
+    use futures::StreamExt;
+    use tokio::sync::mpsc::channel;
+
+    use super::sample_partition;
+
+    #[tokio::test]
+    async fn test_sample_partition() {
+        let (tx, mut rx) = channel(10);
+
+        // the partition has two messages, after which the stream stays open but idle
+        let messages = stream::iter(vec![Ok::<_, anyhow::Error>(vec![1]), Ok(vec![2])])
+            .chain(stream::pending());
+
+        let mut sampled = 0;
+        let more = sample_partition(messages, Duration::from_millis(10), 5, &mut sampled, &tx)
+            .await
+            .unwrap();
+        assert!(
+            more,
+            "the sample isn't full, so other partitions should be read"
+        );
+        assert_eq!(sampled, 2);
+        assert_eq!(rx.try_recv().unwrap().unwrap(), vec![1]);
+        assert_eq!(rx.try_recv().unwrap().unwrap(), vec![2]);
+
+        // once the sample is full, reading stops
+        let messages = stream::iter(vec![Ok::<_, anyhow::Error>(vec![3]), Ok(vec![4])]);
+        let more = sample_partition(messages, Duration::from_millis(10), 3, &mut sampled, &tx)
+            .await
+            .unwrap();
+        assert!(!more);
+        assert_eq!(sampled, 3);
+        assert_eq!(rx.try_recv().unwrap().unwrap(), vec![3]);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sample_partition_error() {
+        let (tx, _rx) = channel(10);
+
+        let messages = stream::iter(vec![Err(anyhow::anyhow!("broken"))]);
+        let mut sampled = 0;
+        let err = sample_partition(messages, Duration::from_millis(10), 5, &mut sampled, &tx)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "broken");
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{anyhow, bail, Result};
+use axum::response::sse::Event;
+use std::convert::Infallible;
+
+use arroyo_rpc::partition_spec::{parse_partition_spec, PartitionField, Transform};
+use arroyo_rpc::types::{ConnectionSchema, ConnectionType, FieldType, Format, TestSourceMessage};
+use arroyo_rpc::OperatorConfig;
+
+use crate::filesystem::{
+    file_settings_from_options, format_settings_from_options, is_s3_url, Catalog, FileSystemTable,
+    FormatSettings, IcebergSettings,
+};
+use crate::{pull_opt, Connection, EmptyConfig};
+
+use super::Connector;
+
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/filesystem/table.json");
+
+pub struct IcebergConnector {}
+
+impl Connector for IcebergConnector {
+    type ProfileT = EmptyConfig;
+
+    type TableT = FileSystemTable;
+
+    fn name(&self) -> &'static str {
+        "iceberg"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::types::Connector {
+        arroyo_rpc::types::Connector {
+            id: "iceberg".to_string(),
+            name: "Iceberg".to_string(),
+            icon: "".to_string(),
+            description: "Write to an Apache Iceberg table".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: false,
+            hidden: true,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = TestSourceMessage {
+                error: false,
+                done: true,
+                message: "Successfully validated connection".to_string(),
+            };
+            tx.send(Ok(Event::default().json_data(message).unwrap()))
+                .await
+                .unwrap();
+        });
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<crate::Connection> {
+        if table.source.is_some() {
+            bail!("Iceberg tables can only be used as sinks");
+        }
+        if table.partitioning.is_some() {
+            bail!("Iceberg tables are partitioned with the partition_spec of the iceberg settings");
+        }
+        let Some(iceberg) = &table.iceberg else {
+            bail!("iceberg settings must be configured for Iceberg tables");
+        };
+        if table.file_settings.is_none() {
+            bail!("file_settings must be configured for Iceberg tables");
+        }
+        let Some(FormatSettings::Parquet { .. }) = &table.format_settings else {
+            bail!("Iceberg tables must use the parquet format");
+        };
+        // the hadoop catalog commits by atomically renaming the next metadata version into
+        // place, which S3 can't do
+        if let Catalog::HadoopCatalog { warehouse } = &iceberg.catalog {
+            if is_s3_url(warehouse) {
+                bail!("the hadoop catalog cannot be used with an S3 warehouse, which does not support atomic commits; use a rest catalog instead");
+            }
+        }
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Iceberg connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for Iceberg connection"))?;
+
+        if !matches!(format, Format::Parquet(_)) {
+            bail!("Iceberg tables must use the parquet format");
+        }
+
+        validate_partition_spec(iceberg, &schema)?;
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            operator: "connectors::filesystem::iceberg::IcebergSink::<#in_k, #in_t, #in_tRecordBatchBuilder>"
+                .to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description: "Iceberg<Parquet>".to_string(),
+        })
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        opts: &mut std::collections::HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<crate::Connection> {
+        let catalog = match opts.remove("catalog").as_deref() {
+            None | Some("hadoop") => Catalog::HadoopCatalog {
+                warehouse: pull_opt("warehouse", opts)?,
+            },
+            Some("rest") => Catalog::RestCatalog {
+                rest_url: pull_opt("rest_url", opts)?,
+                token: opts.remove("token"),
+                location: pull_opt("location", opts)?,
+            },
+            Some(other) => bail!("catalog must be one of 'hadoop' or 'rest', not '{}'", other),
+        };
+
+        let table = FileSystemTable {
+            source: None,
+            write_target: None,
+            file_settings: Some(file_settings_from_options(opts)?),
+            format_settings: Some(format_settings_from_options(opts, schema)?),
+            partitioning: None,
+            iceberg: Some(IcebergSettings {
+                catalog,
+                namespace: pull_opt("namespace", opts)?,
+                table_name: pull_opt("table_name", opts)?,
+                partition_spec: opts.remove("partition_spec"),
+            }),
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+}
+
+/// Checks that the partition spec (like `day(event_time), bucket(16, user_id)`) uses known
+/// transforms on columns in the schema whose types the transforms support
+fn validate_partition_spec(iceberg: &IcebergSettings, schema: &ConnectionSchema) -> Result<()> {
+    let fields = parse_partition_spec(iceberg.partition_spec.as_deref().unwrap_or_default())
+        .map_err(|e| anyhow!("invalid partition_spec: {}", e))?;
+
+    for PartitionField {
+        source, transform, ..
+    } in fields
+    {
+        let Some(field) = schema.fields.iter().find(|f| f.field_name == source) else {
+            bail!("partition column '{}' is not in the schema", source);
+        };
+
+        match &field.field_type.r#type {
+            FieldType::Primitive(primitive) => transform
+                .validate_source(&source, primitive)
+                .map_err(|e| anyhow!(e))?,
+            FieldType::Struct(_) if transform == Transform::Identity => {}
+            FieldType::Struct(_) => bail!(
+                "partition column '{}' is a struct, which can't be partitioned by {}",
+                source,
+                transform.name()
+            ),
+        }
+    }
+
+    Ok(())
+}
@@ -20,6 +20,7 @@ pub fn impulse_schema() -> ConnectionSchema {
     // use source_field_type::Type::Primitive;
     ConnectionSchema {
         format: None,
+        bad_data: None,
         struct_name: Some("arroyo_types::ImpulseEvent".to_string()),
         fields: vec![
             source_field("counter", Primitive(PrimitiveType::UInt64)),
@@ -157,6 +158,9 @@ impl Connector for ImpulseConnector {
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: None,
+            bad_data: None,
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -1,23 +1,31 @@
 use anyhow::{anyhow, bail};
+use arroyo_rpc::kafka::client_configs;
+use arroyo_rpc::schema_resolver::ConfluentSchemaRegistry;
 use arroyo_rpc::OperatorConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::convert::Infallible;
 use typify::import_types;
 
 use axum::response::sse::Event;
+use futures::StreamExt;
+use regex::Regex;
 use std::time::{Duration, Instant};
 
-use arroyo_rpc::types::{ConnectionSchema, TestSourceMessage};
+use arroyo_rpc::types::{
+    AvroFormat, ConnectionSchema, FieldType, Format, JsonFormat, PrimitiveType, TestSourceMessage,
+};
 use rdkafka::{
-    consumer::{BaseConsumer, Consumer},
+    config::FromClientConfig,
+    consumer::{BaseConsumer, Consumer, StreamConsumer},
     message::BorrowedMessage,
-    ClientConfig, Offset, TopicPartitionList,
+    ClientConfig, Message, Offset, TopicPartitionList,
 };
 use tokio::sync::mpsc::Sender;
 use tonic::Status;
 use tracing::{error, info, warn};
 
-use crate::{pull_opt, Connection, ConnectionType};
+use crate::{pull_opt, Connection, ConnectionType, MetadataDef};
 
 use super::Connector;
 
@@ -25,9 +33,44 @@ const CONFIG_SCHEMA: &str = include_str!("../../connector-schemas/kafka/connecti
 const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/kafka/table.json");
 const ICON: &str = include_str!("../resources/kafka.svg");
 
-import_types!(schema = "../connector-schemas/kafka/connection.json",);
 import_types!(schema = "../connector-schemas/kafka/table.json");
 
+pub use arroyo_rpc::kafka::{
+    BootstrapServers, KafkaConfig, KafkaConfigAuthentication, SchemaRegistry, TlsAuthentication,
+};
+
+const METADATA_DEFS: &[MetadataDef] = &[
+    MetadataDef {
+        name: "topic",
+        data_types: &[PrimitiveType::String],
+    },
+    MetadataDef {
+        name: "partition",
+        data_types: &[PrimitiveType::Int32],
+    },
+    MetadataDef {
+        name: "offset",
+        data_types: &[PrimitiveType::Int64],
+    },
+    MetadataDef {
+        name: "key",
+        data_types: &[PrimitiveType::String, PrimitiveType::Bytes],
+    },
+    MetadataDef {
+        name: "timestamp",
+        data_types: &[
+            PrimitiveType::UnixMillis,
+            PrimitiveType::UnixMicros,
+            PrimitiveType::UnixNanos,
+        ],
+    },
+    // headers are exposed as a JSON object from header names to their values
+    MetadataDef {
+        name: "headers",
+        data_types: &[PrimitiveType::String],
+    },
+];
+
 pub struct KafkaConnector {}
 
 impl Connector for KafkaConnector {
@@ -59,6 +102,61 @@ impl Connector for KafkaConnector {
         (*config.bootstrap_servers).clone()
     }
 
+    fn metadata_defs(&self) -> &'static [MetadataDef] {
+        METADATA_DEFS
+    }
+
+    fn upsert_operator(&self) -> Option<&'static str> {
+        Some("connectors::kafka::sink::KafkaUpsertSinkFunc::<#in_k, #in_t>")
+    }
+
+    fn validate_upsert(&self, table: KafkaTable, primary_keys: &[String]) -> anyhow::Result<()> {
+        let TableType::Sink {
+            key_field,
+            key_template,
+            partitioner,
+            ..
+        } = table.type_
+        else {
+            return Ok(());
+        };
+
+        // compaction only keeps the latest value of a key if every message for it is written to
+        // the same partition under the same key
+        if matches!(
+            partitioner,
+            Some(SinkPartitioner::RoundRobin | SinkPartitioner::Field)
+        ) {
+            bail!("sink.partitioner must be 'default' or 'hash' for tables with a primary key");
+        }
+
+        let key_columns = match (&key_field, &key_template) {
+            (Some(key_field), _) => vec![key_field.as_str()],
+            (None, Some(key_template)) => template_fields(key_template)?,
+            (None, None) => return Ok(()),
+        };
+        let mut key_columns: Vec<_> = key_columns.into_iter().map(|c| c.to_string()).collect();
+        key_columns.sort();
+        key_columns.dedup();
+        let mut primary_key_columns = primary_keys.to_vec();
+        primary_key_columns.sort();
+        if key_columns != primary_key_columns {
+            bail!(
+                "the message key of a table with a primary key must be made of the primary key \
+                columns ({}), but {} uses {}",
+                primary_keys.join(", "),
+                if key_field.is_some() {
+                    "sink.key_field"
+                } else {
+                    "sink.key_template"
+                },
+                key_columns.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+
     fn from_config(
         &self,
         id: Option<i64>,
@@ -67,6 +165,8 @@ impl Connector for KafkaConnector {
         table: KafkaTable,
         schema: Option<&ConnectionSchema>,
     ) -> anyhow::Result<Connection> {
+        validate_authentication(&config.authentication)?;
+
         let (typ, operator, desc) = match table.type_ {
             TableType::Source { .. } => (
                 ConnectionType::Source,
@@ -90,11 +190,92 @@ impl Connector for KafkaConnector {
             .map(|t| t.to_owned())
             .ok_or_else(|| anyhow!("'format' must be set for Kafka connection"))?;
 
+        if let Some(schema_registry) = &config.schema_registry {
+            ConfluentSchemaRegistry::new(&schema_registry.endpoint, &table.topic, None, None)
+                .map_err(|e| anyhow!("invalid schema registry: {}", e))?;
+        }
+
+        if let Format::Avro(AvroFormat {
+            confluent_schema_registry: true,
+            ..
+        }) = &format
+        {
+            if config.schema_registry.is_none() {
+                bail!("avro.confluent_schema_registry requires a schema registry to be configured for the Kafka connection");
+            }
+        }
+
+        // JSON sources can simply skip the schema id, but sinks need to register their schema
+        if let (
+            Format::Json(JsonFormat {
+                confluent_schema_registry: true,
+                ..
+            }),
+            TableType::Sink { .. },
+        ) = (&format, &table.type_)
+        {
+            if config.schema_registry.is_none() {
+                bail!("json.confluent_schema_registry requires a schema registry to be configured for the Kafka connection when used in a sink");
+            }
+        }
+
+        if let TableType::Source {
+            offset,
+            start_timestamp,
+            start_offsets,
+            ..
+        } = &table.type_
+        {
+            validate_start_position(offset, start_timestamp, start_offsets)?;
+        }
+
+        match &table.type_ {
+            TableType::Source {
+                subscription: Some(Subscription::Pattern),
+                ..
+            } => {
+                topic_pattern(&table.topic)
+                    .map_err(|e| anyhow!("invalid topic pattern '{}': {}", table.topic, e))?;
+            }
+            TableType::Source { .. } => {
+                if topic_list(&table.topic).is_empty() {
+                    bail!("no topic set for Kafka source");
+                }
+            }
+            TableType::Sink { .. } => {
+                if table.topic.contains(',') {
+                    bail!("Kafka sinks can only write to a single topic");
+                }
+            }
+        }
+
+        if let TableType::Sink {
+            key_field,
+            key_template,
+            headers_field,
+            partitioner,
+            partition_field,
+            ..
+        } = &table.type_
+        {
+            validate_sink_fields(
+                &schema,
+                key_field,
+                key_template,
+                headers_field,
+                partitioner,
+                partition_field,
+            )?;
+        }
+
         let config = OperatorConfig {
             connection: serde_json::to_value(config).unwrap(),
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -125,6 +306,22 @@ impl Connector for KafkaConnector {
         tester.start();
     }
 
+    fn sample(
+        &self,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        count: usize,
+        tx: Sender<Result<Vec<u8>, String>>,
+    ) -> anyhow::Result<()> {
+        tokio::spawn(async move {
+            if let Err(e) = sample_topic(&config, &table, count, &tx).await {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        Ok(())
+    }
+
     fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
         match table.type_ {
             TableType::Source { .. } => ConnectionType::Source,
@@ -146,16 +343,46 @@ impl Connector for KafkaConnector {
                 protocol: pull_opt("auth.protocol", opts)?,
                 username: pull_opt("auth.username", opts)?,
                 password: pull_opt("auth.password", opts)?,
+                ca_certificate: opts.remove("auth.ca_certificate"),
+            },
+            Some("tls") => KafkaConfigAuthentication::Tls {
+                type_: TlsAuthentication::Tls,
+                ca_certificate: opts.remove("auth.ca_certificate"),
+                client_certificate: opts.remove("auth.client_certificate"),
+                client_key: opts.remove("auth.client_key"),
+                client_key_password: opts.remove("auth.client_key_password"),
+            },
+            Some("oauth") => KafkaConfigAuthentication::OAuth {
+                protocol: opts.remove("auth.protocol"),
+                token_endpoint: pull_opt("auth.token_endpoint", opts)?,
+                client_id: pull_opt("auth.client_id", opts)?,
+                client_secret: pull_opt("auth.client_secret", opts)?,
+                scope: opts.remove("auth.scope"),
+                ca_certificate: opts.remove("auth.ca_certificate"),
             },
             Some(other) => bail!("unknown auth type '{}'", other),
         };
 
+        let schema_registry =
+            opts.remove("schema_registry.endpoint")
+                .map(|endpoint| SchemaRegistry {
+                    endpoint,
+                    api_key: opts.remove("schema_registry.api_key"),
+                    api_secret: opts.remove("schema_registry.api_secret"),
+                });
+
         let connection = KafkaConfig {
             authentication: auth,
             bootstrap_servers: BootstrapServers(pull_opt("bootstrap_servers", opts)?),
+            schema_registry,
         };
 
         let typ = pull_opt("type", opts)?;
+        let topic_pattern = opts.remove("topic_pattern");
+        if topic_pattern.is_some() && typ != "source" {
+            bail!("topic_pattern can only be used with sources");
+        }
+
         let table_type = match typ.as_str() {
             "source" => {
                 let offset = opts.remove("source.offset");
@@ -163,13 +390,26 @@ impl Connector for KafkaConnector {
                     offset: match offset.as_ref().map(|f| f.as_str()) {
                         Some("earliest") => SourceOffset::Earliest,
                         None | Some("latest") => SourceOffset::Latest,
+                        Some("timestamp") => SourceOffset::Timestamp,
+                        Some("offsets") => SourceOffset::Offsets,
                         Some(other) => bail!("invalid value for source.offset '{}'", other),
                     },
+                    start_timestamp: opts
+                        .remove("source.start_timestamp")
+                        .map(|ts| parse_start_timestamp(&ts))
+                        .transpose()?,
+                    start_offsets: opts.remove("source.start_offsets"),
+                    group_id: opts.remove("source.group_id"),
                     read_mode: match opts.remove("source.read_mode").as_ref().map(|f| f.as_str()) {
                         Some("read_committed") => Some(ReadMode::ReadCommitted),
                         Some("read_uncommitted") | None => Some(ReadMode::ReadUncommitted),
                         Some(other) => bail!("invalid value for source.read_mode '{}'", other),
                     },
+                    subscription: Some(if topic_pattern.is_some() {
+                        Subscription::Pattern
+                    } else {
+                        Subscription::Topics
+                    }),
                 }
             }
             "sink" => {
@@ -180,6 +420,18 @@ impl Connector for KafkaConnector {
                         Some("exactly_once") => Some(SinkCommitMode::ExactlyOnce),
                         Some(other) => bail!("invalid value for commit_mode '{}'", other),
                     },
+                    key_field: opts.remove("sink.key_field"),
+                    key_template: opts.remove("sink.key_template"),
+                    headers_field: opts.remove("sink.headers_field"),
+                    partitioner: match opts.remove("sink.partitioner").as_deref() {
+                        None => None,
+                        Some("default") => Some(SinkPartitioner::Default),
+                        Some("hash") => Some(SinkPartitioner::Hash),
+                        Some("round_robin") => Some(SinkPartitioner::RoundRobin),
+                        Some("field") => Some(SinkPartitioner::Field),
+                        Some(other) => bail!("invalid value for sink.partitioner '{}'", other),
+                    },
+                    partition_field: opts.remove("sink.partition_field"),
                 }
             }
             _ => {
@@ -187,8 +439,18 @@ impl Connector for KafkaConnector {
             }
         };
 
+        let topic = match topic_pattern {
+            Some(pattern) => {
+                if opts.contains_key("topic") {
+                    bail!("only one of topic and topic_pattern can be set");
+                }
+                pattern
+            }
+            None => pull_opt("topic", opts)?,
+        };
+
         let table = KafkaTable {
-            topic: pull_opt("topic", opts)?,
+            topic,
             type_: table_type,
         };
 
@@ -196,6 +458,139 @@ impl Connector for KafkaConnector {
     }
 }
 
+fn topic_list(topic: &str) -> Vec<&str> {
+    topic
+        .split(',')
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Topic patterns match whole topic names
+fn topic_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+/// The topics read by a table; for pattern sources, these are the existing topics that match
+fn table_topics(client: &impl Consumer, table: &KafkaTable) -> Result<Vec<String>, String> {
+    let TableType::Source {
+        subscription: Some(Subscription::Pattern),
+        ..
+    } = &table.type_
+    else {
+        return Ok(topic_list(&table.topic)
+            .into_iter()
+            .map(|t| t.to_string())
+            .collect());
+    };
+
+    let pattern =
+        topic_pattern(&table.topic).map_err(|e| format!("Invalid topic pattern: {}", e))?;
+
+    let metadata = client
+        .fetch_metadata(None, Duration::from_secs(10))
+        .map_err(|e| format!("Failed to fetch metadata: {:?}", e))?;
+
+    let topics: Vec<String> = metadata
+        .topics()
+        .iter()
+        .map(|t| t.name())
+        .filter(|t| !t.starts_with("__") && pattern.is_match(t))
+        .map(|t| t.to_string())
+        .collect();
+
+    if topics.is_empty() {
+        return Err(format!(
+            "No topics in the configured Kafka cluster match the pattern '{}'",
+            table.topic
+        ));
+    }
+
+    Ok(topics)
+}
+
+/// Returns the columns referenced by a key template like `{tenant}:{user_id}`
+fn template_fields(key_template: &str) -> anyhow::Result<Vec<&str>> {
+    key_template
+        .split('{')
+        .skip(1)
+        .map(|part| {
+            part.split_once('}')
+                .map(|(field, _)| field)
+                .ok_or_else(|| anyhow!("key_template '{}' has an unclosed '{{'", key_template))
+        })
+        .collect()
+}
+
+fn validate_sink_fields(
+    schema: &ConnectionSchema,
+    key_field: &Option<String>,
+    key_template: &Option<String>,
+    headers_field: &Option<String>,
+    partitioner: &Option<SinkPartitioner>,
+    partition_field: &Option<String>,
+) -> anyhow::Result<()> {
+    let check_field = |field: &str, option: &str| {
+        if schema.fields.iter().any(|f| f.field_name == field) {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} '{}' is not a column of the table",
+                option,
+                field
+            ))
+        }
+    };
+
+    if key_field.is_some() && key_template.is_some() {
+        bail!("only one of key_field and key_template may be set");
+    }
+    if let Some(key_field) = key_field {
+        check_field(key_field, "key_field")?;
+    }
+    if let Some(key_template) = key_template {
+        for field in template_fields(key_template)? {
+            check_field(field, "key_template column")?;
+        }
+    }
+    if let Some(headers_field) = headers_field {
+        check_field(headers_field, "headers_field")?;
+    }
+    match (partitioner, partition_field) {
+        (Some(SinkPartitioner::Field), Some(partition_field)) => {
+            check_field(partition_field, "partition_field")?;
+            let field = schema
+                .fields
+                .iter()
+                .find(|f| &f.field_name == partition_field)
+                .unwrap();
+            if !matches!(
+                field.field_type.r#type,
+                FieldType::Primitive(
+                    PrimitiveType::Int32
+                        | PrimitiveType::Int64
+                        | PrimitiveType::UInt32
+                        | PrimitiveType::UInt64
+                )
+            ) {
+                bail!(
+                    "partition_field '{}' must be an integer column",
+                    partition_field
+                );
+            }
+        }
+        (Some(SinkPartitioner::Field), None) => {
+            bail!("partition_field must be set when using the 'field' partitioner");
+        }
+        (_, Some(_)) => {
+            bail!("partition_field can only be used with the 'field' partitioner");
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
 struct KafkaTester {
     connection: KafkaConfig,
     table: KafkaTable,
@@ -206,42 +601,183 @@ pub struct TopicMetadata {
     pub partitions: usize,
 }
 
-impl KafkaTester {
-    async fn connect(&self) -> Result<BaseConsumer, String> {
-        let mut client_config = ClientConfig::new();
-        client_config
-            .set(
-                "bootstrap.servers",
-                &self.connection.bootstrap_servers.to_string(),
+/// Parses a start timestamp, given either as milliseconds since the epoch or as an RFC 3339 string
+fn parse_start_timestamp(timestamp: &str) -> anyhow::Result<i64> {
+    if let Ok(millis) = timestamp.parse::<i64>() {
+        return Ok(millis);
+    }
+
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.timestamp_millis())
+        .map_err(|_| {
+            anyhow!(
+                "invalid source.start_timestamp '{}'; must be milliseconds since the epoch or an RFC 3339 timestamp",
+                timestamp
             )
-            .set("enable.auto.commit", "false")
-            .set("auto.offset.reset", "earliest")
-            .set("group.id", "arroyo-kafka-source-tester");
-
-        match &self.connection.authentication {
-            KafkaConfigAuthentication::None {} => {}
-            KafkaConfigAuthentication::Sasl {
-                mechanism,
-                password,
-                protocol,
-                username,
-            } => {
-                client_config.set("sasl.mechanism", mechanism);
-                client_config.set("security.protocol", protocol);
-                client_config.set("sasl.username", username);
-                client_config.set("sasl.password", password);
+        })
+}
+
+fn validate_start_position(
+    offset: &SourceOffset,
+    start_timestamp: &Option<i64>,
+    start_offsets: &Option<String>,
+) -> anyhow::Result<()> {
+    match (offset, start_timestamp, start_offsets) {
+        (SourceOffset::Timestamp, None, _) => {
+            bail!("a start timestamp must be set for Kafka sources that start from a timestamp")
+        }
+        (SourceOffset::Offsets, _, None) => {
+            bail!("start offsets must be set for Kafka sources that start from offsets")
+        }
+        (offset, _, Some(_)) if !matches!(offset, SourceOffset::Offsets) => {
+            bail!("start offsets can only be set when the offset is 'offsets'")
+        }
+        (offset, Some(_), _) if !matches!(offset, SourceOffset::Timestamp) => {
+            bail!("a start timestamp can only be set when the offset is 'timestamp'")
+        }
+        _ => {}
+    }
+
+    if let Some(offsets) = start_offsets {
+        for entry in offsets
+            .split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+        {
+            let parts: Vec<_> = entry.split(':').collect();
+            let valid = matches!(parts.len(), 2 | 3)
+                && parts[parts.len() - 2].parse::<i32>().is_ok()
+                && parts[parts.len() - 1].parse::<i64>().is_ok();
+            if !valid {
+                bail!(
+                    "invalid start offset '{}'; expected 'partition:offset' or 'topic:partition:offset'",
+                    entry
+                );
             }
-        };
+        }
+    }
 
-        let client: BaseConsumer = client_config
-            .create()
-            .map_err(|e| format!("Failed to connect: {:?}", e))?;
+    Ok(())
+}
 
-        client
-            .fetch_metadata(None, Duration::from_secs(10))
-            .map_err(|e| format!("Failed to connect to Kafka: {:?}", e))?;
+fn validate_authentication(authentication: &KafkaConfigAuthentication) -> anyhow::Result<()> {
+    match authentication {
+        KafkaConfigAuthentication::Tls {
+            client_certificate,
+            client_key,
+            ..
+        } => {
+            if client_certificate.is_some() != client_key.is_some() {
+                bail!("both a client certificate and a client key must be set for mTLS");
+            }
+        }
+        KafkaConfigAuthentication::OAuth { protocol, .. } => {
+            if let Some(protocol) = protocol {
+                if protocol != "SASL_SSL" && protocol != "SASL_PLAINTEXT" {
+                    bail!(
+                        "invalid protocol '{}' for OAuth; must be SASL_SSL or SASL_PLAINTEXT",
+                        protocol
+                    );
+                }
+            }
+        }
+        KafkaConfigAuthentication::None {} | KafkaConfigAuthentication::Sasl { .. } => {}
+    }
 
-        Ok(client)
+    Ok(())
+}
+
+fn create_consumer<C: Consumer + FromClientConfig>(connection: &KafkaConfig) -> Result<C, String> {
+    validate_authentication(&connection.authentication).map_err(|e| e.to_string())?;
+
+    let mut client_config = ClientConfig::new();
+    client_config
+        .set(
+            "bootstrap.servers",
+            &connection.bootstrap_servers.to_string(),
+        )
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .set("group.id", "arroyo-kafka-source-tester");
+
+    for (key, value) in client_configs(connection) {
+        client_config.set(key, value);
+    }
+
+    let client: C = client_config
+        .create()
+        .map_err(|e| format!("Failed to connect: {:?}", e))?;
+
+    client
+        .fetch_metadata(None, Duration::from_secs(10))
+        .map_err(|e| format!("Failed to connect to Kafka: {:?}", e))?;
+
+    Ok(client)
+}
+
+/// Assigns all partitions of the topics to the consumer, starting from the earliest offset
+fn assign_topics(client: &impl Consumer, topics: &[String]) -> Result<(), String> {
+    let mut map = HashMap::new();
+    for topic in topics {
+        map.extend(topic_partitions(client, topic)?);
+    }
+
+    client
+        .assign(&TopicPartitionList::from_topic_map(&map).unwrap())
+        .map_err(|e| {
+            format!(
+                "Failed to subscribe to topics {}: {:?}",
+                topics.join(", "),
+                e
+            )
+        })
+}
+
+fn topic_partitions(
+    client: &impl Consumer,
+    topic: &str,
+) -> Result<HashMap<(String, i32), Offset>, String> {
+    let metadata = client
+        .fetch_metadata(Some(topic), Duration::from_secs(10))
+        .map_err(|e| format!("Failed to fetch metadata: {:?}", e))?;
+
+    let topic_metadata = metadata.topics().get(0).ok_or_else(|| {
+        format!(
+            "Returned metadata was empty; unable to subscribe to topic '{}'",
+            topic
+        )
+    })?;
+
+    if let Some(err) = topic_metadata.error() {
+        match err {
+            rdkafka::types::RDKafkaRespErr::RD_KAFKA_RESP_ERR__UNKNOWN_PARTITION
+            | rdkafka::types::RDKafkaRespErr::RD_KAFKA_RESP_ERR__UNKNOWN_TOPIC
+            | rdkafka::types::RDKafkaRespErr::RD_KAFKA_RESP_ERR_UNKNOWN_TOPIC_OR_PART => {
+                return Err(format!(
+                    "Topic '{}' does not exist in the configured Kafka cluster",
+                    topic
+                ));
+            }
+            e => {
+                error!("Unhandled Kafka error while fetching metadata: {:?}", e);
+                return Err(format!(
+                    "Something went wrong while fetching topic metadata: {:?}",
+                    e
+                ));
+            }
+        }
+    }
+
+    Ok(topic_metadata
+        .partitions()
+        .iter()
+        .map(|p| ((topic.to_string(), p.id()), Offset::Beginning))
+        .collect())
+}
+
+impl KafkaTester {
+    async fn connect(&self) -> Result<BaseConsumer, String> {
+        create_consumer(&self.connection)
     }
 
     #[allow(unused)]
@@ -283,53 +819,11 @@ impl KafkaTester {
 
         self.info("Connected to Kafka").await;
 
-        let topic = self.table.topic.clone();
-
-        let metadata = client
-            .fetch_metadata(Some(&topic), Duration::from_secs(10))
-            .map_err(|e| format!("Failed to fetch metadata: {:?}", e))?;
+        let topics = table_topics(&client, &self.table)?;
+        assign_topics(&client, &topics)?;
 
         self.info("Fetched topic metadata").await;
 
-        {
-            let topic_metadata = metadata.topics().get(0).ok_or_else(|| {
-                format!(
-                    "Returned metadata was empty; unable to subscribe to topic '{}'",
-                    topic
-                )
-            })?;
-
-            if let Some(err) = topic_metadata.error() {
-                match err {
-                    rdkafka::types::RDKafkaRespErr::RD_KAFKA_RESP_ERR__UNKNOWN_PARTITION
-                    | rdkafka::types::RDKafkaRespErr::RD_KAFKA_RESP_ERR__UNKNOWN_TOPIC
-                    | rdkafka::types::RDKafkaRespErr::RD_KAFKA_RESP_ERR_UNKNOWN_TOPIC_OR_PART => {
-                        return Err(format!(
-                            "Topic '{}' does not exist in the configured Kafka cluster",
-                            topic
-                        ));
-                    }
-                    e => {
-                        error!("Unhandled Kafka error while fetching metadata: {:?}", e);
-                        return Err(format!(
-                            "Something went wrong while fetching topic metadata: {:?}",
-                            e
-                        ));
-                    }
-                }
-            }
-
-            let map = topic_metadata
-                .partitions()
-                .iter()
-                .map(|p| ((topic.clone(), p.id()), Offset::Beginning))
-                .collect();
-
-            client
-                .assign(&TopicPartitionList::from_topic_map(&map).unwrap())
-                .map_err(|e| format!("Failed to subscribe to topic '{}': {:?}", topic, e))?;
-        }
-
         if let TableType::Source { .. } = self.table.type_ {
             self.info("Waiting for messages").await;
 
@@ -424,3 +918,55 @@ impl KafkaTester {
         });
     }
 }
+
+async fn sample_topic(
+    connection: &KafkaConfig,
+    table: &KafkaTable,
+    count: usize,
+    tx: &Sender<Result<Vec<u8>, String>>,
+) -> Result<(), String> {
+    let client: StreamConsumer = create_consumer(connection)?;
+    let topics = table_topics(&client, table)?;
+    assign_topics(&client, &topics)?;
+
+    let mut stream = client.stream();
+    let mut sampled = 0;
+    let timeout = Duration::from_secs(30);
+    let deadline = Instant::now() + timeout;
+    while sampled < count {
+        // once messages stop arriving we've likely read everything in the topic
+        let wait = if sampled == 0 {
+            deadline.saturating_duration_since(Instant::now())
+        } else {
+            Duration::from_secs(2).min(deadline.saturating_duration_since(Instant::now()))
+        };
+
+        let payload = match tokio::time::timeout(wait, stream.next()).await {
+            Ok(Some(Ok(message))) => message.payload().map(|p| p.to_vec()),
+            Ok(Some(Err(e))) => {
+                return Err(format!("Error while reading messages from Kafka: {}", e));
+            }
+            Ok(None) | Err(_) => break,
+        };
+
+        // tombstones don't tell us anything about the schema
+        let Some(payload) = payload else {
+            continue;
+        };
+
+        if tx.send(Ok(payload)).await.is_err() {
+            warn!("Sample rx closed while sending message");
+            return Ok(());
+        }
+        sampled += 1;
+    }
+
+    if sampled == 0 {
+        return Err(format!(
+            "No messages received from Kafka within {} seconds",
+            timeout.as_secs()
+        ));
+    }
+
+    Ok(())
+}
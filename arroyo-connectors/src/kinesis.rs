@@ -1,6 +1,11 @@
 use anyhow::{anyhow, bail, Result};
+use aws_config::from_env;
+use aws_sdk_kinesis::{model::ShardIteratorType, Client as KinesisClient, Region};
 use axum::response::sse::Event;
 use std::convert::Infallible;
+use std::future::Future;
+use tokio::sync::mpsc::Sender;
+use tracing::warn;
 use typify::import_types;
 
 use arroyo_rpc::types::TestSourceMessage;
@@ -66,6 +71,22 @@ impl Connector for KinesisConnector {
         });
     }
 
+    fn sample(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        count: usize,
+        tx: Sender<Result<Vec<u8>, String>>,
+    ) -> anyhow::Result<()> {
+        tokio::spawn(async move {
+            if let Err(e) = sample_stream(&table, count, &tx).await {
+                let _ = tx.send(Err(format!("{:?}", e))).await;
+            }
+        });
+
+        Ok(())
+    }
+
     fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
         return match table.type_ {
             TableType::Source { .. } => ConnectionType::Source,
@@ -108,6 +129,9 @@ impl Connector for KinesisConnector {
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -164,3 +188,176 @@ impl Connector for KinesisConnector {
         Self::from_config(&self, None, name, EmptyConfig {}, table, schema)
     }
 }
+
+async fn sample_stream(
+    table: &KinesisTable,
+    count: usize,
+    tx: &Sender<Result<Vec<u8>, String>>,
+) -> anyhow::Result<()> {
+    let mut loader = from_env();
+    if let Some(region) = &table.aws_region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    let client = KinesisClient::new(&loader.load().await);
+
+    let shards = client
+        .list_shards()
+        .stream_name(&table.stream_name)
+        .send()
+        .await?;
+
+    let mut sampled = 0;
+    for shard in shards.shards().unwrap_or_default() {
+        let shard_iterator = client
+            .get_shard_iterator()
+            .stream_name(&table.stream_name)
+            .set_shard_id(shard.shard_id().map(|s| s.to_string()))
+            .shard_iterator_type(ShardIteratorType::TrimHorizon)
+            .send()
+            .await?
+            .shard_iterator()
+            .map(|s| s.to_string());
+
+        let get_records = |iterator: String, limit: usize| {
+            let client = &client;
+            async move {
+                let output = client
+                    .get_records()
+                    .shard_iterator(iterator)
+                    .limit(limit as i32)
+                    .send()
+                    .await?;
+
+                Ok::<_, anyhow::Error>(RecordsPage {
+                    records: output
+                        .records()
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|r| r.data().map(|d| d.as_ref().to_vec()))
+                        .collect(),
+                    next_iterator: output.next_shard_iterator().map(|s| s.to_string()),
+                    caught_up: output.millis_behind_latest() == Some(0),
+                })
+            }
+        };
+
+        if !sample_shard(get_records, shard_iterator, count, &mut sampled, tx).await? {
+            return Ok(());
+        }
+    }
+
+    if sampled == 0 {
+        bail!("Stream {} does not contain any records", table.stream_name);
+    }
+
+    Ok(())
+}
+
+/// The records returned by a single GetRecords call
+struct RecordsPage {
+    records: Vec<Vec<u8>>,
+    next_iterator: Option<String>,
+    /// Whether the page reached the end of the shard
+    caught_up: bool,
+}
+
+/// Sends records from a shard until `count` have been sampled in total or the end of the shard
+/// is reached, returning false if sampling should stop
+async fn sample_shard<F, Fut>(
+    mut get_records: F,
+    mut shard_iterator: Option<String>,
+    count: usize,
+    sampled: &mut usize,
+    tx: &Sender<Result<Vec<u8>, String>>,
+) -> anyhow::Result<bool>
+where
+    F: FnMut(String, usize) -> Fut,
+    Fut: Future<Output = anyhow::Result<RecordsPage>>,
+{
+    while let Some(iterator) = shard_iterator {
+        if *sampled >= count {
+            return Ok(false);
+        }
+
+        let page = get_records(iterator, count - *sampled).await?;
+        for record in page.records {
+            if tx.send(Ok(record)).await.is_err() {
+                warn!("Sample rx closed while sending message");
+                return Ok(false);
+            }
+            *sampled += 1;
+        }
+
+        if page.caught_up {
+            break;
+        }
+        shard_iterator = page.next_iterator;
+    }
+
+    Ok(*sampled < count)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::channel;
+
+    use super::{sample_shard, RecordsPage};
+
+    #[tokio::test]
+    async fn test_sample_shard() {
+        let (tx, mut rx) = channel(10);
+
+        // two pages of two records each, after which the shard is caught up
+        let mut calls = vec![];
+        let get_records = |iterator: String, limit: usize| {
+            calls.push((iterator.clone(), limit));
+            async move {
+                let page: usize = iterator.parse().unwrap();
+                Ok(RecordsPage {
+                    records: vec![vec![page as u8 * 2], vec![page as u8 * 2 + 1]],
+                    next_iterator: Some((page + 1).to_string()),
+                    caught_up: page == 1,
+                })
+            }
+        };
+
+        let mut sampled = 0;
+        let more = sample_shard(get_records, Some("0".to_string()), 10, &mut sampled, &tx)
+            .await
+            .unwrap();
+        assert!(
+            more,
+            "the sample isn't full, so other shards should be read"
+        );
+        assert_eq!(sampled, 4);
+        assert_eq!(calls, vec![("0".to_string(), 10), ("1".to_string(), 8)]);
+
+        for i in 0..4 {
+            assert_eq!(rx.try_recv().unwrap().unwrap(), vec![i]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sample_shard_stops_at_count() {
+        let (tx, mut rx) = channel(10);
+
+        let get_records = |_: String, limit: usize| async move {
+            Ok(RecordsPage {
+                records: vec![vec![0]; limit],
+                next_iterator: Some("next".to_string()),
+                caught_up: false,
+            })
+        };
+
+        let mut sampled = 1;
+        let more = sample_shard(get_records, Some("start".to_string()), 3, &mut sampled, &tx)
+            .await
+            .unwrap();
+        assert!(!more);
+        assert_eq!(sampled, 3);
+
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,12 +1,12 @@
+use self::fluvio::FluvioConnector;
 use anyhow::{anyhow, bail, Context};
 use arroyo_rpc::primitive_to_sql;
 use arroyo_rpc::types::{
-    ConnectionSchema, ConnectionType, FieldType, SourceField, SourceFieldType,
+    ConnectionSchema, ConnectionType, FieldType, PrimitiveType, SourceField, SourceFieldType,
 };
 use arroyo_types::string_to_map;
 use axum::response::sse::Event;
 use blackhole::BlackholeConnector;
-use fluvio::FluvioConnector;
 use impulse::ImpulseConnector;
 use nexmark::NexmarkConnector;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
@@ -22,13 +22,18 @@ use websocket::WebsocketConnector;
 use self::kafka::KafkaConnector;
 
 pub mod blackhole;
+pub mod delta;
 pub mod filesystem;
 pub mod fluvio;
+pub mod iceberg;
 pub mod impulse;
 pub mod kafka;
 pub mod kinesis;
+pub mod mqtt;
 pub mod nexmark;
 pub mod polling_http;
+pub mod postgres;
+pub mod redis;
 pub mod single_file;
 pub mod sse;
 pub mod webhook;
@@ -36,16 +41,21 @@ pub mod websocket;
 pub fn connectors() -> HashMap<&'static str, Box<dyn ErasedConnector>> {
     let mut m: HashMap<&'static str, Box<dyn ErasedConnector>> = HashMap::new();
     m.insert("blackhole", Box::new(BlackholeConnector {}));
+    m.insert("delta", Box::new(delta::DeltaLakeConnector {}));
     m.insert("filesystem", Box::new(filesystem::FileSystemConnector {}));
     m.insert("fluvio", Box::new(FluvioConnector {}));
+    m.insert("iceberg", Box::new(iceberg::IcebergConnector {}));
     m.insert("impulse", Box::new(ImpulseConnector {}));
     m.insert("kafka", Box::new(KafkaConnector {}));
     m.insert("kinesis", Box::new(kinesis::KinesisConnector {}));
+    m.insert("mqtt", Box::new(mqtt::MqttConnector {}));
     m.insert("nexmark", Box::new(NexmarkConnector {}));
     m.insert(
         "polling_http",
         Box::new(polling_http::PollingHTTPConnector {}),
     );
+    m.insert("postgres", Box::new(postgres::PostgresConnector {}));
+    m.insert("redis", Box::new(redis::RedisConnector {}));
     m.insert("single_file", Box::new(single_file::SingleFileConnector {}));
     m.insert("sse", Box::new(SSEConnector {}));
     m.insert("webhook", Box::new(webhook::WebhookConnector {}));
@@ -68,6 +78,12 @@ pub struct Connection {
     pub description: String,
 }
 
+/// Metadata that a source can expose as a column, which must have one of the given types
+pub struct MetadataDef {
+    pub name: &'static str,
+    pub data_types: &'static [PrimitiveType],
+}
+
 pub trait Connector: Send {
     type ProfileT: DeserializeOwned + Serialize;
     type TableT: DeserializeOwned + Serialize;
@@ -110,6 +126,40 @@ pub trait Connector: Send {
         tx: Sender<Result<Event, Infallible>>,
     );
 
+    /// Reads up to `count` messages from the start of a source table, which are used to infer its
+    /// schema. Messages are sent to `tx` as they are read, and the channel is closed when sampling
+    /// is complete.
+    #[allow(unused)]
+    fn sample(
+        &self,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        count: usize,
+        tx: Sender<Result<Vec<u8>, String>>,
+    ) -> anyhow::Result<()> {
+        bail!(
+            "sampling messages is not supported for {} tables",
+            self.name()
+        )
+    }
+
+    /// The metadata of each message that sources of this connector can expose as columns
+    fn metadata_defs(&self) -> &'static [MetadataDef] {
+        &[]
+    }
+
+    /// The operator used for sinks of tables with a primary key, which receive updating data keyed
+    /// by the primary key and write it as upserts; None if the connector doesn't support upserts
+    fn upsert_operator(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Checks that the table can be written as upserts keyed by the given primary keys
+    #[allow(unused)]
+    fn validate_upsert(&self, table: Self::TableT, primary_keys: &[String]) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn from_options(
         &self,
         name: &str,
@@ -160,6 +210,24 @@ pub trait ErasedConnector: Send {
         tx: Sender<Result<Event, Infallible>>,
     ) -> Result<(), serde_json::Error>;
 
+    fn sample(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+        count: usize,
+        tx: Sender<Result<Vec<u8>, String>>,
+    ) -> anyhow::Result<()>;
+
+    fn metadata_defs(&self) -> &'static [MetadataDef];
+
+    fn upsert_operator(&self) -> Option<&'static str>;
+
+    fn validate_upsert(
+        &self,
+        table: &serde_json::Value,
+        primary_keys: &[String],
+    ) -> anyhow::Result<()>;
+
     fn from_options(
         &self,
         name: &str,
@@ -236,6 +304,37 @@ impl<C: Connector> ErasedConnector for C {
         Ok(())
     }
 
+    fn sample(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+        count: usize,
+        tx: Sender<Result<Vec<u8>, String>>,
+    ) -> anyhow::Result<()> {
+        self.sample(
+            self.parse_config(config)?,
+            self.parse_table(table)?,
+            count,
+            tx,
+        )
+    }
+
+    fn metadata_defs(&self) -> &'static [MetadataDef] {
+        self.metadata_defs()
+    }
+
+    fn upsert_operator(&self) -> Option<&'static str> {
+        self.upsert_operator()
+    }
+
+    fn validate_upsert(
+        &self,
+        table: &serde_json::Value,
+        primary_keys: &[String],
+    ) -> anyhow::Result<()> {
+        self.validate_upsert(self.parse_table(table)?, primary_keys)
+    }
+
     fn from_options(
         &self,
         name: &str,
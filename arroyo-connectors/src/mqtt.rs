@@ -0,0 +1,300 @@
+use anyhow::{anyhow, bail};
+use arroyo_rpc::mqtt::{mqtt_options, parse_url, MqttOptions};
+use arroyo_rpc::types::{ConnectionSchema, PrimitiveType, TestSourceMessage};
+use arroyo_rpc::OperatorConfig;
+use axum::response::sse::Event;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tracing::warn;
+use typify::import_types;
+
+use crate::{pull_opt, Connection, ConnectionType, Connector, MetadataDef};
+
+pub struct MqttConnector {}
+
+const CONFIG_SCHEMA: &str = include_str!("../../connector-schemas/mqtt/connection.json");
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/mqtt/table.json");
+const ICON: &str = include_str!("../resources/mqtt.svg");
+
+const METADATA_DEFS: &[MetadataDef] = &[MetadataDef {
+    name: "topic",
+    data_types: &[PrimitiveType::String],
+}];
+
+import_types!(schema = "../connector-schemas/mqtt/table.json");
+
+pub use arroyo_rpc::mqtt::{MqttConfig, MqttConfigTls, ProtocolVersion};
+
+impl Connector for MqttConnector {
+    type ProfileT = MqttConfig;
+    type TableT = MqttTable;
+
+    fn name(&self) -> &'static str {
+        "mqtt"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::types::Connector {
+        arroyo_rpc::types::Connector {
+            id: "mqtt".to_string(),
+            name: "MQTT".to_string(),
+            icon: ICON.to_string(),
+            description: "Subscribe to or publish to topics on an MQTT broker".to_string(),
+            enabled: true,
+            source: true,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_string()),
+            table_config: TABLE_SCHEMA.to_string(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        config.url
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.type_ {
+            TableType::Source {} => ConnectionType::Source,
+            TableType::Sink { .. } => ConnectionType::Sink,
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        _: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::spawn(async move {
+            let message = match test_connection(&config).await {
+                Ok(()) => TestSourceMessage {
+                    error: false,
+                    done: true,
+                    message: "Connection is valid".to_string(),
+                },
+                Err(e) => TestSourceMessage {
+                    error: true,
+                    done: true,
+                    message: e.to_string(),
+                },
+            };
+
+            if tx
+                .send(Ok(Event::default().json_data(message).unwrap()))
+                .await
+                .is_err()
+            {
+                warn!("Test rx closed while sending message");
+            }
+        });
+    }
+
+    fn metadata_defs(&self) -> &'static [MetadataDef] {
+        METADATA_DEFS
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let protocol_version = match options.remove("protocol_version").as_deref() {
+            Some("v3") | None => Some(ProtocolVersion::V3),
+            Some("v5") => Some(ProtocolVersion::V5),
+            Some(other) => bail!("invalid value for protocol_version '{}'", other),
+        };
+
+        let tls = MqttConfigTls {
+            ca_certificate: options.remove("tls.ca_certificate"),
+            client_certificate: options.remove("tls.client_certificate"),
+            client_key: options.remove("tls.client_key"),
+        };
+
+        let connection = MqttConfig {
+            url: pull_opt("url", options)?,
+            protocol_version,
+            username: options.remove("username"),
+            password: options.remove("password"),
+            client_prefix: options.remove("client_prefix"),
+            tls: (tls.ca_certificate.is_some()
+                || tls.client_certificate.is_some()
+                || tls.client_key.is_some())
+            .then_some(tls),
+        };
+
+        let type_ = match pull_opt("type", options)?.as_str() {
+            "source" => TableType::Source {},
+            "sink" => TableType::Sink {
+                retain: options
+                    .remove("sink.retain")
+                    .map(|retain| {
+                        retain
+                            .parse()
+                            .map_err(|_| anyhow!("invalid value for sink.retain '{}'", retain))
+                    })
+                    .transpose()?,
+            },
+            _ => bail!("type must be one of 'source' or 'sink'"),
+        };
+
+        let qos = match options.remove("qos").as_deref() {
+            Some("at_least_once") | None => Some(MqttTableQos::AtLeastOnce),
+            Some("at_most_once") => Some(MqttTableQos::AtMostOnce),
+            Some(other) => bail!("invalid value for qos '{}'", other),
+        };
+
+        let table = MqttTable {
+            topic: pull_opt("topic", options)?,
+            qos,
+            type_,
+        };
+
+        self.from_config(None, name, connection, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: MqttConfig,
+        table: MqttTable,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let (tls, _, _) = parse_url(&config.url)?;
+
+        if let Some(certificates) = &config.tls {
+            if !tls {
+                bail!("TLS certificates can only be used with mqtts:// URLs");
+            }
+            if certificates.client_certificate.is_some() != certificates.client_key.is_some() {
+                bail!("both a client certificate and a client key must be set for mTLS");
+            }
+        }
+
+        let (typ, operator, description) = match table.type_ {
+            TableType::Source {} => {
+                validate_topic_filter(&table.topic)?;
+                (
+                    ConnectionType::Source,
+                    "connectors::mqtt::source::MqttSourceFunc",
+                    format!("MqttSource<{}>", table.topic),
+                )
+            }
+            TableType::Sink { .. } => {
+                if table.topic.is_empty() || table.topic.contains(['+', '#']) {
+                    bail!(
+                        "invalid topic '{}'; sinks must publish to a single topic, without wildcards",
+                        table.topic
+                    );
+                }
+                (
+                    ConnectionType::Sink,
+                    "connectors::mqtt::sink::MqttSinkFunc::<#in_k, #in_t>",
+                    format!("MqttSink<{}>", table.topic),
+                )
+            }
+        };
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for MQTT connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for MQTT connection"))?;
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: typ,
+            schema,
+            operator: operator.to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+}
+
+/// Checks that wildcards in a topic filter occupy whole levels, and that `#` is only used as the
+/// last level
+fn validate_topic_filter(filter: &str) -> anyhow::Result<()> {
+    if filter.is_empty() {
+        bail!("topic filter can't be empty");
+    }
+
+    let levels: Vec<_> = filter.split('/').collect();
+    for (i, level) in levels.iter().enumerate() {
+        let valid = match *level {
+            "#" => i == levels.len() - 1,
+            "+" => true,
+            level => !level.contains(['+', '#']),
+        };
+
+        if !valid {
+            bail!(
+                "invalid topic filter '{}'; wildcards must be a whole level, and # must be the last level",
+                filter
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn test_connection(config: &MqttConfig) -> anyhow::Result<()> {
+    tokio::time::timeout(Duration::from_secs(10), connect(config))
+        .await
+        .map_err(|_| anyhow!("timed out connecting to {}", config.url))?
+        .map_err(|e| anyhow!("failed to connect to MQTT broker: {}", e))
+}
+
+/// Connects to the broker, returning once it has accepted the connection
+async fn connect(config: &MqttConfig) -> anyhow::Result<()> {
+    let client_id = format!(
+        "{}-tester",
+        config.client_prefix.as_deref().unwrap_or("arroyo")
+    );
+
+    match mqtt_options(config, client_id)? {
+        MqttOptions::V3(options) => {
+            let (_client, mut eventloop) = rumqttc::AsyncClient::new(options, 10);
+            loop {
+                if let rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) =
+                    eventloop.poll().await?
+                {
+                    return Ok(());
+                }
+            }
+        }
+        MqttOptions::V5(options) => {
+            let (_client, mut eventloop) = rumqttc::v5::AsyncClient::new(options, 10);
+            loop {
+                if let rumqttc::v5::Event::Incoming(rumqttc::v5::Incoming::ConnAck(_)) =
+                    eventloop.poll().await?
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
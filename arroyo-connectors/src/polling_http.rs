@@ -206,6 +206,9 @@ impl Connector for PollingHTTPConnector {
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -0,0 +1,315 @@
+use anyhow::{anyhow, bail};
+use arroyo_rpc::postgres::quote_qualified;
+use arroyo_rpc::types::{ConnectionSchema, Format, JsonFormat, TestSourceMessage, TimestampFormat};
+use arroyo_rpc::OperatorConfig;
+use axum::response::sse::Event;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tokio::sync::mpsc::Sender;
+use tokio_postgres::{Client, NoTls};
+use tracing::{info, warn};
+use typify::import_types;
+
+use crate::{pull_opt, Connection, ConnectionType, Connector};
+
+pub struct PostgresConnector {}
+
+const CONFIG_SCHEMA: &str = include_str!("../../connector-schemas/postgres/connection.json");
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/postgres/table.json");
+const ICON: &str = include_str!("../resources/postgres.svg");
+
+import_types!(schema = "../connector-schemas/postgres/connection.json");
+import_types!(schema = "../connector-schemas/postgres/table.json");
+
+impl Connector for PostgresConnector {
+    type ProfileT = PostgresConfig;
+    type TableT = PostgresTable;
+
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::types::Connector {
+        arroyo_rpc::types::Connector {
+            id: "postgres".to_string(),
+            name: "Postgres".to_string(),
+            icon: ICON.to_string(),
+            description: "Read changes from or write to a Postgres table".to_string(),
+            enabled: true,
+            source: true,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_string()),
+            table_config: TABLE_SCHEMA.to_string(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        format!(
+            "{}:{}/{}",
+            config.host,
+            config.port.unwrap_or(5432),
+            config.database
+        )
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.type_ {
+            Some(TableType::Source) => ConnectionType::Source,
+            Some(TableType::Sink) | None => ConnectionType::Sink,
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+        tx: Sender<Result<Event, Infallible>>,
+    ) {
+        let schema = schema.cloned();
+        tokio::spawn(async move {
+            let message = match test_table(&config, &table, schema.as_ref()).await {
+                Ok(()) => TestSourceMessage {
+                    error: false,
+                    done: true,
+                    message: "Connection is valid".to_string(),
+                },
+                Err(e) => TestSourceMessage {
+                    error: true,
+                    done: true,
+                    message: e.to_string(),
+                },
+            };
+
+            if tx
+                .send(Ok(Event::default().json_data(message).unwrap()))
+                .await
+                .is_err()
+            {
+                warn!("Test rx closed while sending message");
+            }
+        });
+    }
+
+    fn upsert_operator(&self) -> Option<&'static str> {
+        Some("connectors::postgres::sink::PostgresUpsertSinkFunc::<#in_k, #in_t>")
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let port = options
+            .remove("port")
+            .map(|port| {
+                port.parse()
+                    .map_err(|_| anyhow!("invalid value for port '{}'", port))
+            })
+            .transpose()?;
+
+        let connection = PostgresConfig {
+            host: pull_opt("host", options)?,
+            port,
+            database: pull_opt("database", options)?,
+            username: pull_opt("username", options)?,
+            password: options.remove("password"),
+        };
+
+        let typ = match options.remove("type").as_deref() {
+            None | Some("sink") => TableType::Sink,
+            Some("source") => TableType::Source,
+            Some(_) => bail!("type must be one of 'source' or 'sink'"),
+        };
+
+        let snapshot = options
+            .remove("source.snapshot")
+            .map(|snapshot| {
+                snapshot
+                    .parse()
+                    .map_err(|_| anyhow!("invalid value for source.snapshot '{}'", snapshot))
+            })
+            .transpose()?;
+
+        let commit_mode = options.remove("sink.commit_mode");
+        let batch_size = options
+            .remove("sink.batch_size")
+            .map(|size| {
+                size.parse()
+                    .map_err(|_| anyhow!("invalid value for sink.batch_size '{}'", size))
+            })
+            .transpose()?;
+
+        let table = PostgresTable {
+            table_name: pull_opt("table_name", options)?,
+            type_: Some(typ),
+            slot_name: options.remove("source.slot_name"),
+            publication: options.remove("source.publication"),
+            snapshot,
+            commit_mode: match commit_mode.as_ref().map(|f| f.as_str()) {
+                Some("at_least_once") | None => Some(PostgresTableCommitMode::AtLeastOnce),
+                Some("exactly_once") => Some(PostgresTableCommitMode::ExactlyOnce),
+                Some(other) => bail!("invalid value for commit_mode '{}'", other),
+            },
+            batch_size,
+        };
+
+        self.from_config(None, name, connection, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: PostgresConfig,
+        table: PostgresTable,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Postgres connection"))?;
+
+        // rows are read and written as JSON that Postgres converts from and into the column
+        // types, so timestamps need to be in the default RFC3339 encoding
+        if schema.format.is_some() {
+            bail!("'format' can't be set for Postgres tables");
+        }
+
+        if matches!(table.batch_size, Some(size) if size < 1) {
+            bail!("sink.batch_size must be at least 1");
+        }
+
+        let (typ, operator, description) = match table.type_ {
+            Some(TableType::Source) => {
+                // changes are read as debezium-style records, which makes this an updating table
+                schema.format = Some(Format::Json(JsonFormat {
+                    debezium: true,
+                    timestamp_format: TimestampFormat::RFC3339,
+                    ..Default::default()
+                }));
+
+                (
+                    ConnectionType::Source,
+                    "connectors::postgres::source::PostgresSourceFunc",
+                    format!("PostgresSource<{}>", table.table_name),
+                )
+            }
+            Some(TableType::Sink) | None => (
+                ConnectionType::Sink,
+                "connectors::postgres::sink::PostgresSinkFunc::<#in_k, #in_t>",
+                format!("PostgresSink<{}>", table.table_name),
+            ),
+        };
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: None,
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: typ,
+            schema,
+            operator: operator.to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+}
+
+async fn connect(config: &PostgresConfig) -> anyhow::Result<Client> {
+    let mut pg_config = tokio_postgres::Config::new();
+    pg_config
+        .host(&config.host)
+        .port(config.port.unwrap_or(5432) as u16)
+        .dbname(&config.database)
+        .user(&config.username);
+    if let Some(password) = &config.password {
+        pg_config.password(password);
+    }
+
+    let (client, connection) = pg_config
+        .connect(NoTls)
+        .await
+        .map_err(|e| anyhow!("failed to connect to Postgres: {}", e))?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            info!("Postgres connection closed: {}", e);
+        }
+    });
+
+    Ok(client)
+}
+
+/// Checks that the table exists and has a column for each field of the schema, and for sources
+/// that changes to it can be replicated
+async fn test_table(
+    config: &PostgresConfig,
+    table: &PostgresTable,
+    schema: Option<&ConnectionSchema>,
+) -> anyhow::Result<()> {
+    let client = connect(config).await?;
+
+    let statement = client
+        .prepare(&format!(
+            "SELECT * FROM {} LIMIT 0",
+            quote_qualified(&table.table_name)
+        ))
+        .await
+        .map_err(|e| anyhow!("failed to read table '{}': {}", table.table_name, e))?;
+
+    if let Some(TableType::Source) = table.type_ {
+        let wal_level: String = client.query_one("SHOW wal_level", &[]).await?.get(0);
+        if wal_level != "logical" {
+            bail!(
+                "wal_level must be 'logical' to read changes, but is '{}'",
+                wal_level
+            );
+        }
+
+        let replica_identity: i8 = client
+            .query_one(
+                "SELECT relreplident FROM pg_class WHERE oid = $1::text::regclass",
+                &[&quote_qualified(&table.table_name)],
+            )
+            .await?
+            .get(0);
+        if replica_identity != b'f' as i8 {
+            bail!(
+                "table '{}' must have REPLICA IDENTITY FULL so that updates and deletes include the previous row",
+                table.table_name
+            );
+        }
+    }
+
+    if let Some(schema) = schema {
+        for field in &schema.fields {
+            if !statement
+                .columns()
+                .iter()
+                .any(|c| c.name() == field.field_name)
+            {
+                bail!(
+                    "table '{}' has no column '{}'",
+                    table.table_name,
+                    field.field_name
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,301 @@
+use anyhow::{anyhow, bail};
+use arroyo_rpc::types::{ConnectionSchema, Format, JsonFormat, TestSourceMessage};
+use arroyo_rpc::OperatorConfig;
+use axum::response::sse::Event;
+use redis::IntoConnectionInfo;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tokio::sync::mpsc::Sender;
+use tracing::warn;
+use typify::import_types;
+
+use crate::{pull_opt, Connection, ConnectionType, Connector};
+
+pub struct RedisConnector {}
+
+const CONFIG_SCHEMA: &str = include_str!("../../connector-schemas/redis/connection.json");
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/redis/table.json");
+const ICON: &str = include_str!("../resources/redis.svg");
+
+import_types!(schema = "../connector-schemas/redis/connection.json");
+import_types!(schema = "../connector-schemas/redis/table.json");
+
+impl Connector for RedisConnector {
+    type ProfileT = RedisConfig;
+    type TableT = RedisTable;
+
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::types::Connector {
+        arroyo_rpc::types::Connector {
+            id: "redis".to_string(),
+            name: "Redis".to_string(),
+            icon: ICON.to_string(),
+            description: "Write to Redis keys, hashes, lists and streams, or look up rows in joins"
+                .to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_string()),
+            table_config: TABLE_SCHEMA.to_string(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        config.address
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.type_ {
+            Some(TableType::Lookup) => ConnectionType::Lookup,
+            Some(TableType::Sink) | None => ConnectionType::Sink,
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        _: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::spawn(async move {
+            let message = match test_connection(&config).await {
+                Ok(()) => TestSourceMessage {
+                    error: false,
+                    done: true,
+                    message: "Connection is valid".to_string(),
+                },
+                Err(e) => TestSourceMessage {
+                    error: true,
+                    done: true,
+                    message: e.to_string(),
+                },
+            };
+
+            if tx
+                .send(Ok(Event::default().json_data(message).unwrap()))
+                .await
+                .is_err()
+            {
+                warn!("Test rx closed while sending message");
+            }
+        });
+    }
+
+    fn upsert_operator(&self) -> Option<&'static str> {
+        Some("connectors::redis::sink::RedisUpsertSinkFunc::<#in_k, #in_t>")
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let connection = RedisConfig {
+            address: pull_opt("address", options)?,
+            username: options.remove("username"),
+            password: options.remove("password"),
+        };
+
+        let typ = match options.remove("type").as_deref() {
+            None | Some("sink") => TableType::Sink,
+            Some("lookup") => TableType::Lookup,
+            Some(_) => bail!("type must be one of 'sink' or 'lookup'"),
+        };
+
+        let target = match pull_opt("target", options)?.as_str() {
+            "string" => RedisTableTarget::String,
+            "hash" => RedisTableTarget::Hash,
+            "list" => RedisTableTarget::List,
+            "stream" => RedisTableTarget::Stream,
+            other => bail!("invalid value for target '{}'", other),
+        };
+
+        let ttl_seconds = options
+            .remove("ttl_seconds")
+            .map(|ttl| {
+                ttl.parse()
+                    .map_err(|_| anyhow!("invalid value for ttl_seconds '{}'", ttl))
+            })
+            .transpose()?;
+
+        let batch_option = match typ {
+            TableType::Sink => "sink.batch_size",
+            TableType::Lookup => "lookup.batch_size",
+        };
+        let batch_size = options
+            .remove(batch_option)
+            .map(|size| {
+                size.parse()
+                    .map_err(|_| anyhow!("invalid value for {} '{}'", batch_option, size))
+            })
+            .transpose()?;
+
+        let cache_ttl_seconds = options
+            .remove("lookup.cache_ttl_seconds")
+            .map(|ttl| {
+                ttl.parse()
+                    .map_err(|_| anyhow!("invalid value for lookup.cache_ttl_seconds '{}'", ttl))
+            })
+            .transpose()?;
+
+        let table = RedisTable {
+            type_: Some(typ),
+            target,
+            key_template: pull_opt("key_template", options)?,
+            ttl_seconds,
+            batch_size,
+            cache_ttl_seconds,
+        };
+
+        self.from_config(None, name, connection, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: RedisConfig,
+        table: RedisTable,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Redis connection"))?;
+
+        config
+            .address
+            .as_str()
+            .into_connection_info()
+            .map_err(|e| anyhow!("invalid Redis address '{}': {}", config.address, e))?;
+
+        match (&table.target, &schema.format) {
+            (RedisTableTarget::String | RedisTableTarget::List, None) => {
+                schema.format = Some(Format::Json(JsonFormat::default()));
+            }
+            (RedisTableTarget::String | RedisTableTarget::List, Some(format)) => {
+                if format.is_updating() {
+                    bail!("update formats like debezium_json can't be used for Redis tables");
+                }
+            }
+            // hash fields and stream entries hold individual column values
+            (RedisTableTarget::Hash | RedisTableTarget::Stream, Some(_)) => {
+                bail!("'format' can only be set for Redis tables with the string or list target");
+            }
+            (RedisTableTarget::Hash | RedisTableTarget::Stream, None) => {}
+        }
+
+        for field in template_fields(&table.key_template) {
+            if !schema.fields.iter().any(|f| f.field_name == field) {
+                bail!(
+                    "key_template refers to column '{}', which is not defined",
+                    field
+                );
+            }
+        }
+
+        if matches!(table.ttl_seconds, Some(ttl) if ttl < 1) {
+            bail!("ttl_seconds must be at least 1");
+        }
+
+        if matches!(table.batch_size, Some(size) if size < 1) {
+            bail!("batch_size must be at least 1");
+        }
+
+        if matches!(table.cache_ttl_seconds, Some(ttl) if ttl < 1) {
+            bail!("lookup.cache_ttl_seconds must be at least 1");
+        }
+
+        let (typ, operator, description) = match table.type_ {
+            Some(TableType::Lookup) => {
+                if !matches!(
+                    table.target,
+                    RedisTableTarget::String | RedisTableTarget::Hash
+                ) {
+                    bail!("lookup tables must use the string or hash target");
+                }
+
+                (
+                    ConnectionType::Lookup,
+                    "connectors::redis::lookup::RedisLookupFunc::<#in_k, #in_t, #lookup_t>",
+                    format!("RedisLookup<{}>", table.key_template),
+                )
+            }
+            Some(TableType::Sink) | None => {
+                if table.cache_ttl_seconds.is_some() {
+                    bail!("lookup.cache_ttl_seconds can only be set for lookup tables");
+                }
+                (
+                    ConnectionType::Sink,
+                    "connectors::redis::sink::RedisSinkFunc::<#in_k, #in_t>",
+                    format!("RedisSink<{}>", table.key_template),
+                )
+            }
+        };
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: schema.format.clone(),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: typ,
+            schema,
+            operator: operator.to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+}
+
+/// The columns referred to by a key template like `features:{user_id}`
+fn template_fields(template: &str) -> Vec<String> {
+    Regex::new(r"\{([^}]*)\}")
+        .unwrap()
+        .captures_iter(template)
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+async fn test_connection(config: &RedisConfig) -> anyhow::Result<()> {
+    let mut info = config
+        .address
+        .as_str()
+        .into_connection_info()
+        .map_err(|e| anyhow!("invalid Redis address '{}': {}", config.address, e))?;
+    if config.username.is_some() {
+        info.redis.username = config.username.clone();
+    }
+    if config.password.is_some() {
+        info.redis.password = config.password.clone();
+    }
+
+    let mut connection = redis::Client::open(info)?
+        .get_async_connection()
+        .await
+        .map_err(|e| anyhow!("failed to connect to Redis: {}", e))?;
+
+    redis::cmd("PING")
+        .query_async::<_, String>(&mut connection)
+        .await
+        .map_err(|e| anyhow!("failed to ping Redis: {}", e))?;
+
+    Ok(())
+}
@@ -95,6 +95,7 @@ impl Connector for SingleFileConnector {
             ConnectionType::Sink => {
                 "connectors::filesystem::single_file::sink::FileSink::<#in_k, #in_t>".to_string()
             }
+            ConnectionType::Lookup => bail!("single file tables can't be used as lookup tables"),
         };
 
         let config = OperatorConfig {
@@ -102,6 +103,9 @@ impl Connector for SingleFileConnector {
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            dead_letter: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -41,6 +41,13 @@ export interface paths {
      */
     get: operations["get_confluent_schema"];
   };
+  "/v1/connection_tables/schemas/infer": {
+    /**
+     * Infer a Connection Schema by sampling messages from a source table 
+     * @description Infer a Connection Schema by sampling messages from a source table
+     */
+    post: operations["infer_schema"];
+  };
   "/v1/connection_tables/schemas/test": {
     /**
      * Test a Connection Schema 
@@ -169,7 +176,20 @@ export type webhooks = Record<string, never>;
 
 export interface components {
   schemas: {
-    AvroFormat: Record<string, never>;
+    AvroFormat: {
+      confluentSchemaRegistry?: boolean;
+      schema?: string | null;
+    };
+    /** @description Determines what a source does with messages that cannot be deserialized */
+    BadData: OneOf<[{
+      fail: Record<string, never>;
+    }, {
+      drop: Record<string, never>;
+    }, {
+      dead_letter: {
+        table: string;
+      };
+    }]>;
     Checkpoint: {
       backend: string;
       /** Format: int32 */
@@ -212,6 +232,7 @@ export interface components {
       name: string;
     };
     ConnectionSchema: {
+      badData?: components["schemas"]["BadData"] | null;
       definition?: components["schemas"]["SchemaDefinition"] | null;
       fields: (components["schemas"]["SourceField"])[];
       format?: components["schemas"]["Format"] | null;
@@ -242,7 +263,7 @@ export interface components {
       schema?: components["schemas"]["ConnectionSchema"] | null;
     };
     /** @enum {string} */
-    ConnectionType: "source" | "sink";
+    ConnectionType: "source" | "sink" | "lookup";
     Connector: {
       connectionConfig?: string | null;
       customSchemas: boolean;
@@ -260,6 +281,13 @@ export interface components {
     ConnectorCollection: {
       data: (components["schemas"]["Connector"])[];
     };
+    CsvFormat: {
+      delimiter?: string;
+      escape?: string | null;
+      header?: boolean;
+      nullValue?: string;
+      quote?: string;
+    };
     FieldType: OneOf<[{
       primitive: components["schemas"]["PrimitiveType"];
     }, {
@@ -269,10 +297,16 @@ export interface components {
       json: components["schemas"]["JsonFormat"];
     }, {
       avro: components["schemas"]["AvroFormat"];
+    }, {
+      protobuf: components["schemas"]["ProtobufFormat"];
+    }, {
+      csv: components["schemas"]["CsvFormat"];
     }, {
       parquet: components["schemas"]["ParquetFormat"];
     }, {
       raw_string: components["schemas"]["RawStringFormat"];
+    }, {
+      raw_bytes: components["schemas"]["RawBytesFormat"];
     }]>;
     Job: {
       /** Format: int64 */
@@ -290,6 +324,17 @@ export interface components {
       /** Format: int64 */
       tasks?: number | null;
     };
+    InferredSchema: {
+      /** @description Fields that had different types in different messages, which are left out of the schema */
+      conflicts: (string)[];
+      /**
+       * Format: int32 
+       * @description The number of messages the schema was inferred from
+       */
+      samples: number;
+      schema: components["schemas"]["ConnectionSchema"];
+      warnings: (string)[];
+    };
     JobCollection: {
       data: (components["schemas"]["Job"])[];
     };
@@ -310,9 +355,12 @@ export interface components {
       data: (components["schemas"]["JobLogMessage"])[];
       hasMore: boolean;
     };
+    /** @description Change data capture envelopes, in which each message describes an insert, update or delete */
+    JsonEnvelope: "maxwell" | "canal";
     JsonFormat: {
       confluentSchemaRegistry?: boolean;
       debezium?: boolean;
+      envelope?: components["schemas"]["JsonEnvelope"] | null;
       includeSchema?: boolean;
       timestampFormat?: components["schemas"]["TimestampFormat"];
       unstructured?: boolean;
@@ -412,6 +460,11 @@ export interface components {
     };
     /** @enum {string} */
     PrimitiveType: "int32" | "int64" | "u_int32" | "u_int64" | "f32" | "f64" | "bool" | "string" | "bytes" | "unix_millis" | "unix_micros" | "unix_nanos" | "date_time" | "json";
+    ProtobufFormat: {
+      compiledSchema?: number[] | null;
+      messageName?: string | null;
+    };
+    RawBytesFormat: Record<string, never>;
     RawStringFormat: Record<string, never>;
     SchemaDefinition: OneOf<[{
       json_schema: string;
@@ -422,6 +475,14 @@ export interface components {
     }, {
       raw_schema: string;
     }]>;
+    SchemaInferencePost: {
+      /**
+       * Format: int32 
+       * @description The maximum number of messages to sample from the table; defaults to 100
+       */
+      samples?: number | null;
+      table: components["schemas"]["ConnectionTablePost"];
+    };
     SourceField: {
       fieldName: string;
       fieldType: components["schemas"]["SourceFieldType"];
@@ -455,7 +516,12 @@ export interface components {
       message: string;
     };
     /** @enum {string} */
-    TimestampFormat: "rfc3339" | "unix_millis";
+    TimestampFormat: OneOf<["rfc3339" | "unix_seconds" | "unix_millis" | "unix_micros" | "unix_nanos" | "iso8601", {
+      pattern: {
+        pattern: string;
+        timezone?: string | null;
+      };
+    }]>;
     Udf: {
       definition: string;
       language: components["schemas"]["UdfLanguage"];
@@ -572,6 +638,25 @@ export interface operations {
       };
     };
   };
+  /**
+   * Infer a Connection Schema by sampling messages from a source table 
+   * @description Infer a Connection Schema by sampling messages from a source table
+   */
+  infer_schema: {
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["SchemaInferencePost"];
+      };
+    };
+    responses: {
+      /** @description Inferred schema */
+      200: {
+        content: {
+          "application/json": components["schemas"]["InferredSchema"];
+        };
+      };
+    };
+  };
   /**
    * Test a Connection Schema 
    * @description Test a Connection Schema
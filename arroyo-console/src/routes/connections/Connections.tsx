@@ -45,6 +45,8 @@ const format = (f: Format | undefined) => {
     return 'JSON';
   } else if (f?.raw_string) {
     return 'RawString';
+  } else if (f?.raw_bytes) {
+    return 'RawBytes';
   } else if (f?.parquet) {
     return 'Parquet';
   } else if (f?.avro) {
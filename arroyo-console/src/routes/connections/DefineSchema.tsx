@@ -166,6 +166,55 @@ const RawStringEditor = ({
   );
 };
 
+const RawBytesEditor = ({
+  state,
+  setState,
+  next,
+}: {
+  state: CreateConnectionState;
+  setState: Dispatch<CreateConnectionState>;
+  next: () => void;
+}) => {
+  const submit = () => {
+    setState({
+      ...state,
+      schema: {
+        ...state.schema,
+        definition: { raw_schema: 'value' },
+        fields: [
+          {
+            fieldName: 'value',
+            fieldType: {
+              type: {
+                primitive: 'bytes',
+              },
+            },
+            nullable: true,
+          },
+        ],
+        format: { raw_bytes: {} },
+      },
+    });
+    next();
+  };
+
+  return (
+    <Stack spacing={4} maxW="md">
+      <Text>
+        When using the raw bytes format, values read from the source are passed through unchanged
+        as binary data, which can be decoded with a UDF.
+      </Text>
+
+      <Text>
+        Raw bytes connection tables have a single <Code>value</Code> column of type{' '}
+        <Code>BYTEA</Code>.
+      </Text>
+
+      <Button onClick={submit}>Continue</Button>
+    </Stack>
+  );
+};
+
 export const DefineSchema = ({
   connector,
   state,
@@ -191,6 +240,11 @@ export const DefineSchema = ({
       value: 'raw_string',
       el: <RawStringEditor state={state} setState={setState} next={next} />,
     },
+    {
+      name: 'Raw Bytes',
+      value: 'raw_bytes',
+      el: <RawBytesEditor state={state} setState={setState} next={next} />,
+    },
     {
       name: 'Protobuf (coming soon)',
       value: 'protobuf',
@@ -216,6 +270,18 @@ export const DefineSchema = ({
           fields: [],
         },
       });
+    } else if (String(e.target.value) == 'raw_bytes') {
+      setSelectedFormat('raw_bytes');
+      setState({
+        ...state,
+        schema: {
+          ...state.schema,
+          format: {
+            raw_bytes: {},
+          },
+          fields: [],
+        },
+      });
     } else {
       setSelectedFormat('raw_string');
       setState({
@@ -235,6 +235,7 @@ export function CreatePipeline() {
 
   const sources = connectionTables.filter(s => s.tableType == 'source');
   const sinks = connectionTables.filter(s => s.tableType == 'sink');
+  const lookups = connectionTables.filter(s => s.tableType == 'lookup');
 
   const startPipelineModal = (
     <StartPipelineModal
@@ -299,6 +300,7 @@ export function CreatePipeline() {
       {catalogTruncatedWarning}
       {catalogType('Source', sources)}
       {catalogType('Sink', sinks)}
+      {lookups.length > 0 && catalogType('Lookup', lookups)}
 
       <Spacer />
       <Box p={4} borderTop={'1px solid'} borderColor={'gray.500'}>
@@ -322,6 +322,8 @@ impl From<ConnectorOp> for GrpcApi::ConnectorOp {
 pub enum Operator {
     ConnectorSource(ConnectorOp),
     ConnectorSink(ConnectorOp),
+    /// Queries a lookup table for each record, emitting the record paired with the result
+    ConnectorLookup(ConnectorOp),
     FusedWasmUDFs {
         name: String,
         udfs: Vec<WasmUDF>,
@@ -401,7 +403,9 @@ impl Debug for Operator {
         }
 
         match self {
-            Operator::ConnectorSource(c) | Operator::ConnectorSink(c) => {
+            Operator::ConnectorSource(c)
+            | Operator::ConnectorSink(c)
+            | Operator::ConnectorLookup(c) => {
                 write!(f, "{}", c.description)
             }
             Operator::FusedWasmUDFs { udfs, .. } => {
@@ -1230,7 +1234,9 @@ impl Program {
                         Box::new(#strukt::<#out_k, #out_t>::from_config(#config))
                     }
                 }
-                Operator::ConnectorSink(c)  => {
+                // lookups are generic over their input like sinks; the type of the looked up rows is
+                // filled in when the operator is planned
+                Operator::ConnectorSink(c) | Operator::ConnectorLookup(c) => {
                     // In c.operator, replace #in_k and #in_t with the actual types
                     let replaced_type = c.operator.replace("#in_k", &input.unwrap().weight().key);
                     let replaced_type = replaced_type.replace("#in_t", &input.unwrap().weight().value);
@@ -1815,6 +1821,7 @@ impl From<Operator> for GrpcApi::operator::Operator {
         match operator {
             Operator::ConnectorSource(c) => GrpcOperator::ConnectorSource(c.into()),
             Operator::ConnectorSink(c) => GrpcOperator::ConnectorSink(c.into()),
+            Operator::ConnectorLookup(c) => GrpcOperator::ConnectorLookup(c.into()),
             FusedWasmUDFs { name, udfs } => GrpcOperator::WasmUdfs(GrpcApi::WasmUdfs {
                 name,
                 wasm_functions: udfs.into_iter().map(|udf| udf.into()).collect(),
@@ -2119,6 +2126,7 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
             Some(operator) => match operator {
                 GrpcOperator::ConnectorSource(c) => Operator::ConnectorSource(c.into()),
                 GrpcOperator::ConnectorSink(c) => Operator::ConnectorSink(c.into()),
+                GrpcOperator::ConnectorLookup(c) => Operator::ConnectorLookup(c.into()),
                 GrpcOperator::WasmUdfs(wasm_udfs) => Operator::FusedWasmUDFs {
                     name: wasm_udfs.name,
                     udfs: wasm_udfs
@@ -10,7 +10,7 @@ use quote::{format_ident, quote};
 use syn::parse::{Parse, ParseStream};
 use syn::{
     parse_macro_input, parse_str, Data, DataEnum, DataStruct, DeriveInput, Expr, Ident, ImplItem,
-    ItemImpl, LitInt, LitStr, Token, Type,
+    ItemImpl, LitBool, LitInt, LitStr, Token, Type,
 };
 
 #[derive(Debug)]
@@ -210,12 +210,14 @@ struct StreamTypesAttr {
     out_t: Option<Type>,
     timer_t: Option<Type>,
     tick_ms: Option<LitInt>,
+    commits: Option<LitBool>,
 }
 
 impl Parse for StreamTypesAttr {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let mut fields = HashMap::new();
         let mut tick_ms = None;
+        let mut commits = None;
         while !input.is_empty() {
             let k: Ident = input.parse()?;
             input.parse::<Token![=]>()?;
@@ -223,6 +225,9 @@ impl Parse for StreamTypesAttr {
             let k = k.to_string();
             if k == "tick_ms" {
                 tick_ms = Some(input.parse()?);
+            } else if k == "commits" {
+                commits = Some(input.parse()?);
+                let _ = input.parse::<Token![,]>();
             } else {
                 let v: Type = input.parse()?;
 
@@ -242,6 +247,7 @@ impl Parse for StreamTypesAttr {
             out_t: fields.remove("out_t"),
             timer_t: fields.remove("timer_t"),
             tick_ms,
+            commits,
         })
     }
 }
@@ -276,7 +282,11 @@ pub fn derive_stream_node(item: proc_macro::TokenStream) -> proc_macro::TokenStr
 }
 
 enum StreamNodeType {
-    SourceFn {},
+    /// `commits` is set for sources that take part in commits (like the Kafka source, which
+    /// commits its offsets); other sources never receive commit messages
+    SourceFn {
+        commits: bool,
+    },
     ProcessFn {
         in_k: Type,
         in_t: Type,
@@ -302,8 +312,13 @@ pub fn source_fn(
         .timer_t
         .unwrap_or(parse_str("()").unwrap());
 
+    let commits = stream_types_attr
+        .commits
+        .map(|c| c.value)
+        .unwrap_or_default();
+
     impl_stream_node_type(
-        StreamNodeType::SourceFn {},
+        StreamNodeType::SourceFn { commits },
         out_k,
         out_t,
         timer_t,
@@ -382,8 +397,14 @@ fn impl_stream_node_type(
 
     let mut input = parse_macro_input!(item as ItemImpl);
 
+    let control_rx = if matches!(typ, StreamNodeType::SourceFn { commits: false }) {
+        quote!(crate::engine::without_commits(control_rx))
+    } else {
+        quote!(control_rx)
+    };
+
     let handlers = match typ {
-        StreamNodeType::SourceFn {} => {
+        StreamNodeType::SourceFn { .. } => {
             vec![]
         }
         StreamNodeType::ProcessFn { in_k, in_t, .. } => {
@@ -596,7 +617,7 @@ fn impl_stream_node_type(
                 let mut ctx = crate::engine::Context::<#out_k, #out_t>::new(
                     task_info,
                     restore_from,
-                    control_rx,
+                    #control_rx,
                     control_tx,
                     in_qs.len(),
                     out_qs,
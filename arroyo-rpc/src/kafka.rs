@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use typify::import_types;
+
+import_types!(schema = "../connector-schemas/kafka/connection.json");
+
+/// Sets a TLS certificate or key config, which may be given either inline as PEM or as a path
+fn set_pem(client_configs: &mut HashMap<String, String>, name: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        if value.trim_start().starts_with("-----BEGIN") {
+            client_configs.insert(format!("ssl.{}.pem", name), value.to_string());
+        } else {
+            client_configs.insert(format!("ssl.{}.location", name), value.to_string());
+        }
+    }
+}
+
+/// The librdkafka configs needed to authenticate with the cluster
+pub fn client_configs(connection: &KafkaConfig) -> HashMap<String, String> {
+    let mut client_configs: HashMap<String, String> = HashMap::new();
+
+    match &connection.authentication {
+        KafkaConfigAuthentication::None {} => {}
+        KafkaConfigAuthentication::Sasl {
+            mechanism,
+            password,
+            protocol,
+            username,
+            ca_certificate,
+        } => {
+            client_configs.insert("sasl.mechanism".to_string(), mechanism.to_string());
+            client_configs.insert("security.protocol".to_string(), protocol.to_string());
+            client_configs.insert("sasl.username".to_string(), username.to_string());
+            client_configs.insert("sasl.password".to_string(), password.to_string());
+            set_pem(&mut client_configs, "ca", ca_certificate);
+        }
+        KafkaConfigAuthentication::Tls {
+            ca_certificate,
+            client_certificate,
+            client_key,
+            client_key_password,
+        } => {
+            client_configs.insert("security.protocol".to_string(), "SSL".to_string());
+            set_pem(&mut client_configs, "ca", ca_certificate);
+            set_pem(&mut client_configs, "certificate", client_certificate);
+            set_pem(&mut client_configs, "key", client_key);
+            if let Some(password) = client_key_password {
+                client_configs.insert("ssl.key.password".to_string(), password.to_string());
+            }
+        }
+        KafkaConfigAuthentication::OAuth {
+            protocol,
+            token_endpoint,
+            client_id,
+            client_secret,
+            scope,
+            ca_certificate,
+        } => {
+            client_configs.insert(
+                "security.protocol".to_string(),
+                protocol.clone().unwrap_or_else(|| "SASL_SSL".to_string()),
+            );
+            client_configs.insert("sasl.mechanism".to_string(), "OAUTHBEARER".to_string());
+            client_configs.insert("sasl.oauthbearer.method".to_string(), "oidc".to_string());
+            client_configs.insert(
+                "sasl.oauthbearer.token.endpoint.url".to_string(),
+                token_endpoint.to_string(),
+            );
+            client_configs.insert(
+                "sasl.oauthbearer.client.id".to_string(),
+                client_id.to_string(),
+            );
+            client_configs.insert(
+                "sasl.oauthbearer.client.secret".to_string(),
+                client_secret.to_string(),
+            );
+            if let Some(scope) = scope {
+                client_configs.insert("sasl.oauthbearer.scope".to_string(), scope.to_string());
+            }
+            set_pem(&mut client_configs, "ca", ca_certificate);
+        }
+    };
+
+    client_configs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_configs, KafkaConfig, KafkaConfigAuthentication, TlsAuthentication};
+
+    #[test]
+    fn test_tls_client_configs() {
+        let connection = KafkaConfig {
+            authentication: KafkaConfigAuthentication::Tls {
+                type_: TlsAuthentication::Tls,
+                ca_certificate: Some("/etc/kafka/ca.pem".to_string()),
+                client_certificate: Some(
+                    "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----".to_string(),
+                ),
+                client_key: Some("/etc/kafka/client.key".to_string()),
+                client_key_password: None,
+            },
+            bootstrap_servers: "localhost:9093".to_string().try_into().unwrap(),
+            schema_registry: None,
+        };
+
+        let configs = client_configs(&connection);
+        assert_eq!(configs["security.protocol"], "SSL");
+        assert_eq!(configs["ssl.ca.location"], "/etc/kafka/ca.pem");
+        assert!(configs["ssl.certificate.pem"].starts_with("-----BEGIN CERTIFICATE-----"));
+        assert_eq!(configs["ssl.key.location"], "/etc/kafka/client.key");
+        assert!(!configs.contains_key("ssl.key.password"));
+    }
+
+    #[test]
+    fn test_oauth_client_configs() {
+        let connection = KafkaConfig {
+            authentication: KafkaConfigAuthentication::OAuth {
+                protocol: None,
+                token_endpoint: "https://auth.example.com/oauth2/token".to_string(),
+                client_id: "arroyo".to_string(),
+                client_secret: "secret".to_string(),
+                scope: Some("kafka".to_string()),
+                ca_certificate: None,
+            },
+            bootstrap_servers: "localhost:9093".to_string().try_into().unwrap(),
+            schema_registry: None,
+        };
+
+        let configs = client_configs(&connection);
+        assert_eq!(configs["security.protocol"], "SASL_SSL");
+        assert_eq!(configs["sasl.mechanism"], "OAUTHBEARER");
+        assert_eq!(
+            configs["sasl.oauthbearer.token.endpoint.url"],
+            "https://auth.example.com/oauth2/token"
+        );
+        assert_eq!(configs["sasl.oauthbearer.scope"], "kafka");
+    }
+
+    #[test]
+    fn test_tls_without_certificates() {
+        // TLS without any certificates is distinguished from no authentication by its type
+        let authentication = KafkaConfigAuthentication::Tls {
+            type_: TlsAuthentication::Tls,
+            ca_certificate: None,
+            client_certificate: None,
+            client_key: None,
+            client_key_password: None,
+        };
+        let json = serde_json::to_string(&authentication).unwrap();
+        assert_eq!(json, r#"{"type":"tls"}"#);
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            KafkaConfigAuthentication::Tls { .. }
+        ));
+        assert!(matches!(
+            serde_json::from_str("{}").unwrap(),
+            KafkaConfigAuthentication::None {}
+        ));
+    }
+}
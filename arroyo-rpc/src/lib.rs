@@ -1,10 +1,16 @@
+pub mod kafka;
+pub mod mqtt;
+pub mod partition_spec;
+pub mod postgres;
+pub mod protobuf;
 pub mod public_ids;
+pub mod schema_resolver;
 pub mod types;
 
 use std::{fs, time::SystemTime};
 
 use crate::grpc::{LoadCompactedDataReq, SubtaskCheckpointMetadata};
-use crate::types::{Format, PrimitiveType};
+use crate::types::{BadData, Format, PrimitiveType};
 use arroyo_types::CheckpointBarrier;
 use grpc::{StopMode, TaskCheckpointEventType};
 use serde::{Deserialize, Serialize};
@@ -152,5 +158,30 @@ pub struct OperatorConfig {
     pub connection: Value,
     pub table: Value,
     pub format: Option<Format>,
+    #[serde(default)]
+    pub bad_data: Option<BadData>,
+    /// The sink that bad data is written to, resolved by the planner from the
+    /// `dead_letter` bad data policy
+    #[serde(default)]
+    pub dead_letter: Option<DeadLetterConfig>,
     pub rate_limit: Option<RateLimit>,
+    /// Fields of a source that are filled from the metadata of each message rather than from
+    /// its payload
+    #[serde(default)]
+    pub metadata_fields: Vec<MetadataField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetterConfig {
+    pub table: String,
+    pub operator: String,
+    pub config: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetadataField {
+    pub field_name: String,
+    /// The name of the metadata in the connector, like `offset` for Kafka
+    pub key: String,
+    pub data_type: PrimitiveType,
 }
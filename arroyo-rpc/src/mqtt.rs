@@ -0,0 +1,133 @@
+use anyhow::{anyhow, bail, Result};
+use rumqttc::{TlsConfiguration, Transport};
+use serde::{Deserialize, Serialize};
+use typify::import_types;
+
+import_types!(schema = "../connector-schemas/mqtt/connection.json");
+
+/// The largest packet that can be sent or received
+pub const MAX_PACKET_SIZE: usize = 10 * 1024 * 1024;
+
+/// Connection options for either version of the protocol
+pub enum MqttOptions {
+    V3(rumqttc::MqttOptions),
+    V5(rumqttc::v5::MqttOptions),
+}
+
+/// The options for connecting to the broker with the configured protocol version, transport and
+/// credentials
+pub fn mqtt_options(config: &MqttConfig, client_id: String) -> Result<MqttOptions> {
+    let (tls, host, port) = parse_url(&config.url)?;
+    let transport = transport(config, tls)?;
+
+    Ok(match config.protocol_version {
+        Some(ProtocolVersion::V3) | None => {
+            let mut options = rumqttc::MqttOptions::new(client_id, host, port);
+            options
+                .set_transport(transport)
+                .set_max_packet_size(MAX_PACKET_SIZE, MAX_PACKET_SIZE);
+            if let Some(username) = &config.username {
+                options.set_credentials(username, config.password.clone().unwrap_or_default());
+            }
+            MqttOptions::V3(options)
+        }
+        Some(ProtocolVersion::V5) => {
+            let mut options = rumqttc::v5::MqttOptions::new(client_id, host, port);
+            options
+                .set_transport(transport)
+                .set_max_packet_size(Some(MAX_PACKET_SIZE as u32));
+            if let Some(username) = &config.username {
+                options.set_credentials(username, config.password.clone().unwrap_or_default());
+            }
+            MqttOptions::V5(options)
+        }
+    })
+}
+
+/// Splits a broker URL into whether it uses TLS, and its host and port
+pub fn parse_url(url: &str) -> Result<(bool, String, u16)> {
+    let invalid = || anyhow!("invalid MQTT URL '{}'", url);
+
+    let (scheme, address) = url.split_once("://").ok_or_else(invalid)?;
+    let tls = match scheme {
+        "mqtt" | "tcp" => false,
+        "mqtts" | "ssl" => true,
+        _ => bail!(
+            "invalid MQTT URL '{}'; must start with mqtt:// or mqtts://",
+            url
+        ),
+    };
+
+    let address = address.trim_end_matches('/');
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+        None => (address, if tls { 8883 } else { 1883 }),
+    };
+
+    if host.is_empty() || host.contains('/') {
+        return Err(invalid());
+    }
+
+    Ok((tls, host.to_string(), port))
+}
+
+/// Certificates may be given either as PEM or as the path to a PEM file
+fn read_pem(value: &str) -> Result<Vec<u8>> {
+    if value.trim_start().starts_with("-----BEGIN") {
+        Ok(value.as_bytes().to_vec())
+    } else {
+        std::fs::read(value).map_err(|e| anyhow!("failed to read '{}': {}", value, e))
+    }
+}
+
+/// Over TLS, verifies the broker with the configured CA certificate if there is one, and
+/// otherwise with the system's root certificates
+fn transport(config: &MqttConfig, tls: bool) -> Result<Transport> {
+    if !tls {
+        if config.tls.is_some() {
+            bail!("TLS certificates can only be used with mqtts:// URLs");
+        }
+        return Ok(Transport::Tcp);
+    }
+
+    let Some(certificates) = &config.tls else {
+        return Ok(Transport::tls_with_default_config());
+    };
+
+    let client_auth = match (&certificates.client_certificate, &certificates.client_key) {
+        (Some(certificate), Some(key)) => Some((read_pem(certificate)?, read_pem(key)?)),
+        (None, None) => None,
+        _ => bail!("both a client certificate and a client key must be set for mTLS"),
+    };
+
+    match &certificates.ca_certificate {
+        Some(ca) => Ok(Transport::tls_with_config(TlsConfiguration::Simple {
+            ca: read_pem(ca)?,
+            alpn: None,
+            client_auth,
+        })),
+        None if client_auth.is_some() => {
+            bail!("a CA certificate must be set to use a client certificate")
+        }
+        None => Ok(Transport::tls_with_default_config()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_url;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("mqtt://localhost:1883").unwrap(),
+            (false, "localhost".to_string(), 1883)
+        );
+        assert_eq!(
+            parse_url("mqtts://broker.example.com").unwrap(),
+            (true, "broker.example.com".to_string(), 8883)
+        );
+        assert!(parse_url("http://localhost:1883").is_err());
+        assert!(parse_url("mqtt://localhost:port").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::types::PrimitiveType;
+
+/// A partition column computed by applying a transform to a field of the record, following the
+/// Iceberg partition transforms
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionField {
+    pub source: String,
+    pub name: String,
+    pub transform: Transform,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    Identity,
+    Day,
+    Hour,
+    Bucket(u32),
+}
+
+impl Transform {
+    pub fn name(&self) -> String {
+        match self {
+            Transform::Identity => "identity".to_string(),
+            Transform::Day => "day".to_string(),
+            Transform::Hour => "hour".to_string(),
+            Transform::Bucket(n) => format!("bucket[{}]", n),
+        }
+    }
+
+    /// Checks that the transform can be applied to a source column of the given type: time
+    /// transforms need timestamps, and buckets are only computed for integers, strings and
+    /// timestamps (which are hashed as microseconds, like Iceberg does)
+    pub fn validate_source(&self, source: &str, source_type: &PrimitiveType) -> Result<(), String> {
+        let is_timestamp = matches!(
+            source_type,
+            PrimitiveType::UnixMillis
+                | PrimitiveType::UnixMicros
+                | PrimitiveType::UnixNanos
+                | PrimitiveType::DateTime
+        );
+
+        match self {
+            Transform::Identity => Ok(()),
+            Transform::Day | Transform::Hour if !is_timestamp => Err(format!(
+                "partition column '{}' must be a timestamp to be partitioned by {}, but it is {:?}",
+                source,
+                self.name(),
+                source_type
+            )),
+            Transform::Day | Transform::Hour => Ok(()),
+            Transform::Bucket(_)
+                if is_timestamp
+                    || matches!(
+                        source_type,
+                        PrimitiveType::Int32
+                            | PrimitiveType::Int64
+                            | PrimitiveType::UInt32
+                            | PrimitiveType::UInt64
+                            | PrimitiveType::String
+                    ) =>
+            {
+                Ok(())
+            }
+            Transform::Bucket(_) => Err(format!(
+                "partition column '{}' cannot be bucketed because it is {:?}",
+                source, source_type
+            )),
+        }
+    }
+}
+
+/// Parses a partition spec like `day(event_time), bucket(16, user_id), country`
+pub fn parse_partition_spec(spec: &str) -> Result<Vec<PartitionField>, String> {
+    let mut fields = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut parts = vec![];
+    for (i, c) in spec.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&spec[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&spec[start..]);
+
+    for part in parts
+        .into_iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+    {
+        let Some((transform, args)) = part.strip_suffix(')').and_then(|p| p.split_once('(')) else {
+            fields.push(PartitionField {
+                source: part.to_string(),
+                name: part.to_string(),
+                transform: Transform::Identity,
+            });
+            continue;
+        };
+
+        let args: Vec<&str> = args.split(',').map(|a| a.trim()).collect();
+        let (transform, source) = match (transform.trim().to_lowercase().as_str(), &args[..]) {
+            ("identity", [source]) => (Transform::Identity, *source),
+            ("day" | "days", [source]) => (Transform::Day, *source),
+            ("hour" | "hours", [source]) => (Transform::Hour, *source),
+            ("bucket", [n, source]) => (
+                Transform::Bucket(
+                    n.parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid bucket count in '{}'", part))?,
+                ),
+                *source,
+            ),
+            _ => return Err(format!("invalid partition transform '{}'", part)),
+        };
+
+        let name = match transform {
+            Transform::Identity => source.to_string(),
+            Transform::Day => format!("{}_day", source),
+            Transform::Hour => format!("{}_hour", source),
+            Transform::Bucket(_) => format!("{}_bucket", source),
+        };
+
+        fields.push(PartitionField {
+            source: source.to_string(),
+            name,
+            transform,
+        });
+    }
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::PrimitiveType;
+
+    use super::{parse_partition_spec, Transform};
+
+    #[test]
+    fn test_parse_partition_spec() {
+        let fields = parse_partition_spec("day(ts), bucket(16, id), country").unwrap();
+        assert_eq!(fields[0].transform, Transform::Day);
+        assert_eq!(fields[0].name, "ts_day");
+        assert_eq!(fields[1].transform, Transform::Bucket(16));
+        assert_eq!(fields[1].source, "id");
+        assert_eq!(fields[2].transform, Transform::Identity);
+        assert!(parse_partition_spec("bucket(id)").is_err());
+        assert!(parse_partition_spec("bucket(0, id)").is_err());
+        assert!(parse_partition_spec("month(ts)").is_err());
+    }
+
+    #[test]
+    fn test_validate_source() {
+        assert!(Transform::Day
+            .validate_source("ts", &PrimitiveType::UnixMillis)
+            .is_ok());
+        assert!(Transform::Hour
+            .validate_source("id", &PrimitiveType::Int64)
+            .is_err());
+        assert!(Transform::Bucket(4)
+            .validate_source("ts", &PrimitiveType::DateTime)
+            .is_ok());
+        assert!(Transform::Bucket(4)
+            .validate_source("flag", &PrimitiveType::Bool)
+            .is_err());
+        assert!(Transform::Bucket(4)
+            .validate_source("price", &PrimitiveType::F64)
+            .is_err());
+        assert!(Transform::Identity
+            .validate_source("price", &PrimitiveType::F64)
+            .is_ok());
+    }
+}
@@ -0,0 +1,64 @@
+/// Quotes an identifier, like a column name
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quotes a string literal, for statements like `COMMIT PREPARED` that don't take parameters
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quotes each part of a possibly schema-qualified name like `public.orders`
+pub fn quote_qualified(name: &str) -> String {
+    split_qualified(name)
+        .iter()
+        .map(|part| quote_ident(part))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Splits a possibly schema-qualified name like `public.orders` into its parts. Parts that are
+/// quoted (like `"my.schema"`) are unquoted, others are kept as they are.
+pub fn split_qualified(name: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in name.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            '.' if !quoted => parts.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    parts
+        .iter()
+        .map(|part| {
+            let part = part.trim();
+            match part.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                Some(unquoted) => unquoted.replace("\"\"", "\""),
+                None => part.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::quote_qualified;
+
+    #[test]
+    fn test_quote_qualified() {
+        assert_eq!(quote_qualified("orders"), "\"orders\"");
+        assert_eq!(quote_qualified("public.Orders"), "\"public\".\"Orders\"");
+        assert_eq!(
+            quote_qualified("\"my.schema\".orders; DROP TABLE x"),
+            "\"my.schema\".\"orders; DROP TABLE x\""
+        );
+        assert_eq!(quote_qualified("a\"b"), "\"a\"\"b\"");
+    }
+}
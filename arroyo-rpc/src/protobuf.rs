@@ -0,0 +1,35 @@
+use prost_reflect::{DescriptorPool, MessageDescriptor};
+
+/// Finds the message type for a table, which may be omitted if the schema contains only one
+pub fn get_message(
+    compiled_schema: &[u8],
+    message_name: Option<&str>,
+) -> Result<MessageDescriptor, String> {
+    let pool = DescriptorPool::decode(compiled_schema)
+        .map_err(|e| format!("Invalid protobuf descriptor set: {:?}", e))?;
+
+    match message_name {
+        Some(name) => pool
+            .get_message_by_name(name)
+            .ok_or_else(|| format!("Message '{}' not found in protobuf schema", name)),
+        None => {
+            let messages: Vec<_> = pool
+                .all_messages()
+                .filter(|m| !m.full_name().starts_with("google.protobuf."))
+                .collect();
+
+            match messages.as_slice() {
+                [m] => Ok(m.clone()),
+                [] => Err("Protobuf schema contains no messages".to_string()),
+                _ => Err(format!(
+                    "Protobuf schema contains multiple messages ({}); a message name must be specified",
+                    messages
+                        .iter()
+                        .map(|m| m.full_name().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            }
+        }
+    }
+}
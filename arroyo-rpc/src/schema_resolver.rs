@@ -0,0 +1,317 @@
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tracing::warn;
+
+/// Looks up writer schemas by id, for formats (like Avro) where messages carry a reference to
+/// the schema they were written with rather than the schema itself
+#[async_trait]
+pub trait SchemaResolver: Send {
+    async fn resolve_schema(&self, id: u32) -> Result<Option<String>, String>;
+}
+
+/// A schema resolver that fails on every lookup; used when no schema registry has been configured
+pub struct FailingSchemaResolver {}
+
+impl FailingSchemaResolver {
+    pub fn new() -> Self {
+        FailingSchemaResolver {}
+    }
+}
+
+#[async_trait]
+impl SchemaResolver for FailingSchemaResolver {
+    async fn resolve_schema(&self, id: u32) -> Result<Option<String>, String> {
+        Err(format!(
+            "Schema with id {} not available, and no schema registry configured",
+            id
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ConfluentSchemaType {
+    Avro,
+    Json,
+    Protobuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfluentSchemaResponse {
+    pub id: u32,
+    pub schema: String,
+    #[serde(default = "default_schema_type")]
+    pub schema_type: ConfluentSchemaType,
+    pub subject: String,
+    pub version: u32,
+}
+
+// the registry omits the schema type for Avro schemas, as that was the only type originally supported
+fn default_schema_type() -> ConfluentSchemaType {
+    ConfluentSchemaType::Avro
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ConfluentSchemaIdResponse {
+    schema: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ConfluentSchemaRegisterResponse {
+    id: u32,
+}
+
+/// A client for the Confluent Schema Registry, scoped to the value subject of a single topic
+#[derive(Clone)]
+pub struct ConfluentSchemaRegistry {
+    endpoint: Url,
+    topic: String,
+    client: Client,
+    api_key: Option<String>,
+    api_secret: Option<String>,
+}
+
+impl ConfluentSchemaRegistry {
+    pub fn new(
+        endpoint: &str,
+        topic: &str,
+        api_key: Option<String>,
+        api_secret: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .map_err(|e| anyhow!("could not construct HTTP client: {:?}", e))?;
+
+        let endpoint: Url = endpoint
+            .parse()
+            .map_err(|e| anyhow!("'{}' is not a valid URL: {:?}", endpoint, e))?;
+
+        Ok(Self {
+            endpoint,
+            topic: topic.to_string(),
+            client,
+            api_key,
+            api_secret,
+        })
+    }
+
+    fn subject(&self) -> String {
+        format!("{}-value", self.topic)
+    }
+
+    fn url(&self, path: &str) -> anyhow::Result<Url> {
+        // don't use Url::join, as that would drop any path prefix on the endpoint
+        let url = format!("{}{}", self.endpoint.as_str().trim_end_matches('/'), path);
+        url.parse()
+            .map_err(|e| anyhow!("invalid schema registry url '{}': {:?}", url, e))
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(api_key) = &self.api_key {
+            builder.basic_auth(api_key, self.api_secret.as_ref())
+        } else {
+            builder
+        }
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, url: Url) -> anyhow::Result<Option<T>> {
+        let resp = self
+            .request(self.client.get(url.clone()))
+            .send()
+            .await
+            .map_err(|e| {
+                warn!("Error connecting to schema registry at {}: {:?}", url, e);
+                anyhow!(
+                    "Could not connect to Schema Registry at {}: unknown error",
+                    self.endpoint
+                )
+            })?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !resp.status().is_success() {
+            bail!(
+                "Received an error status code from the schema registry: {} {}",
+                resp.status().as_u16(),
+                resp.text()
+                    .await
+                    .unwrap_or_else(|_| "<failed to read body>".to_string())
+            );
+        }
+
+        resp.json()
+            .await
+            .map(Some)
+            .context("Schema registry returned invalid JSON")
+    }
+
+    /// Fetches the latest version of the value schema for the topic
+    pub async fn get_schema_for_topic(&self) -> anyhow::Result<Option<ConfluentSchemaResponse>> {
+        let url = self.url(&format!("/subjects/{}/versions/latest", self.subject()))?;
+        self.get(url).await
+    }
+
+    /// Fetches a schema by its globally-unique id
+    pub async fn get_schema_for_id(&self, id: u32) -> anyhow::Result<Option<String>> {
+        let url = self.url(&format!("/schemas/ids/{}", id))?;
+        let resp: Option<ConfluentSchemaIdResponse> = self.get(url).await?;
+        Ok(resp.map(|r| r.schema))
+    }
+
+    /// Registers a schema under the topic's value subject, returning its id. If an identical schema
+    /// has already been registered, the registry will return the existing id.
+    pub async fn write_schema(
+        &self,
+        schema: impl Into<String>,
+        schema_type: ConfluentSchemaType,
+    ) -> anyhow::Result<u32> {
+        let url = self.url(&format!("/subjects/{}/versions", self.subject()))?;
+
+        let body = json!({
+            "schema": schema.into(),
+            "schemaType": schema_type,
+        });
+
+        let resp = self
+            .request(self.client.post(url))
+            .header("Content-Type", "application/vnd.schemaregistry.v1+json")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| {
+                anyhow!(
+                    "Could not connect to Schema Registry at {}: {:?}",
+                    self.endpoint,
+                    e
+                )
+            })?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp
+                .text()
+                .await
+                .unwrap_or_else(|_| "<failed to read body>".to_string());
+
+            if status == StatusCode::CONFLICT {
+                bail!(
+                    "Schema is incompatible with the existing schema for subject '{}': {}",
+                    self.subject(),
+                    body
+                );
+            }
+
+            bail!(
+                "Failed to register schema for subject '{}': {} {}",
+                self.subject(),
+                status.as_u16(),
+                body
+            );
+        }
+
+        let resp: ConfluentSchemaRegisterResponse = resp
+            .json()
+            .await
+            .context("Schema registry returned invalid JSON")?;
+
+        Ok(resp.id)
+    }
+}
+
+#[async_trait]
+impl SchemaResolver for ConfluentSchemaRegistry {
+    async fn resolve_schema(&self, id: u32) -> Result<Option<String>, String> {
+        self.get_schema_for_id(id)
+            .await
+            .map_err(|e| format!("Failed to fetch schema {} from registry: {}", id, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::{Path, State};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+
+    use super::{ConfluentSchemaRegistry, ConfluentSchemaType};
+
+    type Schemas = Arc<Mutex<Vec<Value>>>;
+
+    async fn register(
+        State(schemas): State<Schemas>,
+        Path(subject): Path<String>,
+        Json(body): Json<Value>,
+    ) -> Json<Value> {
+        assert_eq!(subject, "orders-value");
+        let mut schemas = schemas.lock().unwrap();
+        let id = match schemas.iter().position(|s| *s == body) {
+            Some(id) => id,
+            None => {
+                schemas.push(body);
+                schemas.len() - 1
+            }
+        };
+        Json(json!({ "id": id + 100 }))
+    }
+
+    async fn get_by_id(State(schemas): State<Schemas>, Path(id): Path<usize>) -> Json<Value> {
+        let schemas = schemas.lock().unwrap();
+        Json(json!({ "schema": schemas[id - 100]["schema"] }))
+    }
+
+    // a minimal mock of the schema registry's register and lookup endpoints
+    fn mock_registry() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let app = Router::new()
+            .route("/subjects/:subject/versions", post(register))
+            .route("/schemas/ids/:id", get(get_by_id))
+            .with_state(Schemas::default());
+
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_write_schema() {
+        let endpoint = mock_registry();
+        let registry = ConfluentSchemaRegistry::new(&endpoint, "orders", None, None).unwrap();
+
+        let schema =
+            json!({"type": "object", "properties": {"id": {"type": "integer"}}}).to_string();
+
+        let id = registry
+            .write_schema(schema.clone(), ConfluentSchemaType::Json)
+            .await
+            .unwrap();
+
+        // registering the same schema again should return the existing id
+        assert_eq!(
+            id,
+            registry
+                .write_schema(schema.clone(), ConfluentSchemaType::Json)
+                .await
+                .unwrap()
+        );
+
+        assert_eq!(Some(schema), registry.get_schema_for_id(id).await.unwrap());
+    }
+}
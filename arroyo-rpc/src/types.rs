@@ -1,6 +1,8 @@
 use crate::grpc as grpc_proto;
 use crate::grpc::api as api_proto;
 use anyhow::bail;
+use base64::Engine;
+use chrono::format::{Item, StrftimeItems};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
@@ -345,6 +347,8 @@ pub struct ConnectionProfilePost {
 pub enum ConnectionType {
     Source,
     Sink,
+    /// A table that is queried by key to enrich the records of a stream, rather than read in full
+    Lookup,
 }
 
 impl Display for ConnectionType {
@@ -352,6 +356,7 @@ impl Display for ConnectionType {
         match self {
             ConnectionType::Source => write!(f, "SOURCE"),
             ConnectionType::Sink => write!(f, "SINK"),
+            ConnectionType::Lookup => write!(f, "LOOKUP"),
         }
     }
 }
@@ -363,6 +368,7 @@ impl TryFrom<String> for ConnectionType {
         match value.to_lowercase().as_str() {
             "source" => Ok(ConnectionType::Source),
             "sink" => Ok(ConnectionType::Sink),
+            "lookup" => Ok(ConnectionType::Lookup),
             _ => Err(format!("Invalid connection type: {}", value)),
         }
     }
@@ -376,7 +382,21 @@ pub enum TimestampFormat {
     #[default]
     #[serde(rename = "rfc3339")]
     RFC3339,
+    /// Unix timestamps are written as integers, but may be either integers or fractional numbers
+    /// when read
+    UnixSeconds,
     UnixMillis,
+    UnixMicros,
+    UnixNanos,
+    /// An ISO-8601 date time without a timezone, which is interpreted as UTC
+    #[serde(rename = "iso8601")]
+    ISO8601,
+    /// A strftime-style pattern. Timestamps that don't include an offset are interpreted in the
+    /// given timezone, or UTC if none is set.
+    Pattern {
+        pattern: String,
+        timezone: Option<String>,
+    },
 }
 
 impl TryFrom<&str> for TimestampFormat {
@@ -384,13 +404,66 @@ impl TryFrom<&str> for TimestampFormat {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
-            "RFC3339" => Ok(TimestampFormat::RFC3339),
+            "RFC3339" | "rfc3339" => Ok(TimestampFormat::RFC3339),
+            "UnixSeconds" | "unix_seconds" => Ok(TimestampFormat::UnixSeconds),
             "UnixMillis" | "unix_millis" => Ok(TimestampFormat::UnixMillis),
+            "UnixMicros" | "unix_micros" => Ok(TimestampFormat::UnixMicros),
+            "UnixNanos" | "unix_nanos" => Ok(TimestampFormat::UnixNanos),
+            "ISO8601" | "iso8601" => Ok(TimestampFormat::ISO8601),
             _ => Err(()),
         }
     }
 }
 
+impl TimestampFormat {
+    fn from_opts(
+        default: TimestampFormat,
+        opts: &mut HashMap<String, String>,
+    ) -> Result<TimestampFormat, String> {
+        let format = opts.remove("json.timestamp_format");
+        let pattern = opts.remove("json.timestamp_pattern");
+        let timezone = opts.remove("json.timestamp_timezone");
+
+        if let Some(pattern) = pattern {
+            if format.is_some() {
+                return Err(
+                    "only one of json.timestamp_format and json.timestamp_pattern may be set"
+                        .to_string(),
+                );
+            }
+
+            let format = TimestampFormat::Pattern { pattern, timezone };
+            format.validate()?;
+            return Ok(format);
+        }
+
+        if timezone.is_some() {
+            return Err("json.timestamp_timezone requires json.timestamp_pattern".to_string());
+        }
+
+        Ok(format
+            .map(|t| t.as_str().try_into())
+            .transpose()
+            .map_err(|_| "json.timestamp_format".to_string())?
+            .unwrap_or(default))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let TimestampFormat::Pattern { pattern, timezone } = self {
+            if StrftimeItems::new(pattern).any(|i| i == Item::Error) {
+                return Err(format!("invalid timestamp pattern '{}'", pattern));
+            }
+
+            if let Some(tz) = timezone {
+                tz.parse::<chrono_tz::Tz>()
+                    .map_err(|_| format!("invalid timezone '{}'", tz))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(
     Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
 )]
@@ -405,6 +478,11 @@ pub struct JsonFormat {
     #[serde(default)]
     pub debezium: bool,
 
+    /// Set for the Maxwell and Canal change data capture envelopes, which like Debezium produce
+    /// updating tables
+    #[serde(default)]
+    pub envelope: Option<JsonEnvelope>,
+
     #[serde(default)]
     pub unstructured: bool,
 
@@ -413,7 +491,11 @@ pub struct JsonFormat {
 }
 
 impl JsonFormat {
-    fn from_opts(debezium: bool, opts: &mut HashMap<String, String>) -> Result<Self, String> {
+    fn from_opts(
+        debezium: bool,
+        envelope: Option<JsonEnvelope>,
+        opts: &mut HashMap<String, String>,
+    ) -> Result<Self, String> {
         let confluent_schema_registry = opts
             .remove("json.confluent_schema_registry")
             .filter(|t| t == "true")
@@ -429,36 +511,197 @@ impl JsonFormat {
             .filter(|t| t == "true")
             .is_some();
 
-        let timestamp_format: TimestampFormat = opts
-            .remove("json.timestamp_format")
-            .map(|t| t.as_str().try_into())
-            .transpose()
-            .map_err(|_| "json.timestamp_format".to_string())?
-            .unwrap_or_else(|| {
-                if debezium {
-                    TimestampFormat::UnixMillis
-                } else {
-                    TimestampFormat::default()
-                }
-            });
+        // MySQL-based CDC tools write datetimes as strings like '2023-01-01 12:00:00'
+        let default_timestamp_format = if debezium {
+            TimestampFormat::UnixMillis
+        } else if envelope.is_some() {
+            TimestampFormat::ISO8601
+        } else {
+            TimestampFormat::default()
+        };
+        let timestamp_format = TimestampFormat::from_opts(default_timestamp_format, opts)?;
 
         Ok(Self {
             confluent_schema_registry,
             include_schema,
             debezium,
+            envelope,
             unstructured,
             timestamp_format,
         })
     }
 }
 
+/// Change data capture envelopes, in which each message describes an insert, update or delete
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonEnvelope {
+    /// `{"type": "insert", "data": {...}, "old": {...}}`, as written by Maxwell
+    Maxwell,
+    /// `{"type": "INSERT", "data": [{...}], "old": [{...}]}`, as written by Canal
+    Canal,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RawStringFormat {}
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AvroFormat {}
+pub struct RawBytesFormat {}
+
+#[derive(
+    Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct AvroFormat {
+    /// Whether messages are framed with the Confluent Schema Registry wire format (a magic byte
+    /// followed by a 4-byte schema id)
+    #[serde(default)]
+    pub confluent_schema_registry: bool,
+
+    /// The Avro schema for the table, as JSON. For sources this is used as the reader schema
+    /// when resolving against the writer schema; for sinks it is the schema records are written
+    /// with. If unset, sinks will derive a schema from the table's fields.
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+impl AvroFormat {
+    fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
+        let confluent_schema_registry = opts
+            .remove("avro.confluent_schema_registry")
+            .filter(|t| t == "true")
+            .is_some();
+
+        Ok(Self {
+            confluent_schema_registry,
+            schema: opts.remove("avro.schema"),
+        })
+    }
+}
+
+#[derive(
+    Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtobufFormat {
+    /// The fully-qualified name of the message type records are encoded as. May be omitted if
+    /// the schema contains a single message.
+    #[serde(default)]
+    pub message_name: Option<String>,
+
+    /// A serialized `FileDescriptorSet` containing the message type and all of its dependencies
+    #[serde(default)]
+    pub compiled_schema: Option<Vec<u8>>,
+}
+
+impl ProtobufFormat {
+    fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
+        let compiled_schema = opts
+            .remove("protobuf.descriptor_set")
+            .map(|s| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(s.trim())
+                    .map_err(|_| "protobuf.descriptor_set must be base64-encoded".to_string())
+            })
+            .transpose()?;
+
+        Ok(Self {
+            message_name: opts.remove("protobuf.message_name"),
+            compiled_schema,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvFormat {
+    #[serde(default = "default_csv_delimiter")]
+    pub delimiter: char,
+
+    #[serde(default = "default_csv_quote")]
+    pub quote: char,
+
+    /// The character used to escape quotes inside quoted fields; if unset, quotes are escaped
+    /// by doubling them
+    #[serde(default)]
+    pub escape: Option<char>,
+
+    /// Whether the data starts with a header row. When reading, the header is used to match
+    /// columns to fields by name rather than by position; when writing, a header row is
+    /// written at the start of each file.
+    #[serde(default)]
+    pub header: bool,
+
+    /// The string that represents a null value
+    #[serde(default)]
+    pub null_value: String,
+}
+
+fn default_csv_delimiter() -> char {
+    ','
+}
+
+fn default_csv_quote() -> char {
+    '"'
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        Self {
+            delimiter: default_csv_delimiter(),
+            quote: default_csv_quote(),
+            escape: None,
+            header: false,
+            null_value: String::new(),
+        }
+    }
+}
+
+impl CsvFormat {
+    /// Builds a format from its settings, which are checked to be single ASCII characters
+    pub fn new(
+        delimiter: Option<&str>,
+        quote: Option<&str>,
+        escape: Option<&str>,
+        header: bool,
+        null_value: Option<String>,
+    ) -> Result<Self, String> {
+        fn single_char(value: Option<&str>, key: &str) -> Result<Option<char>, String> {
+            value
+                .map(|s| {
+                    let mut chars = s.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if c.is_ascii() => Ok(c),
+                        _ => Err(format!("{} must be a single ASCII character", key)),
+                    }
+                })
+                .transpose()
+        }
+
+        Ok(Self {
+            delimiter: single_char(delimiter, "csv.delimiter")?
+                .unwrap_or_else(default_csv_delimiter),
+            quote: single_char(quote, "csv.quote")?.unwrap_or_else(default_csv_quote),
+            escape: single_char(escape, "csv.escape")?,
+            header,
+            null_value: null_value.unwrap_or_default(),
+        })
+    }
+
+    fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
+        let header = opts.remove("csv.header").filter(|t| t == "true").is_some();
+
+        Self::new(
+            opts.remove("csv.delimiter").as_deref(),
+            opts.remove("csv.quote").as_deref(),
+            opts.remove("csv.escape").as_deref(),
+            header,
+            opts.remove("csv.null_value"),
+        )
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -469,8 +712,11 @@ pub struct ParquetFormat {}
 pub enum Format {
     Json(JsonFormat),
     Avro(AvroFormat),
+    Protobuf(ProtobufFormat),
+    Csv(CsvFormat),
     Parquet(ParquetFormat),
     RawString(RawStringFormat),
+    RawBytes(RawBytesFormat),
 }
 
 impl Format {
@@ -480,11 +726,23 @@ impl Format {
         };
 
         Ok(Some(match name.as_str() {
-            "json" => Format::Json(JsonFormat::from_opts(false, opts)?),
-            "debezium_json" => Format::Json(JsonFormat::from_opts(true, opts)?),
-            "protobuf" => return Err("protobuf is not yet supported".to_string()),
-            "avro" => return Err("avro is not yet supported".to_string()),
+            "json" => Format::Json(JsonFormat::from_opts(false, None, opts)?),
+            "debezium_json" => Format::Json(JsonFormat::from_opts(true, None, opts)?),
+            "maxwell_json" => Format::Json(JsonFormat::from_opts(
+                false,
+                Some(JsonEnvelope::Maxwell),
+                opts,
+            )?),
+            "canal_json" => Format::Json(JsonFormat::from_opts(
+                false,
+                Some(JsonEnvelope::Canal),
+                opts,
+            )?),
+            "protobuf" => Format::Protobuf(ProtobufFormat::from_opts(opts)?),
+            "avro" => Format::Avro(AvroFormat::from_opts(opts)?),
+            "csv" => Format::Csv(CsvFormat::from_opts(opts)?),
             "raw_string" => Format::RawString(RawStringFormat {}),
+            "raw_bytes" => Format::RawBytes(RawBytesFormat {}),
             "parquet" => Format::Parquet(ParquetFormat {}),
             f => return Err(format!("Unknown format '{}'", f)),
         }))
@@ -492,12 +750,65 @@ impl Format {
 
     pub fn is_updating(&self) -> bool {
         match self {
-            Format::Json(JsonFormat { debezium: true, .. }) => true,
-            Format::Json(_) | Format::Avro(_) | Format::Parquet(_) | Format::RawString(_) => false,
+            Format::Json(JsonFormat { debezium: true, .. })
+            | Format::Json(JsonFormat {
+                envelope: Some(_), ..
+            }) => true,
+            Format::Json(_)
+            | Format::Avro(_)
+            | Format::Protobuf(_)
+            | Format::Csv(_)
+            | Format::Parquet(_)
+            | Format::RawString(_)
+            | Format::RawBytes(_) => false,
         }
     }
 }
 
+/// Determines what a source does with messages that cannot be deserialized. When no policy is
+/// set, the error is reported and the message is skipped.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BadData {
+    /// Fail the job
+    Fail {},
+    /// Drop the message, counting it in the deserialization errors metric
+    Drop {},
+    /// Write the raw message and the error to another (sink) table; only supported for Kafka
+    /// sources, and the table must be a Kafka sink
+    DeadLetter { table: String },
+}
+
+impl BadData {
+    pub fn from_opts(opts: &mut HashMap<String, String>) -> Result<Option<Self>, String> {
+        let Some(policy) = opts.remove("bad_data") else {
+            if opts.contains_key("bad_data.dead_letter_table") {
+                return Err(
+                    "bad_data.dead_letter_table requires bad_data = 'dead_letter'".to_string(),
+                );
+            }
+            return Ok(None);
+        };
+
+        Ok(Some(match policy.as_str() {
+            "fail" => BadData::Fail {},
+            "drop" => BadData::Drop {},
+            "dead_letter" => BadData::DeadLetter {
+                table: opts.remove("bad_data.dead_letter_table").ok_or_else(|| {
+                    "bad_data = 'dead_letter' requires bad_data.dead_letter_table to be set"
+                        .to_string()
+                })?,
+            },
+            p => {
+                return Err(format!(
+                "Unknown bad_data policy '{}'; expected one of 'fail', 'drop', or 'dead_letter'",
+                p
+            ))
+            }
+        }))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum PrimitiveType {
@@ -562,17 +873,21 @@ pub struct ConnectionSchema {
     pub struct_name: Option<String>,
     pub fields: Vec<SourceField>,
     pub definition: Option<SchemaDefinition>,
+    #[serde(default)]
+    pub bad_data: Option<BadData>,
 }
 
 impl ConnectionSchema {
     pub fn try_new(
         format: Option<Format>,
+        bad_data: Option<BadData>,
         struct_name: Option<String>,
         fields: Vec<SourceField>,
         definition: Option<SchemaDefinition>,
     ) -> anyhow::Result<Self> {
         let s = ConnectionSchema {
             format,
+            bad_data,
             struct_name,
             fields,
             definition,
@@ -591,6 +906,19 @@ impl ConnectionSchema {
                     bail!("raw_string format requires a schema with a single field of type TEXT");
                 }
             }
+            Some(Format::RawBytes(_)) => {
+                if self.fields.len() != 1
+                    || self.fields.get(0).unwrap().field_type.r#type
+                        != FieldType::Primitive(PrimitiveType::Bytes)
+                {
+                    bail!("raw_bytes format requires a schema with a single field of type BYTEA");
+                }
+            }
+            Some(Format::Json(json)) => {
+                if let Err(e) = json.timestamp_format.validate() {
+                    bail!("{}", e);
+                }
+            }
             _ => {}
         }
 
@@ -639,6 +967,25 @@ pub struct ConfluentSchema {
     pub schema: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaInferencePost {
+    pub table: ConnectionTablePost,
+    /// The maximum number of messages to sample from the table; defaults to 100
+    pub samples: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InferredSchema {
+    pub schema: ConnectionSchema,
+    /// The number of messages the schema was inferred from
+    pub samples: u32,
+    /// Fields that had different types in different messages, which are left out of the schema
+    pub conflicts: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, IntoParams)]
 #[serde(rename_all = "camelCase")]
 pub struct ConfluentSchemaQueryParams {
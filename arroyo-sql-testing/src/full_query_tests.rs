@@ -131,6 +131,52 @@ INSERT INTO kafka_raw_sink
 SELECT bid.price FROM nexmark;
 "}
 
+full_pipeline_codegen! {"maxwell_aggregate_to_canal_sink", "
+CREATE TABLE orders (
+  customer_id int,
+  amount double,
+  updated_at timestamp
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'source',
+  topic = 'orders',
+  format = 'maxwell_json'
+);
+
+CREATE TABLE totals (
+  customer_id int,
+  total double
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'sink',
+  topic = 'totals',
+  format = 'canal_json'
+);
+
+INSERT INTO totals
+SELECT customer_id, sum(amount) FROM orders GROUP BY 1;
+"}
+
+full_pipeline_codegen! {"kafka_upsert_sink", "
+CREATE TABLE auction_bids (
+  auction bigint,
+  bids bigint,
+  PRIMARY KEY (auction)
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'sink',
+  topic = 'auction_bids',
+  format = 'json',
+  'sink.key_field' = 'auction'
+);
+
+INSERT INTO auction_bids
+SELECT bid.auction, count(*) FROM nexmark WHERE bid IS NOT NULL GROUP BY 1;
+"}
+
 full_pipeline_codegen! {"filter_on_updating_aggregates", "
 SELECT auction  / 2 as half_auction
 FROM (
@@ -421,6 +467,32 @@ SELECT 'test'
 FROM nexmark;
 "}
 
+full_pipeline_codegen! {"raw_bytes_test",
+"CREATE TABLE raw_source (
+  value BYTEA
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'source',
+  topic = 'inputs',
+  format = 'raw_bytes'
+);
+
+CREATE TABLE raw_sink (
+  output BYTEA
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'sink',
+  topic = 'outputs',
+  format = 'raw_bytes'
+);
+
+INSERT INTO raw_sink
+SELECT value
+FROM raw_source;
+"}
+
 full_pipeline_codegen! {"polling_http_source",
 "CREATE TABLE polling_source (
   value TEXT NOT NULL
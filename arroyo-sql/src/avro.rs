@@ -0,0 +1,236 @@
+use apache_avro::Schema;
+use arrow::datatypes::Field;
+use arrow_schema::{DataType, TimeUnit};
+
+use crate::types::{StructDef, StructField, TypeDef};
+
+pub fn convert_avro_schema(name: &str, schema: &str) -> Result<Vec<StructField>, String> {
+    let schema = Schema::parse_str(schema).map_err(|e| format!("Invalid avro schema: {:?}", e))?;
+
+    let Schema::Record(record) = &schema else {
+        return Err(format!(
+            "Top-level avro schema for {} must be a record",
+            name
+        ));
+    };
+
+    record
+        .fields
+        .iter()
+        .map(|f| {
+            let (data_type, original) = to_schema_type(&f.name, &f.schema)?;
+            Ok(StructField::with_rename(
+                f.name.clone(),
+                None,
+                data_type,
+                None,
+                original,
+            ))
+        })
+        .collect()
+}
+
+fn to_schema_type(field_name: &str, schema: &Schema) -> Result<(TypeDef, Option<String>), String> {
+    use DataType::*;
+
+    let data_type = match schema {
+        Schema::Boolean => Boolean,
+        Schema::Int | Schema::TimeMillis => Int32,
+        Schema::Long | Schema::TimeMicros => Int64,
+        Schema::Float => Float32,
+        Schema::Double => Float64,
+        Schema::Bytes | Schema::Fixed(_) => Binary,
+        Schema::String | Schema::Enum(_) | Schema::Uuid => Utf8,
+        Schema::TimestampMillis | Schema::TimestampMicros | Schema::Date => {
+            Timestamp(TimeUnit::Millisecond, None)
+        }
+        // arrays and maps are represented as JSON-encoded text
+        Schema::Array(_) | Schema::Map(_) => Utf8,
+        Schema::Union(union) => {
+            // nullable fields are represented as unions of null and a single other type
+            let non_null: Vec<_> = union
+                .variants()
+                .iter()
+                .filter(|v| !matches!(v, Schema::Null))
+                .collect();
+
+            if non_null.len() != 1 {
+                return Err(format!(
+                    "avro field '{}' is a union of several types, which is not supported; only \
+                    unions of null and a single other type can be used",
+                    field_name
+                ));
+            }
+
+            let (t, original) = to_schema_type(field_name, non_null[0])?;
+            return Ok((
+                if union.is_nullable() {
+                    t.to_optional()
+                } else {
+                    t
+                },
+                original,
+            ));
+        }
+        Schema::Record(record) => {
+            let fields = record
+                .fields
+                .iter()
+                .map(|f| {
+                    let (t, original) = to_schema_type(&f.name, &f.schema)?;
+                    Ok(StructField::with_rename(
+                        f.name.clone(),
+                        None,
+                        t,
+                        None,
+                        original,
+                    ))
+                })
+                .collect::<Result<_, String>>()?;
+
+            return Ok((
+                TypeDef::StructDef(StructDef::for_name(None, fields), false),
+                None,
+            ));
+        }
+        s => {
+            return Err(format!(
+                "avro field '{}' has type {:?}, which is not supported",
+                field_name, s
+            ));
+        }
+    };
+
+    Ok((TypeDef::DataType(data_type, false), None))
+}
+
+/// Checks that records with these fields can be written with an Avro schema generated from their
+/// types, which is how sinks without a configured schema write them
+pub fn validate_generated_schema(fields: &[StructField]) -> Result<(), String> {
+    for field in fields {
+        let field: Field = field.clone().into();
+        check_avro_type(field.name(), field.data_type())?;
+    }
+
+    Ok(())
+}
+
+fn check_avro_type(name: &str, data_type: &DataType) -> Result<(), String> {
+    use DataType::*;
+
+    match data_type {
+        Null
+        | Boolean
+        | Int8
+        | Int16
+        | Int32
+        | Int64
+        | UInt8
+        | UInt16
+        | UInt32
+        | UInt64
+        | Float16
+        | Float32
+        | Float64
+        | Utf8
+        | LargeUtf8
+        | Binary
+        | LargeBinary
+        | FixedSizeBinary(_)
+        | Timestamp(_, _) => Ok(()),
+        List(t) | FixedSizeList(t, _) | LargeList(t) => check_avro_type(name, t.data_type()),
+        Struct(fields) => fields
+            .iter()
+            .try_for_each(|f| check_avro_type(f.name(), f.data_type())),
+        t => Err(format!(
+            "field '{}' has type {:?}, which can't be written as avro",
+            name, t
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arrow_schema::{DataType, TimeUnit};
+
+    use crate::types::{StructField, TypeDef};
+
+    use super::{convert_avro_schema, validate_generated_schema};
+
+    #[test]
+    fn test_convert() {
+        let schema = r#"
+        {
+            "type": "record",
+            "name": "Order",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "customer", "type": ["null", "string"], "default": null},
+                {"name": "created_at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+                {"name": "address", "type": {
+                    "type": "record",
+                    "name": "Address",
+                    "fields": [{"name": "city", "type": "string"}]
+                }},
+                {"name": "tags", "type": {"type": "array", "items": "string"}}
+            ]
+        }"#;
+
+        let fields = convert_avro_schema("orders", schema).unwrap();
+        let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["id", "customer", "created_at", "address", "tags"]
+        );
+
+        assert_eq!(
+            fields[0].data_type,
+            TypeDef::DataType(DataType::Int64, false)
+        );
+        assert_eq!(fields[1].data_type, TypeDef::DataType(DataType::Utf8, true));
+        assert_eq!(
+            fields[2].data_type,
+            TypeDef::DataType(DataType::Timestamp(TimeUnit::Millisecond, None), false)
+        );
+        assert!(matches!(fields[3].data_type, TypeDef::StructDef(_, false)));
+        assert_eq!(
+            fields[4].data_type,
+            TypeDef::DataType(DataType::Utf8, false)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_union() {
+        let schema = r#"
+        {
+            "type": "record",
+            "name": "Event",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "value", "type": ["null", "string", "long"]}
+            ]
+        }"#;
+
+        let err = convert_avro_schema("events", schema).unwrap_err();
+        assert!(err.contains("avro field 'value' is a union of several types"));
+    }
+
+    #[test]
+    fn test_validate_generated_schema() {
+        let field = |name: &str, data_type| {
+            StructField::new(name.to_string(), None, TypeDef::DataType(data_type, false))
+        };
+
+        assert!(validate_generated_schema(&[
+            field("id", DataType::Int64),
+            field(
+                "created_at",
+                DataType::Timestamp(TimeUnit::Millisecond, None)
+            ),
+        ])
+        .is_ok());
+
+        let err = validate_generated_schema(&[field("day", DataType::Date32)]).unwrap_err();
+        assert!(err.contains("field 'day' has type Date32"));
+    }
+}
@@ -32,6 +32,8 @@ pub enum SinkUpdateType {
     Allow,
     Disallow,
     Force,
+    /// Updates are written as upserts, keyed by the given primary key columns
+    Upsert(Vec<String>),
 }
 
 #[derive(Clone, Debug)]
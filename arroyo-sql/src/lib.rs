@@ -8,6 +8,7 @@ use arroyo_connectors::{Connection, Connector};
 use arroyo_datastream::Program;
 use datafusion::physical_plan::functions::make_scalar_function;
 
+pub mod avro;
 pub(crate) mod code_gen;
 pub mod expressions;
 pub mod external;
@@ -16,6 +17,7 @@ mod operators;
 mod optimizations;
 mod pipeline;
 mod plan_graph;
+pub mod protobuf;
 pub mod schemas;
 mod tables;
 pub mod types;
@@ -377,7 +379,12 @@ pub fn parse_and_get_program_sync(
         };
     }
 
-    let mut sql_pipeline_builder = SqlPipelineBuilder::new(&mut schema_provider);
+    let source_projections = optimizations::source_projections(
+        inserts.iter().map(Insert::logical_plan),
+        &schema_provider,
+    );
+    let mut sql_pipeline_builder =
+        SqlPipelineBuilder::new(&mut schema_provider, source_projections);
     for insert in inserts {
         sql_pipeline_builder.add_insert(insert)?;
     }
@@ -410,9 +417,11 @@ pub fn parse_and_get_program_sync(
                 debezium: insert.is_updating(),
                 ..Default::default()
             })),
+            bad_data: None,
             event_time_field: None,
             watermark_field: None,
             idle_time: DEFAULT_IDLE_TIME,
+            primary_keys: vec![],
         });
 
         plan_graph.add_sql_operator(sink.as_sql_sink(insert)?);
@@ -580,6 +589,7 @@ pub fn get_test_expression(
     let struct_def = test_struct_def();
     let schema = ConnectionSchema {
         format: Some(Format::Json(JsonFormat::default())),
+        bad_data: None,
         struct_name: struct_def.name.clone(),
         fields: struct_def
             .fields
@@ -603,6 +613,10 @@ pub fn get_test_expression(
                 type_: arroyo_connectors::kafka::TableType::Source {
                     offset: arroyo_connectors::kafka::SourceOffset::Latest,
                     read_mode: Some(arroyo_connectors::kafka::ReadMode::ReadUncommitted),
+                    start_timestamp: None,
+                    start_offsets: None,
+                    group_id: None,
+                    subscription: None,
                 },
             },
             Some(&schema),
@@ -1,7 +1,9 @@
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
 use anyhow::Result;
 use arroyo_datastream::{EdgeType, ExpressionReturnType, ExpressionReturnType::*, WindowType};
+use datafusion_expr::LogicalPlan;
 
 use petgraph::data::DataMap;
 use petgraph::graph::DiGraph;
@@ -19,6 +21,8 @@ use crate::pipeline::RecordTransform;
 use crate::plan_graph::{
     FusedRecordTransform, PlanEdge, PlanNode, PlanOperator, PlanType, WindowFunctionOperator,
 };
+use crate::tables::Table;
+use crate::ArroyoSchemaProvider;
 
 pub fn optimize(graph: &mut DiGraph<PlanNode, PlanEdge>) {
     WindowTopNOptimization::default().optimize(graph);
@@ -532,3 +536,48 @@ impl Optimizer for WindowTopNOptimization {
         false
     }
 }
+
+/// Computes the fields of each connector table that are read by the given plans, based on the
+/// projections that DataFusion has pushed down into their table scans. Sources can then skip
+/// deserializing the rest. A table that is scanned without a projection needs all of its fields.
+pub fn source_projections<'a>(
+    plans: impl IntoIterator<Item = &'a LogicalPlan>,
+    schema_provider: &ArroyoSchemaProvider,
+) -> HashMap<String, HashSet<String>> {
+    let mut projections = HashMap::new();
+    for plan in plans {
+        add_source_projections(plan, schema_provider, &mut projections);
+    }
+    projections
+}
+
+fn add_source_projections(
+    plan: &LogicalPlan,
+    schema_provider: &ArroyoSchemaProvider,
+    projections: &mut HashMap<String, HashSet<String>>,
+) {
+    if let LogicalPlan::TableScan(table_scan) = plan {
+        let table_name = table_scan.table_name.to_string();
+        match schema_provider.get_table(&table_name) {
+            Some(Table::ConnectorTable(table)) => {
+                let fields = table.fields.iter().map(|f| f.struct_field().name.clone());
+                let used: Vec<String> = match &table_scan.projection {
+                    Some(projection) => {
+                        let fields: Vec<_> = fields.collect();
+                        projection.iter().map(|i| fields[*i].clone()).collect()
+                    }
+                    None => fields.collect(),
+                };
+                projections.entry(table_name).or_default().extend(used);
+            }
+            Some(Table::TableFromQuery { logical_plan, .. }) => {
+                add_source_projections(logical_plan, schema_provider, projections);
+            }
+            Some(Table::MemoryTable { .. }) | None => {}
+        }
+    }
+
+    for input in plan.inputs() {
+        add_source_projections(input, schema_provider, projections);
+    }
+}
@@ -1,5 +1,5 @@
 #![allow(clippy::comparison_chain)]
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use std::time::Duration;
 use std::unreachable;
@@ -8,6 +8,7 @@ use anyhow::Result;
 use anyhow::{anyhow, bail};
 use arrow_schema::DataType;
 use arroyo_datastream::{Operator, WindowType};
+use arroyo_rpc::types::ConnectionType;
 use datafusion_common::{DFField, ScalarValue};
 use datafusion_expr::expr::ScalarUDF;
 use datafusion_expr::{
@@ -20,7 +21,7 @@ use crate::code_gen::{CodeGenerator, ValuePointerContext, VecAggregationContext}
 use crate::expressions::{AggregateComputation, AggregateResultExtraction, ExpressionContext};
 use crate::external::{ProcessingMode, SqlSink, SqlSource};
 use crate::schemas::window_type_def;
-use crate::tables::{Insert, Table};
+use crate::tables::{ConnectorTable, Insert, Table};
 use crate::{
     expressions::{Column, ColumnExpression, Expression, SortExpression},
     operators::{AggregateProjection, Projection},
@@ -33,6 +34,7 @@ pub enum SqlOperator {
     Source(SourceOperator),
     Aggregator(Box<SqlOperator>, AggregateOperator),
     JoinOperator(Box<SqlOperator>, Box<SqlOperator>, JoinOperator),
+    LookupJoin(Box<SqlOperator>, LookupJoinOperator),
     Window(Box<SqlOperator>, SqlWindowOperator),
     RecordTransform(Box<SqlOperator>, RecordTransform),
     Sink(String, SqlSink, Box<SqlOperator>),
@@ -180,6 +182,21 @@ pub struct JoinOperator {
     pub join_type: JoinType,
 }
 
+/// A join with a lookup table, which is queried for each record of the input rather than read as a
+/// stream
+#[derive(Debug, Clone)]
+pub struct LookupJoinOperator {
+    /// Computes the key that the table is queried with from each input record. Its fields have the
+    /// names of the columns of the lookup table that are joined on.
+    pub key: Projection,
+    /// The rows that are read from the table
+    pub lookup_struct: StructDef,
+    /// The fields of the table as they're named in the query
+    pub right_struct: StructDef,
+    pub operator: Operator,
+    pub join_type: JoinType,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum JoinType {
     /// Inner Join
@@ -283,6 +300,9 @@ impl SqlOperator {
             SqlOperator::JoinOperator(left, right, operator) => operator
                 .join_type
                 .output_struct(&left.return_type(), &right.return_type()),
+            SqlOperator::LookupJoin(input, lookup) => lookup
+                .join_type
+                .output_struct(&input.return_type(), &lookup.right_struct),
             SqlOperator::Window(input, window) => {
                 let mut input_struct = input.return_type();
                 input_struct.fields.push(StructField::new(
@@ -307,6 +327,7 @@ impl SqlOperator {
                 !matches!(aggregator.window, WindowType::Instant) || input.has_window()
             }
             SqlOperator::JoinOperator(left, right, _) => left.has_window() || right.has_window(),
+            SqlOperator::LookupJoin(input, _) => input.has_window(),
             SqlOperator::Window(_, _) => true,
             SqlOperator::RecordTransform(input, _) => input.has_window(),
             SqlOperator::Sink(_, _, input) => input.has_window(),
@@ -328,6 +349,8 @@ impl SqlOperator {
                     || (!left.has_window() && join_operator.join_type.left_nullable())
                     || (!right.has_window() && join_operator.join_type.right_nullable())
             }
+            // each record is only joined once, with the row the table holds when it's processed
+            SqlOperator::LookupJoin(input, _) => input.is_updating(),
             SqlOperator::Window(input, sql_window_operator) => {
                 input.is_updating() // TODO: figure out when this second case is supposed to be triggered.
                     || (!input.has_window() && sql_window_operator.window_type == WindowType::Instant)
@@ -348,6 +371,7 @@ impl SqlOperator {
                 WindowType::Instant => input.get_window(),
             },
             SqlOperator::JoinOperator(left, _, _) => left.get_window(),
+            SqlOperator::LookupJoin(input, _) => input.get_window(),
             SqlOperator::Window(_, sql_window_operator) => {
                 Some(sql_window_operator.window_type.clone())
             }
@@ -363,14 +387,20 @@ pub struct SqlPipelineBuilder<'a> {
     pub schema_provider: &'a ArroyoSchemaProvider,
     pub planned_tables: HashMap<String, SqlOperator>,
     pub insert_nodes: Vec<SqlOperator>,
+    // the fields of each source table that are read by the query
+    pub source_projections: HashMap<String, HashSet<String>>,
 }
 
 impl<'a> SqlPipelineBuilder<'a> {
-    pub fn new(schema_provider: &'a ArroyoSchemaProvider) -> Self {
+    pub fn new(
+        schema_provider: &'a ArroyoSchemaProvider,
+        source_projections: HashMap<String, HashSet<String>>,
+    ) -> Self {
         SqlPipelineBuilder {
             schema_provider,
             planned_tables: HashMap::new(),
             insert_nodes: vec![],
+            source_projections,
         }
     }
 
@@ -693,6 +723,16 @@ impl<'a> SqlPipelineBuilder<'a> {
     }
 
     fn insert_join(&mut self, join: &datafusion_expr::logical_plan::Join) -> Result<SqlOperator> {
+        if let Some(table) = self.lookup_table(&join.left) {
+            bail!(
+                "lookup table {} must be on the right side of the join",
+                table.name
+            );
+        }
+        if let Some(table) = self.lookup_table(&join.right) {
+            return self.insert_lookup_join(join, table);
+        }
+
         let left_input = self.insert_sql_plan(&join.left)?;
         let right_input = self.insert_sql_plan(&join.right)?;
         if left_input.is_updating() || right_input.is_updating() {
@@ -783,6 +823,91 @@ impl<'a> SqlPipelineBuilder<'a> {
         ))
     }
 
+    /// Returns the lookup table that a join input reads from, if it's a (possibly aliased) scan of one
+    fn lookup_table(&self, plan: &LogicalPlan) -> Option<ConnectorTable> {
+        let table_scan = match plan {
+            LogicalPlan::TableScan(table_scan) => table_scan,
+            LogicalPlan::SubqueryAlias(alias) => match alias.input.as_ref() {
+                LogicalPlan::TableScan(table_scan) => table_scan,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        match self
+            .schema_provider
+            .get_table(&table_scan.table_name.to_string())?
+        {
+            Table::ConnectorTable(table)
+                if matches!(table.connection_type, ConnectionType::Lookup) =>
+            {
+                Some(table.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn insert_lookup_join(
+        &mut self,
+        join: &datafusion_expr::logical_plan::Join,
+        table: ConnectorTable,
+    ) -> Result<SqlOperator> {
+        let input = self.insert_sql_plan(&join.left)?;
+        if input.is_updating() {
+            bail!("don't support lookup joins with updating inputs");
+        }
+
+        let join_type = join.join_type.try_into()?;
+        if !matches!(join_type, JoinType::Inner | JoinType::Left) {
+            bail!("only inner and left joins are supported with lookup tables");
+        }
+
+        if join.filter.is_some() || join.on.is_empty() {
+            bail!(
+                "lookup joins must be equality joins on columns of the lookup table {}",
+                table.name
+            );
+        }
+
+        let input_struct = input.return_type();
+        let (names, computations): (Vec<_>, Vec<_>) = join
+            .on
+            .iter()
+            .map(|(left, right)| {
+                // the key may be cast to the type of the input's side of the join
+                let right = match right {
+                    Expr::Cast(datafusion_expr::Cast { expr, .. }) => expr.as_ref(),
+                    right => right,
+                };
+                let Expr::Column(column) = right else {
+                    bail!(
+                        "lookup joins must be equality joins on columns of the lookup table {}",
+                        table.name
+                    );
+                };
+                Ok((
+                    Column {
+                        relation: None,
+                        name: column.name.clone(),
+                    },
+                    self.ctx(&input_struct).compile_expr(left)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+
+        table
+            .as_sql_lookup(
+                self.schema_provider,
+                input,
+                Projection::new(names, computations),
+                join.right.schema(),
+                join_type,
+            )
+            .map_err(|e| anyhow!("failed to plan lookup join with {}: {}", table.name, e))
+    }
+
     fn insert_table_scan(
         &mut self,
         table_scan: &datafusion::logical_expr::TableScan,
@@ -796,11 +921,25 @@ impl<'a> SqlPipelineBuilder<'a> {
             .as_sql_source(self)
             .map_err(|e| anyhow!("failed to plan {}: {}", table_scan.table_name, e))?;
 
-        if let Some(projection) = table_scan.projection.as_ref() {
-            let fields: Vec<StructField> = projection
+        if table_scan.projection.is_some() {
+            // the source may have been narrowed to the fields used by the query, so the projected
+            // fields are looked up by name rather than by index
+            let return_type = source.return_type();
+            let fields: Vec<StructField> = table_scan
+                .projected_schema
+                .fields()
                 .iter()
-                .map(|i| source.return_type().fields[*i].clone())
-                .collect();
+                .map(|f| {
+                    return_type
+                        .fields
+                        .iter()
+                        .find(|t| t.name == *f.name())
+                        .cloned()
+                        .ok_or_else(|| {
+                            anyhow!("field {} not found in {}", f.name(), table_scan.table_name)
+                        })
+                })
+                .collect::<Result<_>>()?;
 
             let field_names = fields
                 .iter()
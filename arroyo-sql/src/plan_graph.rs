@@ -20,7 +20,7 @@ use crate::{
         MemoryAddingContext, MemoryAggregatingContext, MemoryRemovingContext,
         ValueBinMergingContext, ValuePointerContext, VecAggregationContext,
     },
-    expressions::SortExpression,
+    expressions::{Column, ColumnExpression, Expression, SortExpression},
     external::{ProcessingMode, SinkUpdateType, SqlSink, SqlSource},
     operators::{AggregateProjection, Projection, TwoPhaseAggregateProjection},
     optimizations::optimize,
@@ -65,6 +65,16 @@ pub enum PlanOperator {
     },
     JoinListMerge(JoinType, StructPair),
     JoinPairMerge(JoinType, StructPair),
+    /// Queries a lookup table with the key of each record
+    Lookup(Operator),
+    /// Merges records with the rows looked up for them, renaming the fields of the rows to the
+    /// names used in the query
+    LookupJoinMerge {
+        join_type: JoinType,
+        left: StructDef,
+        lookup: StructDef,
+        right: StructDef,
+    },
     Flatten,
     // TODO: figure out naming of various things called 'window'
     WindowFunction(WindowFunctionOperator),
@@ -90,6 +100,7 @@ pub enum PlanOperator {
     StreamOperator(String, Operator),
     ToDebezium,
     FromDebezium,
+    ToUpdating,
     Sink(String, SqlSink),
 }
 
@@ -316,6 +327,8 @@ impl PlanNode {
             PlanOperator::JoinWithExpiration { .. } => "join_with_expiration".to_string(),
             PlanOperator::JoinListMerge(_, _) => "join_list_merge".to_string(),
             PlanOperator::JoinPairMerge(_, _) => "join_pair_merge".to_string(),
+            PlanOperator::Lookup(_) => "lookup".to_string(),
+            PlanOperator::LookupJoinMerge { .. } => "lookup_join_merge".to_string(),
             PlanOperator::Flatten => "flatten".to_string(),
             PlanOperator::WindowFunction { .. } => "window_function".to_string(),
             PlanOperator::StreamOperator(name, _) => name.to_string(),
@@ -325,6 +338,7 @@ impl PlanNode {
             PlanOperator::Sink(name, _) => format!("sink_{}", name),
             PlanOperator::ToDebezium => "to_debezium".to_string(),
             PlanOperator::FromDebezium => "from_debezium".to_string(),
+            PlanOperator::ToUpdating => "to_updating".to_string(),
             PlanOperator::NonWindowAggregate { .. } => "non_window_aggregate".to_string(),
         }
     }
@@ -700,6 +714,59 @@ impl PlanNode {
                     converter,
                 })
             }
+            PlanOperator::Lookup(operator) => operator.clone(),
+            PlanOperator::LookupJoinMerge {
+                join_type,
+                left,
+                lookup,
+                right,
+            } => {
+                let output_type = join_type.output_struct(left, right).get_type();
+                let left_assignments = left.fields.iter().map(|field| {
+                    let ident = field.field_ident();
+                    quote!(#ident: left.#ident.clone())
+                });
+                let right_assignments = lookup.fields.iter().zip(right.fields.iter()).map(
+                    |(lookup_field, right_field)| {
+                        let ident = lookup_field.field_ident();
+                        let output_ident = right_field.field_ident();
+                        match join_type {
+                            JoinType::Inner => quote!(#output_ident: right.#ident.clone()),
+                            _ if lookup_field.data_type.is_optional() => {
+                                quote!(#output_ident: right.as_ref().and_then(|right| right.#ident.clone()))
+                            }
+                            _ => quote!(#output_ident: right.as_ref().map(|right| right.#ident.clone())),
+                        }
+                    },
+                );
+                let merged = quote!(arroyo_types::Record {
+                    timestamp: record.timestamp,
+                    key: None,
+                    value: #output_type {
+                        #(#left_assignments,)*
+                        #(#right_assignments,)*
+                    },
+                });
+
+                // records that aren't found in the table are dropped by inner joins
+                let expression = match join_type {
+                    JoinType::Inner => quote!({
+                        let left = &record.value.0;
+                        record.value.1.as_ref().map(|right| #merged)
+                    }),
+                    _ => quote!({
+                        let left = &record.value.0;
+                        let right = &record.value.1;
+                        Some(#merged)
+                    }),
+                };
+
+                arroyo_datastream::Operator::ExpressionOperator {
+                    name: "lookup_join_merge".into(),
+                    expression: expression.to_string(),
+                    return_type: ExpressionReturnType::OptionalRecord,
+                }
+            }
             PlanOperator::Flatten => arroyo_datastream::Operator::FlattenOperator {
                 name: "flatten".into(),
             },
@@ -728,6 +795,18 @@ impl PlanNode {
                 .to_string(),
                 return_type: ExpressionReturnType::Record,
             },
+            PlanOperator::ToUpdating => arroyo_datastream::Operator::ExpressionOperator {
+                name: "to_updating".into(),
+                expression: quote!({
+                    arroyo_types::Record {
+                        timestamp: record.timestamp,
+                        key: record.key.clone(),
+                        value: arroyo_types::UpdatingData::Append(record.value.clone()),
+                    }
+                })
+                .to_string(),
+                return_type: ExpressionReturnType::Record,
+            },
             PlanOperator::NonWindowAggregate {
                 input_is_update,
                 projection,
@@ -880,6 +959,12 @@ pub enum PlanType {
         left_value: StructDef,
         right_value: StructDef,
     },
+    /// Records paired with the row looked up for them, if there was one
+    KeyedLookupPair {
+        key: StructDef,
+        left_value: StructDef,
+        right_value: StructDef,
+    },
     KeyedLiteralTypeValue {
         key: Option<StructDef>,
         value: String,
@@ -921,6 +1006,15 @@ impl PlanType {
                 let right_type = right_value.get_type();
                 parse_quote!((Vec<#left_type>,Vec<#right_type>))
             }
+            PlanType::KeyedLookupPair {
+                key: _,
+                left_value,
+                right_value,
+            } => {
+                let left_type = left_value.get_type();
+                let right_type = right_value.get_type();
+                parse_quote!((#left_type,Option<#right_type>))
+            }
             PlanType::KeyedLiteralTypeValue { key: _, value } => parse_str(value).unwrap(),
             PlanType::UnkeyedList(value) => {
                 let value_type = value.get_type();
@@ -944,7 +1038,8 @@ impl PlanType {
             PlanType::Keyed { key, .. }
             | PlanType::KeyedPair { key, .. }
             | PlanType::KeyedLiteralTypeValue { key: Some(key), .. }
-            | PlanType::KeyedListPair { key, .. } => key.get_type(),
+            | PlanType::KeyedListPair { key, .. }
+            | PlanType::KeyedLookupPair { key, .. } => key.get_type(),
             PlanType::Updating(inner) => inner.key_type(),
         }
     }
@@ -966,7 +1061,8 @@ impl PlanType {
             PlanType::Keyed { key, .. }
             | PlanType::KeyedPair { key, .. }
             | PlanType::KeyedLiteralTypeValue { key: Some(key), .. }
-            | PlanType::KeyedListPair { key, .. } => key.all_names(),
+            | PlanType::KeyedListPair { key, .. }
+            | PlanType::KeyedLookupPair { key, .. } => key.all_names(),
             PlanType::Updating(inner) => inner.get_key_struct_names(),
         }
     }
@@ -991,6 +1087,11 @@ impl PlanType {
                 key,
                 left_value,
                 right_value,
+            }
+            | PlanType::KeyedLookupPair {
+                key,
+                left_value,
+                right_value,
             } => {
                 let mut result = key.all_structs();
                 result.extend(left_value.all_structs());
@@ -1044,6 +1145,15 @@ impl PlanType {
                 left_value: left_value.clone(),
                 right_value: right_value.clone(),
             },
+            PlanType::KeyedLookupPair {
+                key: _,
+                left_value,
+                right_value,
+            } => PlanType::KeyedLookupPair {
+                key,
+                left_value: left_value.clone(),
+                right_value: right_value.clone(),
+            },
             PlanType::KeyedLiteralTypeValue { key: _, value } => PlanType::KeyedLiteralTypeValue {
                 key: Some(key),
                 value: value.clone(),
@@ -1067,6 +1177,11 @@ impl PlanType {
                 key: _,
                 left_value: _,
                 right_value: _,
+            }
+            | PlanType::KeyedLookupPair {
+                key: _,
+                left_value: _,
+                right_value: _,
             } => unreachable!(),
             PlanType::KeyedLiteralTypeValue { key: _, value: _ } => unreachable!(),
             PlanType::Updating(inner) => PlanType::Updating(Box::new(inner.with_value(value))),
@@ -1109,6 +1224,7 @@ impl PlanGraph {
             SqlOperator::JoinOperator(left, right, join_operator) => {
                 self.add_join(left, right, join_operator)
             }
+            SqlOperator::LookupJoin(input, lookup) => self.add_lookup_join(input, lookup),
             SqlOperator::Window(input, window_operator) => self.add_window(input, window_operator),
             SqlOperator::RecordTransform(input, transform) => {
                 self.add_record_transform(input, transform)
@@ -1367,6 +1483,79 @@ impl PlanGraph {
         }
     }
 
+    fn add_lookup_join(
+        &mut self,
+        input: Box<SqlOperator>,
+        lookup: crate::pipeline::LookupJoinOperator,
+    ) -> NodeIndex {
+        let input_type = input.return_type();
+        let input_index = self.add_sql_operator(*input);
+
+        let key_struct = lookup.key.output_struct();
+        let key_index = self.insert_operator(
+            PlanOperator::RecordTransform(RecordTransform::KeyProjection(lookup.key)),
+            PlanType::Keyed {
+                key: key_struct.clone(),
+                value: input_type.clone(),
+            },
+        );
+        self.graph.add_edge(
+            input_index,
+            key_index,
+            PlanEdge {
+                edge_type: EdgeType::Forward,
+            },
+        );
+
+        // the lookup operator is generic over the type of the rows it reads, which isn't known
+        // to the connector
+        let mut operator = lookup.operator;
+        if let Operator::ConnectorLookup(connector_op) = &mut operator {
+            let lookup_type = lookup.lookup_struct.get_type();
+            connector_op.operator = connector_op
+                .operator
+                .replace("#lookup_t", &quote!(#lookup_type).to_string());
+        }
+
+        let lookup_index = self.insert_operator(
+            PlanOperator::Lookup(operator),
+            PlanType::KeyedLookupPair {
+                key: key_struct,
+                left_value: input_type.clone(),
+                right_value: lookup.lookup_struct.clone(),
+            },
+        );
+        self.graph.add_edge(
+            key_index,
+            lookup_index,
+            PlanEdge {
+                edge_type: EdgeType::Forward,
+            },
+        );
+
+        let merge_type = lookup
+            .join_type
+            .output_struct(&input_type, &lookup.right_struct);
+        let merge_index = self.insert_operator(
+            PlanOperator::LookupJoinMerge {
+                join_type: lookup.join_type,
+                left: input_type,
+                lookup: lookup.lookup_struct,
+                right: lookup.right_struct,
+            },
+            PlanType::Unkeyed(merge_type),
+        );
+        self.graph.add_edge(
+            lookup_index,
+            merge_index,
+            PlanEdge {
+                edge_type: EdgeType::Forward,
+            },
+        );
+
+        merge_index
+    }
+
     fn add_post_window_join(
         &mut self,
         left_index: NodeIndex,
@@ -1583,6 +1772,11 @@ impl PlanGraph {
         input: Box<SqlOperator>,
     ) -> NodeIndex {
         let input_index = self.add_sql_operator(*input);
+        if let SinkUpdateType::Upsert(primary_keys) = &sql_sink.updating_type {
+            let primary_keys = primary_keys.clone();
+            return self.add_upsert_sink(name, sql_sink, input_index, &primary_keys);
+        }
+
         let input_node = self.get_plan_node(input_index);
         if let PlanType::Updating(inner) = &input_node.output_type {
             let value_type = inner.as_syn_type();
@@ -1642,6 +1836,83 @@ impl PlanGraph {
         }
     }
 
+    /// Upsert sinks receive the updates themselves, keyed by the primary key, rather than
+    /// debezium records
+    fn add_upsert_sink(
+        &mut self,
+        name: String,
+        mut sql_sink: crate::external::SqlSink,
+        mut input_index: NodeIndex,
+        primary_keys: &[String],
+    ) -> NodeIndex {
+        let input_type = self.get_plan_node(input_index).output_type.clone();
+        let updating_type = if input_type.is_updating() {
+            input_type
+        } else {
+            // append-only queries are written as if every record were a new key
+            let updating_type = PlanType::Updating(Box::new(input_type));
+            let to_updating_index =
+                self.insert_operator(PlanOperator::ToUpdating, updating_type.clone());
+            let edge = PlanEdge {
+                edge_type: EdgeType::Forward,
+            };
+            self.graph.add_edge(input_index, to_updating_index, edge);
+            input_index = to_updating_index;
+            updating_type
+        };
+
+        let (field_names, field_computations) = primary_keys
+            .iter()
+            .map(|key| {
+                let field = sql_sink
+                    .struct_def
+                    .fields
+                    .iter()
+                    .find(|f| f.name() == *key)
+                    .expect("primary key columns should be in the sink")
+                    .clone();
+                (
+                    Column {
+                        relation: None,
+                        name: key.clone(),
+                    },
+                    Expression::Column(ColumnExpression::new(field)),
+                )
+            })
+            .unzip();
+        let key_projection = Projection::new(field_names, field_computations);
+        let keyed_type = updating_type.with_key(key_projection.output_struct());
+        let key_index = self.insert_operator(
+            PlanOperator::RecordTransform(RecordTransform::KeyProjection(key_projection)),
+            keyed_type.clone(),
+        );
+        let key_edge = PlanEdge {
+            edge_type: EdgeType::Forward,
+        };
+        self.graph.add_edge(input_index, key_index, key_edge);
+
+        // the sink operator is generic over the type of the rows, rather than the updates
+        let PlanType::Updating(inner) = &keyed_type else {
+            unreachable!("upsert sinks take updating input");
+        };
+        let value_type = inner.as_syn_type();
+        if let Operator::ConnectorSink(connector_op) = &mut sql_sink.operator {
+            connector_op.operator = connector_op
+                .operator
+                .replace("#in_t", &quote!(#value_type).to_string());
+        }
+
+        // the same key may be updated on several subtasks of the input, so changes are shuffled
+        // by key to have every change to a key written in order by a single subtask
+        let plan_node_index =
+            self.insert_operator(PlanOperator::Sink(name, sql_sink), keyed_type.clone());
+        let sink_edge = PlanEdge {
+            edge_type: EdgeType::Shuffle,
+        };
+        self.graph.add_edge(key_index, plan_node_index, sink_edge);
+        plan_node_index
+    }
+
     fn add_updating_aggregator(
         &mut self,
         input: Box<SqlOperator>,
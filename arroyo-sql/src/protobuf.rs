@@ -0,0 +1,149 @@
+use arrow_schema::{DataType, TimeUnit};
+use arroyo_rpc::protobuf::get_message;
+use prost::Message;
+use prost_reflect::{FieldDescriptor, Kind, MessageDescriptor};
+use protox::file::{ChainFileResolver, File, FileResolver, GoogleFileResolver};
+
+use crate::types::{StructDef, StructField, TypeDef};
+
+const SCHEMA_FILE_NAME: &str = "schema.proto";
+
+struct SourceFileResolver<'a> {
+    source: &'a str,
+}
+
+impl<'a> FileResolver for SourceFileResolver<'a> {
+    fn open_file(&self, name: &str) -> Result<File, protox::Error> {
+        if name == SCHEMA_FILE_NAME {
+            File::from_source(name, self.source)
+        } else {
+            Err(protox::Error::file_not_found(name))
+        }
+    }
+}
+
+/// Compiles protobuf source into a serialized `FileDescriptorSet`, which is what the runtime uses
+/// to decode and encode messages. Imports of the well-known google.protobuf types are supported.
+pub fn compile_proto_schema(source: &str) -> Result<Vec<u8>, String> {
+    let mut resolver = ChainFileResolver::new();
+    resolver.add(SourceFileResolver { source });
+    resolver.add(GoogleFileResolver::new());
+
+    let mut compiler = protox::Compiler::with_file_resolver(resolver);
+    compiler.include_imports(true);
+    compiler
+        .open_file(SCHEMA_FILE_NAME)
+        .map_err(|e| format!("Invalid protobuf schema: {}", e))?;
+
+    Ok(compiler.file_descriptor_set().encode_to_vec())
+}
+
+pub fn convert_protobuf_schema(
+    compiled_schema: &[u8],
+    message_name: Option<&str>,
+) -> Result<Vec<StructField>, String> {
+    let message = get_message(compiled_schema, message_name)?;
+    Ok(message_fields(&message))
+}
+
+fn message_fields(message: &MessageDescriptor) -> Vec<StructField> {
+    message
+        .fields()
+        .map(|f| {
+            StructField::with_rename(f.name().to_string(), None, to_schema_type(&f), None, None)
+        })
+        .collect()
+}
+
+fn to_schema_type(field: &FieldDescriptor) -> TypeDef {
+    use DataType::*;
+
+    // repeated and map fields are represented as JSON-encoded text
+    if field.is_list() || field.is_map() {
+        return TypeDef::DataType(Utf8, false);
+    }
+
+    let data_type = match field.kind() {
+        Kind::Double => Float64,
+        Kind::Float => Float32,
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Int32,
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Int64,
+        Kind::Uint32 | Kind::Fixed32 => UInt32,
+        Kind::Uint64 | Kind::Fixed64 => UInt64,
+        Kind::Bool => Boolean,
+        Kind::String | Kind::Enum(_) => Utf8,
+        Kind::Bytes => Binary,
+        Kind::Message(m) if m.full_name() == "google.protobuf.Timestamp" => {
+            return TypeDef::DataType(Timestamp(TimeUnit::Microsecond, None), true);
+        }
+        // message fields are always optional
+        Kind::Message(m) => {
+            return TypeDef::StructDef(StructDef::for_name(None, message_fields(&m)), true);
+        }
+    };
+
+    TypeDef::DataType(data_type, field.supports_presence())
+}
+
+#[cfg(test)]
+mod test {
+    use arrow_schema::DataType;
+
+    use crate::types::TypeDef;
+
+    use super::{compile_proto_schema, convert_protobuf_schema};
+
+    #[test]
+    fn test_convert() {
+        let schema = r#"
+        syntax = "proto3";
+        package orders;
+
+        import "google/protobuf/timestamp.proto";
+
+        enum Status {
+          PENDING = 0;
+          SHIPPED = 1;
+        }
+
+        message Address {
+          string city = 1;
+        }
+
+        message Order {
+          int64 id = 1;
+          optional string customer = 2;
+          Status status = 3;
+          Address address = 4;
+          repeated string tags = 5;
+          google.protobuf.Timestamp created_at = 6;
+        }
+        "#;
+
+        let compiled = compile_proto_schema(schema).unwrap();
+
+        assert!(convert_protobuf_schema(&compiled, None).is_err());
+
+        let fields = convert_protobuf_schema(&compiled, Some("orders.Order")).unwrap();
+        let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["id", "customer", "status", "address", "tags", "created_at"]
+        );
+
+        assert_eq!(
+            fields[0].data_type,
+            TypeDef::DataType(DataType::Int64, false)
+        );
+        assert_eq!(fields[1].data_type, TypeDef::DataType(DataType::Utf8, true));
+        assert_eq!(
+            fields[2].data_type,
+            TypeDef::DataType(DataType::Utf8, false)
+        );
+        assert!(matches!(fields[3].data_type, TypeDef::StructDef(_, true)));
+        assert_eq!(
+            fields[4].data_type,
+            TypeDef::DataType(DataType::Utf8, false)
+        );
+    }
+}
@@ -1,16 +1,26 @@
 use std::str::FromStr;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use anyhow::{anyhow, bail, Result};
 use arrow_schema::{DataType, Field};
-use arroyo_connectors::{connector_for_type, Connection};
+use arroyo_connectors::{connector_for_type, Connection, ErasedConnector};
 use arroyo_datastream::{ConnectorOp, Operator};
-use arroyo_rpc::types::{ConnectionSchema, ConnectionType, Format, SchemaDefinition, SourceField};
+use arroyo_rpc::types::{
+    AvroFormat, BadData, ConnectionSchema, ConnectionType, FieldType, Format, JsonFormat,
+    ProtobufFormat, SchemaDefinition, SourceField, SourceFieldType,
+};
+use arroyo_rpc::{primitive_to_sql, DeadLetterConfig, MetadataField, OperatorConfig};
 use datafusion::{
     optimizer::{analyzer::Analyzer, optimizer::Optimizer, OptimizerContext},
     sql::{
         planner::{PlannerContext, SqlToRel},
-        sqlparser::ast::{ColumnDef, ColumnOption, Statement, Value},
+        sqlparser::ast::{
+            ColumnDef, ColumnOption, Expr as SqlExpr, Function, FunctionArg, FunctionArgExpr,
+            Statement, TableConstraint, Value,
+        },
     },
 };
 use datafusion_common::{config::ConfigOptions, DFField, DFSchema};
@@ -23,11 +33,12 @@ use crate::expressions::CastExpression;
 use crate::external::SinkUpdateType;
 use crate::DEFAULT_IDLE_TIME;
 use crate::{
+    avro,
     expressions::{Column, ColumnExpression, Expression, ExpressionContext},
     external::{ProcessingMode, SqlSink, SqlSource},
     json_schema,
     operators::Projection,
-    pipeline::{SourceOperator, SqlOperator, SqlPipelineBuilder},
+    pipeline::{JoinType, LookupJoinOperator, SourceOperator, SqlOperator, SqlPipelineBuilder},
     types::{convert_data_type, StructDef, StructField, TypeDef},
     ArroyoSchemaProvider,
 };
@@ -43,9 +54,12 @@ pub struct ConnectorTable {
     pub config: String,
     pub description: String,
     pub format: Option<Format>,
+    pub bad_data: Option<BadData>,
     pub event_time_field: Option<String>,
     pub watermark_field: Option<String>,
     pub idle_time: Option<Duration>,
+    /// Sinks with a primary key write updating data as upserts, keyed by these columns
+    pub primary_keys: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -55,19 +69,28 @@ pub enum FieldSpec {
         field: StructField,
         expression: Expression,
     },
+    /// A field that is read from the metadata of each message (like the Kafka offset) by the
+    /// source, rather than from the payload
+    MetadataField {
+        field: StructField,
+        key: String,
+    },
 }
 
 impl FieldSpec {
     fn is_virtual(&self) -> bool {
         match self {
-            FieldSpec::StructField(_) => false,
+            FieldSpec::StructField(_) | FieldSpec::MetadataField { .. } => false,
             FieldSpec::VirtualField { .. } => true,
         }
     }
-    fn struct_field(&self) -> &StructField {
+    fn is_metadata(&self) -> bool {
+        matches!(self, FieldSpec::MetadataField { .. })
+    }
+    pub(crate) fn struct_field(&self) -> &StructField {
         match self {
             FieldSpec::StructField(f) => f,
-            FieldSpec::VirtualField { field, .. } => field,
+            FieldSpec::VirtualField { field, .. } | FieldSpec::MetadataField { field, .. } => field,
         }
     }
 }
@@ -85,8 +108,9 @@ fn schema_type(name: &str, schema: &ConnectionSchema) -> Option<String> {
             SchemaDefinition::JsonSchema(_) => {
                 Some(format!("{}::{}", name, json_schema::ROOT_NAME))
             }
-            SchemaDefinition::ProtobufSchema(_) => todo!(),
-            SchemaDefinition::AvroSchema(_) => todo!(),
+            // avro and protobuf tables are generated from their fields
+            SchemaDefinition::ProtobufSchema(_) => None,
+            SchemaDefinition::AvroSchema(_) => None,
             SchemaDefinition::RawSchema(_) => Some("arroyo_types::RawJson".to_string()),
         }
     })
@@ -97,8 +121,8 @@ pub fn schema_defs(name: &str, schema: &ConnectionSchema) -> Option<String> {
 
     match def {
         SchemaDefinition::JsonSchema(s) => Some(json_schema::get_defs(&name, &s).unwrap()),
-        SchemaDefinition::ProtobufSchema(_) => todo!(),
-        SchemaDefinition::AvroSchema(_) => todo!(),
+        SchemaDefinition::ProtobufSchema(_) => None,
+        SchemaDefinition::AvroSchema(_) => None,
         SchemaDefinition::RawSchema(_) => None,
     }
 }
@@ -139,9 +163,11 @@ impl From<Connection> for ConnectorTable {
             config: value.config,
             description: value.description,
             format: value.schema.format.clone(),
+            bad_data: value.schema.bad_data.clone(),
             event_time_field: None,
             watermark_field: None,
             idle_time: DEFAULT_IDLE_TIME,
+            primary_keys: vec![],
         }
     }
 }
@@ -151,15 +177,30 @@ impl ConnectorTable {
         name: &str,
         connector: &str,
         fields: Vec<FieldSpec>,
+        primary_keys: Vec<String>,
         options: &mut HashMap<String, String>,
     ) -> Result<Self> {
         let connector = connector_for_type(connector)
             .ok_or_else(|| anyhow!("Unknown connector '{}'", connector))?;
 
         let format = Format::from_opts(options).map_err(|e| anyhow!("invalid format: '{e}'"))?;
+        if let Some(format) = &format {
+            Self::validate_format(name, format)?;
+        }
+        let bad_data =
+            BadData::from_opts(options).map_err(|e| anyhow!("invalid bad_data: '{e}'"))?;
+        // dead letter tables are currently only supported for Kafka sources
+        if matches!(bad_data, Some(BadData::DeadLetter { .. })) && connector.name() != "kafka" {
+            bail!(
+                "bad_data = 'dead_letter' is not supported for {} tables",
+                connector.name()
+            );
+        }
 
+        // metadata fields aren't part of the payload, so they're left out of the connection schema
         let schema_fields: Result<Vec<SourceField>> = fields
             .iter()
+            .filter(|f| !f.is_metadata())
             .map(|f| {
                 let struct_field = f.struct_field();
                 struct_field.clone().try_into().map_err(|_| {
@@ -172,12 +213,60 @@ impl ConnectorTable {
             })
             .collect();
 
-        let schema = ConnectionSchema::try_new(format, None, schema_fields?, None)?;
+        let schema = ConnectionSchema::try_new(format, bad_data, None, schema_fields?, None)?;
 
-        let connection = connector.from_options(name, options, Some(&schema))?;
+        let mut connection = connector.from_options(name, options, Some(&schema))?;
+
+        let metadata_fields = Self::metadata_fields(&*connector, &fields)?;
+        if !metadata_fields.is_empty() {
+            if !matches!(connection.connection_type, ConnectionType::Source) {
+                bail!("metadata fields can only be used in sources");
+            }
+            if schema_type(name, &connection.schema).is_some() {
+                bail!("metadata fields can't be added to tables with a predefined type");
+            }
+            // metadata is merged into each record before it is deserialized, which relies on
+            // fields being matched by name
+            if let Some(Format::Csv(_)) = &connection.schema.format {
+                bail!("metadata fields are not supported for tables with the CSV format");
+            }
+
+            let mut config: OperatorConfig = serde_json::from_str(&connection.config)
+                .map_err(|e| anyhow!("invalid config for table {}: {:?}", name, e))?;
+            config.metadata_fields = metadata_fields;
+            connection.config = serde_json::to_string(&config).unwrap();
+        }
+
+        if !primary_keys.is_empty() {
+            if !matches!(connection.connection_type, ConnectionType::Sink) {
+                bail!("primary keys can only be set for sinks");
+            }
+            if connection
+                .schema
+                .format
+                .as_ref()
+                .map(|f| f.is_updating())
+                .unwrap_or(false)
+            {
+                bail!("primary keys can't be used with update formats like debezium_json");
+            }
+            for key in &primary_keys {
+                if !fields.iter().any(|f| f.struct_field().name == *key) {
+                    bail!("primary key column '{}' is not defined", key);
+                }
+            }
+            let Some(operator) = connector.upsert_operator() else {
+                bail!("{} sinks do not support primary keys", connector.name());
+            };
+            let config: OperatorConfig = serde_json::from_str(&connection.config)
+                .map_err(|e| anyhow!("invalid config for table {}: {:?}", name, e))?;
+            connector.validate_upsert(&config.table, &primary_keys)?;
+            connection.operator = operator.to_string();
+        }
 
         let mut table: ConnectorTable = connection.into();
         table.fields = fields;
+        table.primary_keys = primary_keys;
         table.event_time_field = options.remove("event_time_field");
         table.watermark_field = options.remove("watermark_field");
 
@@ -201,10 +290,99 @@ impl ConnectorTable {
         Ok(table)
     }
 
+    /// Checks the schemas that formats are configured with, so that invalid ones are rejected
+    /// when the table is created rather than when the pipeline starts
+    fn validate_format(name: &str, format: &Format) -> Result<()> {
+        match format {
+            Format::Avro(AvroFormat {
+                schema: Some(schema),
+                ..
+            }) => {
+                avro::convert_avro_schema(name, schema)
+                    .map_err(|e| anyhow!("invalid avro.schema: {}", e))?;
+            }
+            Format::Protobuf(ProtobufFormat {
+                message_name,
+                compiled_schema,
+            }) => {
+                let compiled_schema = compiled_schema.as_ref().ok_or_else(|| {
+                    anyhow!("protobuf.descriptor_set must be set for the protobuf format")
+                })?;
+                arroyo_rpc::protobuf::get_message(compiled_schema, message_name.as_deref())
+                    .map_err(|e| anyhow!("invalid protobuf.descriptor_set: {}", e))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the metadata fields are supported by the connector and have a valid type
+    fn metadata_fields(
+        connector: &dyn ErasedConnector,
+        fields: &[FieldSpec],
+    ) -> Result<Vec<MetadataField>> {
+        fields
+            .iter()
+            .filter_map(|f| match f {
+                FieldSpec::MetadataField { field, key } => Some((field, key)),
+                _ => None,
+            })
+            .map(|(field, key)| {
+                let def = connector
+                    .metadata_defs()
+                    .iter()
+                    .find(|def| def.name == key)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "'{}' is not a valid metadata key for {} tables",
+                            key,
+                            connector.name()
+                        )
+                    })?;
+
+                let data_type = match SourceField::try_from(field.clone()) {
+                    Ok(SourceField {
+                        field_type:
+                            SourceFieldType {
+                                r#type: FieldType::Primitive(p),
+                                ..
+                            },
+                        ..
+                    }) if def.data_types.contains(&p) => p,
+                    _ => {
+                        let types: Vec<_> = def
+                            .data_types
+                            .iter()
+                            .map(|t| primitive_to_sql(t.clone()))
+                            .collect();
+                        bail!(
+                            "metadata field '{}' must have type {}",
+                            field.name,
+                            types.join(" or ")
+                        );
+                    }
+                };
+
+                Ok(MetadataField {
+                    field_name: field.name.clone(),
+                    key: key.clone(),
+                    data_type,
+                })
+            })
+            .collect()
+    }
+
     fn has_virtual_fields(&self) -> bool {
         self.fields.iter().any(|f| f.is_virtual())
     }
 
+    /// Whether the table's connector has a schema registry, which confluent-framed records refer
+    /// to; currently only Kafka connections can configure one
+    fn has_schema_registry(&self) -> bool {
+        self.operator.starts_with("connectors::kafka::")
+    }
+
     fn is_update(&self) -> bool {
         self.format
             .as_ref()
@@ -219,7 +397,7 @@ impl ConnectorTable {
                 .iter()
                 .map(|field| {
                     match field {
-                        FieldSpec::StructField(struct_field) => Ok((Column{relation: None, name: struct_field.name.clone()}, Expression::Column(ColumnExpression::new(struct_field.clone())))),
+                        FieldSpec::StructField(struct_field) | FieldSpec::MetadataField { field: struct_field, .. } => Ok((Column{relation: None, name: struct_field.name.clone()}, Expression::Column(ColumnExpression::new(struct_field.clone())))),
                         FieldSpec::VirtualField { field, expression } => {
                             let expression_type_def = expression.expression_type(&ValuePointerContext);
                             let expression_return_type = expression_type_def.as_datatype().expect("virtual fields shouldn't return structs");
@@ -325,6 +503,46 @@ impl ConnectorTable {
         }
     }
 
+    /// For tables with the `dead_letter` bad data policy, embeds the operator and config of the
+    /// dead letter table into the source's config so that the source can write to it directly
+    fn source_op(&self, schema_provider: &ArroyoSchemaProvider) -> Result<ConnectorOp> {
+        let mut op = self.connector_op();
+
+        let Some(BadData::DeadLetter { table }) = &self.bad_data else {
+            return Ok(op);
+        };
+
+        let Some(Table::ConnectorTable(dead_letter)) = schema_provider.get_table(table) else {
+            bail!(
+                "dead letter table '{}' not found; it must be a connection table",
+                table
+            );
+        };
+
+        if !matches!(dead_letter.connection_type, ConnectionType::Sink) {
+            bail!("dead letter table '{}' must be a sink", table);
+        }
+
+        // dead letters are written as the raw message bytes, which is currently only supported by Kafka
+        if !dead_letter
+            .operator
+            .starts_with("connectors::kafka::sink::")
+        {
+            bail!("dead letter table '{}' must be a Kafka table", table);
+        }
+
+        let mut config: OperatorConfig = serde_json::from_str(&op.config)
+            .map_err(|e| anyhow!("invalid config for table {}: {:?}", self.name, e))?;
+        config.dead_letter = Some(DeadLetterConfig {
+            table: table.clone(),
+            operator: dead_letter.operator.clone(),
+            config: dead_letter.config.clone(),
+        });
+        op.config = serde_json::to_string(&config).unwrap();
+
+        Ok(op)
+    }
+
     fn processing_mode(&self) -> ProcessingMode {
         if self.is_update() {
             ProcessingMode::Update
@@ -333,18 +551,98 @@ impl ConnectorTable {
         }
     }
 
-    pub fn as_sql_source(&self) -> Result<SqlOperator> {
+    /// Whether the format matches fields by name, so that fields which aren't in the schema are
+    /// skipped when deserializing
+    fn supports_projection(&self) -> bool {
+        match &self.format {
+            Some(Format::Json(_)) | Some(Format::Avro(_)) | Some(Format::Protobuf(_)) => true,
+            Some(Format::Csv(csv)) => csv.header,
+            _ => false,
+        }
+    }
+
+    /// Removes the fields that aren't used by the query, so that the source doesn't need to
+    /// deserialize them
+    fn projected(&self, used: &HashSet<String>) -> ConnectorTable {
+        // named types are defined outside of the query, so can't be changed
+        if self.type_name.is_some() || !self.supports_projection() {
+            return self.clone();
+        }
+
+        let is_used = |name: &String| {
+            used.contains(name)
+                || self.event_time_field.as_ref() == Some(name)
+                || self.watermark_field.as_ref() == Some(name)
+        };
+
+        // we don't track which fields virtual field expressions read from, so if any are used
+        // all of the physical fields are kept
+        let uses_virtual_fields = self
+            .fields
+            .iter()
+            .any(|f| f.is_virtual() && is_used(&f.struct_field().name));
+
+        let fields: Vec<FieldSpec> = self
+            .fields
+            .iter()
+            .filter(|f| is_used(&f.struct_field().name) || (uses_virtual_fields && !f.is_virtual()))
+            .cloned()
+            .collect();
+
+        if fields.is_empty() {
+            return self.clone();
+        }
+
+        ConnectorTable {
+            fields,
+            ..self.clone()
+        }
+    }
+
+    pub fn as_sql_source(
+        &self,
+        schema_provider: &ArroyoSchemaProvider,
+        used_fields: Option<&HashSet<String>>,
+    ) -> Result<SqlOperator> {
         match self.connection_type {
             ConnectionType::Source => {}
             ConnectionType::Sink => {
                 bail!("cannot read from sink")
             }
+            ConnectionType::Lookup => {
+                bail!("lookup tables can only be used on the right side of a join")
+            }
         };
 
+        if let Some(used_fields) = used_fields {
+            let projected = self.projected(used_fields);
+            if projected.fields.len() < self.fields.len() {
+                return projected.as_sql_source(schema_provider, None);
+            }
+        }
+
+        // the writer schemas of avro records are looked up in the schema registry
+        if let Some(Format::Avro(AvroFormat {
+            confluent_schema_registry: true,
+            ..
+        })) = &self.format
+        {
+            if !self.has_schema_registry() {
+                bail!(
+                    "avro.confluent_schema_registry is only supported for Kafka sources, which have a schema registry; cannot read from source {}",
+                    self.name
+                );
+            }
+        }
+
         if self.is_update() && self.has_virtual_fields() {
             bail!("can't read from a source with virtual fields and update mode.")
         }
 
+        if self.is_update() && self.fields.iter().any(|f| f.is_metadata()) {
+            bail!("can't read from a source with metadata fields and update mode.")
+        }
+
         let virtual_field_projection = self.virtual_field_projection()?;
         let timestamp_override = self.timestamp_override()?;
         let watermark_column = self.watermark_column()?;
@@ -356,13 +654,17 @@ impl ConnectorTable {
                 self.fields
                     .iter()
                     .filter_map(|field| match field {
-                        FieldSpec::StructField(struct_field) => Some(struct_field.clone()),
+                        FieldSpec::StructField(struct_field)
+                        | FieldSpec::MetadataField {
+                            field: struct_field,
+                            ..
+                        } => Some(struct_field.clone()),
                         FieldSpec::VirtualField { .. } => None,
                     })
                     .collect(),
                 self.format.clone(),
             ),
-            operator: Operator::ConnectorSource(self.connector_op()),
+            operator: Operator::ConnectorSource(self.source_op(schema_provider)?),
             processing_mode: self.processing_mode(),
             idle_time: self.idle_time,
         };
@@ -382,6 +684,9 @@ impl ConnectorTable {
                 bail!("Inserting into a source is not allowed")
             }
             ConnectionType::Sink => {}
+            ConnectionType::Lookup => {
+                bail!("Inserting into a lookup table is not allowed")
+            }
         }
 
         if self.has_virtual_fields() {
@@ -389,7 +694,13 @@ impl ConnectorTable {
             bail!("Virtual fields are not currently supported in sinks");
         }
 
-        let updating_type = if self.is_update() {
+        if self.fields.iter().any(|f| f.is_metadata()) {
+            bail!("Metadata fields are not supported in sinks");
+        }
+
+        let updating_type = if !self.primary_keys.is_empty() {
+            SinkUpdateType::Upsert(self.primary_keys.clone())
+        } else if self.is_update() {
             SinkUpdateType::Force
         } else {
             SinkUpdateType::Disallow
@@ -400,7 +711,73 @@ impl ConnectorTable {
         }
 
         if let Some(format) = &self.format {
+            // sinks register their schema before writing, so they need a schema registry
+            let registry_format = match format {
+                Format::Json(JsonFormat {
+                    confluent_schema_registry: true,
+                    ..
+                }) => Some("json"),
+                Format::Avro(AvroFormat {
+                    confluent_schema_registry: true,
+                    ..
+                }) => Some("avro"),
+                _ => None,
+            };
+            if let Some(registry_format) = registry_format {
+                if !self.has_schema_registry() {
+                    bail!(
+                        "{}.confluent_schema_registry is only supported for Kafka sinks, which have a schema registry; cannot write to sink {}",
+                        registry_format,
+                        self.name
+                    );
+                }
+            }
+
             let output_struct: StructDef = input.return_type();
+            match format {
+                Format::Avro(AvroFormat { schema: None, .. }) => {
+                    avro::validate_generated_schema(&output_struct.fields)
+                        .map_err(|e| anyhow!("cannot write to avro sink {}: {}", self.name, e))?;
+                }
+                Format::RawString(_) | Format::RawBytes(_) => {
+                    let (format_name, data_type, sql_type) = match format {
+                        Format::RawString(_) => ("raw_string", DataType::Utf8, "TEXT"),
+                        _ => ("raw_bytes", DataType::Binary, "BYTEA"),
+                    };
+
+                    // raw formats write a single column as the whole message, so they can't
+                    // encode other records or updates
+                    if output_struct.fields.len() != 1
+                        || !matches!(&output_struct.fields[0].data_type,
+                            TypeDef::DataType(t, _) if *t == data_type)
+                    {
+                        bail!(
+                            "{} format requires the query written to sink {} to return a single field of type {}",
+                            format_name,
+                            self.name,
+                            sql_type
+                        );
+                    }
+
+                    if updating_type != SinkUpdateType::Disallow {
+                        bail!(
+                            "{} format cannot be used for updating sink {}",
+                            format_name,
+                            self.name
+                        );
+                    }
+                }
+                _ => {}
+            }
+
+            if self.operator.starts_with("connectors::filesystem::delta::") {
+                for field in &output_struct.fields {
+                    let field: Field = field.clone().into();
+                    check_delta_type(field.name(), field.data_type())
+                        .map_err(|e| anyhow!("cannot write to delta sink {}: {}", self.name, e))?;
+                }
+            }
+
             // we may need to copy the record into a new struct, that has the appropriate annotations
             // for serializing into our format
             let mut projection = Projection::new(
@@ -438,6 +815,60 @@ impl ConnectorTable {
             Box::new(input),
         ))
     }
+
+    /// Plans a join of `input` with this lookup table, which is queried with the `key` computed
+    /// from each input record. `schema` is the schema of the table as it's used in the query, which
+    /// may be aliased and contain only some of its fields.
+    pub fn as_sql_lookup(
+        &self,
+        schema_provider: &ArroyoSchemaProvider,
+        input: SqlOperator,
+        key: Projection,
+        schema: &DFSchema,
+        join_type: JoinType,
+    ) -> Result<SqlOperator> {
+        if self.has_virtual_fields() {
+            bail!("Virtual fields are not supported in lookup tables");
+        }
+
+        // rows are deserialized with the table's own field names, and are renamed to the names
+        // used in the query when they're merged with the input record
+        let fields = schema
+            .fields()
+            .iter()
+            .map(|f| {
+                self.fields
+                    .iter()
+                    .map(|field| field.struct_field())
+                    .find(|field| field.name == *f.name())
+                    .cloned()
+                    .ok_or_else(|| anyhow!("field {} not found in {}", f.name(), self.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let right_struct = StructDef::for_fields(
+            schema
+                .fields()
+                .iter()
+                .zip(fields.iter())
+                .map(|(f, field)| StructField {
+                    alias: f.qualifier().map(|q| q.to_string()),
+                    ..field.clone()
+                })
+                .collect(),
+        );
+
+        Ok(SqlOperator::LookupJoin(
+            Box::new(input),
+            LookupJoinOperator {
+                key,
+                lookup_struct: StructDef::for_format(fields, self.format.clone()),
+                right_struct,
+                operator: Operator::ConnectorLookup(self.source_op(schema_provider)?),
+                join_type,
+            },
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -453,6 +884,44 @@ pub enum Table {
     },
 }
 
+/// Checks that a field has a type that Delta Lake tables can hold
+fn check_delta_type(name: &str, data_type: &DataType) -> Result<(), String> {
+    use DataType::*;
+
+    match data_type {
+        Boolean
+        | Int8
+        | Int16
+        | Int32
+        | Int64
+        | UInt8
+        | UInt16
+        | UInt32
+        | UInt64
+        | Float16
+        | Float32
+        | Float64
+        | Utf8
+        | LargeUtf8
+        | Binary
+        | LargeBinary
+        | FixedSizeBinary(_)
+        | Date32
+        | Date64
+        | Timestamp(_, _)
+        | Decimal128(_, _)
+        | Decimal256(_, _) => Ok(()),
+        List(t) | LargeList(t) => check_delta_type(name, t.data_type()),
+        Struct(fields) => fields
+            .iter()
+            .try_for_each(|f| check_delta_type(f.name(), f.data_type())),
+        t => Err(format!(
+            "field '{}' has type {:?}, which delta tables don't support",
+            name, t
+        )),
+    }
+}
+
 fn value_to_inner_string(value: &Value) -> Result<String> {
     match value {
         Value::SingleQuotedString(inner_string)
@@ -462,6 +931,53 @@ fn value_to_inner_string(value: &Value) -> Result<String> {
     }
 }
 
+/// The columns of the primary key, which may be declared either on a column or as a table constraint
+fn primary_keys(columns: &[ColumnDef], constraints: &[TableConstraint]) -> Vec<String> {
+    let column_keys = columns
+        .iter()
+        .filter(|column| {
+            column
+                .options
+                .iter()
+                .any(|option| matches!(option.option, ColumnOption::Unique { is_primary: true }))
+        })
+        .map(|column| column.name.value.to_string());
+
+    let constraint_keys = constraints
+        .iter()
+        .filter_map(|constraint| match constraint {
+            TableConstraint::Unique {
+                columns,
+                is_primary: true,
+                ..
+            } => Some(columns),
+            _ => None,
+        })
+        .flatten()
+        .map(|column| column.value.to_string());
+
+    column_keys.chain(constraint_keys).collect()
+}
+
+/// Columns defined as `GENERATED ALWAYS AS (metadata('offset'))` are read from the metadata of each
+/// message by the source; returns the metadata key for those expressions
+fn metadata_key(expr: &SqlExpr) -> Result<Option<String>> {
+    let SqlExpr::Function(Function { name, args, .. }) = expr else {
+        return Ok(None);
+    };
+
+    if name.to_string().to_lowercase() != "metadata" {
+        return Ok(None);
+    }
+
+    match &args[..] {
+        [FunctionArg::Unnamed(FunctionArgExpr::Expr(SqlExpr::Value(value)))] => {
+            Ok(Some(value_to_inner_string(value)?))
+        }
+        _ => bail!("metadata() takes a single string literal, like metadata('offset')"),
+    }
+}
+
 impl Table {
     fn schema_from_columns(
         columns: &Vec<ColumnDef>,
@@ -490,7 +1006,17 @@ impl Table {
                         None
                     }
                 });
-                Ok((struct_field, generating_expression))
+
+                // metadata fields are filled in by the source, so they're physical fields
+                let metadata_key = generating_expression
+                    .as_ref()
+                    .map(metadata_key)
+                    .transpose()?
+                    .flatten();
+                let generating_expression =
+                    generating_expression.filter(|_| metadata_key.is_none());
+
+                Ok((struct_field, generating_expression, metadata_key))
             })
             .collect::<Result<Vec<_>>>()?;
 
@@ -498,7 +1024,7 @@ impl Table {
             struct_field_pairs
                 .iter()
                 .filter_map(
-                    |(field, generating_expression)| match generating_expression {
+                    |(field, generating_expression, _)| match generating_expression {
                         Some(_) => None,
                         None => Some(field.clone()),
                     },
@@ -528,8 +1054,13 @@ impl Table {
         let sql_to_rel = SqlToRel::new(schema_provider);
         struct_field_pairs
             .into_iter()
-            .map(|(struct_field, generating_expression)| {
-                if let Some(generating_expression) = generating_expression {
+            .map(|(struct_field, generating_expression, metadata_key)| {
+                if let Some(key) = metadata_key {
+                    Ok(FieldSpec::MetadataField {
+                        field: struct_field,
+                        key,
+                    })
+                } else if let Some(generating_expression) = generating_expression {
                     // TODO: Implement automatic type coercion here, as we have elsewhere.
                     // It is done by calling the Analyzer which inserts CAST operators where necessary.
 
@@ -557,6 +1088,7 @@ impl Table {
         if let Statement::CreateTable {
             name,
             columns,
+            constraints,
             with_options,
             query: None,
             ..
@@ -573,6 +1105,7 @@ impl Table {
 
             let connector = with_map.remove("connector");
             let fields = Self::schema_from_columns(columns, schema_provider)?;
+            let primary_keys = primary_keys(columns, constraints);
 
             match connector.as_ref().map(|c| c.as_str()) {
                 Some("memory") | None => {
@@ -580,6 +1113,10 @@ impl Table {
                         bail!("Virtual fields are not supported in memory tables; instead write a query");
                     }
 
+                    if fields.iter().any(|f| f.is_metadata()) {
+                        bail!("Metadata fields are only supported in connection tables");
+                    }
+
                     if !with_map.is_empty() {
                         if connector.is_some() {
                             bail!("Memory tables do not allow with options");
@@ -597,8 +1134,14 @@ impl Table {
                     }))
                 }
                 Some(connector) => Ok(Some(Table::ConnectorTable(
-                    ConnectorTable::from_options(&name, connector, fields, &mut with_map)
-                        .map_err(|e| anyhow!("Failed to construct table '{}': {:?}", name, e))?,
+                    ConnectorTable::from_options(
+                        &name,
+                        connector,
+                        fields,
+                        primary_keys,
+                        &mut with_map,
+                    )
+                    .map_err(|e| anyhow!("Failed to construct table '{}': {:?}", name, e))?,
                 ))),
             }
         } else {
@@ -658,7 +1201,10 @@ impl Table {
 
     pub fn as_sql_source(&self, builder: &mut SqlPipelineBuilder) -> Result<SqlOperator> {
         match self {
-            Table::ConnectorTable(cn) => cn.as_sql_source(),
+            Table::ConnectorTable(cn) => cn.as_sql_source(
+                builder.schema_provider,
+                builder.source_projections.get(&cn.name),
+            ),
             Table::MemoryTable { name, .. } => Ok(builder
                 .planned_tables
                 .get(name)
@@ -697,6 +1243,14 @@ pub enum Insert {
 }
 
 impl Insert {
+    pub fn logical_plan(&self) -> &LogicalPlan {
+        match self {
+            Insert::InsertQuery { logical_plan, .. } | Insert::Anonymous { logical_plan } => {
+                logical_plan
+            }
+        }
+    }
+
     pub fn try_from_statement(
         statement: &Statement,
         schema_provider: &ArroyoSchemaProvider,
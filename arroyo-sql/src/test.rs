@@ -1,9 +1,19 @@
+use std::collections::HashSet;
+
 use arrow_schema::DataType;
 use arroyo_connectors::{
     nexmark::{NexmarkConnector, NexmarkTable},
     Connector, EmptyConfig,
 };
+use arroyo_datastream::{EdgeType, Operator};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
 
+use crate::optimizations::source_projections;
+use crate::pipeline::SqlOperator;
+use crate::tables::{Insert, Table};
 use crate::{parse_and_get_program, types::TypeDef, ArroyoSchemaProvider, SqlConfig};
 
 #[tokio::test]
@@ -217,3 +227,1389 @@ async fn test_udf() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn test_timestamp_pattern_validation() {
+    let sql = "CREATE TABLE events (
+        id int,
+        created_at timestamp
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'events',
+        format = 'json',
+        json.timestamp_pattern = '%Y-%m-%d %H:%M:%S',
+        json.timestamp_timezone = '{}'
+      );
+      SELECT * FROM events";
+
+    parse_and_get_program(
+        &sql.replace("{}", "America/New_York"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = parse_and_get_program(
+        &sql.replace("{}", "Mars/Olympus_Mons"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("invalid timezone 'Mars/Olympus_Mons'"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_raw_sink_validation() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        customer text
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json'
+      );
+      CREATE TABLE raw_out (
+        {columns}
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'sink',
+        topic = 'raw_out',
+        format = '{format}'
+      );
+      INSERT INTO raw_out {query}";
+
+    for (format, columns, query, error) in [
+        ("raw_string", "value text", "SELECT customer FROM orders", None),
+        (
+            "raw_string",
+            "value text, id int",
+            "SELECT customer, id FROM orders",
+            Some("raw_string format requires the query written to sink raw_out to return a single field of type TEXT"),
+        ),
+        (
+            "raw_bytes",
+            "value text",
+            "SELECT customer FROM orders",
+            Some("raw_bytes format requires the query written to sink raw_out to return a single field of type BYTEA"),
+        ),
+    ] {
+        let result = parse_and_get_program(
+            &sql.replace("{columns}", columns)
+                .replace("{format}", format)
+                .replace("{query}", query),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await;
+
+        match error {
+            None => {
+                result.unwrap();
+            }
+            Some(error) => {
+                let err = result.unwrap_err();
+                assert!(err.to_string().contains(error), "{}", err);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_delta_rejects_s3() {
+    let sql = "CREATE TABLE lake (
+        id bigint
+      ) WITH (
+        connector = 'delta',
+        {target},
+        format = 'parquet'
+      );
+      SELECT * FROM nexmark";
+
+    for target in [
+        "path = 's3://my-bucket/tables/lake'",
+        "s3_bucket = 'my-bucket', s3_directory = 'tables/lake', aws_region = 'us-east-1'",
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{target}", target),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Delta Lake tables cannot be written to S3"),
+            "{}",
+            err
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_delta_rejects_unsupported_types() {
+    let sql = "CREATE TABLE lake WITH (
+        connector = 'delta',
+        path = 'file:///tmp/tables/lake',
+        format = 'parquet'
+      );
+      INSERT INTO lake SELECT {column} FROM nexmark";
+
+    parse_and_get_program(
+        &sql.replace("{column}", "bid.auction as auction"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = parse_and_get_program(
+        &sql.replace("{column}", "INTERVAL '1' SECOND as delay"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("cannot write to delta sink lake: field 'delay' has type"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_iceberg_validation() {
+    let sql = "CREATE TABLE lake (
+        id bigint,
+        price double
+      ) WITH (
+        connector = 'iceberg',
+        warehouse = '{warehouse}',
+        namespace = 'db',
+        table_name = 'lake',
+        partition_spec = '{spec}',
+        format = 'parquet'
+      );
+      INSERT INTO lake SELECT bid.auction, CAST(bid.price AS double) FROM nexmark";
+
+    for (warehouse, spec, error) in [
+        (
+            "s3://my-bucket/warehouse",
+            "",
+            "the hadoop catalog cannot be used with an S3 warehouse",
+        ),
+        (
+            "/tmp/warehouse",
+            "bucket(16, price)",
+            "partition column 'price' cannot be bucketed",
+        ),
+        (
+            "/tmp/warehouse",
+            "day(id)",
+            "partition column 'id' must be a timestamp to be partitioned by day",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{warehouse}", warehouse)
+                .replace("{spec}", spec),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_dead_letter() {
+    let sql = "CREATE TABLE dlq (
+        value text
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'sink',
+        topic = 'dlq',
+        format = 'raw_string'
+      );
+      CREATE TABLE orders (
+        id int
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json',
+        bad_data = 'dead_letter',
+        bad_data.dead_letter_table = '{}'
+      );
+      SELECT * FROM orders";
+
+    parse_and_get_program(
+        &sql.replace("{}", "dlq"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = parse_and_get_program(
+        &sql.replace("{}", "missing"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("dead letter table 'missing' not found"));
+
+    // dead letters can only be written to Kafka
+    let err = parse_and_get_program(
+        &sql.replace("{}", "mqtt_dlq").replace(
+            "SELECT * FROM orders",
+            "CREATE TABLE mqtt_dlq (
+                value text
+              ) WITH (
+                connector = 'mqtt',
+                url = 'mqtt://localhost:1883',
+                type = 'sink',
+                topic = 'dlq',
+                format = 'raw_string'
+              );
+              SELECT * FROM orders",
+        ),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("dead letter table 'mqtt_dlq' must be a Kafka table"),
+        "{}",
+        err
+    );
+
+    // and only Kafka sources support them
+    let err = parse_and_get_program(
+        &sql.replace("{}", "dlq").replace(
+            "connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',",
+            "connector = 'mqtt',
+        url = 'mqtt://localhost:1883',
+        type = 'source',
+        topic = 'orders',",
+        ),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("bad_data = 'dead_letter' is not supported for mqtt tables"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_source_projection() {
+    let sql = "CREATE TABLE wide (
+        a int,
+        b text,
+        c bigint,
+        d double
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'wide',
+        format = 'json'
+      );
+      SELECT a FROM wide WHERE c > 10";
+
+    let (program, _) = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let defs = program.other_defs.join("\n");
+    assert!(defs.contains("pub c :"));
+    assert!(!defs.contains("pub b :"));
+    assert!(!defs.contains("pub d :"));
+}
+
+/// Plans the source of `table` the way `parse_and_get_program` does, returning the fields that
+/// the queries in `sql` read from it and the fields of the struct that the source deserializes
+fn planned_source_fields(sql: &str, table: &str) -> (HashSet<String>, HashSet<String>) {
+    let mut schema_provider = get_test_schema_provider();
+    let mut inserts = vec![];
+    for statement in Parser::parse_sql(&PostgreSqlDialect {}, sql).unwrap() {
+        if let Some(table) = Table::try_from_statement(&statement, &schema_provider).unwrap() {
+            schema_provider.insert_table(table);
+        } else {
+            inserts.push(Insert::try_from_statement(&statement, &schema_provider).unwrap());
+        }
+    }
+
+    let projections =
+        source_projections(inserts.iter().map(Insert::logical_plan), &schema_provider);
+    let used = projections.get(table).cloned().unwrap_or_default();
+
+    let Some(Table::ConnectorTable(connector_table)) =
+        schema_provider.get_table(&table.to_string())
+    else {
+        panic!("table {} not found", table);
+    };
+    let SqlOperator::Source(source) = connector_table
+        .as_sql_source(&schema_provider, Some(&used))
+        .unwrap()
+    else {
+        panic!("expected a source");
+    };
+
+    let fields = source
+        .source
+        .struct_def
+        .fields
+        .iter()
+        .map(|f| f.name.clone())
+        .collect();
+    (used, fields)
+}
+
+#[test]
+fn test_source_projection_fields() {
+    let sql = "CREATE TABLE wide (
+        a int,
+        b text,
+        c bigint,
+        d double,
+        ts timestamp,
+        doubled double GENERATED ALWAYS AS (d * 2.0),
+        wm timestamp GENERATED ALWAYS AS (ts - INTERVAL '1 second')
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'wide',
+        format = 'json'
+        {options}
+      );
+      {query}";
+
+    let fields =
+        |names: &[&str]| -> HashSet<String> { names.iter().map(|name| name.to_string()).collect() };
+    let all = fields(&["a", "b", "c", "d", "ts"]);
+
+    for (options, query, used, expected) in [
+        // only the columns that the query reads are deserialized
+        (
+            "",
+            "SELECT a FROM wide WHERE c > 10",
+            Some(fields(&["a", "c"])),
+            fields(&["a", "c"]),
+        ),
+        // columns that are only read for the event time are kept
+        (
+            ", event_time_field = 'ts'",
+            "SELECT a FROM wide",
+            Some(fields(&["a"])),
+            fields(&["a", "ts"]),
+        ),
+        // virtual fields (like a watermark) may read any column, so all of them are kept
+        (
+            ", event_time_field = 'ts', watermark_field = 'wm'",
+            "SELECT a FROM wide",
+            None,
+            all.clone(),
+        ),
+        ("", "SELECT doubled FROM wide", None, all.clone()),
+        // SELECT * reads every column, so nothing is projected away
+        ("", "SELECT * FROM wide", None, all.clone()),
+    ] {
+        let (actual_used, actual) = planned_source_fields(
+            &sql.replace("{options}", options).replace("{query}", query),
+            "wide",
+        );
+        if let Some(used) = used {
+            assert_eq!(actual_used, used, "{}", query);
+        }
+        assert_eq!(actual, expected, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn test_metadata_fields() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        kafka_partition int GENERATED ALWAYS AS (metadata('partition')),
+        kafka_offset bigint GENERATED ALWAYS AS (metadata('offset')),
+        kafka_key {key_type} GENERATED ALWAYS AS (metadata('key')),
+        broker_time timestamp GENERATED ALWAYS AS (metadata('timestamp'))
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json',
+        event_time_field = 'broker_time'
+      );
+      SELECT id, kafka_offset, kafka_key FROM orders";
+
+    for key_type in ["text", "bytea"] {
+        parse_and_get_program(
+            &sql.replace("{key_type}", key_type),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let err = parse_and_get_program(
+        &sql.replace("{key_type}", "int"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("metadata field 'kafka_key' must have type TEXT or BINARY"));
+
+    let err = parse_and_get_program(
+        &sql.replace("{key_type}", "text")
+            .replace("metadata('key')", "metadata('leader')"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("'leader' is not a valid metadata key for kafka tables"));
+}
+
+#[tokio::test]
+async fn test_kafka_start_position() {
+    let sql = "CREATE TABLE orders (
+        id int
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json',
+        {start}
+      );
+      SELECT id FROM orders";
+
+    for start in [
+        "'source.offset' = 'timestamp', 'source.start_timestamp' = '2023-09-01T00:00:00Z'",
+        "'source.offset' = 'offsets', 'source.start_offsets' = '0:1500, orders:1:20'",
+        "'source.offset' = 'earliest', 'source.group_id' = 'arroyo-orders'",
+    ] {
+        parse_and_get_program(
+            &sql.replace("{start}", start),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    for (start, error) in [
+        (
+            "'source.offset' = 'timestamp'",
+            "a start timestamp must be set",
+        ),
+        (
+            "'source.offset' = 'latest', 'source.start_offsets' = '0:1500'",
+            "start offsets can only be set when the offset is 'offsets'",
+        ),
+        (
+            "'source.offset' = 'offsets', 'source.start_offsets' = '0:latest'",
+            "invalid start offset '0:latest'",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{start}", start),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_kafka_upsert_sink() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        amount bigint
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json'
+      );
+
+      CREATE TABLE order_counts (
+        id int,
+        count bigint,
+        {primary_key}
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = '{type}',
+        topic = 'order_counts',
+        format = 'json'{options}
+      );
+
+      INSERT INTO order_counts
+      SELECT id, count(*) FROM orders GROUP BY id";
+
+    parse_and_get_program(
+        &sql.replace("{primary_key}", "PRIMARY KEY (id)")
+            .replace("{type}", "sink")
+            .replace("{options}", ""),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    for (primary_key, typ, error) in [
+        (
+            "PRIMARY KEY (id)",
+            "source",
+            "primary keys can only be set for sinks",
+        ),
+        (
+            "PRIMARY KEY (order_id)",
+            "sink",
+            "primary key column 'order_id' is not defined",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{primary_key}", primary_key)
+                .replace("{type}", typ)
+                .replace("{options}", ""),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+
+    for options in [
+        ", 'sink.key_field' = 'id'",
+        ", 'sink.key_template' = 'order-{id}'",
+        ", 'sink.partitioner' = 'hash'",
+    ] {
+        parse_and_get_program(
+            &sql.replace("{primary_key}", "PRIMARY KEY (id)")
+                .replace("{type}", "sink")
+                .replace("{options}", options),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    for (options, error) in [
+        (
+            ", 'sink.partitioner' = 'round_robin'",
+            "sink.partitioner must be 'default' or 'hash'",
+        ),
+        (", 'sink.key_field' = 'count'", "sink.key_field uses count"),
+        (
+            ", 'sink.key_template' = '{id}-{count}'",
+            "sink.key_template uses count, id",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{primary_key}", "PRIMARY KEY (id)")
+                .replace("{type}", "sink")
+                .replace("{options}", options),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_upsert_sink_shuffles_by_key() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        amount bigint
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json'
+      );
+
+      CREATE TABLE latest_orders (
+        id int,
+        amount bigint,
+        PRIMARY KEY (id)
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'sink',
+        topic = 'latest_orders',
+        format = 'json'
+      );
+
+      INSERT INTO latest_orders
+      SELECT id, amount FROM orders";
+
+    let (program, _) = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    // append-only input may carry the same key on every subtask
+    let sink = program
+        .graph
+        .node_indices()
+        .find(|idx| matches!(program.graph[*idx].operator, Operator::ConnectorSink(_)))
+        .unwrap();
+    let edges: Vec<_> = program
+        .graph
+        .edges_directed(sink, Direction::Incoming)
+        .map(|e| e.weight().typ.clone())
+        .collect();
+    assert_eq!(edges, vec![EdgeType::Shuffle]);
+}
+
+#[tokio::test]
+async fn test_kafka_partition_field() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        region text,
+        part int
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'sink',
+        topic = 'orders',
+        format = 'json',
+        'sink.partitioner' = 'field',
+        'sink.partition_field' = '{field}'
+      );
+
+      INSERT INTO orders
+      SELECT CAST(bid.auction AS int), bid.channel, CAST(bid.auction % 8 AS int) FROM nexmark";
+
+    parse_and_get_program(
+        &sql.replace("{field}", "part"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = parse_and_get_program(
+        &sql.replace("{field}", "region"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("partition_field 'region' must be an integer column"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_postgres_sink() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        amount bigint
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json'
+      );
+
+      CREATE TABLE order_counts (
+        id int,
+        count bigint
+        {primary_key}
+      ) WITH (
+        connector = 'postgres',
+        host = 'localhost',
+        database = 'dashboards',
+        username = 'arroyo',
+        type = 'sink',
+        table_name = 'order_counts'
+        {options}
+      );
+
+      INSERT INTO order_counts
+      {query}";
+
+    let aggregate = "SELECT id, count(*) FROM orders GROUP BY id";
+    for (primary_key, options, query) in [
+        (", PRIMARY KEY (id)", "", aggregate),
+        (
+            ", PRIMARY KEY (id)",
+            ", 'sink.commit_mode' = 'exactly_once'",
+            aggregate,
+        ),
+        (
+            "",
+            ", 'sink.batch_size' = '500'",
+            "SELECT id, amount FROM orders",
+        ),
+    ] {
+        parse_and_get_program(
+            &sql.replace("{primary_key}", primary_key)
+                .replace("{options}", options)
+                .replace("{query}", query),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    for (options, error) in [
+        (
+            ", 'sink.commit_mode' = 'eventually'",
+            "invalid value for commit_mode 'eventually'",
+        ),
+        (
+            ", format = 'json'",
+            "'format' can't be set for Postgres tables",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{primary_key}", "")
+                .replace("{options}", options)
+                .replace("{query}", "SELECT id, amount FROM orders"),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+
+    // updating queries need a primary key to write upserts
+    parse_and_get_program(
+        &sql.replace("{primary_key}", "")
+            .replace("{options}", "")
+            .replace("{query}", aggregate),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+
+    // tables are sinks unless the type says otherwise
+    parse_and_get_program(
+        &sql.replace("type = 'sink',", "")
+            .replace("{primary_key}", "")
+            .replace("{options}", "")
+            .replace("{query}", "SELECT id, amount FROM orders"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_postgres_cdc_source() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        amount double,
+        created_at timestamp
+      ) WITH (
+        connector = 'postgres',
+        host = 'localhost',
+        database = 'shop',
+        username = 'arroyo',
+        type = 'source',
+        table_name = 'public.orders'
+        {options}
+      );
+
+      CREATE TABLE order_copies (
+        id int,
+        amount double,
+        PRIMARY KEY (id)
+      ) WITH (
+        connector = 'postgres',
+        host = 'localhost',
+        database = 'dashboards',
+        username = 'arroyo',
+        type = 'sink',
+        table_name = 'order_copies'
+      );
+
+      INSERT INTO order_copies
+      SELECT id, amount FROM orders";
+
+    for options in [
+        "",
+        ", 'source.snapshot' = 'true', 'source.slot_name' = 'orders_slot'",
+    ] {
+        parse_and_get_program(
+            &sql.replace("{options}", options),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let err = parse_and_get_program(
+        &sql.replace("{options}", ", 'source.snapshot' = 'yes'"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("invalid value for source.snapshot 'yes'"));
+}
+
+#[tokio::test]
+async fn test_redis_sink() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        user_id text,
+        amount bigint
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json'
+      );
+
+      CREATE TABLE user_orders (
+        user_id text,
+        amount bigint
+        {primary_key}
+      ) WITH (
+        connector = 'redis',
+        address = 'redis://localhost:6379',
+        type = 'sink',
+        {options}
+      );
+
+      INSERT INTO user_orders
+      {query}";
+
+    let aggregate = "SELECT user_id, sum(amount) FROM orders GROUP BY user_id";
+    let select = "SELECT user_id, amount FROM orders";
+    for (primary_key, options, query) in [
+        (
+            "",
+            "target = 'string', key_template = 'orders:{user_id}', ttl_seconds = '3600'",
+            select,
+        ),
+        (
+            "",
+            "target = 'list', key_template = 'orders:{user_id}', format = 'json'",
+            select,
+        ),
+        (
+            "",
+            "target = 'stream', key_template = 'orders', 'sink.batch_size' = '100'",
+            select,
+        ),
+        (
+            ", PRIMARY KEY (user_id)",
+            "target = 'hash', key_template = 'totals:{user_id}'",
+            aggregate,
+        ),
+    ] {
+        parse_and_get_program(
+            &sql.replace("{primary_key}", primary_key)
+                .replace("{options}", options)
+                .replace("{query}", query),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    for (options, error) in [
+        (
+            "target = 'set', key_template = 'orders'",
+            "invalid value for target 'set'",
+        ),
+        (
+            "target = 'string', key_template = 'orders:{id}'",
+            "key_template refers to column 'id', which is not defined",
+        ),
+        (
+            "target = 'hash', key_template = 'orders:{user_id}', format = 'json'",
+            "'format' can only be set for Redis tables with the string or list target",
+        ),
+        (
+            "target = 'string', key_template = 'orders:{user_id}', ttl_seconds = '0'",
+            "ttl_seconds must be at least 1",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{primary_key}", "")
+                .replace("{options}", options)
+                .replace("{query}", select),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_redis_lookup_join() {
+    let sql = "CREATE TABLE orders (
+        id int,
+        user_id text,
+        amount bigint
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'json'
+      );
+
+      CREATE TABLE features (
+        user_id text,
+        score double,
+        segment text
+      ) WITH (
+        connector = 'redis',
+        address = 'redis://localhost:6379',
+        type = 'lookup',
+        target = '{target}',
+        key_template = 'features:{user_id}'{options}
+      );
+
+      CREATE TABLE enriched_orders (
+        id int,
+        amount bigint,
+        score double,
+        segment text
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'sink',
+        topic = 'enriched_orders',
+        format = 'json'
+      );
+
+      {insert}";
+
+    for (target, insert) in [
+        (
+            "hash",
+            "INSERT INTO enriched_orders
+            SELECT o.id, o.amount, f.score, f.segment FROM orders o
+            LEFT JOIN features f ON o.user_id = f.user_id",
+        ),
+        (
+            "string",
+            "INSERT INTO enriched_orders
+            SELECT o.id, o.amount, f.score, f.segment FROM orders o
+            JOIN features f ON o.user_id = f.user_id",
+        ),
+    ] {
+        parse_and_get_program(
+            &sql.replace("{target}", target)
+                .replace("{options}", "")
+                .replace("{insert}", insert),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    for (target, insert, error) in [
+        (
+            "hash",
+            "INSERT INTO enriched_orders
+            SELECT o.id, o.amount, f.score, f.segment FROM features f
+            LEFT JOIN orders o ON o.user_id = f.user_id",
+            "lookup table features must be on the right side of the join",
+        ),
+        (
+            "hash",
+            "INSERT INTO enriched_orders SELECT 1, 2, score, segment FROM features",
+            "lookup tables can only be used on the right side of a join",
+        ),
+        (
+            "hash",
+            "INSERT INTO features SELECT user_id, 1.0, 'a' FROM orders",
+            "Inserting into a lookup table is not allowed",
+        ),
+        (
+            "list",
+            "INSERT INTO enriched_orders
+            SELECT o.id, o.amount, f.score, f.segment FROM orders o
+            LEFT JOIN features f ON o.user_id = f.user_id",
+            "lookup tables must use the string or hash target",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{target}", target)
+                .replace("{options}", "")
+                .replace("{insert}", insert),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+
+    let insert = "INSERT INTO enriched_orders
+        SELECT o.id, o.amount, f.score, f.segment FROM orders o
+        LEFT JOIN features f ON o.user_id = f.user_id";
+
+    parse_and_get_program(
+        &sql.replace("{target}", "hash")
+            .replace(
+                "{options}",
+                ",\n        'lookup.batch_size' = '50',\n        'lookup.cache_ttl_seconds' = '60'",
+            )
+            .replace("{insert}", insert),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    for (options, error) in [
+        (
+            ",\n        'lookup.batch_size' = '0'",
+            "batch_size must be at least 1",
+        ),
+        (
+            ",\n        'lookup.cache_ttl_seconds' = '0'",
+            "lookup.cache_ttl_seconds must be at least 1",
+        ),
+        (
+            ",\n        'lookup.cache_ttl_seconds' = 'forever'",
+            "invalid value for lookup.cache_ttl_seconds 'forever'",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{target}", "hash")
+                .replace("{options}", options)
+                .replace("{insert}", insert),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_mqtt() {
+    let sql = "CREATE TABLE telemetry (
+        device_id text,
+        temperature double,
+        mqtt_topic text GENERATED ALWAYS AS (metadata('topic'))
+      ) WITH (
+        connector = 'mqtt',
+        url = 'mqtt://localhost:1883',
+        type = 'source',
+        {source_options}
+      );
+
+      CREATE TABLE alerts (
+        device_id text,
+        temperature double
+      ) WITH (
+        connector = 'mqtt',
+        url = 'mqtt://localhost:1883',
+        type = 'sink',
+        {sink_options}
+      );
+
+      INSERT INTO alerts
+      SELECT device_id, temperature FROM telemetry WHERE temperature > 80";
+
+    for (source_options, sink_options) in [
+        (
+            "topic = 'devices/+/telemetry', format = 'json'",
+            "topic = 'alerts', format = 'json'",
+        ),
+        (
+            "topic = 'devices/#', qos = 'at_most_once', format = 'json'",
+            "topic = 'alerts', 'sink.retain' = 'true', protocol_version = 'v5', format = 'json'",
+        ),
+    ] {
+        parse_and_get_program(
+            &sql.replace("{source_options}", source_options)
+                .replace("{sink_options}", sink_options),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let source_options = "topic = 'devices/+/telemetry', format = 'json'";
+    let sink_options = "topic = 'alerts', format = 'json'";
+    for (source_options, sink_options, error) in [
+        (
+            source_options,
+            "topic = 'alerts/+', format = 'json'",
+            "sinks must publish to a single topic, without wildcards",
+        ),
+        (
+            "topic = 'devices/#/telemetry', format = 'json'",
+            sink_options,
+            "wildcards must be a whole level",
+        ),
+        (
+            "topic = 'devices/+/telemetry', format = 'json', 'tls.ca_certificate' = '/etc/ca.pem'",
+            sink_options,
+            "TLS certificates can only be used with mqtts:// URLs",
+        ),
+        (
+            source_options,
+            "topic = 'alerts', format = 'json', qos = 'exactly_once'",
+            "invalid value for qos 'exactly_once'",
+        ),
+        (
+            "topic = 'devices/+/telemetry'",
+            sink_options,
+            "'format' must be set for MQTT connection",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{source_options}", source_options)
+                .replace("{sink_options}", sink_options),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_avro_schema_validation() {
+    let sql = "CREATE TABLE events (
+        id bigint,
+        tags text
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'events',
+        format = 'avro',
+        'avro.schema' = '{\"type\": \"record\", \"name\": \"Event\", \"fields\": [
+            {\"name\": \"id\", \"type\": \"long\"},
+            {\"name\": \"tags\", \"type\": {tags_type}}
+        ]}'
+      );
+      SELECT id, tags FROM events";
+
+    parse_and_get_program(
+        &sql.replace(
+            "{tags_type}",
+            "{\"type\": \"array\", \"items\": \"string\"}",
+        ),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = parse_and_get_program(
+        &sql.replace("{tags_type}", "[\"null\", \"string\", \"long\"]"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("avro field 'tags' is a union of several types"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_schema_registry_validation() {
+    let sql = "CREATE TABLE events (
+        id bigint
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'sink',
+        topic = 'events',
+        format = 'avro',
+        'avro.confluent_schema_registry' = 'true',
+        'schema_registry.endpoint' = '{endpoint}'
+      );
+      INSERT INTO events SELECT bid.auction FROM nexmark";
+
+    parse_and_get_program(
+        &sql.replace("{endpoint}", "http://localhost:8081"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = parse_and_get_program(
+        &sql.replace("{endpoint}", "localhost 8081"),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("invalid schema registry"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_avro_schema_registry_requires_kafka() {
+    let sql = "CREATE TABLE events (
+        id bigint
+      ) WITH (
+        connector = 'mqtt',
+        url = 'mqtt://localhost:1883',
+        type = '{type}',
+        topic = 'events',
+        format = 'avro',
+        'avro.confluent_schema_registry' = 'true'
+      );
+      {query}";
+
+    for (typ, query, error) in [
+        (
+            "sink",
+            "INSERT INTO events SELECT bid.auction FROM nexmark",
+            "avro.confluent_schema_registry is only supported for Kafka sinks",
+        ),
+        (
+            "source",
+            "SELECT id FROM events",
+            "avro.confluent_schema_registry is only supported for Kafka sources",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{type}", typ).replace("{query}", query),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_json_schema_registry_requires_kafka() {
+    let sql = "CREATE TABLE events (
+        id bigint
+      ) WITH (
+        connector = 'mqtt',
+        url = 'mqtt://localhost:1883',
+        type = 'sink',
+        topic = 'events',
+        format = 'json',
+        'json.confluent_schema_registry' = 'true'
+      );
+      INSERT INTO events SELECT bid.auction FROM nexmark";
+
+    let err = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("json.confluent_schema_registry is only supported for Kafka sinks"),
+        "{}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_protobuf_schema_validation() {
+    let sql = "CREATE TABLE events (
+        id bigint
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'events',
+        format = 'protobuf'
+        {options}
+      );
+      SELECT id FROM events";
+
+    for (options, error) in [
+        (
+            "",
+            "protobuf.descriptor_set must be set for the protobuf format",
+        ),
+        (
+            ", 'protobuf.descriptor_set' = ''",
+            "Protobuf schema contains no messages",
+        ),
+    ] {
+        let err = parse_and_get_program(
+            &sql.replace("{options}", options),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
@@ -97,7 +97,14 @@ impl StructDef {
     }
 
     pub fn def(&self, is_key: bool) -> String {
-        let fields = self.fields.iter().map(|field| field.def(&self.format));
+        let timestamp_formatter = self.timestamp_formatter();
+        let fields = self.fields.iter().map(|field| {
+            field.def(
+                &self.format,
+                timestamp_formatter.as_ref().map(|(name, _)| name),
+            )
+        });
+        let timestamp_formatter = timestamp_formatter.map(|(_, def)| def);
         let schema_name: Type = parse_str(&self.struct_name()).unwrap();
         let extra_derives = if is_key {
             quote!(#[derive(Eq,  Hash,  Ord)])
@@ -111,10 +118,62 @@ impl StructDef {
                 #(#fields)
                 ,*
             }
+
+            #timestamp_formatter
         )
         .to_string()
     }
 
+    /// Timestamp formats that aren't handled by the fixed serde modules in arroyo_worker::formats
+    /// are configured via a generated type implementing `TimestampFormatter`. Returns the name of
+    /// that type and its definition, if this struct needs one.
+    fn timestamp_formatter(&self) -> Option<(String, TokenStream)> {
+        let Some(Format::Json(JsonFormat {
+            timestamp_format, ..
+        })) = &self.format
+        else {
+            return None;
+        };
+
+        let encoding = match timestamp_format {
+            TimestampFormat::RFC3339 | TimestampFormat::UnixMillis => return None,
+            TimestampFormat::UnixSeconds => quote!(UnixSeconds),
+            TimestampFormat::UnixMicros => quote!(UnixMicros),
+            TimestampFormat::UnixNanos => quote!(UnixNanos),
+            TimestampFormat::ISO8601 => quote!(Iso8601),
+            TimestampFormat::Pattern { pattern, timezone } => {
+                let timezone = match timezone {
+                    Some(tz) => quote!(Some(#tz)),
+                    None => quote!(None),
+                };
+                quote!(Pattern { pattern: #pattern, timezone: #timezone })
+            }
+        };
+
+        let has_timestamps = self
+            .fields
+            .iter()
+            .any(|f| matches!(f.data_type, TypeDef::DataType(DataType::Timestamp(_, _), _)));
+        if !has_timestamps {
+            return None;
+        }
+
+        let name = format!("{}_timestamp_format", self.struct_name_ident());
+        let ident = format_ident!("{}", name);
+        Some((
+            name,
+            quote! {
+                #[allow(non_camel_case_types)]
+                pub struct #ident;
+
+                impl arroyo_worker::formats::timestamps::TimestampFormatter for #ident {
+                    const ENCODING: arroyo_worker::formats::timestamps::TimestampEncoding =
+                        arroyo_worker::formats::timestamps::TimestampEncoding::#encoding;
+                }
+            },
+        ))
+    }
+
     pub fn all_structs(&self) -> Vec<StructDef> {
         if self.name.is_some() {
             return vec![];
@@ -261,7 +320,28 @@ impl StructDef {
                     }
                 }
             } else {
-                quote! { unimplemented!("to_raw_string is not implemented for this type") }
+                // sinks that can't be written with this format are rejected when they're planned
+                quote! { unreachable!("raw_string is only planned for sinks with a single TEXT field") }
+            };
+
+            let to_raw_bytes = if self.fields.len() == 1
+                && matches!(
+                    self.fields[0].data_type,
+                    TypeDef::DataType(DataType::Binary, _)
+                ) {
+                let field = &self.fields[0].field_ident();
+                if self.fields[0].nullable() {
+                    quote! {
+                        self.#field.clone()
+                    }
+                } else {
+                    quote! {
+                        Some(self.#field.clone())
+                    }
+                }
+            } else {
+                // sinks that can't be written with this format are rejected when they're planned
+                quote! { unreachable!("raw_bytes is only planned for sinks with a single BYTEA field") }
             };
 
             Some(quote! {
@@ -278,6 +358,10 @@ impl StructDef {
                     fn to_raw_string(&self) -> Option<Vec<u8>> {
                         #to_raw_string
                     }
+
+                    fn to_raw_bytes(&self) -> Option<Vec<u8>> {
+                        #to_raw_bytes
+                    }
                 }
             })
         } else {
@@ -581,7 +665,7 @@ impl TypeDef {
             ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => {
                 parse_quote!(#value.to_string())
             }
-            ScalarValue::Binary(Some(bin)) => parse_str(&format!("{:?}", bin)).unwrap(),
+            ScalarValue::Binary(Some(bin)) => parse_str(&format!("vec!{:?}", bin)).unwrap(),
             ScalarValue::LargeBinary(_) => todo!(),
             ScalarValue::List(_, _) => todo!(),
             ScalarValue::Date32(Some(val)) => parse_str(&format!(
@@ -670,16 +754,37 @@ impl StructField {
         }
     }
 
-    fn def(&self, format: &Option<Format>) -> TokenStream {
+    fn def(&self, format: &Option<Format>, timestamp_formatter: Option<&String>) -> TokenStream {
         let name: Ident = self.field_ident();
         let type_string = self.get_type();
         // special case time fields
         if let TypeDef::DataType(DataType::Timestamp(_, _), nullable) = self.data_type {
+            if let Some(formatter) = timestamp_formatter {
+                let with = if nullable {
+                    format!(
+                        "arroyo_worker::formats::timestamps::OptTimestampAs::<{}>",
+                        formatter
+                    )
+                } else {
+                    format!(
+                        "arroyo_worker::formats::timestamps::TimestampAs::<{}>",
+                        formatter
+                    )
+                };
+                let default = nullable.then(|| quote!(#[serde(default)]));
+                return quote! {
+                    #default
+                    #[serde(with = #with)]
+                    pub #name: #type_string
+                };
+            }
+
             match format.as_ref().map(|t| &*t) {
                 Some(Format::Json(JsonFormat {
                     timestamp_format: TimestampFormat::UnixMillis,
                     ..
-                })) => {
+                }))
+                | Some(Format::Avro(_)) => {
                     if nullable {
                         return quote! {
                             #[serde(default)]
@@ -776,7 +881,7 @@ impl StructField {
             DataType::Time64(_) => todo!(),
             DataType::Duration(_) => todo!(),
             DataType::Interval(_) => todo!(),
-            DataType::Binary => todo!(),
+            DataType::Binary => quote!(arrow::datatypes::DataType::Binary),
             DataType::FixedSizeBinary(_) => todo!(),
             DataType::LargeBinary => todo!(),
             DataType::Utf8 => quote!(arrow::datatypes::DataType::Utf8),
@@ -883,7 +988,7 @@ impl StructField {
             DataType::Time32(_) => todo!(),
             DataType::Time64(_) => todo!(),
             DataType::Duration(_) | DataType::Interval(_) => "std::time::Duration".to_string(),
-            DataType::Binary => todo!(),
+            DataType::Binary => "Vec<u8>".to_string(),
             DataType::FixedSizeBinary(_) => todo!(),
             DataType::LargeBinary => todo!(),
             DataType::Utf8 => "String".to_string(),
@@ -945,7 +1050,9 @@ impl StructField {
                 DataType::Time64(_) => todo!(),
                 DataType::Duration(_) => todo!(),
                 DataType::Interval(_) => todo!(),
-                DataType::Binary => todo!(),
+                DataType::Binary => quote!(arrow_array::builder::GenericByteBuilder::<
+                    arrow_array::types::GenericBinaryType<i32>,
+                >::new()),
                 DataType::FixedSizeBinary(_) => todo!(),
                 DataType::LargeBinary => todo!(),
                 DataType::Utf8 => quote!(arrow_array::builder::GenericByteBuilder::<
@@ -1035,7 +1142,13 @@ impl StructField {
                 DataType::Time64(_) => todo!(),
                 DataType::Duration(_) => todo!(),
                 DataType::Interval(_) => todo!(),
-                DataType::Binary => todo!(),
+                DataType::Binary => {
+                    quote!(
+                        arrow_array::builder::GenericByteBuilder<
+                            arrow_array::types::GenericBinaryType<i32>,
+                        >
+                    )
+                }
                 DataType::FixedSizeBinary(_) => todo!(),
                 DataType::LargeBinary => todo!(),
                 DataType::Utf8 => {
@@ -1122,7 +1235,7 @@ pub(crate) fn data_type_as_syn_type(data_type: &DataType) -> syn::Type {
         DataType::Duration(_) | DataType::Interval(_) => {
             parse_quote!(std::time::Duration)
         }
-        DataType::Binary => todo!(),
+        DataType::Binary => parse_quote!(Vec<u8>),
         DataType::FixedSizeBinary(_) => todo!(),
         DataType::LargeBinary => todo!(),
         DataType::Utf8 => parse_quote!(String),
@@ -36,6 +36,11 @@ impl<'a, K: Key, V: Data, S: BackingStore> GlobalKeyedState<'a, K, V, S> {
             .set(self.cache.values.len() as f64);
     }
 
+    pub async fn remove(&mut self, key: &mut K) {
+        self.cache.values.remove(key);
+        self.parquet.delete_key_value::<K>(self.table, key).await;
+    }
+
     pub fn get_all(&mut self) -> Vec<&V> {
         self.cache.values.values().collect()
     }
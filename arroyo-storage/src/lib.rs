@@ -1,3 +1,4 @@
+use std::ops::Range;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::{
@@ -8,6 +9,8 @@ use std::{
 use arroyo_types::{S3_ENDPOINT_ENV, S3_REGION_ENV};
 use aws::ArroyoCredentialProvider;
 use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
 use object_store::gcp::GoogleCloudStorageBuilder;
 use object_store::path::Path;
 use object_store::{aws::AmazonS3Builder, local::LocalFileSystem, ObjectStore};
@@ -39,6 +42,9 @@ pub enum StorageError {
 
     #[error("failed to load credentials: {0}")]
     CredentialsError(String),
+
+    #[error("invalid glob: {0}")]
+    InvalidGlob(String),
 }
 
 // https://s3.us-west-2.amazonaws.com/DOC-EXAMPLE-BUCKET1/puppy.jpg
@@ -364,6 +370,36 @@ impl StorageProvider {
         Ok(bytes)
     }
 
+    /// Streams the contents of an object, for reading files that may not fit in memory
+    pub async fn get_stream<P: Into<String>>(
+        &self,
+        path: P,
+    ) -> Result<BoxStream<'static, Result<Bytes, StorageError>>, StorageError> {
+        let path: String = path.into();
+        Ok(self
+            .object_store
+            .get(&path.into())
+            .await?
+            .into_stream()
+            .map_err(|e| e.into())
+            .boxed())
+    }
+
+    pub async fn get_range<P: Into<String>>(
+        &self,
+        path: P,
+        range: Range<usize>,
+    ) -> Result<Bytes, StorageError> {
+        let path: String = path.into();
+        Ok(self.object_store.get_range(&path.into(), range).await?)
+    }
+
+    /// The size of an object in bytes
+    pub async fn size<P: Into<String>>(&self, path: P) -> Result<usize, StorageError> {
+        let path: String = path.into();
+        Ok(self.object_store.head(&path.into()).await?.size)
+    }
+
     pub async fn put<P: Into<String>>(
         &self,
         path: P,
@@ -384,6 +420,33 @@ impl StorageProvider {
         };
     }
 
+    /// Lists the paths of all objects under the key of the URL this provider was created from (or
+    /// under its root, if the URL has no key). The returned paths can be passed to `get`.
+    pub async fn list(&self) -> Result<Vec<String>, StorageError> {
+        let prefix: Option<Path> = self.key().map(|k| k.into());
+
+        let objects: Vec<_> = self
+            .object_store
+            .list(prefix.as_ref())
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(objects
+            .into_iter()
+            .map(|o| o.location.to_string())
+            .collect())
+    }
+
+    /// The key within the bucket that was included in the URL, if any
+    pub fn key(&self) -> Option<&str> {
+        match &self.config {
+            BackendConfig::S3(s3) => s3.key.as_deref(),
+            BackendConfig::GCS(gcs) => gcs.key.as_deref(),
+            BackendConfig::Local(local) => local.key.as_deref(),
+        }
+    }
+
     /// Produces a URL representation of this path that can be read by other systems,
     /// in particular Nomad's artifact fetcher and Arroyo's artifact fetcher.
     pub fn canonical_url(&self) -> &str {
@@ -395,13 +458,115 @@ impl StorageProvider {
     }
 }
 
+/// Splits a path into the longest prefix without glob characters and a regex matching the rest.
+/// Globs support `*` (within a path segment), `**` (across segments), `?` and character classes
+/// like `[a-z]` or `[!0-9]`.
+pub fn split_glob(path: &str) -> Result<(String, Option<Regex>), StorageError> {
+    let segments: Vec<&str> = path.split('/').collect();
+    let Some(first_glob) = segments.iter().position(|s| s.contains(['*', '?', '['])) else {
+        return Ok((path.trim_end_matches('/').to_string(), None));
+    };
+
+    let mut pattern = String::from("^");
+    let glob = segments[first_glob..].join("/");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                pattern.push_str(".*");
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => {
+                pattern.push('[');
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    pattern.push('^');
+                }
+
+                let mut closed = false;
+                let mut empty = true;
+                for c in chars.by_ref() {
+                    match c {
+                        // a `]` at the start of a class is a literal
+                        ']' if !empty => {
+                            closed = true;
+                            break;
+                        }
+                        '/' => {
+                            return Err(StorageError::InvalidGlob(format!(
+                                "character classes can't match '/' in '{}'",
+                                path
+                            )))
+                        }
+                        '-' => pattern.push('-'),
+                        c => pattern.push_str(&regex::escape(&c.to_string())),
+                    }
+                    empty = false;
+                }
+
+                if !closed {
+                    return Err(StorageError::InvalidGlob(format!(
+                        "unclosed '[' in '{}'",
+                        path
+                    )));
+                }
+                pattern.push(']');
+            }
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+
+    let regex = Regex::new(&pattern)
+        .map_err(|e| StorageError::InvalidGlob(format!("'{}': {}", path, e)))?;
+
+    Ok((segments[..first_glob].join("/"), Some(regex)))
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::SystemTime;
 
     use arroyo_types::to_nanos;
 
-    use crate::{matchers, BackendConfig, StorageProvider};
+    use crate::{matchers, split_glob, BackendConfig, StorageProvider};
+
+    #[test]
+    fn test_split_glob() {
+        let (base, pattern) = split_glob("s3://bucket/events/").unwrap();
+        assert_eq!(base, "s3://bucket/events");
+        assert!(pattern.is_none());
+
+        let (base, pattern) = split_glob("s3://bucket/events/*/part-?.json").unwrap();
+        assert_eq!(base, "s3://bucket/events");
+        let pattern = pattern.unwrap();
+        assert!(pattern.is_match("2023-10-01/part-1.json"));
+        assert!(!pattern.is_match("2023-10-01/nested/part-1.json"));
+        assert!(!pattern.is_match("2023-10-01/part-1.json.gz"));
+
+        let (base, pattern) = split_glob("/data/lake/**.parquet").unwrap();
+        assert_eq!(base, "/data/lake");
+        assert!(pattern.unwrap().is_match("year=2023/month=10/a.parquet"));
+
+        let (_, pattern) = split_glob("/data/part-[0-4][!a].json").unwrap();
+        let pattern = pattern.unwrap();
+        assert!(pattern.is_match("part-3b.json"));
+        assert!(!pattern.is_match("part-5b.json"));
+        assert!(!pattern.is_match("part-3a.json"));
+
+        // regex syntax in classes is escaped
+        let (_, pattern) = split_glob("/data/[]&.]").unwrap();
+        let pattern = pattern.unwrap();
+        assert!(pattern.is_match("]"));
+        assert!(pattern.is_match("."));
+        assert!(!pattern.is_match("x"));
+
+        assert!(split_glob("/data/part-[0-4.json").is_err());
+        assert!(split_glob("/data/[a/b]").is_err());
+        assert!(split_glob("/data/[z-a]").is_err());
+    }
 
     #[test]
     fn test_regex_compilation() {
@@ -529,6 +694,8 @@ mod tests {
             data.clone()
         );
 
+        assert!(storage.list().await.unwrap().contains(&key));
+
         storage.delete_if_present(&key).await.unwrap();
 
         assert!(
@@ -0,0 +1,453 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use arroyo_rpc::types::BadData;
+use arroyo_rpc::{DeadLetterConfig, OperatorConfig};
+use arroyo_types::{Data, Key, UserError, DESERIALIZATION_ERRORS, DROPPED_MESSAGES};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
+use rdkafka::util::Timeout;
+use rdkafka::ClientConfig;
+use tracing::warn;
+
+use crate::engine::Context;
+
+use super::kafka::{client_configs, KafkaConfig, KafkaTable};
+
+/// Applies a table's `bad_data` policy to messages that could not be deserialized, so that all
+/// sources handle them the same way
+#[derive(Clone, Default)]
+pub enum BadDataHandler {
+    /// Report the error and skip the message; used when no policy is set
+    #[default]
+    Report,
+    Fail,
+    Drop(DropLog),
+    /// Writes to the dead letter table, or fails the job with the error from creating the writer
+    DeadLetter(Result<DeadLetterWriter, String>),
+}
+
+impl BadDataHandler {
+    pub fn from_config(config: &OperatorConfig) -> Self {
+        match config.bad_data.clone() {
+            None => BadDataHandler::Report,
+            Some(BadData::Fail {}) => BadDataHandler::Fail,
+            Some(BadData::Drop {}) => BadDataHandler::Drop(DropLog::default()),
+            Some(BadData::DeadLetter { table }) => {
+                BadDataHandler::DeadLetter(match &config.dead_letter {
+                    Some(dead_letter) => DeadLetterWriter::from_config(dead_letter),
+                    None => Err(format!(
+                        "No sink was configured for dead letter table '{}'",
+                        table
+                    )),
+                })
+            }
+        }
+    }
+
+    /// Takes the result of deserializing `msg`, returning the value if it succeeded, or None if
+    /// the message was reported, dropped or written to the dead letter table. If the policy is to
+    /// fail, the error is reported and the job fails.
+    pub async fn handle<K: Key, T: Data, V>(
+        &self,
+        ctx: &mut Context<K, T>,
+        msg: &[u8],
+        result: Result<V, UserError>,
+    ) -> Option<V> {
+        let error = match result {
+            Ok(value) => return Some(value),
+            Err(error) => error,
+        };
+
+        if let Some(c) = ctx.counters.get(DESERIALIZATION_ERRORS) {
+            c.inc();
+        }
+
+        let error = match self {
+            BadDataHandler::Report => {
+                ctx.report_user_error(error).await;
+                return None;
+            }
+            BadDataHandler::Fail => error,
+            BadDataHandler::Drop(log) => {
+                if let Some(c) = ctx.counters.get(DROPPED_MESSAGES) {
+                    c.inc();
+                }
+                log.dropped(&error);
+                return None;
+            }
+            BadDataHandler::DeadLetter(Ok(writer)) => match writer.write(msg, &error).await {
+                Ok(()) => return None,
+                Err(e) => e,
+            },
+            BadDataHandler::DeadLetter(Err(e)) => {
+                UserError::new("Failed to create dead letter writer", e.clone())
+            }
+        };
+
+        let message = format!("{}: {}", error.name, error.details);
+        ctx.report_user_error(error).await;
+        panic!("{}", message);
+    }
+
+    /// Waits for the messages written to the dead letter table to be delivered, failing the job
+    /// if any weren't. Sources call this before checkpointing, so that a checkpoint never covers
+    /// bad messages that were lost.
+    pub async fn flush<K: Key, T: Data>(&self, ctx: &mut Context<K, T>) {
+        let BadDataHandler::DeadLetter(Ok(writer)) = self else {
+            return;
+        };
+
+        if let Err(error) = writer.flush().await {
+            let message = format!("{}: {}", error.name, error.details);
+            ctx.report_user_error(error).await;
+            panic!("{}", message);
+        }
+    }
+}
+
+/// Logs dropped messages at most once per [`DROP_LOG_INTERVAL`], with the number dropped since the
+/// last warning; every dropped message is counted in the dropped messages metric
+#[derive(Clone, Default)]
+pub struct DropLog {
+    /// The time of the last warning and the number of messages dropped since then
+    state: Arc<Mutex<(Option<Instant>, u64)>>,
+}
+
+const DROP_LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+impl DropLog {
+    fn dropped(&self, error: &UserError) {
+        let mut state = self.state.lock().unwrap();
+        state.1 += 1;
+
+        if state
+            .0
+            .map(|last| last.elapsed() >= DROP_LOG_INTERVAL)
+            .unwrap_or(true)
+        {
+            warn!(
+                "Dropped {} bad messages since the last warning; most recent: {}: {}",
+                state.1, error.name, error.details
+            );
+            *state = (Some(Instant::now()), 0);
+        }
+    }
+}
+
+/// Writes bad messages to a Kafka dead letter table; the planner rejects dead letter tables
+/// that aren't Kafka sinks. The message value is the raw bytes that
+/// failed to deserialize, and the error is written to the `arroyo_error` header.
+///
+/// Messages are only enqueued as they're written, so that a burst of bad messages or a slow
+/// broker doesn't hold up reading; their deliveries are awaited by `flush` at checkpoints.
+#[derive(Clone)]
+pub struct DeadLetterWriter {
+    table: String,
+    topic: String,
+    producer: FutureProducer,
+    write_futures: Arc<Mutex<Vec<DeliveryFuture>>>,
+}
+
+impl DeadLetterWriter {
+    fn from_config(dead_letter: &DeadLetterConfig) -> Result<Self, String> {
+        let config: OperatorConfig = serde_json::from_str(&dead_letter.config).map_err(|e| {
+            format!(
+                "Invalid config for dead letter table '{}': {:?}",
+                dead_letter.table, e
+            )
+        })?;
+        let connection: KafkaConfig = serde_json::from_value(config.connection).map_err(|e| {
+            format!(
+                "Invalid connection config for dead letter table '{}': {:?}",
+                dead_letter.table, e
+            )
+        })?;
+        let table: KafkaTable = serde_json::from_value(config.table).map_err(|e| {
+            format!(
+                "Invalid table config for dead letter table '{}': {:?}",
+                dead_letter.table, e
+            )
+        })?;
+
+        Self::for_topic(&dead_letter.table, &connection, table.topic)
+    }
+
+    fn for_topic(table: &str, connection: &KafkaConfig, topic: String) -> Result<Self, String> {
+        let mut client_config = ClientConfig::new();
+        client_config.set(
+            "bootstrap.servers",
+            connection.bootstrap_servers.to_string(),
+        );
+        for (key, value) in client_configs(connection) {
+            client_config.set(key, value);
+        }
+
+        let producer = client_config.create().map_err(|e| {
+            format!(
+                "Failed to create producer for dead letter table '{}': {:?}",
+                table, e
+            )
+        })?;
+
+        Ok(Self {
+            table: table.to_string(),
+            topic,
+            producer,
+            write_futures: Arc::new(Mutex::new(vec![])),
+        })
+    }
+
+    fn write_error(&self, e: KafkaError) -> UserError {
+        UserError::new(
+            "Failed to write to dead letter table",
+            format!(
+                "Could not write bad message to dead letter table '{}': {:?}",
+                self.table, e
+            ),
+        )
+    }
+
+    async fn write(&self, msg: &[u8], error: &UserError) -> Result<(), UserError> {
+        let headers = OwnedHeaders::new().insert(Header {
+            key: "arroyo_error",
+            value: Some(&error.details),
+        });
+
+        let mut record: FutureRecord<(), [u8]> =
+            FutureRecord::to(&self.topic).payload(msg).headers(headers);
+
+        loop {
+            match self.producer.send_result(record) {
+                Ok(future) => {
+                    self.write_futures.lock().unwrap().push(future);
+                    return Ok(());
+                }
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), r)) => {
+                    record = r;
+                }
+                Err((e, _)) => return Err(self.write_error(e)),
+            }
+
+            // back off and retry
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Waits for the messages written since the last flush to be delivered
+    async fn flush(&self) -> Result<(), UserError> {
+        // FutureProducer has a thread polling every 100ms, but polling now sends the messages
+        // immediately
+        self.producer.poll(Timeout::After(Duration::ZERO));
+
+        let write_futures: Vec<_> = self.write_futures.lock().unwrap().drain(..).collect();
+        for future in write_futures {
+            match future.await {
+                Ok(Ok(_)) => {}
+                Ok(Err((e, _))) => return Err(self.write_error(e)),
+                Err(_) => return Err(self.write_error(KafkaError::Canceled)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::AssertUnwindSafe;
+    use std::time::Duration;
+
+    use arroyo_rpc::types::BadData;
+    use arroyo_rpc::{ControlResp, DeadLetterConfig, OperatorConfig};
+    use arroyo_types::UserError;
+    use futures::FutureExt;
+    use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+    use rdkafka::consumer::{Consumer, StreamConsumer};
+    use rdkafka::message::Headers;
+    use rdkafka::{ClientConfig, Message};
+    use tokio::sync::mpsc::{channel, Receiver};
+
+    use crate::connectors::kafka::{KafkaConfig, KafkaConfigAuthentication};
+    use crate::engine::{Context, OutQueue};
+
+    use super::{BadDataHandler, DeadLetterWriter, DropLog};
+
+    fn error<V>() -> Result<V, UserError> {
+        Err(UserError::new("Deserialization failed", "bad message"))
+    }
+
+    #[tokio::test]
+    async fn test_policies() {
+        let (mut ctx, _) = Context::<(), String>::new_for_test();
+
+        let drop = BadDataHandler::Drop(DropLog::default());
+        for _ in 0..3 {
+            let result: Option<String> = drop.handle(&mut ctx, b"x", error()).await;
+            assert_eq!(result, None);
+        }
+
+        let result = BadDataHandler::Fail
+            .handle(&mut ctx, b"x", Ok("good".to_string()))
+            .await;
+        assert_eq!(result, Some("good".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_fail() {
+        let (mut ctx, mut command_rx) = test_context().await;
+
+        let result =
+            AssertUnwindSafe(BadDataHandler::Fail.handle::<_, _, String>(&mut ctx, b"x", error()))
+                .catch_unwind()
+                .await;
+        assert!(result.is_err(), "the fail policy should fail the job");
+
+        let Ok(ControlResp::Error {
+            message, details, ..
+        }) = command_rx.try_recv()
+        else {
+            panic!("the error should be reported to the controller");
+        };
+        assert_eq!(message, "Deserialization failed");
+        assert_eq!(details, "bad message");
+    }
+
+    async fn test_context() -> (Context<(), String>, Receiver<ControlResp>) {
+        let (_, control_rx) = channel(128);
+        let (command_tx, command_rx) = channel(128);
+        let (data_tx, _data_rx) = channel(128);
+
+        let ctx = Context::new(
+            arroyo_types::get_test_task_info(),
+            None,
+            control_rx,
+            command_tx,
+            1,
+            vec![vec![OutQueue::new(data_tx, false)]],
+            vec![],
+        )
+        .await;
+
+        (ctx, command_rx)
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let (mut ctx, mut command_rx) = test_context().await;
+
+        let config = OperatorConfig {
+            connection: serde_json::json!({}),
+            table: serde_json::json!({}),
+            format: None,
+            bad_data: None,
+            dead_letter: None,
+            rate_limit: None,
+            metadata_fields: vec![],
+        };
+
+        // without a policy, errors are reported and the message is skipped
+        let handler = BadDataHandler::from_config(&config);
+        let result: Option<String> = handler.handle(&mut ctx, b"x", error()).await;
+        assert_eq!(result, None);
+
+        let Ok(ControlResp::Error { message, .. }) = command_rx.try_recv() else {
+            panic!("the error should be reported to the controller");
+        };
+        assert_eq!(message, "Deserialization failed");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_dead_letter() {
+        let (mut ctx, mut command_rx) = test_context().await;
+
+        let config = OperatorConfig {
+            connection: serde_json::json!({}),
+            table: serde_json::json!({}),
+            format: None,
+            bad_data: Some(BadData::DeadLetter {
+                table: "dlq".to_string(),
+            }),
+            dead_letter: Some(DeadLetterConfig {
+                table: "dlq".to_string(),
+                operator: "connectors::kafka::sink::KafkaSinkFunc::<#in_k, #in_t>".to_string(),
+                config: "{}".to_string(),
+            }),
+            rate_limit: None,
+            metadata_fields: vec![],
+        };
+
+        let handler = BadDataHandler::from_config(&config);
+        let result = AssertUnwindSafe(handler.handle::<_, _, String>(&mut ctx, b"x", error()))
+            .catch_unwind()
+            .await;
+        assert!(
+            result.is_err(),
+            "an invalid dead letter table should fail the job"
+        );
+
+        let Ok(ControlResp::Error { message, .. }) = command_rx.try_recv() else {
+            panic!("the error should be reported to the controller");
+        };
+        assert_eq!(message, "Failed to create dead letter writer");
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter() {
+        let server = "localhost:9092";
+        let topic = "__arroyo-dead-letter-test";
+
+        let admin_client: AdminClient<_> = ClientConfig::new()
+            .set("bootstrap.servers", server)
+            .create()
+            .unwrap();
+        admin_client
+            .delete_topics(&[topic], &AdminOptions::new())
+            .await
+            .expect("deletion should have worked");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        admin_client
+            .create_topics(
+                [&NewTopic::new(topic, 1, TopicReplication::Fixed(1))],
+                &AdminOptions::new(),
+            )
+            .await
+            .expect("new topic should be present");
+
+        let connection = KafkaConfig {
+            authentication: KafkaConfigAuthentication::None {},
+            bootstrap_servers: server.to_string().try_into().unwrap(),
+            schema_registry: None,
+        };
+        let handler = BadDataHandler::DeadLetter(DeadLetterWriter::for_topic(
+            "dlq",
+            &connection,
+            topic.to_string(),
+        ));
+
+        let (mut ctx, _) = Context::<(), String>::new_for_test();
+        let result: Option<String> = handler.handle(&mut ctx, b"not json", error()).await;
+        assert_eq!(result, None);
+        handler.flush(&mut ctx).await;
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", server)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .set("group.id", "dead-letter-test-consumer")
+            .create()
+            .expect("Consumer creation failed");
+        consumer.subscribe(&[topic]).expect("success");
+
+        let message = tokio::time::timeout(Duration::from_secs(30), consumer.recv())
+            .await
+            .expect("timed out waiting for the dead letter")
+            .expect("shouldn't have errored");
+        assert_eq!(message.payload(), Some(&b"not json"[..]));
+
+        let header = message.headers().unwrap().get(0);
+        assert_eq!(header.key, "arroyo_error");
+        assert_eq!(header.value, Some(&b"bad message"[..]));
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    any::TypeId, collections::HashMap, fs::File, io::Write, marker::PhantomData, sync::Mutex,
+};
+
+use anyhow::{anyhow, Result};
+use apache_avro::{Codec, Schema, Writer};
+use lazy_static::lazy_static;
+
+use crate::formats::avro::{arrow_to_avro_schema, to_avro_value};
+use crate::SchemaData;
+
+use super::{
+    local::{CurrentFileRecovery, LocalWriter},
+    AvroCompression, BatchBufferingWriter, FileSettings, FileSystemTable, FormatSettings,
+};
+
+fn avro_codec(config: &FileSystemTable) -> Codec {
+    match config.format_settings {
+        Some(FormatSettings::Avro {
+            compression: Some(AvroCompression::Deflate),
+        }) => Codec::Deflate,
+        Some(FormatSettings::Avro {
+            compression: Some(AvroCompression::Zstd),
+        }) => Codec::Zstandard,
+        _ => Codec::Null,
+    }
+}
+
+/// Returns the avro schema of the record type. Avro writers borrow their schema, so the schema of
+/// each type is built once and kept for the life of the worker.
+fn avro_schema<D: SchemaData>() -> &'static Schema {
+    lazy_static! {
+        static ref SCHEMAS: Mutex<HashMap<TypeId, &'static Schema>> = Mutex::new(HashMap::new());
+    }
+
+    SCHEMAS
+        .lock()
+        .unwrap()
+        .entry(TypeId::of::<D>())
+        .or_insert_with(|| {
+            // unsupported types are rejected when the sink is planned
+            let schema = arrow_to_avro_schema(D::name(), D::schema().fields())
+                .unwrap_or_else(|e| panic!("{}", e));
+            Box::leak(Box::new(schema))
+        })
+}
+
+fn append<D: SchemaData, W: Write>(
+    writer: &mut Writer<'static, W>,
+    schema: &Schema,
+    record: &D,
+) -> Result<()> {
+    let value = to_avro_value(schema, record).map_err(|e| anyhow!(e))?;
+    writer
+        .append(value)
+        .map_err(|e| anyhow!("failed to write record to avro: {}", e))?;
+    Ok(())
+}
+
+pub struct AvroWriter<D: SchemaData> {
+    schema: &'static Schema,
+    // parts are taken from the writer's buffer once its blocks reach the target part size
+    writer: Writer<'static, Vec<u8>>,
+    target_part_size: usize,
+    phantom: PhantomData<D>,
+}
+
+impl<D: SchemaData> AvroWriter<D> {
+    /// Writes the records that haven't filled a block yet as a block of their own
+    fn flush(&mut self) {
+        self.writer
+            .flush()
+            .expect("avro blocks are written to memory");
+    }
+}
+
+impl<D: SchemaData> BatchBufferingWriter for AvroWriter<D> {
+    type BatchData = D;
+
+    fn new(config: &FileSystemTable) -> Self {
+        let target_part_size = if let Some(FileSettings {
+            target_part_size: Some(target_part_size),
+            ..
+        }) = config.file_settings
+        {
+            target_part_size as usize
+        } else {
+            5 * 1024 * 1024
+        };
+        let schema = avro_schema::<D>();
+        Self {
+            schema,
+            writer: Writer::with_codec(schema, Vec::new(), avro_codec(config)),
+            target_part_size,
+            phantom: PhantomData,
+        }
+    }
+
+    fn suffix(_config: &FileSystemTable) -> String {
+        "avro".to_string()
+    }
+
+    fn add_batch_data(&mut self, data: Self::BatchData) -> Result<Option<Vec<u8>>> {
+        append(&mut self.writer, self.schema, &data)?;
+        if self.buffer_length() > self.target_part_size {
+            Ok(Some(std::mem::take(self.writer.get_mut())))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // records in the block that's being built aren't counted until the block is full
+    fn buffer_length(&self) -> usize {
+        self.writer.get_ref().len()
+    }
+
+    fn evict_current_buffer(&mut self) -> Vec<u8> {
+        self.flush();
+        std::mem::take(self.writer.get_mut())
+    }
+
+    fn get_trailing_bytes_for_checkpoint(&mut self) -> Option<Vec<u8>> {
+        // the pending records are flushed as a block, so that the buffer ends at a block boundary
+        self.flush();
+        let buffer = self.writer.get_ref();
+        (!buffer.is_empty()).then(|| buffer.clone())
+    }
+
+    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Result<Option<Vec<u8>>> {
+        if let Some(final_batch) = final_batch {
+            if let Some(final_batch) = self.add_batch_data(final_batch)? {
+                return Ok(Some(final_batch));
+            }
+        }
+        let bytes = self.evict_current_buffer();
+        if bytes.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(bytes))
+        }
+    }
+}
+
+pub struct AvroLocalWriter {
+    tmp_path: String,
+    final_path: String,
+    schema: &'static Schema,
+    writer: Writer<'static, File>,
+}
+
+impl<D: SchemaData> LocalWriter<D> for AvroLocalWriter {
+    fn new(tmp_path: String, final_path: String, table_properties: &FileSystemTable) -> Self {
+        let file = File::create(&tmp_path).unwrap();
+        let schema = avro_schema::<D>();
+        AvroLocalWriter {
+            tmp_path,
+            final_path,
+            schema,
+            writer: Writer::with_codec(schema, file, avro_codec(table_properties)),
+        }
+    }
+
+    fn file_suffix(_table_properties: &FileSystemTable) -> String {
+        "avro".to_string()
+    }
+
+    fn write(&mut self, value: D) -> Result<()> {
+        append(&mut self.writer, self.schema, &value)
+    }
+
+    fn sync(&mut self) -> Result<usize> {
+        // only whole blocks are written, so the file is valid up to the returned size
+        self.writer.flush()?;
+        let file = self.writer.get_mut();
+        file.flush()?;
+        let size = file.metadata()?.len() as usize;
+        Ok(size)
+    }
+
+    fn close(&mut self) -> Result<super::local::FilePreCommit> {
+        LocalWriter::<D>::sync(self)?;
+        Ok(super::local::FilePreCommit {
+            tmp_file: self.tmp_path.clone(),
+            destination: self.final_path.clone(),
+        })
+    }
+
+    fn checkpoint(&mut self) -> Result<Option<CurrentFileRecovery>> {
+        let bytes_written = LocalWriter::<D>::sync(self)?;
+        Ok(Some(CurrentFileRecovery {
+            tmp_file: self.tmp_path.clone(),
+            bytes_written,
+            suffix: None,
+            destination: self.final_path.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apache_avro::{types::Value, Reader};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use crate::connectors::filesystem::{
+        AvroCompression, BatchBufferingWriter, FileSettings, FileSystemTable, FormatSettings,
+    };
+    use crate::SchemaData;
+
+    use super::AvroWriter;
+
+    #[derive(
+        Clone,
+        Debug,
+        bincode::Encode,
+        bincode::Decode,
+        PartialEq,
+        PartialOrd,
+        serde::Serialize,
+        serde::Deserialize,
+    )]
+    struct TestRecord {
+        id: i64,
+        name: String,
+    }
+
+    impl SchemaData for TestRecord {
+        fn name() -> &'static str {
+            "test_record"
+        }
+
+        fn schema() -> Schema {
+            Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("name", DataType::Utf8, false),
+            ])
+        }
+
+        fn to_raw_string(&self) -> Option<Vec<u8>> {
+            None
+        }
+
+        fn to_raw_bytes(&self) -> Option<Vec<u8>> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_avro_parts_form_a_container_file() {
+        let table = FileSystemTable {
+            source: None,
+            write_target: None,
+            file_settings: Some(FileSettings {
+                inactivity_rollover_seconds: None,
+                max_parts: None,
+                rollover_seconds: None,
+                target_file_size: None,
+                target_part_size: Some(1024),
+            }),
+            format_settings: Some(FormatSettings::Avro {
+                compression: Some(AvroCompression::Deflate),
+            }),
+            partitioning: None,
+            iceberg: None,
+        };
+
+        let mut writer = AvroWriter::<TestRecord>::new(&table);
+        let mut file = vec![];
+        for id in 0..10_000 {
+            if let Some(part) = writer
+                .add_batch_data(TestRecord {
+                    id,
+                    name: format!("record {}", id),
+                })
+                .unwrap()
+            {
+                file.extend(part);
+            }
+        }
+        assert!(!file.is_empty());
+        file.extend(writer.close(None).unwrap().unwrap());
+
+        let ids: Vec<_> = Reader::new(&file[..])
+            .unwrap()
+            .map(|record| match record.unwrap() {
+                Value::Record(fields) => fields[0].1.clone(),
+                other => panic!("unexpected value {:?}", other),
+            })
+            .collect();
+        assert_eq!(ids.len(), 10_000);
+        assert_eq!(ids[9_999], Value::Long(9_999));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{fs::File, io::Write, marker::PhantomData};
+
+use arroyo_rpc::types::CsvFormat;
+use arroyo_types::Data;
+use serde::Serialize;
+
+use crate::formats::csv::CsvCodec;
+
+use super::{
+    local::{CurrentFileRecovery, LocalWriter},
+    BatchBufferingWriter, FileSettings, FileSystemTable, FormatSettings,
+};
+
+fn csv_codec(config: &FileSystemTable) -> CsvCodec {
+    let Some(FormatSettings::Csv {
+        delimiter,
+        quote,
+        escape,
+        include_header,
+        null_value,
+    }) = &config.format_settings
+    else {
+        panic!("CSV writer requires CSV format settings");
+    };
+
+    let format = CsvFormat::new(
+        Some(delimiter.as_str()),
+        quote.as_deref(),
+        escape.as_deref(),
+        include_header.unwrap_or_default(),
+        null_value.clone(),
+    )
+    .expect("CSV format settings are validated when the table is created");
+    CsvCodec::new(&format)
+}
+
+pub struct CsvWriter<D: Data + Serialize> {
+    codec: CsvCodec,
+    current_buffer: Vec<u8>,
+    header_written: bool,
+    target_part_size: usize,
+    phantom: PhantomData<D>,
+}
+
+impl<D: Data + Serialize> BatchBufferingWriter for CsvWriter<D> {
+    type BatchData = D;
+
+    fn new(config: &FileSystemTable) -> Self {
+        let target_part_size = if let Some(FileSettings {
+            target_part_size: Some(target_part_size),
+            ..
+        }) = config.file_settings
+        {
+            target_part_size as usize
+        } else {
+            5 * 1024 * 1024
+        };
+        Self {
+            codec: csv_codec(config),
+            current_buffer: Vec::new(),
+            header_written: false,
+            target_part_size,
+            phantom: PhantomData,
+        }
+    }
+
+    fn suffix(_config: &FileSystemTable) -> String {
+        "csv".to_string()
+    }
+
+    fn add_batch_data(&mut self, data: Self::BatchData) -> anyhow::Result<Option<Vec<u8>>> {
+        // each writer produces a single file, so the header only needs to be written once
+        if self.codec.header() && !self.header_written {
+            self.codec
+                .write_header(&mut self.current_buffer, &data)
+                .map_err(|e| anyhow::anyhow!(e))?;
+            self.header_written = true;
+        }
+        self.codec
+            .write_record(&mut self.current_buffer, &data)
+            .map_err(|e| anyhow::anyhow!(e))?;
+        if self.buffer_length() > self.target_part_size {
+            Ok(Some(self.evict_current_buffer()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn buffer_length(&self) -> usize {
+        self.current_buffer.len()
+    }
+
+    fn evict_current_buffer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.current_buffer)
+    }
+
+    fn get_trailing_bytes_for_checkpoint(&mut self) -> Option<Vec<u8>> {
+        if self.current_buffer.is_empty() {
+            None
+        } else {
+            Some(self.current_buffer.clone())
+        }
+    }
+
+    fn close(&mut self, final_batch: Option<Self::BatchData>) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(final_batch) = final_batch {
+            if let Some(final_batch) = self.add_batch_data(final_batch)? {
+                return Ok(Some(final_batch));
+            }
+        }
+        if self.current_buffer.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.evict_current_buffer()))
+        }
+    }
+}
+
+pub struct CsvLocalWriter {
+    tmp_path: String,
+    final_path: String,
+    file: File,
+    codec: CsvCodec,
+    header_written: bool,
+    buffer: Vec<u8>,
+}
+
+impl<D: Data + Serialize> LocalWriter<D> for CsvLocalWriter {
+    fn new(tmp_path: String, final_path: String, table_properties: &FileSystemTable) -> Self {
+        let file = File::create(&tmp_path).unwrap();
+        CsvLocalWriter {
+            tmp_path,
+            final_path,
+            file,
+            codec: csv_codec(table_properties),
+            header_written: false,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn file_suffix(_table_properties: &FileSystemTable) -> String {
+        "csv".to_string()
+    }
+
+    fn write(&mut self, value: D) -> anyhow::Result<()> {
+        if self.codec.header() && !self.header_written {
+            self.codec
+                .write_header(&mut self.buffer, &value)
+                .map_err(|e| anyhow::anyhow!(e))?;
+            self.header_written = true;
+        }
+        self.codec
+            .write_record(&mut self.buffer, &value)
+            .map_err(|e| anyhow::anyhow!(e))?;
+        self.file.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn sync(&mut self) -> anyhow::Result<usize> {
+        self.file.flush()?;
+        let size = self.file.metadata()?.len() as usize;
+        Ok(size)
+    }
+
+    fn close(&mut self) -> anyhow::Result<super::local::FilePreCommit> {
+        LocalWriter::<D>::sync(self)?;
+        Ok(super::local::FilePreCommit {
+            tmp_file: self.tmp_path.clone(),
+            destination: self.final_path.clone(),
+        })
+    }
+
+    fn checkpoint(&mut self) -> anyhow::Result<Option<super::local::CurrentFileRecovery>> {
+        let bytes_written = LocalWriter::<D>::sync(self)?;
+        if bytes_written > 0 {
+            Ok(Some(CurrentFileRecovery {
+                tmp_file: self.tmp_path.clone(),
+                bytes_written,
+                suffix: None,
+                destination: self.final_path.clone(),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
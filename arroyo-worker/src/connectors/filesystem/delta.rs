@@ -0,0 +1,555 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, Field, Int64Type, SchemaRef};
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::{Data, Key, RecordBatchBuilder};
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use object_store::{path::Path, ObjectStore};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::connectors::two_phase_committer::TwoPhaseCommitterOperator;
+
+use super::table_format::{TableFormat, TableFormatSink};
+use super::{object_store_for_table, FileSystemTable, FileToFinish, ParquetFileSystemSink};
+
+/// Writes parquet files like the [`ParquetFileSystemSink`], then registers them in a Delta Lake
+/// table by appending a commit to its transaction log. Each commit records the checkpoint epoch as
+/// a Delta `txn` version for the subtask that wrote the files.
+pub type DeltaLakeSink<K, T, R> = TableFormatSink<K, T, R, DeltaLog>;
+
+impl<K: Key, T: Data + Sync + Serialize, R: RecordBatchBuilder<Data = T> + 'static>
+    DeltaLakeSink<K, T, R>
+{
+    pub fn from_config(config_str: &str) -> TwoPhaseCommitterOperator<K, T, Self> {
+        let config: OperatorConfig =
+            serde_json::from_str(config_str).expect("Invalid config for DeltaLakeSink");
+        let table: FileSystemTable =
+            serde_json::from_value(config.table).expect("Invalid table config for DeltaLakeSink");
+        let (object_store, path) = object_store_for_table(&table);
+        let object_store: Arc<dyn ObjectStore> = Arc::from(object_store);
+
+        TwoPhaseCommitterOperator::new(Self::new(
+            ParquetFileSystemSink::new(object_store.clone(), path.clone(), table),
+            DeltaLog::new(object_store, path, R::default().schema()),
+        ))
+    }
+}
+
+/// Appends commits to the `_delta_log` of a table, creating the table on the first commit
+pub struct DeltaLog {
+    object_store: Arc<dyn ObjectStore>,
+    table_path: Path,
+    schema: SchemaRef,
+    // the next version of the log that we haven't read
+    next_version: u64,
+    // the latest committed txn version of each app id
+    txn_versions: HashMap<String, i64>,
+}
+
+impl DeltaLog {
+    fn new(object_store: Arc<dyn ObjectStore>, table_path: Path, schema: SchemaRef) -> Self {
+        Self {
+            object_store,
+            table_path,
+            schema,
+            next_version: 0,
+            txn_versions: HashMap::new(),
+        }
+    }
+
+    fn log_path(&self, file: &str) -> Path {
+        self.table_path.child("_delta_log").child(file)
+    }
+
+    fn record_txn(&mut self, app_id: &str, txn_version: i64) {
+        let latest = self.txn_versions.entry(app_id.to_string()).or_insert(-1);
+        *latest = txn_version.max(*latest);
+    }
+
+    /// Loads the transactions recorded in the latest checkpoint, if the table has one, so that
+    /// only the commits after it need to be replayed. Older commits may have been cleaned up.
+    async fn load_checkpoint(&mut self) -> Result<()> {
+        let last_checkpoint = match self
+            .object_store
+            .get(&self.log_path("_last_checkpoint"))
+            .await
+        {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let last_checkpoint: Value = serde_json::from_slice(&last_checkpoint)?;
+        let Some(version) = last_checkpoint.get("version").and_then(|v| v.as_u64()) else {
+            bail!(
+                "invalid _last_checkpoint in delta table {}",
+                self.table_path
+            );
+        };
+
+        // large checkpoints may be split into several parts
+        let files: Vec<String> = match last_checkpoint.get("parts").and_then(|p| p.as_u64()) {
+            Some(parts) => (1..=parts)
+                .map(|part| {
+                    format!(
+                        "{:020}.checkpoint.{:010}.{:010}.parquet",
+                        version, part, parts
+                    )
+                })
+                .collect(),
+            None => vec![format!("{:020}.checkpoint.parquet", version)],
+        };
+
+        for file in files {
+            let bytes = self
+                .object_store
+                .get(&self.log_path(&file))
+                .await?
+                .bytes()
+                .await?;
+
+            let builder = ParquetRecordBatchReaderBuilder::try_new(bytes)?;
+            let Some(txn_index) = builder
+                .parquet_schema()
+                .root_schema()
+                .get_fields()
+                .iter()
+                .position(|f| f.name() == "txn")
+            else {
+                continue;
+            };
+            let mask = ProjectionMask::roots(builder.parquet_schema(), [txn_index]);
+
+            for batch in builder.with_projection(mask).build()? {
+                let batch = batch?;
+                let Some(txn) = batch.column_by_name("txn") else {
+                    continue;
+                };
+                let txn = txn.as_struct();
+                let (Some(app_ids), Some(versions)) =
+                    (txn.column_by_name("appId"), txn.column_by_name("version"))
+                else {
+                    continue;
+                };
+                let app_ids = app_ids.as_string::<i32>();
+                let versions = versions.as_primitive::<Int64Type>();
+
+                for i in 0..txn.len() {
+                    if txn.is_valid(i) && app_ids.is_valid(i) && versions.is_valid(i) {
+                        self.record_txn(app_ids.value(i), versions.value(i));
+                    }
+                }
+            }
+        }
+
+        self.next_version = version + 1;
+        Ok(())
+    }
+
+    /// Reads the log entries written since we last looked, tracking the transactions they contain
+    async fn update(&mut self) -> Result<()> {
+        if self.next_version == 0 {
+            self.load_checkpoint().await?;
+        }
+
+        let log_dir = self.table_path.child("_delta_log");
+        let mut versions: Vec<u64> = self
+            .object_store
+            .list(Some(&log_dir))
+            .await?
+            .map_ok(|meta| meta.location)
+            .try_collect::<Vec<_>>()
+            .await?
+            .iter()
+            .filter_map(|location| {
+                // versions are zero-padded to 20 digits, which excludes our temporary files
+                let name = location.filename()?.strip_suffix(".json")?;
+                if name.len() == 20 {
+                    name.parse().ok()
+                } else {
+                    None
+                }
+            })
+            .filter(|version| *version >= self.next_version)
+            .collect();
+        versions.sort();
+
+        for version in versions {
+            if version != self.next_version {
+                bail!(
+                    "delta log for {} is missing version {}",
+                    self.table_path,
+                    self.next_version
+                );
+            }
+
+            let bytes = self
+                .object_store
+                .get(&self.log_path(&format!("{:020}.json", version)))
+                .await?
+                .bytes()
+                .await?;
+
+            for line in bytes.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+                let action: Value = serde_json::from_slice(line)?;
+                if let Some(txn) = action.get("txn") {
+                    if let (Some(app_id), Some(txn_version)) = (
+                        txn.get("appId").and_then(|a| a.as_str()),
+                        txn.get("version").and_then(|v| v.as_i64()),
+                    ) {
+                        self.record_txn(app_id, txn_version);
+                    }
+                }
+            }
+
+            self.next_version += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Atomically writes a version of the log, returning false if it already exists
+    async fn try_write_version(&self, version: u64, body: String) -> Result<bool> {
+        let tmp = self.log_path(&format!("_commit_{}.json.tmp", uuid::Uuid::new_v4()));
+        let target = self.log_path(&format!("{:020}.json", version));
+
+        self.object_store.put(&tmp, body.into()).await?;
+        let result = self.object_store.rename_if_not_exists(&tmp, &target).await;
+        match result {
+            Ok(()) => Ok(true),
+            Err(object_store::Error::AlreadyExists { .. }) => {
+                self.object_store.delete(&tmp).await?;
+                Ok(false)
+            }
+            Err(e) => {
+                self.object_store.delete(&tmp).await?;
+                bail!(
+                    "failed to commit version {} of delta table {}: {}",
+                    version,
+                    self.table_path,
+                    e
+                )
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TableFormat for DeltaLog {
+    fn name(&self) -> String {
+        "delta_lake_sink".to_string()
+    }
+
+    async fn commit(&mut self, app_id: &str, epoch: u32, files: &[FileToFinish]) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let mut adds = vec![];
+        for file in files {
+            let location = Path::parse(&file.filename)?;
+            let meta = self.object_store.head(&location).await?;
+            let Some(relative) = file.filename.strip_prefix(&format!("{}/", self.table_path))
+            else {
+                bail!(
+                    "file {} is not in delta table {}",
+                    file.filename,
+                    self.table_path
+                );
+            };
+
+            adds.push(json!({
+                "add": {
+                    "path": relative,
+                    "partitionValues": {},
+                    "size": meta.size,
+                    "modificationTime": meta.last_modified.timestamp_millis(),
+                    "dataChange": true,
+                }
+            }));
+        }
+
+        loop {
+            self.update().await?;
+
+            if self.txn_versions.get(app_id).copied().unwrap_or(-1) >= epoch as i64 {
+                info!(
+                    "epoch {} has already been committed to {} by {}",
+                    epoch, self.table_path, app_id
+                );
+                return Ok(());
+            }
+
+            let mut actions = vec![];
+            if self.next_version == 0 {
+                actions.push(json!({
+                    "protocol": {
+                        "minReaderVersion": 1,
+                        "minWriterVersion": 2,
+                    }
+                }));
+                actions.push(json!({
+                    "metaData": {
+                        "id": uuid::Uuid::new_v4().to_string(),
+                        "format": {"provider": "parquet", "options": {}},
+                        "schemaString": delta_schema(&self.schema).to_string(),
+                        "partitionColumns": [],
+                        "configuration": {},
+                        "createdTime": now,
+                    }
+                }));
+            }
+            actions.push(json!({
+                "txn": {
+                    "appId": app_id,
+                    "version": epoch,
+                    "lastUpdated": now,
+                }
+            }));
+            actions.extend(adds.iter().cloned());
+            actions.push(json!({
+                "commitInfo": {
+                    "timestamp": now,
+                    "operation": "STREAMING UPDATE",
+                    "operationParameters": {
+                        "outputMode": "Append",
+                        "queryId": app_id,
+                        "epochId": epoch,
+                    },
+                    "isBlindAppend": true,
+                }
+            }));
+
+            let body = actions
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if self.try_write_version(self.next_version, body).await? {
+                info!(
+                    "committed {} files to {} at version {}",
+                    files.len(),
+                    self.table_path,
+                    self.next_version
+                );
+                self.next_version += 1;
+                self.txn_versions.insert(app_id.to_string(), epoch as i64);
+                return Ok(());
+            }
+            // another writer committed this version first, so read it and try the next one
+        }
+    }
+}
+
+fn delta_schema(schema: &SchemaRef) -> Value {
+    json!({
+        "type": "struct",
+        "fields": schema.fields().iter().map(|f| delta_field(f)).collect::<Vec<_>>(),
+    })
+}
+
+fn delta_field(field: &Field) -> Value {
+    json!({
+        "name": field.name(),
+        "type": delta_type(field.data_type()),
+        "nullable": field.is_nullable(),
+        "metadata": {},
+    })
+}
+
+fn delta_type(data_type: &DataType) -> Value {
+    match data_type {
+        DataType::Boolean => "boolean".into(),
+        DataType::Int8 => "byte".into(),
+        DataType::Int16 | DataType::UInt8 => "short".into(),
+        DataType::Int32 | DataType::UInt16 => "integer".into(),
+        DataType::Int64 | DataType::UInt32 => "long".into(),
+        // unsigned longs don't fit in a long, and Spark reads them from parquet as decimal(20,0)
+        DataType::UInt64 => "decimal(20,0)".into(),
+        DataType::Float16 | DataType::Float32 => "float".into(),
+        DataType::Float64 => "double".into(),
+        DataType::Utf8 | DataType::LargeUtf8 => "string".into(),
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => "binary".into(),
+        DataType::Date32 | DataType::Date64 => "date".into(),
+        DataType::Timestamp(_, _) => "timestamp".into(),
+        DataType::Decimal128(precision, scale) | DataType::Decimal256(precision, scale) => {
+            format!("decimal({},{})", precision, scale).into()
+        }
+        DataType::Struct(fields) => json!({
+            "type": "struct",
+            "fields": fields.iter().map(|f| delta_field(f)).collect::<Vec<_>>(),
+        }),
+        DataType::List(field) | DataType::LargeList(field) => json!({
+            "type": "array",
+            "elementType": delta_type(field.data_type()),
+            "containsNull": field.is_nullable(),
+        }),
+        other => unreachable!(
+            "{:?} columns are rejected when delta sinks are planned",
+            other
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Int64Array, StringArray, StructArray};
+    use arrow::datatypes::{DataType, Field, Fields, Schema};
+    use arrow::record_batch::RecordBatch;
+    use object_store::{local::LocalFileSystem, path::Path, ObjectStore};
+    use parquet::arrow::ArrowWriter;
+    use serde_json::Value;
+
+    use super::{DeltaLog, FileToFinish, TableFormat};
+
+    async fn read_version(store: &LocalFileSystem, table: &Path, version: u64) -> Vec<Value> {
+        let bytes = store
+            .get(
+                &table
+                    .child("_delta_log")
+                    .child(format!("{:020}.json", version)),
+            )
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        bytes
+            .split(|b| *b == b'\n')
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_delta_log_commits() {
+        let now = arroyo_types::to_nanos(std::time::SystemTime::now());
+        let dir = format!("/tmp/arroyo-testing/delta-tests/{}", now);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let store = Arc::new(LocalFileSystem::new());
+        let table = Path::from(dir.as_str());
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+
+        let file_path = table.child("00000-000.parquet");
+        store.put(&file_path, vec![0; 10].into()).await.unwrap();
+        let files = vec![FileToFinish {
+            filename: file_path.to_string(),
+            multi_part_upload_id: "".to_string(),
+            completed_parts: vec!["0".to_string()],
+        }];
+
+        let mut log = DeltaLog::new(store.clone(), table.clone(), schema.clone());
+        log.commit("app-0", 1, &files).await.unwrap();
+
+        let actions = read_version(&store, &table, 0).await;
+        assert!(actions[0].get("protocol").is_some());
+        assert!(actions[1].get("metaData").is_some());
+        assert_eq!(actions[2]["txn"]["version"], 1);
+        assert_eq!(actions[3]["add"]["path"], "00000-000.parquet");
+        assert_eq!(actions[3]["add"]["size"], 10);
+
+        // replaying the same epoch from a fresh writer is a no-op
+        let mut log = DeltaLog::new(store.clone(), table.clone(), schema.clone());
+        log.commit("app-0", 1, &files).await.unwrap();
+        assert_eq!(log.next_version, 1);
+
+        // other subtasks append new versions without redefining the table
+        log.commit("app-1", 1, &files).await.unwrap();
+        let actions = read_version(&store, &table, 1).await;
+        assert_eq!(actions[0]["txn"]["appId"], "app-1");
+        assert!(actions.iter().all(|a| a.get("metaData").is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_delta_log_checkpoint() {
+        let now = arroyo_types::to_nanos(std::time::SystemTime::now());
+        let dir = format!("/tmp/arroyo-testing/delta-tests/{}", now);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let store = Arc::new(LocalFileSystem::new());
+        let table = Path::from(dir.as_str());
+        let log_dir = table.child("_delta_log");
+
+        // a table whose log has been cleaned up before the checkpoint at version 5
+        let txn_fields = Fields::from(vec![
+            Field::new("appId", DataType::Utf8, true),
+            Field::new("version", DataType::Int64, true),
+        ]);
+        let txn = StructArray::new(
+            txn_fields.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![Some("app-0"), None])) as ArrayRef,
+                Arc::new(Int64Array::from(vec![Some(7), None])),
+            ],
+            Some(vec![true, false].into()),
+        );
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("txn", DataType::Struct(txn_fields), true),
+                Field::new("add", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(txn),
+                Arc::new(StringArray::from(vec![None, Some("ignored")])),
+            ],
+        )
+        .unwrap();
+
+        let mut checkpoint = vec![];
+        let mut writer = ArrowWriter::try_new(&mut checkpoint, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        store
+            .put(
+                &log_dir.child(format!("{:020}.checkpoint.parquet", 5)),
+                checkpoint.into(),
+            )
+            .await
+            .unwrap();
+        store
+            .put(
+                &log_dir.child("_last_checkpoint"),
+                r#"{"version":5,"size":2}"#.into(),
+            )
+            .await
+            .unwrap();
+        store
+            .put(
+                &log_dir.child(format!("{:020}.json", 6)),
+                r#"{"txn":{"appId":"app-1","version":3,"lastUpdated":0}}"#.into(),
+            )
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let mut log = DeltaLog::new(store.clone(), table.clone(), schema);
+        log.update().await.unwrap();
+
+        assert_eq!(log.next_version, 7);
+        assert_eq!(log.txn_versions["app-0"], 7);
+        assert_eq!(log.txn_versions["app-1"], 3);
+
+        // epochs already in the checkpoint aren't committed again
+        log.commit("app-0", 7, &[]).await.unwrap();
+        assert_eq!(log.next_version, 7);
+    }
+}
@@ -0,0 +1,402 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use futures::TryStreamExt;
+use object_store::{path::Path, ObjectStore};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use super::metadata::{current_snapshot, TableUpdate};
+
+/// The metadata of a table, as loaded from its catalog
+pub struct LoadedTable {
+    pub metadata: Value,
+    // the version of the metadata file, for hadoop tables
+    version: u64,
+}
+
+/// Where the current metadata of an Iceberg table is tracked
+pub enum IcebergCatalog {
+    /// Tables are stored under the warehouse directory, with versioned metadata files that are
+    /// committed by atomically creating the next version
+    Hadoop {
+        object_store: Arc<dyn ObjectStore>,
+        table_path: Path,
+    },
+    /// Tables are managed by a service implementing the Iceberg REST catalog API
+    Rest {
+        client: reqwest::Client,
+        url: String,
+        token: Option<String>,
+        namespace: String,
+        table_name: String,
+    },
+}
+
+impl IcebergCatalog {
+    pub fn name(&self) -> String {
+        match self {
+            IcebergCatalog::Hadoop { table_path, .. } => table_path.to_string(),
+            IcebergCatalog::Rest {
+                namespace,
+                table_name,
+                ..
+            } => format!("{}.{}", namespace, table_name),
+        }
+    }
+
+    /// Loads the current metadata of the table, or None if it doesn't exist
+    pub async fn load_table(&self) -> Result<Option<LoadedTable>> {
+        match self {
+            IcebergCatalog::Hadoop {
+                object_store,
+                table_path,
+            } => {
+                let metadata_dir = table_path.child("metadata");
+                let hint = match object_store
+                    .get(&metadata_dir.child("version-hint.text"))
+                    .await
+                {
+                    Ok(result) => String::from_utf8(result.bytes().await?.to_vec())?
+                        .trim()
+                        .parse::<u64>()
+                        .ok(),
+                    Err(object_store::Error::NotFound { .. }) => None,
+                    Err(e) => return Err(e.into()),
+                };
+
+                let mut version = match hint {
+                    Some(version) => version,
+                    None => {
+                        // fall back to listing the metadata files
+                        let versions: Vec<u64> = object_store
+                            .list(Some(&metadata_dir))
+                            .await?
+                            .map_ok(|meta| meta.location)
+                            .try_collect::<Vec<_>>()
+                            .await?
+                            .iter()
+                            .filter_map(|location| {
+                                location
+                                    .filename()?
+                                    .strip_prefix('v')?
+                                    .strip_suffix(".metadata.json")?
+                                    .parse()
+                                    .ok()
+                            })
+                            .collect();
+                        match versions.into_iter().max() {
+                            Some(version) => version,
+                            None => return Ok(None),
+                        }
+                    }
+                };
+
+                // the hint is updated after the commit, so newer versions may exist
+                loop {
+                    let path = Self::hadoop_metadata_path(table_path, version + 1);
+                    match object_store.head(&path).await {
+                        Ok(_) => version += 1,
+                        Err(object_store::Error::NotFound { .. }) => break,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+
+                let bytes = object_store
+                    .get(&Self::hadoop_metadata_path(table_path, version))
+                    .await?
+                    .bytes()
+                    .await?;
+
+                Ok(Some(LoadedTable {
+                    metadata: serde_json::from_slice(&bytes)?,
+                    version,
+                }))
+            }
+            IcebergCatalog::Rest { .. } => {
+                let response = self.rest_request(reqwest::Method::GET, "", None).await?;
+                match response.status() {
+                    StatusCode::NOT_FOUND => Ok(None),
+                    status if status.is_success() => {
+                        let body: Value = serde_json::from_str(&response.text().await?)?;
+                        Ok(Some(LoadedTable {
+                            metadata: body["metadata"].clone(),
+                            version: 0,
+                        }))
+                    }
+                    status => bail!(
+                        "failed to load iceberg table {}: {} {}",
+                        self.name(),
+                        status,
+                        response.text().await.unwrap_or_default()
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Creates the table with the given metadata. Returns false if the table was concurrently
+    /// created by another writer.
+    pub async fn create_table(&self, metadata: &Value) -> Result<bool> {
+        match self {
+            IcebergCatalog::Hadoop {
+                object_store,
+                table_path,
+            } => {
+                let created = self.write_hadoop_version(1, metadata).await?;
+                if created {
+                    Self::write_version_hint(object_store, table_path, 1).await;
+                }
+                Ok(created)
+            }
+            IcebergCatalog::Rest { namespace, .. } => {
+                let body = json!({
+                    "name": self.rest_table_name(),
+                    "location": metadata["location"],
+                    "schema": metadata["schemas"][0],
+                    "partition-spec": metadata["partition-specs"][0],
+                    "write-order": metadata["sort-orders"][0],
+                    "properties": metadata["properties"],
+                });
+
+                let mut created_namespace = false;
+                loop {
+                    let response = self
+                        .rest_namespace_request(reqwest::Method::POST, "/tables", Some(&body))
+                        .await?;
+                    match response.status() {
+                        StatusCode::CONFLICT => return Ok(false),
+                        StatusCode::NOT_FOUND if !created_namespace => {
+                            // the namespace doesn't exist yet
+                            let response = self
+                                .rest_request_to(
+                                    reqwest::Method::POST,
+                                    "/v1/namespaces".to_string(),
+                                    Some(&json!({"namespace": namespace.split('.').collect::<Vec<_>>()})),
+                                )
+                                .await?;
+                            if !response.status().is_success()
+                                && response.status() != StatusCode::CONFLICT
+                            {
+                                bail!(
+                                    "failed to create iceberg namespace {}: {} {}",
+                                    namespace,
+                                    response.status(),
+                                    response.text().await.unwrap_or_default()
+                                );
+                            }
+                            created_namespace = true;
+                        }
+                        status if status.is_success() => return Ok(true),
+                        status => bail!(
+                            "failed to create iceberg table {}: {} {}",
+                            self.name(),
+                            status,
+                            response.text().await.unwrap_or_default()
+                        ),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Commits `updates` on top of `base`. Returns false if the table was changed since `base`
+    /// was loaded, in which case the commit should be retried against the new metadata.
+    pub async fn commit(&self, base: &LoadedTable, updates: &[TableUpdate]) -> Result<bool> {
+        match self {
+            IcebergCatalog::Hadoop {
+                object_store,
+                table_path,
+            } => {
+                let mut metadata = base.metadata.clone();
+                for update in updates {
+                    update.apply(&mut metadata)?;
+                }
+                let previous = json!({
+                    "timestamp-ms": base.metadata["last-updated-ms"],
+                    "metadata-file": format!(
+                        "{}/metadata/v{}.metadata.json",
+                        base.metadata["location"].as_str().unwrap_or_default(),
+                        base.version
+                    ),
+                });
+                match metadata["metadata-log"].as_array_mut() {
+                    Some(log) => log.push(previous),
+                    None => metadata["metadata-log"] = json!([previous]),
+                }
+
+                let version = base.version + 1;
+                let committed = self.write_hadoop_version(version, &metadata).await?;
+                if committed {
+                    Self::write_version_hint(object_store, table_path, version).await;
+                }
+                Ok(committed)
+            }
+            IcebergCatalog::Rest { .. } => {
+                let body = json!({
+                    "requirements": [
+                        {
+                            "type": "assert-table-uuid",
+                            "uuid": base.metadata["table-uuid"],
+                        },
+                        {
+                            "type": "assert-ref-snapshot-id",
+                            "ref": "main",
+                            "snapshot-id": current_snapshot(&base.metadata)
+                                .map(|s| s["snapshot-id"].clone())
+                                .unwrap_or(Value::Null),
+                        },
+                        {
+                            "type": "assert-current-schema-id",
+                            "current-schema-id": base.metadata["current-schema-id"],
+                        },
+                    ],
+                    "updates": updates.iter().map(|u| u.to_rest()).collect::<Vec<_>>(),
+                });
+
+                let response = self
+                    .rest_request(reqwest::Method::POST, "", Some(&body))
+                    .await?;
+                match response.status() {
+                    StatusCode::CONFLICT => Ok(false),
+                    status if status.is_success() => Ok(true),
+                    status => bail!(
+                        "failed to commit to iceberg table {}: {} {}",
+                        self.name(),
+                        status,
+                        response.text().await.unwrap_or_default()
+                    ),
+                }
+            }
+        }
+    }
+
+    fn hadoop_metadata_path(table_path: &Path, version: u64) -> Path {
+        table_path
+            .child("metadata")
+            .child(format!("v{}.metadata.json", version))
+    }
+
+    async fn write_hadoop_version(&self, version: u64, metadata: &Value) -> Result<bool> {
+        let IcebergCatalog::Hadoop {
+            object_store,
+            table_path,
+        } = self
+        else {
+            unreachable!("only hadoop catalogs have metadata versions");
+        };
+
+        let tmp = table_path.child("metadata").child(format!(
+            "_v{}.{}.tmp",
+            version,
+            uuid::Uuid::new_v4()
+        ));
+        let target = Self::hadoop_metadata_path(table_path, version);
+
+        object_store
+            .put(&tmp, serde_json::to_vec(metadata)?.into())
+            .await?;
+        match object_store.rename_if_not_exists(&tmp, &target).await {
+            Ok(()) => Ok(true),
+            Err(object_store::Error::AlreadyExists { .. }) => {
+                object_store.delete(&tmp).await?;
+                Ok(false)
+            }
+            Err(e) => {
+                object_store.delete(&tmp).await?;
+                bail!(
+                    "failed to write version {} of iceberg table {}: {}",
+                    version,
+                    table_path,
+                    e
+                )
+            }
+        }
+    }
+
+    async fn write_version_hint(
+        object_store: &Arc<dyn ObjectStore>,
+        table_path: &Path,
+        version: u64,
+    ) {
+        // the hint is only an optimization for readers, so failing to write it isn't fatal
+        if let Err(e) = object_store
+            .put(
+                &table_path.child("metadata").child("version-hint.text"),
+                version.to_string().into(),
+            )
+            .await
+        {
+            warn!("failed to update version hint of {}: {}", table_path, e);
+        }
+    }
+
+    fn rest_table_name(&self) -> String {
+        match self {
+            IcebergCatalog::Rest { table_name, .. } => table_name.clone(),
+            IcebergCatalog::Hadoop { .. } => unreachable!("not a REST catalog"),
+        }
+    }
+
+    async fn rest_request(
+        &self,
+        method: reqwest::Method,
+        suffix: &str,
+        body: Option<&Value>,
+    ) -> Result<reqwest::Response> {
+        let table_suffix = format!("/tables/{}{}", self.rest_table_name(), suffix);
+        self.rest_namespace_request(method, &table_suffix, body)
+            .await
+    }
+
+    async fn rest_namespace_request(
+        &self,
+        method: reqwest::Method,
+        suffix: &str,
+        body: Option<&Value>,
+    ) -> Result<reqwest::Response> {
+        let IcebergCatalog::Rest { namespace, .. } = self else {
+            unreachable!("not a REST catalog");
+        };
+        // multi-level namespaces are separated by the unit separator in paths
+        let namespace = namespace.replace('.', "\u{1f}");
+        let namespace =
+            url::form_urlencoded::byte_serialize(namespace.as_bytes()).collect::<String>();
+        self.rest_request_to(
+            method,
+            format!("/v1/namespaces/{}{}", namespace, suffix),
+            body,
+        )
+        .await
+    }
+
+    async fn rest_request_to(
+        &self,
+        method: reqwest::Method,
+        path: String,
+        body: Option<&Value>,
+    ) -> Result<reqwest::Response> {
+        let IcebergCatalog::Rest {
+            client, url, token, ..
+        } = self
+        else {
+            unreachable!("not a REST catalog");
+        };
+
+        let mut request = client.request(method, format!("{}{}", url.trim_end_matches('/'), path));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+
+        request
+            .send()
+            .await
+            .map_err(|e| anyhow!("request to iceberg catalog {} failed: {}", url, e))
+    }
+}
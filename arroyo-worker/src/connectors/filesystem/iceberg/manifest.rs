@@ -0,0 +1,335 @@
+use anyhow::{bail, Result};
+use apache_avro::{types::Value as AvroValue, Reader, Schema, Writer};
+use serde_json::{json, Value};
+
+/// A parquet file added by a commit
+pub struct DataFile {
+    pub path: String,
+    pub partition: Vec<AvroValue>,
+    pub record_count: i64,
+    pub file_size: i64,
+}
+
+/// An entry in a manifest list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestFile {
+    pub path: String,
+    pub length: i64,
+    pub spec_id: i32,
+    pub sequence_number: i64,
+    pub min_sequence_number: i64,
+    pub snapshot_id: i64,
+    pub added_files: i32,
+    pub existing_files: i32,
+    pub deleted_files: i32,
+    pub added_rows: i64,
+    pub existing_rows: i64,
+    pub deleted_rows: i64,
+}
+
+/// A field of the partition tuple, as it is written into manifests
+pub struct PartitionColumn {
+    pub name: String,
+    pub field_id: i64,
+    pub avro_type: Value,
+}
+
+const STATUS_ADDED: i32 = 1;
+
+fn manifest_entry_schema(partition: &[PartitionColumn]) -> Value {
+    let partition_fields: Vec<Value> = partition
+        .iter()
+        .map(|p| {
+            json!({
+                "name": p.name,
+                "type": ["null", p.avro_type],
+                "default": null,
+                "field-id": p.field_id,
+            })
+        })
+        .collect();
+
+    json!({
+        "type": "record",
+        "name": "manifest_entry",
+        "fields": [
+            {"name": "status", "type": "int", "field-id": 0},
+            {"name": "snapshot_id", "type": ["null", "long"], "default": null, "field-id": 1},
+            {"name": "sequence_number", "type": ["null", "long"], "default": null, "field-id": 3},
+            {"name": "file_sequence_number", "type": ["null", "long"], "default": null, "field-id": 4},
+            {"name": "data_file", "field-id": 2, "type": {
+                "type": "record",
+                "name": "r2",
+                "fields": [
+                    {"name": "content", "type": "int", "field-id": 134},
+                    {"name": "file_path", "type": "string", "field-id": 100},
+                    {"name": "file_format", "type": "string", "field-id": 101},
+                    {"name": "partition", "field-id": 102, "type": {
+                        "type": "record",
+                        "name": "r102",
+                        "fields": partition_fields,
+                    }},
+                    {"name": "record_count", "type": "long", "field-id": 103},
+                    {"name": "file_size_in_bytes", "type": "long", "field-id": 104},
+                ]
+            }},
+        ]
+    })
+}
+
+fn manifest_list_schema() -> Value {
+    json!({
+        "type": "record",
+        "name": "manifest_file",
+        "fields": [
+            {"name": "manifest_path", "type": "string", "field-id": 500},
+            {"name": "manifest_length", "type": "long", "field-id": 501},
+            {"name": "partition_spec_id", "type": "int", "field-id": 502},
+            {"name": "content", "type": "int", "field-id": 517},
+            {"name": "sequence_number", "type": "long", "field-id": 515},
+            {"name": "min_sequence_number", "type": "long", "field-id": 516},
+            {"name": "added_snapshot_id", "type": "long", "field-id": 503},
+            {"name": "added_files_count", "type": "int", "field-id": 504},
+            {"name": "existing_files_count", "type": "int", "field-id": 505},
+            {"name": "deleted_files_count", "type": "int", "field-id": 506},
+            {"name": "added_rows_count", "type": "long", "field-id": 512},
+            {"name": "existing_rows_count", "type": "long", "field-id": 513},
+            {"name": "deleted_rows_count", "type": "long", "field-id": 514},
+        ]
+    })
+}
+
+/// Writes a manifest that adds `files` to the table
+pub fn write_manifest(
+    table_schema: &Value,
+    partition_spec: &Value,
+    partition: &[PartitionColumn],
+    snapshot_id: i64,
+    files: &[DataFile],
+) -> Result<Vec<u8>> {
+    let schema = Schema::parse(&manifest_entry_schema(partition))?;
+    let mut writer = Writer::new(&schema, vec![]);
+    writer.add_user_metadata("schema".to_string(), table_schema.to_string())?;
+    writer.add_user_metadata(
+        "schema-id".to_string(),
+        table_schema["schema-id"].to_string(),
+    )?;
+    writer.add_user_metadata(
+        "partition-spec".to_string(),
+        partition_spec["fields"].to_string(),
+    )?;
+    writer.add_user_metadata(
+        "partition-spec-id".to_string(),
+        partition_spec["spec-id"].to_string(),
+    )?;
+    writer.add_user_metadata("format-version".to_string(), "2")?;
+    writer.add_user_metadata("content".to_string(), "data")?;
+
+    for file in files {
+        if file.partition.len() != partition.len() {
+            bail!(
+                "{} has {} partition values, but the partition spec has {} fields",
+                file.path,
+                file.partition.len(),
+                partition.len()
+            );
+        }
+
+        let partition_values = partition
+            .iter()
+            .zip(file.partition.iter())
+            .map(|(column, value)| {
+                let value = match value {
+                    AvroValue::Null => AvroValue::Union(0, Box::new(AvroValue::Null)),
+                    v => AvroValue::Union(1, Box::new(v.clone())),
+                };
+                (column.name.clone(), value)
+            })
+            .collect();
+
+        writer.append(AvroValue::Record(vec![
+            ("status".to_string(), AvroValue::Int(STATUS_ADDED)),
+            (
+                "snapshot_id".to_string(),
+                AvroValue::Union(1, Box::new(AvroValue::Long(snapshot_id))),
+            ),
+            // the sequence numbers of added files are inherited from the manifest list
+            (
+                "sequence_number".to_string(),
+                AvroValue::Union(0, Box::new(AvroValue::Null)),
+            ),
+            (
+                "file_sequence_number".to_string(),
+                AvroValue::Union(0, Box::new(AvroValue::Null)),
+            ),
+            (
+                "data_file".to_string(),
+                AvroValue::Record(vec![
+                    ("content".to_string(), AvroValue::Int(0)),
+                    (
+                        "file_path".to_string(),
+                        AvroValue::String(file.path.clone()),
+                    ),
+                    (
+                        "file_format".to_string(),
+                        AvroValue::String("PARQUET".to_string()),
+                    ),
+                    ("partition".to_string(), AvroValue::Record(partition_values)),
+                    (
+                        "record_count".to_string(),
+                        AvroValue::Long(file.record_count),
+                    ),
+                    (
+                        "file_size_in_bytes".to_string(),
+                        AvroValue::Long(file.file_size),
+                    ),
+                ]),
+            ),
+        ]))?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+/// Reads the entries of a manifest list
+pub fn read_manifest_list(bytes: &[u8]) -> Result<Vec<ManifestFile>> {
+    let reader = Reader::new(bytes)?;
+    let mut manifests = vec![];
+    for record in reader {
+        let AvroValue::Record(fields) = record? else {
+            bail!("invalid manifest list: entries must be records");
+        };
+
+        // older writers used different names for some of the counts
+        let get = |names: &[&str]| {
+            fields
+                .iter()
+                .find(|(name, _)| names.contains(&name.as_str()))
+                .map(|(_, value)| match value {
+                    AvroValue::Union(_, v) => v.as_ref().clone(),
+                    v => v.clone(),
+                })
+        };
+        let long = |names: &[&str]| match get(names) {
+            Some(AvroValue::Long(l)) => l,
+            Some(AvroValue::Int(i)) => i as i64,
+            _ => 0,
+        };
+
+        let Some(AvroValue::String(path)) = get(&["manifest_path"]) else {
+            bail!("invalid manifest list: missing manifest_path");
+        };
+
+        manifests.push(ManifestFile {
+            path,
+            length: long(&["manifest_length"]),
+            spec_id: long(&["partition_spec_id"]) as i32,
+            sequence_number: long(&["sequence_number"]),
+            min_sequence_number: long(&["min_sequence_number"]),
+            snapshot_id: long(&["added_snapshot_id"]),
+            added_files: long(&["added_files_count", "added_data_files_count"]) as i32,
+            existing_files: long(&["existing_files_count", "existing_data_files_count"]) as i32,
+            deleted_files: long(&["deleted_files_count", "deleted_data_files_count"]) as i32,
+            added_rows: long(&["added_rows_count"]),
+            existing_rows: long(&["existing_rows_count"]),
+            deleted_rows: long(&["deleted_rows_count"]),
+        });
+    }
+    Ok(manifests)
+}
+
+/// Writes a manifest list containing `manifests`
+pub fn write_manifest_list(
+    snapshot_id: i64,
+    parent_snapshot_id: Option<i64>,
+    sequence_number: i64,
+    manifests: &[ManifestFile],
+) -> Result<Vec<u8>> {
+    let schema = Schema::parse(&manifest_list_schema())?;
+    let mut writer = Writer::new(&schema, vec![]);
+    writer.add_user_metadata("snapshot-id".to_string(), snapshot_id.to_string())?;
+    writer.add_user_metadata(
+        "parent-snapshot-id".to_string(),
+        parent_snapshot_id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "null".to_string()),
+    )?;
+    writer.add_user_metadata("sequence-number".to_string(), sequence_number.to_string())?;
+    writer.add_user_metadata("format-version".to_string(), "2")?;
+
+    for m in manifests {
+        writer.append(AvroValue::Record(vec![
+            (
+                "manifest_path".to_string(),
+                AvroValue::String(m.path.clone()),
+            ),
+            ("manifest_length".to_string(), AvroValue::Long(m.length)),
+            ("partition_spec_id".to_string(), AvroValue::Int(m.spec_id)),
+            ("content".to_string(), AvroValue::Int(0)),
+            (
+                "sequence_number".to_string(),
+                AvroValue::Long(m.sequence_number),
+            ),
+            (
+                "min_sequence_number".to_string(),
+                AvroValue::Long(m.min_sequence_number),
+            ),
+            (
+                "added_snapshot_id".to_string(),
+                AvroValue::Long(m.snapshot_id),
+            ),
+            (
+                "added_files_count".to_string(),
+                AvroValue::Int(m.added_files),
+            ),
+            (
+                "existing_files_count".to_string(),
+                AvroValue::Int(m.existing_files),
+            ),
+            (
+                "deleted_files_count".to_string(),
+                AvroValue::Int(m.deleted_files),
+            ),
+            (
+                "added_rows_count".to_string(),
+                AvroValue::Long(m.added_rows),
+            ),
+            (
+                "existing_rows_count".to_string(),
+                AvroValue::Long(m.existing_rows),
+            ),
+            (
+                "deleted_rows_count".to_string(),
+                AvroValue::Long(m.deleted_rows),
+            ),
+        ]))?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_manifest_list, write_manifest_list, ManifestFile};
+
+    #[test]
+    fn test_manifest_list_roundtrip() {
+        let manifests = vec![ManifestFile {
+            path: "file:///tmp/table/metadata/a-m0.avro".to_string(),
+            length: 1024,
+            spec_id: 0,
+            sequence_number: 3,
+            min_sequence_number: 3,
+            snapshot_id: 42,
+            added_files: 2,
+            existing_files: 0,
+            deleted_files: 0,
+            added_rows: 100,
+            existing_rows: 0,
+            deleted_rows: 0,
+        }];
+
+        let bytes = write_manifest_list(42, None, 3, &manifests).unwrap();
+        assert_eq!(read_manifest_list(&bytes).unwrap(), manifests);
+    }
+}
@@ -0,0 +1,655 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::array::{Array, ArrayRef, GenericListArray, OffsetSizeTrait, StructArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use serde_json::{json, Map, Value};
+
+use crate::connectors::filesystem::partitioning::{PartitionField, Transform};
+
+/// Property Iceberg readers use to map columns of data files without field ids (like ours) to
+/// the table schema
+pub const NAME_MAPPING_PROPERTY: &str = "schema.name-mapping.default";
+
+/// Returns the schema data files of an Iceberg table are written with. Iceberg timestamps are
+/// microseconds, so timestamp columns in other units are converted.
+pub fn iceberg_arrow_schema(schema: &Schema) -> SchemaRef {
+    Arc::new(Schema::new_with_metadata(
+        iceberg_fields(schema.fields()),
+        schema.metadata().clone(),
+    ))
+}
+
+fn iceberg_fields(fields: &Fields) -> Fields {
+    fields
+        .iter()
+        .map(|f| {
+            f.as_ref()
+                .clone()
+                .with_data_type(iceberg_data_type(f.data_type()))
+        })
+        .collect()
+}
+
+fn iceberg_data_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Timestamp(_, tz) => DataType::Timestamp(TimeUnit::Microsecond, tz.clone()),
+        DataType::Struct(fields) => DataType::Struct(iceberg_fields(fields)),
+        DataType::List(element) => DataType::List(Arc::new(
+            element
+                .as_ref()
+                .clone()
+                .with_data_type(iceberg_data_type(element.data_type())),
+        )),
+        DataType::LargeList(element) => DataType::LargeList(Arc::new(
+            element
+                .as_ref()
+                .clone()
+                .with_data_type(iceberg_data_type(element.data_type())),
+        )),
+        other => other.clone(),
+    }
+}
+
+/// Converts a batch to the schema returned by [`iceberg_arrow_schema`]
+pub fn to_iceberg_batch(batch: &RecordBatch, schema: SchemaRef) -> Result<RecordBatch> {
+    if batch.schema() == schema {
+        return Ok(batch.clone());
+    }
+
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| to_iceberg_array(column, field.data_type()))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn to_iceberg_array(array: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+    if array.data_type() == data_type {
+        return Ok(array.clone());
+    }
+
+    Ok(match data_type {
+        DataType::Struct(fields) => {
+            let array = array
+                .as_any()
+                .downcast_ref::<StructArray>()
+                .ok_or_else(|| anyhow!("expected struct array"))?;
+            let columns = array
+                .columns()
+                .iter()
+                .zip(fields)
+                .map(|(column, field)| to_iceberg_array(column, field.data_type()))
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(StructArray::new(
+                fields.clone(),
+                columns,
+                array.nulls().cloned(),
+            ))
+        }
+        DataType::List(element) => to_iceberg_list::<i32>(array, element)?,
+        DataType::LargeList(element) => to_iceberg_list::<i64>(array, element)?,
+        _ => cast(array, data_type)?,
+    })
+}
+
+fn to_iceberg_list<O: OffsetSizeTrait>(array: &ArrayRef, element: &Arc<Field>) -> Result<ArrayRef> {
+    let array = array
+        .as_any()
+        .downcast_ref::<GenericListArray<O>>()
+        .ok_or_else(|| anyhow!("expected list array"))?;
+    let values = to_iceberg_array(array.values(), element.data_type())?;
+    Ok(Arc::new(GenericListArray::<O>::new(
+        element.clone(),
+        array.offsets().clone(),
+        values,
+        array.nulls().cloned(),
+    )))
+}
+
+/// Converts arrow fields to Iceberg struct fields. Fields that already exist in `existing` (when
+/// evolving the schema of an existing table) keep their ids, new fields are assigned ids after
+/// `last_column_id` and are added as optional columns. Returns the fields and whether they differ
+/// from `existing`.
+pub fn convert_fields(
+    fields: &Fields,
+    existing: Option<&[Value]>,
+    last_column_id: &mut i64,
+) -> Result<(Vec<Value>, bool)> {
+    let mut changed = false;
+    let mut result = vec![];
+
+    for field in fields {
+        let existing_field =
+            existing.and_then(|e| e.iter().find(|f| f["name"].as_str() == Some(field.name())));
+
+        let (id, required) = match existing_field {
+            Some(e) => {
+                let required = e["required"].as_bool().unwrap_or(false);
+                if required && field.is_nullable() {
+                    bail!(
+                        "column '{}' is required in the iceberg table but nullable in the query",
+                        field.name()
+                    );
+                }
+                (id_of(e, "id")?, required)
+            }
+            None => {
+                changed |= existing.is_some();
+                *last_column_id += 1;
+                // columns can only be added to existing tables as optional
+                (*last_column_id, existing.is_none() && !field.is_nullable())
+            }
+        };
+
+        let (field_type, type_changed) = convert_type(
+            field,
+            existing_field.map(|e| &e["type"]),
+            existing.is_none(),
+            last_column_id,
+        )?;
+        changed |= type_changed;
+
+        result.push(json!({
+            "id": id,
+            "name": field.name(),
+            "required": required,
+            "type": field_type,
+        }));
+    }
+
+    // columns that are no longer written are kept so that existing data can still be read
+    for existing_field in existing.unwrap_or_default() {
+        let name = existing_field["name"].as_str().unwrap_or_default();
+        if fields.iter().all(|f| f.name() != name) {
+            if existing_field["required"].as_bool().unwrap_or(false) {
+                bail!(
+                    "required column '{}' of the iceberg table is not written by the query",
+                    name
+                );
+            }
+            result.push(existing_field.clone());
+        }
+    }
+
+    Ok((result, changed))
+}
+
+fn convert_type(
+    field: &Field,
+    existing: Option<&Value>,
+    new_table: bool,
+    last_column_id: &mut i64,
+) -> Result<(Value, bool)> {
+    let converted = match field.data_type() {
+        DataType::Struct(fields) => {
+            let existing_fields = match existing {
+                Some(e) => Some(
+                    e["fields"]
+                        .as_array()
+                        .ok_or_else(|| type_mismatch(field, e))?
+                        .as_slice(),
+                ),
+                None => None,
+            };
+            let (fields, changed) = convert_fields(
+                fields,
+                existing_fields.or(if new_table {
+                    None
+                } else {
+                    Some(&[] as &[Value])
+                }),
+                last_column_id,
+            )?;
+            return Ok((json!({"type": "struct", "fields": fields}), changed));
+        }
+        DataType::List(element) | DataType::LargeList(element) => {
+            let existing_element = existing.map(|e| &e["element"]);
+            let element_id = match existing {
+                Some(e) => id_of(e, "element-id").map_err(|_| type_mismatch(field, e))?,
+                None => {
+                    *last_column_id += 1;
+                    *last_column_id
+                }
+            };
+            let (element_type, changed) =
+                convert_type(element, existing_element, new_table, last_column_id)?;
+            return Ok((
+                json!({
+                    "type": "list",
+                    "element-id": element_id,
+                    "element": element_type,
+                    "element-required": !element.is_nullable(),
+                }),
+                changed || existing.is_none() && !new_table,
+            ));
+        }
+        data_type => Value::String(primitive_type(data_type)?),
+    };
+
+    if let Some(existing) = existing {
+        let compatible = existing == &converted
+            || matches!(
+                (existing.as_str(), converted.as_str()),
+                (Some("long"), Some("int")) | (Some("double"), Some("float"))
+            );
+        if !compatible {
+            return Err(type_mismatch(field, existing));
+        }
+        return Ok((existing.clone(), false));
+    }
+
+    Ok((converted, false))
+}
+
+fn primitive_type(data_type: &DataType) -> Result<String> {
+    Ok(match data_type {
+        DataType::Boolean => "boolean".to_string(),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            "int".to_string()
+        }
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => "long".to_string(),
+        DataType::Float16 | DataType::Float32 => "float".to_string(),
+        DataType::Float64 => "double".to_string(),
+        DataType::Utf8 | DataType::LargeUtf8 => "string".to_string(),
+        DataType::Binary | DataType::LargeBinary => "binary".to_string(),
+        DataType::FixedSizeBinary(n) => format!("fixed[{}]", n),
+        DataType::Date32 | DataType::Date64 => "date".to_string(),
+        // other units are converted to microseconds by `iceberg_arrow_schema` before writing
+        DataType::Timestamp(TimeUnit::Microsecond, None) => "timestamp".to_string(),
+        DataType::Timestamp(TimeUnit::Microsecond, Some(_)) => "timestamptz".to_string(),
+        DataType::Decimal128(precision, scale) => format!("decimal({}, {})", precision, scale),
+        other => bail!("{:?} is not supported in iceberg tables", other),
+    })
+}
+
+fn type_mismatch(field: &Field, existing: &Value) -> anyhow::Error {
+    anyhow!(
+        "column '{}' has type {} in the iceberg table, which is not compatible with {:?}",
+        field.name(),
+        existing,
+        field.data_type()
+    )
+}
+
+pub fn id_of(value: &Value, key: &str) -> Result<i64> {
+    value[key]
+        .as_i64()
+        .ok_or_else(|| anyhow!("invalid iceberg metadata: missing {} in {}", key, value))
+}
+
+/// Builds the name mapping for a list of schema fields
+pub fn name_mapping(fields: &[Value]) -> Value {
+    Value::Array(
+        fields
+            .iter()
+            .map(|f| {
+                let mut mapping = json!({
+                    "field-id": f["id"],
+                    "names": [f["name"]],
+                });
+                if let Some(nested) = nested_mapping(&f["type"]) {
+                    mapping["fields"] = nested;
+                }
+                mapping
+            })
+            .collect(),
+    )
+}
+
+fn nested_mapping(field_type: &Value) -> Option<Value> {
+    match field_type["type"].as_str()? {
+        "struct" => Some(name_mapping(field_type["fields"].as_array()?)),
+        "list" => {
+            // arrow names list elements "item", while other writers use "element"
+            let mut element = json!({
+                "field-id": field_type["element-id"],
+                "names": ["element", "item"],
+            });
+            if let Some(nested) = nested_mapping(&field_type["element"]) {
+                element["fields"] = nested;
+            }
+            Some(json!([element]))
+        }
+        _ => None,
+    }
+}
+
+/// Builds the partition spec fields for `fields` against the given schema
+pub fn partition_spec_fields(
+    partition_fields: &[PartitionField],
+    schema_fields: &[Value],
+) -> Result<Vec<Value>> {
+    partition_fields
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let source = schema_fields
+                .iter()
+                .find(|f| f["name"].as_str() == Some(&p.source))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "partition source column '{}' is not in the schema",
+                        p.source
+                    )
+                })?;
+            Ok(json!({
+                "source-id": source["id"],
+                "field-id": 1000 + i,
+                "name": p.name,
+                "transform": p.transform.name(),
+            }))
+        })
+        .collect()
+}
+
+/// Returns the Iceberg type of the values produced by a partition transform
+pub fn partition_result_type(transform: Transform, source_type: &Value) -> Value {
+    match transform {
+        Transform::Identity => source_type.clone(),
+        Transform::Day => json!("date"),
+        Transform::Hour | Transform::Bucket(_) => json!("int"),
+    }
+}
+
+/// Metadata for a new format version 2 table
+pub fn new_table_metadata(
+    location: &str,
+    fields: Vec<Value>,
+    last_column_id: i64,
+    spec_fields: Vec<Value>,
+    now: i64,
+) -> Value {
+    let last_partition_id = 999 + spec_fields.len() as i64;
+    let mapping = name_mapping(&fields).to_string();
+    json!({
+        "format-version": 2,
+        "table-uuid": uuid::Uuid::new_v4().to_string(),
+        "location": location,
+        "last-sequence-number": 0,
+        "last-updated-ms": now,
+        "last-column-id": last_column_id,
+        "current-schema-id": 0,
+        "schemas": [{"type": "struct", "schema-id": 0, "fields": fields}],
+        "default-spec-id": 0,
+        "partition-specs": [{"spec-id": 0, "fields": spec_fields}],
+        "last-partition-id": last_partition_id,
+        "default-sort-order-id": 0,
+        "sort-orders": [{"order-id": 0, "fields": []}],
+        "properties": {NAME_MAPPING_PROPERTY: mapping},
+        "refs": {},
+        "snapshots": [],
+        "snapshot-log": [],
+        "metadata-log": [],
+    })
+}
+
+pub fn current_schema(metadata: &Value) -> Result<&Value> {
+    let schema_id = id_of(metadata, "current-schema-id")?;
+    metadata["schemas"]
+        .as_array()
+        .and_then(|s| {
+            s.iter()
+                .find(|s| s["schema-id"].as_i64() == Some(schema_id))
+        })
+        .ok_or_else(|| anyhow!("invalid iceberg metadata: missing current schema"))
+}
+
+pub fn default_spec(metadata: &Value) -> Result<&Value> {
+    let spec_id = id_of(metadata, "default-spec-id")?;
+    metadata["partition-specs"]
+        .as_array()
+        .and_then(|s| s.iter().find(|s| s["spec-id"].as_i64() == Some(spec_id)))
+        .ok_or_else(|| anyhow!("invalid iceberg metadata: missing default partition spec"))
+}
+
+pub fn current_snapshot(metadata: &Value) -> Option<&Value> {
+    let snapshot_id = metadata["current-snapshot-id"].as_i64()?;
+    metadata["snapshots"]
+        .as_array()?
+        .iter()
+        .find(|s| s["snapshot-id"].as_i64() == Some(snapshot_id))
+}
+
+/// Returns the table property that records the latest epoch committed by `app_id`. This is kept
+/// in the properties rather than read from snapshot summaries, as old snapshots may be expired.
+pub fn committed_epoch_property(app_id: &str) -> String {
+    format!("arroyo.committed-epoch.{}", app_id)
+}
+
+/// Returns the latest epoch committed to the table by `app_id`
+pub fn committed_epoch(metadata: &Value, app_id: &str) -> Option<i64> {
+    metadata["properties"][committed_epoch_property(app_id)]
+        .as_str()?
+        .parse()
+        .ok()
+}
+
+/// A change to the table metadata, which is sent to REST catalogs as-is and applied locally for
+/// hadoop catalogs
+#[derive(Debug, Clone)]
+pub enum TableUpdate {
+    AddSchema { schema: Value, last_column_id: i64 },
+    SetCurrentSchema { schema_id: i64 },
+    SetProperties(Map<String, Value>),
+    AddSnapshot(Value),
+    SetMainRef { snapshot_id: i64 },
+}
+
+impl TableUpdate {
+    pub fn to_rest(&self) -> Value {
+        match self {
+            TableUpdate::AddSchema {
+                schema,
+                last_column_id,
+            } => json!({
+                "action": "add-schema",
+                "schema": schema,
+                "last-column-id": last_column_id,
+            }),
+            // -1 refers to the schema added in this commit
+            TableUpdate::SetCurrentSchema { .. } => json!({
+                "action": "set-current-schema",
+                "schema-id": -1,
+            }),
+            TableUpdate::SetProperties(properties) => json!({
+                "action": "set-properties",
+                "updates": properties,
+            }),
+            TableUpdate::AddSnapshot(snapshot) => json!({
+                "action": "add-snapshot",
+                "snapshot": snapshot,
+            }),
+            TableUpdate::SetMainRef { snapshot_id } => json!({
+                "action": "set-snapshot-ref",
+                "ref-name": "main",
+                "type": "branch",
+                "snapshot-id": snapshot_id,
+            }),
+        }
+    }
+
+    pub fn apply(&self, metadata: &mut Value) -> Result<()> {
+        match self {
+            TableUpdate::AddSchema {
+                schema,
+                last_column_id,
+            } => {
+                push(metadata, "schemas", schema.clone())?;
+                metadata["last-column-id"] = json!(last_column_id);
+            }
+            TableUpdate::SetCurrentSchema { schema_id } => {
+                metadata["current-schema-id"] = json!(schema_id);
+            }
+            TableUpdate::SetProperties(properties) => {
+                if !metadata["properties"].is_object() {
+                    metadata["properties"] = json!({});
+                }
+                let existing = metadata["properties"].as_object_mut().unwrap();
+                for (k, v) in properties {
+                    existing.insert(k.clone(), v.clone());
+                }
+            }
+            TableUpdate::AddSnapshot(snapshot) => {
+                push(metadata, "snapshots", snapshot.clone())?;
+                metadata["last-sequence-number"] = snapshot["sequence-number"].clone();
+                metadata["last-updated-ms"] = snapshot["timestamp-ms"].clone();
+            }
+            TableUpdate::SetMainRef { snapshot_id } => {
+                let timestamp = metadata["last-updated-ms"].clone();
+                if !metadata["refs"].is_object() {
+                    metadata["refs"] = json!({});
+                }
+                metadata["refs"]["main"] = json!({
+                    "snapshot-id": snapshot_id,
+                    "type": "branch",
+                });
+                metadata["current-snapshot-id"] = json!(snapshot_id);
+                push(
+                    metadata,
+                    "snapshot-log",
+                    json!({"timestamp-ms": timestamp, "snapshot-id": snapshot_id}),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn push(metadata: &mut Value, key: &str, value: Value) -> Result<()> {
+    if metadata[key].is_null() {
+        metadata[key] = json!([]);
+    }
+    metadata[key]
+        .as_array_mut()
+        .ok_or_else(|| anyhow!("invalid iceberg metadata: {} is not a list", key))?
+        .push(value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Array, StructArray, TimestampMicrosecondArray, TimestampMillisecondArray};
+    use arrow::datatypes::{DataType, Field, Fields, Schema, TimeUnit};
+    use arrow::record_batch::RecordBatch;
+    use serde_json::json;
+
+    use super::{convert_fields, iceberg_arrow_schema, name_mapping, to_iceberg_batch};
+
+    #[test]
+    fn test_schema_evolution() {
+        let fields = Fields::from(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+        let mut last_column_id = 0;
+        let (schema, _) = convert_fields(&fields, None, &mut last_column_id).unwrap();
+        assert_eq!(
+            schema,
+            vec![
+                json!({"id": 1, "name": "id", "required": true, "type": "long"}),
+                json!({"id": 2, "name": "name", "required": false, "type": "string"}),
+            ]
+        );
+
+        // unchanged
+        let (_, changed) = convert_fields(&fields, Some(&schema), &mut last_column_id).unwrap();
+        assert!(!changed);
+
+        // added columns are optional and get new ids, even if they're not nullable
+        let evolved = Fields::from(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("count", DataType::Int32, false),
+        ]);
+        let (new_schema, changed) =
+            convert_fields(&evolved, Some(&schema), &mut last_column_id).unwrap();
+        assert!(changed);
+        assert_eq!(
+            new_schema[2],
+            json!({"id": 3, "name": "count", "required": false, "type": "int"})
+        );
+        assert_eq!(
+            name_mapping(&new_schema)[2],
+            json!({"field-id": 3, "names": ["count"]})
+        );
+
+        // incompatible types are rejected
+        let incompatible = Fields::from(vec![Field::new("id", DataType::Utf8, false)]);
+        assert!(convert_fields(&incompatible, Some(&schema), &mut last_column_id).is_err());
+    }
+
+    #[test]
+    fn test_timestamps_written_as_micros() {
+        let inner = Fields::from(vec![Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        )]);
+        let schema = Schema::new(vec![
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("nested", DataType::Struct(inner.clone()), true),
+        ]);
+
+        let iceberg_schema = iceberg_arrow_schema(&schema);
+        assert_eq!(
+            iceberg_schema.field(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, None)
+        );
+
+        let mut last_column_id = 0;
+        let (fields, _) =
+            convert_fields(iceberg_schema.fields(), None, &mut last_column_id).unwrap();
+        assert_eq!(fields[0]["type"], json!("timestamp"));
+        // millisecond timestamps can't be declared as iceberg timestamps
+        assert!(convert_fields(schema.fields(), None, &mut 0).is_err());
+
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(vec![1_000, 2_500])),
+                Arc::new(StructArray::new(
+                    inner,
+                    vec![Arc::new(TimestampMillisecondArray::from(vec![
+                        Some(3),
+                        None,
+                    ]))],
+                    None,
+                )),
+            ],
+        )
+        .unwrap();
+
+        let converted = to_iceberg_batch(&batch, iceberg_schema).unwrap();
+        let ts = converted
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(ts.values(), &[1_000_000, 2_500_000]);
+
+        let nested = converted
+            .column(1)
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap()
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap()
+            .clone();
+        assert_eq!(nested.value(0), 3_000);
+        assert!(nested.is_null(1));
+    }
+}
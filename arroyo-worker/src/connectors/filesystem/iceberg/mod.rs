@@ -0,0 +1,618 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use apache_avro::types::Value as AvroValue;
+use arrow::datatypes::SchemaRef;
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::{Data, Key, RecordBatchBuilder};
+use async_trait::async_trait;
+use bytes::Bytes;
+use object_store::{path::Path, ObjectStore};
+use parquet::file::footer::{decode_footer, decode_metadata};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tracing::info;
+
+use crate::connectors::two_phase_committer::TwoPhaseCommitterOperator;
+
+use self::catalog::IcebergCatalog;
+use self::manifest::{DataFile, ManifestFile, PartitionColumn};
+use self::metadata::{
+    committed_epoch, committed_epoch_property, convert_fields, current_schema, current_snapshot,
+    default_spec, id_of, name_mapping, new_table_metadata, partition_result_type,
+    partition_spec_fields, TableUpdate, NAME_MAPPING_PROPERTY,
+};
+
+use super::partitioning::{
+    parse_partition_spec, PartitionField, PartitionPath, PartitionValue, Transform,
+};
+use super::table_format::{TableFormat, TableFormatSink};
+use super::{Catalog, FileSystemTable, FileToFinish, IcebergSettings, ParquetFileSystemSink};
+
+mod catalog;
+mod manifest;
+mod metadata;
+
+pub use self::metadata::{iceberg_arrow_schema, to_iceberg_batch};
+
+/// Writes parquet files like the [`ParquetFileSystemSink`] into the data directory of an Iceberg
+/// table, then appends them to the table in a new snapshot through its catalog. The latest epoch
+/// committed by each subtask is recorded in the table properties.
+pub type IcebergSink<K, T, R> = TableFormatSink<K, T, R, IcebergTable>;
+
+impl<K: Key, T: Data + Sync + Serialize, R: RecordBatchBuilder<Data = T> + 'static>
+    IcebergSink<K, T, R>
+{
+    pub fn from_config(config_str: &str) -> TwoPhaseCommitterOperator<K, T, Self> {
+        let config: OperatorConfig =
+            serde_json::from_str(config_str).expect("Invalid config for IcebergSink");
+        let table: FileSystemTable =
+            serde_json::from_value(config.table).expect("Invalid table config for IcebergSink");
+        let iceberg = table
+            .iceberg
+            .clone()
+            .expect("IcebergSink requires iceberg settings");
+
+        let location = table_location(&iceberg);
+        let (object_store, location_path) =
+            object_store::parse_url(&url::Url::parse(&location).expect("invalid table location"))
+                .expect("unsupported table location");
+        let object_store: Arc<dyn ObjectStore> = Arc::from(object_store);
+
+        let catalog = match &iceberg.catalog {
+            Catalog::HadoopCatalog { .. } => IcebergCatalog::Hadoop {
+                object_store: object_store.clone(),
+                table_path: location_path.clone(),
+            },
+            Catalog::RestCatalog {
+                rest_url, token, ..
+            } => IcebergCatalog::Rest {
+                client: reqwest::Client::new(),
+                url: rest_url.clone(),
+                token: token.clone(),
+                namespace: iceberg.namespace.clone(),
+                table_name: iceberg.table_name.clone(),
+            },
+        };
+
+        let partition_fields =
+            parse_partition_spec(iceberg.partition_spec.as_deref().unwrap_or_default())
+                .expect("invalid iceberg partition spec");
+
+        TwoPhaseCommitterOperator::new(Self::new(
+            ParquetFileSystemSink::new(object_store.clone(), location_path.child("data"), table),
+            IcebergTable {
+                catalog,
+                object_store,
+                location,
+                location_path,
+                schema: iceberg_arrow_schema(&R::default().schema()),
+                partition_fields,
+            },
+        ))
+    }
+}
+
+/// Returns the URI of the table's root directory
+fn table_location(iceberg: &IcebergSettings) -> String {
+    match &iceberg.catalog {
+        Catalog::HadoopCatalog { warehouse } => {
+            let warehouse = if warehouse.contains("://") {
+                warehouse.trim_end_matches('/').to_string()
+            } else {
+                format!("file://{}", warehouse.trim_end_matches('/'))
+            };
+            format!(
+                "{}/{}/{}",
+                warehouse,
+                iceberg.namespace.replace('.', "/"),
+                iceberg.table_name
+            )
+        }
+        Catalog::RestCatalog { location, .. } => location.trim_end_matches('/').to_string(),
+    }
+}
+
+/// A data file written by the sink, before it's been added to a manifest
+struct WrittenFile {
+    path: String,
+    // the partition directories, like `event_time_day=2023-10-01`
+    partition: HashMap<String, String>,
+    record_count: i64,
+    file_size: i64,
+}
+
+/// Appends data files to an Iceberg table, creating it on the first commit and evolving its
+/// schema when the query adds columns
+pub struct IcebergTable {
+    catalog: IcebergCatalog,
+    object_store: Arc<dyn ObjectStore>,
+    location: String,
+    location_path: Path,
+    schema: SchemaRef,
+    partition_fields: Vec<PartitionField>,
+}
+
+#[async_trait]
+impl TableFormat for IcebergTable {
+    fn name(&self) -> String {
+        "iceberg_sink".to_string()
+    }
+
+    async fn commit(&mut self, app_id: &str, epoch: u32, files: &[FileToFinish]) -> Result<()> {
+        let mut written = vec![];
+        for file in files {
+            written.push(self.written_file(file).await?);
+        }
+
+        loop {
+            let Some(table) = self.catalog.load_table().await? else {
+                self.create_table().await?;
+                continue;
+            };
+
+            if committed_epoch(&table.metadata, app_id).unwrap_or(-1) >= epoch as i64 {
+                info!(
+                    "epoch {} has already been committed to {} by {}",
+                    epoch,
+                    self.catalog.name(),
+                    app_id
+                );
+                return Ok(());
+            }
+
+            let metadata = &table.metadata;
+            let mut updates = vec![];
+
+            // add any new columns to the schema
+            let mut schema = current_schema(metadata)?.clone();
+            let mut last_column_id = id_of(metadata, "last-column-id")?;
+            let (fields, changed) = convert_fields(
+                self.schema.fields(),
+                schema["fields"].as_array().map(|f| f.as_slice()),
+                &mut last_column_id,
+            )?;
+            if changed {
+                let schema_id = metadata["schemas"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|s| s["schema-id"].as_i64())
+                    .max()
+                    .unwrap_or(-1)
+                    + 1;
+                schema = json!({"type": "struct", "schema-id": schema_id, "fields": fields});
+                info!("evolving schema of {} to {}", self.catalog.name(), schema);
+                updates.push(TableUpdate::AddSchema {
+                    schema: schema.clone(),
+                    last_column_id,
+                });
+                updates.push(TableUpdate::SetCurrentSchema { schema_id });
+            }
+
+            // the epoch is recorded in the same commit as the files it covers
+            let mut properties = Map::new();
+            properties.insert(
+                committed_epoch_property(app_id),
+                Value::String(epoch.to_string()),
+            );
+            let schema_fields = schema["fields"].as_array().cloned().unwrap_or_default();
+            let mapping = name_mapping(&schema_fields).to_string();
+            if metadata["properties"][NAME_MAPPING_PROPERTY].as_str() != Some(&mapping) {
+                properties.insert(NAME_MAPPING_PROPERTY.to_string(), Value::String(mapping));
+            }
+            updates.push(TableUpdate::SetProperties(properties));
+
+            let spec = default_spec(metadata)?.clone();
+            let partition_columns = self.partition_columns(&spec, &schema_fields)?;
+
+            let snapshot_id = (uuid::Uuid::new_v4().as_u64_pair().0 & i64::MAX as u64) as i64;
+            let sequence_number = metadata["last-sequence-number"].as_i64().unwrap_or(0) + 1;
+            let parent = current_snapshot(metadata);
+
+            let data_files = written
+                .iter()
+                .map(|f| {
+                    Ok(DataFile {
+                        path: f.path.clone(),
+                        partition: partition_columns
+                            .iter()
+                            .map(|(_, field, source_type)| {
+                                partition_value(field, source_type, f.partition.get(&field.name))
+                            })
+                            .collect::<Result<_>>()?,
+                        record_count: f.record_count,
+                        file_size: f.file_size,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let columns: Vec<_> = partition_columns.into_iter().map(|(c, _, _)| c).collect();
+            let manifest =
+                manifest::write_manifest(&schema, &spec, &columns, snapshot_id, &data_files)?;
+            let manifest_name = format!("{}-m0.avro", uuid::Uuid::new_v4());
+            let manifest_length = manifest.len() as i64;
+            self.put_metadata_file(&manifest_name, manifest).await?;
+
+            let mut manifests = match parent.and_then(|p| p["manifest-list"].as_str()) {
+                Some(list) => manifest::read_manifest_list(&self.read_file(list).await?)?,
+                None => vec![],
+            };
+            manifests.push(ManifestFile {
+                path: format!("{}/metadata/{}", self.location, manifest_name),
+                length: manifest_length,
+                spec_id: id_of(&spec, "spec-id")? as i32,
+                sequence_number,
+                min_sequence_number: sequence_number,
+                snapshot_id,
+                added_files: data_files.len() as i32,
+                existing_files: 0,
+                deleted_files: 0,
+                added_rows: data_files.iter().map(|f| f.record_count).sum(),
+                existing_rows: 0,
+                deleted_rows: 0,
+            });
+
+            let parent_snapshot_id = parent.and_then(|p| p["snapshot-id"].as_i64());
+            let manifest_list = manifest::write_manifest_list(
+                snapshot_id,
+                parent_snapshot_id,
+                sequence_number,
+                &manifests,
+            )?;
+            let manifest_list_name =
+                format!("snap-{}-1-{}.avro", snapshot_id, uuid::Uuid::new_v4());
+            self.put_metadata_file(&manifest_list_name, manifest_list)
+                .await?;
+
+            let mut snapshot = json!({
+                "snapshot-id": snapshot_id,
+                "sequence-number": sequence_number,
+                "timestamp-ms": now_millis(),
+                "manifest-list": format!("{}/metadata/{}", self.location, manifest_list_name),
+                "schema-id": schema["schema-id"],
+                "summary": {
+                    "operation": "append",
+                    "added-data-files": data_files.len().to_string(),
+                    "added-records": data_files.iter().map(|f| f.record_count).sum::<i64>().to_string(),
+                    "added-files-size": data_files.iter().map(|f| f.file_size).sum::<i64>().to_string(),
+                    "arroyo.app-id": app_id,
+                    "arroyo.epoch": epoch.to_string(),
+                },
+            });
+            if let Some(parent_snapshot_id) = parent_snapshot_id {
+                snapshot["parent-snapshot-id"] = json!(parent_snapshot_id);
+            }
+
+            updates.push(TableUpdate::AddSnapshot(snapshot));
+            updates.push(TableUpdate::SetMainRef { snapshot_id });
+
+            if self.catalog.commit(&table, &updates).await? {
+                info!(
+                    "committed {} files to {} in snapshot {}",
+                    data_files.len(),
+                    self.catalog.name(),
+                    snapshot_id
+                );
+                return Ok(());
+            }
+            // another writer committed first, so reload the table and try again
+        }
+    }
+}
+
+impl IcebergTable {
+    async fn create_table(&self) -> Result<()> {
+        let mut last_column_id = 0;
+        let (fields, _) = convert_fields(self.schema.fields(), None, &mut last_column_id)?;
+        let spec_fields = partition_spec_fields(&self.partition_fields, &fields)?;
+        let metadata = new_table_metadata(
+            &self.location,
+            fields,
+            last_column_id,
+            spec_fields,
+            now_millis(),
+        );
+
+        if self.catalog.create_table(&metadata).await? {
+            info!("created iceberg table {}", self.catalog.name());
+        }
+        Ok(())
+    }
+
+    /// Matches the table's partition spec against the configured one, returning the manifest
+    /// partition columns along with the transform and source column type of each
+    fn partition_columns(
+        &self,
+        spec: &Value,
+        schema_fields: &[Value],
+    ) -> Result<Vec<(PartitionColumn, PartitionField, Value)>> {
+        let spec_fields = spec["fields"].as_array().cloned().unwrap_or_default();
+        let mut columns = vec![];
+        for spec_field in &spec_fields {
+            let source = schema_fields
+                .iter()
+                .find(|f| f["id"] == spec_field["source-id"])
+                .ok_or_else(|| {
+                    anyhow!(
+                        "partition field {} has an unknown source column",
+                        spec_field
+                    )
+                })?;
+            let transform = spec_field["transform"].as_str().unwrap_or_default();
+            let source_name = source["name"].as_str().unwrap_or_default();
+
+            let Some(field) = self
+                .partition_fields
+                .get(columns.len())
+                .filter(|f| f.source == source_name && f.transform.name() == transform)
+            else {
+                break;
+            };
+
+            let avro_type = match (field.transform, &source["type"]) {
+                (Transform::Day, _) => json!({"type": "int", "logicalType": "date"}),
+                (Transform::Hour | Transform::Bucket(_), _) => json!("int"),
+                (Transform::Identity, Value::String(t))
+                    if ["string", "int", "long", "boolean"].contains(&t.as_str()) =>
+                {
+                    json!(t)
+                }
+                (Transform::Identity, t) => {
+                    bail!(
+                        "identity partitions on columns of type {} are not supported",
+                        t
+                    )
+                }
+            };
+
+            columns.push((
+                PartitionColumn {
+                    name: spec_field["name"].as_str().unwrap_or_default().to_string(),
+                    field_id: id_of(spec_field, "field-id")?,
+                    avro_type,
+                },
+                field.clone(),
+                partition_result_type(field.transform, &source["type"]),
+            ));
+        }
+
+        if columns.len() != spec_fields.len() || columns.len() != self.partition_fields.len() {
+            bail!(
+                "the partition spec of iceberg table {} ({}) does not match the configured spec ({})",
+                self.catalog.name(),
+                spec_fields
+                    .iter()
+                    .map(|f| format!("{}", f))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                self.partition_fields
+                    .iter()
+                    .map(|f| format!("{}({})", f.transform.name(), f.source))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        Ok(columns)
+    }
+
+    async fn written_file(&self, file: &FileToFinish) -> Result<WrittenFile> {
+        let path = Path::parse(&file.filename)?;
+        let size = self.object_store.head(&path).await?.size;
+        let record_count = parquet_row_count(self.object_store.as_ref(), &path, size).await?;
+
+        let Some(relative) = file
+            .filename
+            .strip_prefix(&format!("{}/", self.location_path))
+        else {
+            bail!(
+                "file {} is not in iceberg table {}",
+                file.filename,
+                self.location
+            );
+        };
+
+        // files are written to data/<partition directories>/<file>
+        let mut directories: Vec<&str> = relative.split('/').collect();
+        directories.pop();
+        let partition = directories
+            .into_iter()
+            .skip(1)
+            .filter_map(|d| d.split_once('='))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Ok(WrittenFile {
+            path: format!("{}/{}", self.location, relative),
+            partition,
+            record_count,
+            file_size: size as i64,
+        })
+    }
+
+    async fn put_metadata_file(&self, name: &str, bytes: Vec<u8>) -> Result<()> {
+        let path = self.location_path.child("metadata").child(name);
+        self.object_store.put(&path, bytes.into()).await?;
+        Ok(())
+    }
+
+    /// Reads a file referenced by the table metadata
+    async fn read_file(&self, location: &str) -> Result<Bytes> {
+        if let Some(relative) = location.strip_prefix(&format!("{}/", self.location)) {
+            let path = Path::parse(format!("{}/{}", self.location_path, relative))?;
+            return Ok(self.object_store.get(&path).await?.bytes().await?);
+        }
+
+        // files outside of our location, for example if the table was created elsewhere
+        let (object_store, path) = object_store::parse_url(&url::Url::parse(location)?)?;
+        Ok(object_store.get(&path).await?.bytes().await?)
+    }
+}
+
+/// Converts the partition directory value of a file to the value stored in manifests
+fn partition_value(
+    field: &PartitionField,
+    source_type: &Value,
+    path_value: Option<&String>,
+) -> Result<AvroValue> {
+    let Some(path_value) = path_value else {
+        bail!("file is missing partition directory for {}", field.name);
+    };
+
+    Ok(match field.transform.parse_path_value(path_value) {
+        None => AvroValue::Null,
+        Some(PartitionValue::Int(i)) if field.transform == Transform::Day => AvroValue::Date(i),
+        Some(PartitionValue::Int(i)) => AvroValue::Int(i),
+        Some(PartitionValue::String(s)) => match source_type.as_str() {
+            Some("int") => AvroValue::Int(s.parse()?),
+            Some("long") => AvroValue::Long(s.parse()?),
+            Some("boolean") => AvroValue::Boolean(s.parse()?),
+            _ => AvroValue::String(s),
+        },
+    })
+}
+
+/// Reads the number of rows from the footer of a parquet file
+async fn parquet_row_count(
+    object_store: &dyn ObjectStore,
+    path: &Path,
+    size: usize,
+) -> Result<i64> {
+    if size < 8 {
+        bail!("{} is not a parquet file", path);
+    }
+    let footer: [u8; 8] = object_store
+        .get_range(path, size - 8..size)
+        .await?
+        .as_ref()
+        .try_into()?;
+    let metadata_length = decode_footer(&footer)?;
+    let Some(start) = size.checked_sub(8 + metadata_length) else {
+        bail!("{} has an invalid parquet footer", path);
+    };
+    let metadata = decode_metadata(&object_store.get_range(path, start..size - 8).await?)?;
+    Ok(metadata.file_metadata().num_rows())
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use object_store::{local::LocalFileSystem, path::Path, ObjectStore};
+    use parquet::arrow::ArrowWriter;
+    use serde_json::Value;
+
+    use super::catalog::IcebergCatalog;
+    use super::{FileToFinish, IcebergTable, TableFormat};
+
+    async fn write_parquet(
+        store: &LocalFileSystem,
+        path: &Path,
+        batch: &RecordBatch,
+    ) -> FileToFinish {
+        let mut bytes = vec![];
+        let mut writer = ArrowWriter::try_new(&mut bytes, batch.schema(), None).unwrap();
+        writer.write(batch).unwrap();
+        writer.close().unwrap();
+        store.put(path, bytes.into()).await.unwrap();
+
+        FileToFinish {
+            filename: path.to_string(),
+            multi_part_upload_id: "".to_string(),
+            completed_parts: vec!["0".to_string()],
+        }
+    }
+
+    async fn load_metadata(table: &IcebergTable) -> Value {
+        table.catalog.load_table().await.unwrap().unwrap().metadata
+    }
+
+    #[tokio::test]
+    async fn test_hadoop_table_commits() {
+        let now = arroyo_types::to_nanos(std::time::SystemTime::now());
+        let dir = format!("/tmp/arroyo-testing/iceberg-tests/{}", now);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let store = Arc::new(LocalFileSystem::new());
+        let location_path = Path::from(dir.as_str());
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+
+        let mut table = IcebergTable {
+            catalog: IcebergCatalog::Hadoop {
+                object_store: store.clone(),
+                table_path: location_path.clone(),
+            },
+            object_store: store.clone(),
+            location: format!("file://{}", dir),
+            location_path: location_path.clone(),
+            schema: schema.clone(),
+            partition_fields: vec![],
+        };
+
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![1, 2]))])
+                .unwrap();
+        let file = write_parquet(
+            &store,
+            &location_path.child("data").child("0.parquet"),
+            &batch,
+        )
+        .await;
+
+        table.commit("app-0", 1, &[file.clone()]).await.unwrap();
+        let metadata = load_metadata(&table).await;
+        assert_eq!(metadata["snapshots"].as_array().unwrap().len(), 1);
+        assert_eq!(metadata["snapshots"][0]["summary"]["added-records"], "2");
+        assert_eq!(metadata["properties"]["arroyo.committed-epoch.app-0"], "1");
+        assert_eq!(metadata["schemas"][0]["fields"][0]["name"], "id");
+
+        // replaying the epoch is a no-op
+        table.commit("app-0", 1, &[file]).await.unwrap();
+        let metadata = load_metadata(&table).await;
+        assert_eq!(metadata["snapshots"].as_array().unwrap().len(), 1);
+
+        // adding a nullable column evolves the schema
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        table.schema = schema.clone();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![3])),
+                Arc::new(StringArray::from(vec![Some("c")])),
+            ],
+        )
+        .unwrap();
+        let file = write_parquet(
+            &store,
+            &location_path.child("data").child("1.parquet"),
+            &batch,
+        )
+        .await;
+        table.commit("app-0", 2, &[file]).await.unwrap();
+
+        let metadata = load_metadata(&table).await;
+        assert_eq!(metadata["snapshots"].as_array().unwrap().len(), 2);
+        assert_eq!(metadata["current-schema-id"], 1);
+        assert_eq!(metadata["schemas"][1]["fields"][1]["name"], "name");
+        assert_eq!(metadata["schemas"][1]["fields"][1]["required"], false);
+        assert_eq!(metadata["last-sequence-number"], 2);
+    }
+}
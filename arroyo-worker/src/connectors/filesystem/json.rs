@@ -1,13 +1,51 @@
 use std::{fs::File, io::Write, marker::PhantomData};
 
+use anyhow::Result;
 use arroyo_types::Data;
+use flate2::write::GzEncoder;
 use serde::Serialize;
 
 use super::{
     local::{CurrentFileRecovery, LocalWriter},
-    BatchBufferingWriter, BatchBuilder, FileSettings,
+    BatchBufferingWriter, BatchBuilder, FileSettings, FileSystemTable, FormatSettings,
+    JsonCompression,
 };
 
+fn json_compression(config: &FileSystemTable) -> JsonCompression {
+    if let Some(FormatSettings::Json {
+        compression: Some(compression),
+    }) = config.format_settings
+    {
+        compression
+    } else {
+        JsonCompression::None
+    }
+}
+
+fn json_suffix(compression: JsonCompression) -> String {
+    match compression {
+        JsonCompression::None => "json",
+        JsonCompression::Gzip => "json.gz",
+        JsonCompression::Zstd => "json.zst",
+    }
+    .to_string()
+}
+
+/// Compresses a chunk of lines as a complete gzip member or zstd frame. Readers decode
+/// concatenated members (or frames) as a single stream, so every part of a file can be compressed
+/// on its own and a file truncated to the end of a chunk is still valid.
+fn compress(compression: JsonCompression, data: &[u8]) -> Vec<u8> {
+    match compression {
+        JsonCompression::None => data.to_vec(),
+        JsonCompression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        }
+        JsonCompression::Zstd => zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL).unwrap(),
+    }
+}
+
 pub struct PassThrough<D: Data> {
     _phantom: PhantomData<D>,
 }
@@ -38,6 +76,7 @@ impl<D: Data> BatchBuilder for PassThrough<D> {
 
 pub struct JsonWriter<D: Data + Serialize> {
     current_buffer: Vec<u8>,
+    compression: JsonCompression,
     target_part_size: usize,
     phantom: PhantomData<D>,
 }
@@ -57,22 +96,22 @@ impl<D: Data + Serialize> BatchBufferingWriter for JsonWriter<D> {
         };
         Self {
             current_buffer: Vec::new(),
+            compression: json_compression(config),
             target_part_size,
             phantom: PhantomData,
         }
     }
-    fn suffix() -> String {
-        "json".to_string()
+    fn suffix(config: &FileSystemTable) -> String {
+        json_suffix(json_compression(config))
     }
 
-    fn add_batch_data(&mut self, data: Self::BatchData) -> Option<Vec<u8>> {
-        self.current_buffer
-            .extend(serde_json::to_vec(&data).unwrap());
+    fn add_batch_data(&mut self, data: Self::BatchData) -> Result<Option<Vec<u8>>> {
+        serde_json::to_writer(&mut self.current_buffer, &data)?;
         self.current_buffer.extend(b"\n");
         if self.buffer_length() > self.target_part_size {
-            Some(self.evict_current_buffer())
+            Ok(Some(self.evict_current_buffer()))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -83,27 +122,27 @@ impl<D: Data + Serialize> BatchBufferingWriter for JsonWriter<D> {
     fn evict_current_buffer(&mut self) -> Vec<u8> {
         // take
         let result = std::mem::take(&mut self.current_buffer);
-        result
+        compress(self.compression, &result)
     }
 
     fn get_trailing_bytes_for_checkpoint(&mut self) -> Option<Vec<u8>> {
         if self.current_buffer.is_empty() {
             None
         } else {
-            Some(self.current_buffer.clone())
+            Some(compress(self.compression, &self.current_buffer))
         }
     }
 
-    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Option<Vec<u8>> {
+    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Result<Option<Vec<u8>>> {
         if let Some(final_batch) = final_batch {
-            if let Some(final_batch) = self.add_batch_data(final_batch) {
-                return Some(final_batch);
+            if let Some(final_batch) = self.add_batch_data(final_batch)? {
+                return Ok(Some(final_batch));
             }
         }
         if self.current_buffer.is_empty() {
-            None
+            Ok(None)
         } else {
-            Some(self.evict_current_buffer())
+            Ok(Some(self.evict_current_buffer()))
         }
     }
 }
@@ -112,34 +151,48 @@ pub struct JsonLocalWriter {
     tmp_path: String,
     final_path: String,
     file: File,
+    compression: JsonCompression,
+    // lines that haven't been compressed yet
+    pending: Vec<u8>,
 }
 
 impl<D: Data + Serialize> LocalWriter<D> for JsonLocalWriter {
     fn new(
         tmp_path: String,
         final_path: String,
-        _table_properties: &super::FileSystemTable,
+        table_properties: &super::FileSystemTable,
     ) -> Self {
         let file = File::create(&tmp_path).unwrap();
         JsonLocalWriter {
             tmp_path,
             final_path,
             file,
+            compression: json_compression(table_properties),
+            pending: Vec::new(),
         }
     }
 
-    fn file_suffix() -> &'static str {
-        "json"
+    fn file_suffix(table_properties: &FileSystemTable) -> String {
+        json_suffix(json_compression(table_properties))
     }
 
     fn write(&mut self, value: D) -> anyhow::Result<()> {
-        self.file
-            .write_all(serde_json::to_vec(&value)?.as_slice())?;
-        self.file.write_all(b"\n")?;
+        if self.compression == JsonCompression::None {
+            self.file
+                .write_all(serde_json::to_vec(&value)?.as_slice())?;
+            self.file.write_all(b"\n")?;
+        } else {
+            serde_json::to_writer(&mut self.pending, &value)?;
+            self.pending.push(b'\n');
+        }
         Ok(())
     }
 
     fn sync(&mut self) -> anyhow::Result<usize> {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.file.write_all(&compress(self.compression, &pending))?;
+        }
         self.file.flush()?;
         let size = self.file.metadata()?.len() as usize;
         Ok(size)
@@ -167,3 +220,75 @@ impl<D: Data + Serialize> LocalWriter<D> for JsonLocalWriter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+
+    use crate::connectors::filesystem::{
+        BatchBufferingWriter, FileSettings, FileSystemTable, FormatSettings, JsonCompression,
+    };
+
+    use super::JsonWriter;
+
+    #[derive(Clone, Debug, bincode::Encode, bincode::Decode, PartialEq, serde::Serialize)]
+    struct TestRecord {
+        id: i64,
+    }
+
+    /// Writes records into several separately compressed parts, returning the whole file
+    fn write_file(compression: JsonCompression) -> Vec<u8> {
+        let table = FileSystemTable {
+            source: None,
+            write_target: None,
+            file_settings: Some(FileSettings {
+                inactivity_rollover_seconds: None,
+                max_parts: None,
+                rollover_seconds: None,
+                target_file_size: None,
+                target_part_size: Some(100),
+            }),
+            format_settings: Some(FormatSettings::Json {
+                compression: Some(compression),
+            }),
+            partitioning: None,
+            iceberg: None,
+        };
+
+        let mut writer = JsonWriter::<TestRecord>::new(&table);
+        let mut file = vec![];
+        let mut parts = 0;
+        for id in 0..100 {
+            if let Some(part) = writer.add_batch_data(TestRecord { id }).unwrap() {
+                file.extend(part);
+                parts += 1;
+            }
+        }
+        assert!(parts > 1);
+        file.extend(writer.close(None).unwrap().unwrap());
+        file
+    }
+
+    fn expected_lines() -> String {
+        (0..100).map(|id| format!("{{\"id\":{}}}\n", id)).collect()
+    }
+
+    #[test]
+    fn test_gzip_parts_form_a_file() {
+        let file = write_file(JsonCompression::Gzip);
+        let mut lines = String::new();
+        MultiGzDecoder::new(&file[..])
+            .read_to_string(&mut lines)
+            .unwrap();
+        assert_eq!(lines, expected_lines());
+    }
+
+    #[test]
+    fn test_zstd_parts_form_a_file() {
+        let file = write_file(JsonCompression::Zstd);
+        let lines = String::from_utf8(zstd::decode_all(&file[..]).unwrap()).unwrap();
+        assert_eq!(lines, expected_lines());
+    }
+}
@@ -5,6 +5,7 @@ use std::{
 use arroyo_types::{Data, Key, Record, TaskInfo};
 use async_trait::async_trait;
 use bincode::{Decode, Encode};
+use serde::Serialize;
 use tokio::{fs::OpenOptions, io::AsyncWriteExt};
 use tracing::info;
 
@@ -12,23 +13,31 @@ use crate::connectors::two_phase_committer::TwoPhaseCommitter;
 
 use anyhow::{bail, Result};
 
-use super::{FileSystemTable, MultiPartWriterStats, RollingPolicy};
+use super::{
+    partitioning::Partitioner, FileSystemTable, MultiPartWriterStats, RollingPolicy,
+    MAX_OPEN_WRITERS,
+};
 
 pub struct LocalFileSystemWriter<K: Key, D: Data + Sync, V: LocalWriter<D>> {
-    // writer to a local tmp file
-    writer: Option<V>,
+    // writers to local tmp files, one per partition
+    writers: HashMap<Option<String>, ActiveFile<V>>,
     tmp_dir: String,
     final_dir: String,
     next_file_index: usize,
     subtask_id: usize,
     finished_files: Vec<FilePreCommit>,
-    first_write: Option<Instant>,
-    last_write: Option<Instant>,
     rolling_policy: RollingPolicy,
+    partitioner: Option<Partitioner>,
     table_properties: FileSystemTable,
     phantom: PhantomData<(K, D)>,
 }
 
+struct ActiveFile<V> {
+    writer: V,
+    first_write: Instant,
+    last_write: Instant,
+}
+
 impl<K: Key, D: Data + Sync, V: LocalWriter<D>> LocalFileSystemWriter<K, D, V> {
     pub fn new(final_dir: String, table_properties: FileSystemTable) -> Self {
         // TODO: explore configuration options here
@@ -37,61 +46,75 @@ impl<K: Key, D: Data + Sync, V: LocalWriter<D>> LocalFileSystemWriter<K, D, V> {
         create_dir_all(&tmp_dir).unwrap();
 
         Self {
-            writer: None,
+            writers: HashMap::new(),
             tmp_dir,
             final_dir,
             next_file_index: 0,
             subtask_id: 0,
             finished_files: Vec::new(),
-            first_write: None,
-            last_write: None,
             rolling_policy: RollingPolicy::from_file_settings(
                 table_properties.file_settings.as_ref().unwrap(),
             ),
+            partitioner: Partitioner::from_table(&table_properties),
             table_properties,
             phantom: PhantomData,
         }
     }
 
-    fn should_roll(&mut self) -> bool {
-        if !self.first_write.is_some() {
-            return false;
-        }
-        if let Some(writer) = self.writer.as_mut() {
-            let bytes_written = writer.sync().unwrap();
-            let stats = MultiPartWriterStats {
-                bytes_written,
-                parts_written: 0,
-                last_write_at: self.last_write.unwrap(),
-                first_write_at: self.first_write.unwrap(),
-            };
-            self.rolling_policy.should_roll(&stats)
-        } else {
-            false
-        }
+    fn should_roll(rolling_policy: &RollingPolicy, file: &mut ActiveFile<V>) -> Result<bool> {
+        let bytes_written = file.writer.sync()?;
+        let stats = MultiPartWriterStats {
+            bytes_written,
+            parts_written: 0,
+            last_write_at: file.last_write,
+            first_write_at: file.first_write,
+        };
+        Ok(rolling_policy.should_roll(&stats))
     }
 
-    fn init_writer(&mut self) -> Result<()> {
+    fn init_writer(&mut self, partition: Option<&str>) -> Result<ActiveFile<V>> {
         let file_name = format!(
             "{:>05}-{:>03}.{}",
             self.next_file_index,
             self.subtask_id,
-            V::file_suffix()
+            V::file_suffix(&self.table_properties)
         );
-        self.writer = Some(V::new(
+        let final_dir = match partition {
+            Some(partition) => format!("{}/{}", self.final_dir, partition),
+            None => self.final_dir.clone(),
+        };
+        create_dir_all(&final_dir)?;
+        let writer = V::new(
             format!("{}/{}", self.tmp_dir, file_name),
-            format!("{}/{}", self.final_dir, file_name),
+            format!("{}/{}", final_dir, file_name),
             &self.table_properties,
-        ));
+        );
         self.next_file_index += 1;
-        self.first_write = Some(Instant::now());
+        Ok(ActiveFile {
+            writer,
+            first_write: Instant::now(),
+            last_write: Instant::now(),
+        })
+    }
+
+    fn close_least_recently_written(&mut self) -> Result<()> {
+        let Some(partition) = self
+            .writers
+            .iter()
+            .min_by_key(|(_, file)| file.last_write)
+            .map(|(partition, _)| partition.clone())
+        else {
+            return Ok(());
+        };
+        let mut file = self.writers.remove(&partition).unwrap();
+        self.finished_files.push(file.writer.close()?);
         Ok(())
     }
 }
 
 pub trait LocalWriter<T: Data>: Send + 'static {
     fn new(tmp_path: String, final_path: String, table_properties: &FileSystemTable) -> Self;
-    fn file_suffix() -> &'static str;
+    fn file_suffix(table_properties: &FileSystemTable) -> String;
     fn write(&mut self, value: T) -> Result<()>;
     // returns the total size of the file
     fn sync(&mut self) -> Result<usize>;
@@ -102,7 +125,7 @@ pub trait LocalWriter<T: Data>: Send + 'static {
 #[derive(Debug, Clone, Decode, Encode, PartialEq, PartialOrd)]
 pub struct LocalFileDataRecovery {
     next_file_index: usize,
-    current_file: Option<CurrentFileRecovery>,
+    current_files: Vec<CurrentFileRecovery>,
 }
 
 #[derive(Debug, Clone, Decode, Encode, PartialEq, PartialOrd)]
@@ -120,7 +143,7 @@ pub struct FilePreCommit {
 }
 
 #[async_trait]
-impl<K: Key, D: Data + Sync, V: LocalWriter<D> + Send + 'static> TwoPhaseCommitter<K, D>
+impl<K: Key, D: Data + Sync + Serialize, V: LocalWriter<D> + Send + 'static> TwoPhaseCommitter<K, D>
     for LocalFileSystemWriter<K, D, V>
 {
     type DataRecovery = LocalFileDataRecovery;
@@ -139,7 +162,7 @@ impl<K: Key, D: Data + Sync, V: LocalWriter<D> + Send + 'static> TwoPhaseCommitt
         let mut recovered_files = Vec::new();
         for LocalFileDataRecovery {
             next_file_index,
-            current_file,
+            current_files,
         } in data_recovery
         {
             max_file_index = max_file_index.max(next_file_index);
@@ -149,29 +172,28 @@ impl<K: Key, D: Data + Sync, V: LocalWriter<D> + Send + 'static> TwoPhaseCommitt
             if task_info.task_index > 0 {
                 continue;
             }
-            let Some(CurrentFileRecovery {
+            for CurrentFileRecovery {
                 tmp_file,
                 bytes_written,
                 suffix,
                 destination,
-            }) = current_file
-            else {
-                continue;
-            };
-            let mut file = OpenOptions::new()
-                .write(true)
-                .open(tmp_file.clone())
-                .await?;
-            file.set_len(bytes_written as u64).await?;
-            if let Some(suffix) = suffix {
-                file.write_all(&suffix).await?;
+            } in current_files
+            {
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .open(tmp_file.clone())
+                    .await?;
+                file.set_len(bytes_written as u64).await?;
+                if let Some(suffix) = suffix {
+                    file.write_all(&suffix).await?;
+                }
+                file.flush().await?;
+                file.sync_all().await?;
+                recovered_files.push(FilePreCommit {
+                    tmp_file,
+                    destination,
+                })
             }
-            file.flush().await?;
-            file.sync_all().await?;
-            recovered_files.push(FilePreCommit {
-                tmp_file,
-                destination,
-            })
         }
         self.subtask_id = task_info.task_index;
         self.finished_files = recovered_files;
@@ -180,17 +202,27 @@ impl<K: Key, D: Data + Sync, V: LocalWriter<D> + Send + 'static> TwoPhaseCommitt
     }
 
     async fn insert_record(&mut self, record: &Record<K, D>) -> Result<()> {
-        if self.first_write.is_none() {
-            self.init_writer()?;
-        };
-        self.writer.as_mut().unwrap().write(record.value.clone())?;
-        self.last_write = Some(Instant::now());
+        let partition = self
+            .partitioner
+            .as_ref()
+            .map(|p| p.partition(&record.value, record.timestamp));
+        if !self.writers.contains_key(&partition) {
+            if self.writers.len() >= MAX_OPEN_WRITERS {
+                self.close_least_recently_written()?;
+            }
+            let file = self.init_writer(partition.as_deref())?;
+            self.writers.insert(partition.clone(), file);
+        }
+        let file = self.writers.get_mut(&partition).unwrap();
+        file.writer.write(record.value.clone())?;
+        file.last_write = Instant::now();
         Ok(())
     }
 
     async fn commit(
         &mut self,
         _task_info: &TaskInfo,
+        _epoch: u32,
         pre_commit: Vec<Self::PreCommit>,
     ) -> Result<()> {
         for FilePreCommit {
@@ -220,25 +252,171 @@ impl<K: Key, D: Data + Sync, V: LocalWriter<D> + Send + 'static> TwoPhaseCommitt
         _task_info: &TaskInfo,
         stopping: bool,
     ) -> Result<(Self::DataRecovery, HashMap<String, Self::PreCommit>)> {
-        if self.should_roll() || stopping {
-            let pre_commit = self.writer.take().unwrap().close()?;
-            self.first_write = None;
-            self.last_write = None;
-            self.finished_files.push(pre_commit);
+        let mut to_roll = vec![];
+        for (partition, file) in self.writers.iter_mut() {
+            if stopping || Self::should_roll(&self.rolling_policy, file)? {
+                to_roll.push(partition.clone());
+            }
+        }
+        for partition in to_roll {
+            let mut file = self.writers.remove(&partition).unwrap();
+            self.finished_files.push(file.writer.close()?);
         }
         let mut pre_commits = HashMap::new();
         for pre_commit in self.finished_files.drain(..) {
             pre_commits.insert(pre_commit.destination.to_string(), pre_commit);
         }
+        let mut current_files = vec![];
+        for file in self.writers.values_mut() {
+            if let Some(recovery) = file.writer.checkpoint()? {
+                current_files.push(recovery);
+            }
+        }
         let data_recovery = LocalFileDataRecovery {
             next_file_index: self.next_file_index,
-            current_file: self
-                .writer
-                .as_mut()
-                .map(|writer| writer.checkpoint())
-                .transpose()?
-                .flatten(),
+            current_files,
         };
         Ok((data_recovery, pre_commits))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use arroyo_types::Record;
+    use serde::Serialize;
+
+    use crate::connectors::filesystem::json::JsonLocalWriter;
+    use crate::connectors::filesystem::{
+        FileSettings, FileSystemTable, Partitioning, MAX_OPEN_WRITERS,
+    };
+    use crate::connectors::two_phase_committer::TwoPhaseCommitter;
+
+    use super::LocalFileSystemWriter;
+
+    #[derive(Clone, Debug, bincode::Encode, bincode::Decode, PartialEq, Serialize)]
+    struct Event {
+        country: Option<String>,
+        id: i64,
+    }
+
+    fn writer(dir: &str, fields: &str) -> LocalFileSystemWriter<(), Event, JsonLocalWriter> {
+        LocalFileSystemWriter::new(
+            dir.to_string(),
+            FileSystemTable {
+                source: None,
+                write_target: None,
+                file_settings: Some(FileSettings {
+                    inactivity_rollover_seconds: None,
+                    max_parts: None,
+                    rollover_seconds: None,
+                    target_file_size: None,
+                    target_part_size: None,
+                }),
+                format_settings: None,
+                partitioning: Some(Partitioning {
+                    time_pattern: None,
+                    partition_fields: Some(fields.to_string()),
+                }),
+                iceberg: None,
+            },
+        )
+    }
+
+    fn record(country: Option<&str>, id: i64) -> Record<(), Event> {
+        Record {
+            timestamp: SystemTime::now(),
+            key: None,
+            value: Event {
+                country: country.map(|c| c.to_string()),
+                id,
+            },
+        }
+    }
+
+    fn read_ids(path: String) -> Vec<i64> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| {
+                serde_json::from_str::<serde_json::Value>(l).unwrap()["id"]
+                    .as_i64()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_commit() {
+        let now = arroyo_types::to_nanos(SystemTime::now());
+        let dir = format!("/tmp/arroyo-testing/filesystem-tests/{}", now);
+        let task_info = arroyo_types::get_test_task_info();
+
+        let mut writer = writer(&dir, "country");
+        writer.init(&task_info, vec![]).await.unwrap();
+        for record in [
+            record(Some("US"), 1),
+            record(Some("DE"), 2),
+            record(Some("US"), 3),
+            record(None, 4),
+        ] {
+            writer.insert_record(&record).await.unwrap();
+        }
+
+        let (recovery, pre_commits) = writer.checkpoint(&task_info, true).await.unwrap();
+        assert!(recovery.current_files.is_empty());
+        assert_eq!(pre_commits.len(), 3);
+
+        // nothing is visible until the checkpoint is committed
+        assert!(std::fs::read_dir(format!("{}/country=US", dir))
+            .unwrap()
+            .next()
+            .is_none());
+
+        writer
+            .commit(&task_info, 1, pre_commits.into_values().collect())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read_ids(format!("{}/country=US/00000-000.json", dir)),
+            vec![1, 3]
+        );
+        assert_eq!(
+            read_ids(format!("{}/country=DE/00001-000.json", dir)),
+            vec![2]
+        );
+        assert_eq!(
+            read_ids(format!(
+                "{}/country=__HIVE_DEFAULT_PARTITION__/00002-000.json",
+                dir
+            )),
+            vec![4]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_open_writers() {
+        let now = arroyo_types::to_nanos(SystemTime::now());
+        let dir = format!("/tmp/arroyo-testing/filesystem-tests/{}", now);
+        let task_info = arroyo_types::get_test_task_info();
+
+        let mut writer = writer(&dir, "id");
+        writer.init(&task_info, vec![]).await.unwrap();
+        for id in 0..=MAX_OPEN_WRITERS as i64 {
+            writer.insert_record(&record(None, id)).await.unwrap();
+        }
+
+        // the least recently written partition was closed to make room for the last one
+        assert_eq!(writer.writers.len(), MAX_OPEN_WRITERS);
+        assert!(!writer.writers.contains_key(&Some("id=0".to_string())));
+
+        let (recovery, pre_commits) = writer.checkpoint(&task_info, false).await.unwrap();
+        assert_eq!(recovery.current_files.len(), MAX_OPEN_WRITERS);
+        assert_eq!(
+            pre_commits.into_keys().collect::<Vec<_>>(),
+            vec![format!("{}/id=0/00000-000.json", dir)]
+        );
+    }
+}
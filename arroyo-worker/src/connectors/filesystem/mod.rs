@@ -28,15 +28,25 @@ use typify::import_types;
 import_types!(schema = "../connector-schemas/filesystem/table.json");
 
 use arroyo_types::*;
+pub mod avro;
+pub mod csv;
+pub mod delta;
+pub mod iceberg;
 pub mod json;
 pub mod local;
 pub mod parquet;
+pub mod partitioning;
 pub mod single_file;
+pub mod source;
+pub mod table_format;
 
 use self::{
+    avro::{AvroLocalWriter, AvroWriter},
+    csv::{CsvLocalWriter, CsvWriter},
     json::{JsonLocalWriter, JsonWriter, PassThrough},
     local::{LocalFileSystemWriter, LocalWriter},
     parquet::{FixedSizeRecordBatchBuilder, ParquetLocalWriter, RecordBatchBufferingWriter},
+    partitioning::Partitioner,
 };
 
 use super::two_phase_committer::{TwoPhaseCommitter, TwoPhaseCommitterOperator};
@@ -60,17 +70,31 @@ pub type ParquetFileSystemSink<K, T, R> = FileSystemSink<
 pub type JsonFileSystemSink<K, T> =
     FileSystemSink<K, T, BatchMultipartWriter<PassThrough<T>, JsonWriter<T>>>;
 
+pub type CsvFileSystemSink<K, T> =
+    FileSystemSink<K, T, BatchMultipartWriter<PassThrough<T>, CsvWriter<T>>>;
+
+pub type AvroFileSystemSink<K, T> =
+    FileSystemSink<K, T, BatchMultipartWriter<PassThrough<T>, AvroWriter<T>>>;
+
 pub type LocalParquetFileSystemSink<K, T, R> = LocalFileSystemWriter<K, T, ParquetLocalWriter<R>>;
 
 pub type LocalJsonFileSystemSink<K, T> = LocalFileSystemWriter<K, T, JsonLocalWriter>;
 
-impl<K: Key, T: Data + Sync, V: LocalWriter<T>> LocalFileSystemWriter<K, T, V> {
+pub type LocalCsvFileSystemSink<K, T> = LocalFileSystemWriter<K, T, CsvLocalWriter>;
+
+pub type LocalAvroFileSystemSink<K, T> = LocalFileSystemWriter<K, T, AvroLocalWriter>;
+
+impl<K: Key, T: Data + Sync + Serialize, V: LocalWriter<T>> LocalFileSystemWriter<K, T, V> {
     pub fn from_config(config_str: &str) -> TwoPhaseCommitterOperator<K, T, Self> {
         let config: OperatorConfig =
             serde_json::from_str(config_str).expect("Invalid config for FileSystemSink");
         let table: FileSystemTable =
             serde_json::from_value(config.table).expect("Invalid table config for FileSystemSink");
-        let (_object_store, path): (Box<dyn ObjectStore>, Path) = match table.write_target.clone() {
+        let (_object_store, path): (Box<dyn ObjectStore>, Path) = match table
+            .write_target
+            .clone()
+            .expect("FileSystemSink requires a write_target")
+        {
             Destination::LocalFilesystem { local_directory } => {
                 (Box::new(LocalFileSystem::new()), local_directory.into())
             }
@@ -86,7 +110,7 @@ impl<K: Key, T: Data + Sync, V: LocalWriter<T>> LocalFileSystemWriter<K, T, V> {
     }
 }
 
-impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
+impl<K: Key, T: Data + Sync + Serialize, R: MultiPartWriter<InputType = T> + Send + 'static>
     FileSystemSink<K, T, R>
 {
     pub fn from_config(config_str: &str) -> TwoPhaseCommitterOperator<K, T, Self> {
@@ -94,38 +118,17 @@ impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
             serde_json::from_str(config_str).expect("Invalid config for FileSystemSink");
         let table: FileSystemTable =
             serde_json::from_value(config.table).expect("Invalid table config for FileSystemSink");
-        let (object_store, path): (Box<dyn ObjectStore>, Path) = match table.write_target.clone() {
-            Destination::LocalFilesystem { local_directory } => {
-                (Box::new(LocalFileSystem::new()), local_directory.into())
-            }
-            Destination::S3Bucket {
-                s3_bucket,
-                s3_directory,
-                aws_region,
-            } => {
-                (
-                    Box::new(
-                        // use default credentials
-                        AmazonS3Builder::from_env()
-                            .with_bucket_name(s3_bucket)
-                            .with_credentials(Arc::new(S3Credentialing::try_new().unwrap()))
-                            .with_region(aws_region)
-                            .build()
-                            .unwrap(),
-                    ),
-                    s3_directory.into(),
-                )
-            }
-            Destination::FolderUri { path } => {
-                object_store::parse_url(&url::Url::parse(&path).unwrap()).unwrap()
-            }
-        };
+        let (object_store, path) = object_store_for_table(&table);
 
+        TwoPhaseCommitterOperator::new(Self::new(Arc::from(object_store), path, table))
+    }
+
+    fn new(object_store: Arc<dyn ObjectStore>, path: Path, table: FileSystemTable) -> Self {
         let (sender, receiver) = tokio::sync::mpsc::channel(10000);
         let (checkpoint_sender, checkpoint_receiver) = tokio::sync::mpsc::channel(10000);
         let mut writer = AsyncMultipartFileSystemWriter::<T, R>::new(
             path,
-            Arc::new(object_store),
+            object_store,
             receiver,
             checkpoint_sender,
             table,
@@ -133,11 +136,44 @@ impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
         tokio::spawn(async move {
             writer.run().await.unwrap();
         });
-        TwoPhaseCommitterOperator::new(Self {
+        Self {
             sender,
             checkpoint_receiver,
             _ts: PhantomData,
-        })
+        }
+    }
+}
+
+fn object_store_for_table(table: &FileSystemTable) -> (Box<dyn ObjectStore>, Path) {
+    match table
+        .write_target
+        .clone()
+        .expect("FileSystemSink requires a write_target")
+    {
+        Destination::LocalFilesystem { local_directory } => {
+            (Box::new(LocalFileSystem::new()), local_directory.into())
+        }
+        Destination::S3Bucket {
+            s3_bucket,
+            s3_directory,
+            aws_region,
+        } => {
+            (
+                Box::new(
+                    // use default credentials
+                    AmazonS3Builder::from_env()
+                        .with_bucket_name(s3_bucket)
+                        .with_credentials(Arc::new(S3Credentialing::try_new().unwrap()))
+                        .with_region(aws_region)
+                        .build()
+                        .unwrap(),
+                ),
+                s3_directory.into(),
+            )
+        }
+        Destination::FolderUri { path } => {
+            object_store::parse_url(&url::Url::parse(&path).unwrap()).unwrap()
+        }
     }
 }
 
@@ -168,6 +204,7 @@ enum CheckpointData<T: Data> {
 #[derive(Debug, Decode, Encode, Clone, PartialEq, Eq)]
 struct InProgressFileCheckpoint<T: Data> {
     filename: String,
+    partition: Option<String>,
     data: FileCheckpointData,
     buffered_data: Vec<T>,
 }
@@ -241,19 +278,32 @@ impl CredentialProvider for S3Credentialing {
     }
 }
 
+/// The most writers a subtask keeps open at once; once it's reached, the least recently written
+/// writer is closed before another partition's writer is opened
+const MAX_OPEN_WRITERS: usize = 100;
+
 struct AsyncMultipartFileSystemWriter<T: Data + Sync, R: MultiPartWriter> {
     path: Path,
-    current_writer_name: String,
+    // the open writer for each partition
+    active_writers: HashMap<Option<String>, ActiveWriter>,
     max_file_index: usize,
     subtask_id: usize,
     object_store: Arc<dyn ObjectStore>,
     writers: HashMap<String, R>,
+    // the partition of each writer, including those that have been closed but not yet finished
+    partitions: HashMap<String, Option<String>>,
     receiver: Receiver<FileSystemMessages<T>>,
     checkpoint_sender: Sender<CheckpointData<T>>,
     futures: FuturesUnordered<BoxedTryFuture<MultipartCallbackWithName>>,
     files_to_finish: Vec<FileToFinish>,
     properties: FileSystemTable,
     rolling_policy: RollingPolicy,
+    partitioner: Option<Partitioner>,
+}
+
+struct ActiveWriter {
+    name: String,
+    last_write_at: Instant,
 }
 
 #[async_trait]
@@ -459,7 +509,7 @@ pub struct MultiPartWriterStats {
 
 impl<T, R> AsyncMultipartFileSystemWriter<T, R>
 where
-    T: Data + std::marker::Sync,
+    T: Data + std::marker::Sync + Serialize,
     R: MultiPartWriter<InputType = T>,
 {
     fn new(
@@ -471,11 +521,12 @@ where
     ) -> Self {
         Self {
             path,
-            current_writer_name: "".to_string(),
+            active_writers: HashMap::new(),
             max_file_index: 0,
             subtask_id: 0,
             object_store,
             writers: HashMap::new(),
+            partitions: HashMap::new(),
             receiver,
             checkpoint_sender,
             futures: FuturesUnordered::new(),
@@ -483,6 +534,7 @@ where
             rolling_policy: RollingPolicy::from_file_settings(
                 writer_properties.file_settings.as_ref().unwrap(),
             ),
+            partitioner: Partitioner::from_table(&writer_properties),
             properties: writer_properties,
         }
     }
@@ -498,37 +550,22 @@ where
                 Some(message) = self.receiver.recv() => {
                     match message {
                         FileSystemMessages::Data{value, time} => {
-                            let Some(writer) = self.writers.get_mut(&self.current_writer_name) else {
-                                bail!("expect the current writer to be initialized");
-                            };
-                            if let Some(future) = writer.insert_value(value, time).await? {
-                                self.futures.push(future);
-                            }
+                            let partition = self.partitioner.as_ref().map(|p| p.partition(&value, time));
+                            self.insert_value(partition, value, time).await?;
                         },
                         FileSystemMessages::Init {max_file_index, subtask_id, recovered_files } => {
-                            if let Some(writer) = self.writers.get_mut(&self.current_writer_name) {
-                                if let Some(future) = writer.close()? {
-                                    self.futures.push(future);
-                                }
-                            }
+                            self.close_active_writers()?;
                             self.max_file_index = max_file_index;
                             self.subtask_id = subtask_id;
-                            let new_writer = self.new_writer();
-                            self.current_writer_name = new_writer.name();
-                            self.writers.insert(new_writer.name(), new_writer);
                             for recovered_file in recovered_files {
                                 if let Some(file_to_finish) = from_checkpoint(
                                      &Path::parse(&recovered_file.filename)?, recovered_file.data, self.object_store.clone()).await? {
                                         self.add_part_to_finish(file_to_finish);
                                      }
 
+                                // buffered data goes back into the partition it was originally written to
                                 for value in recovered_file.buffered_data {
-                                    let Some(writer) = self.writers.get_mut(&self.current_writer_name) else {
-                                        bail!("expect the current writer to be initialized");
-                                    };
-                                    if let Some(future) = writer.insert_value(value, SystemTime::now()).await? {
-                                        self.futures.push(future);
-                                    }
+                                    self.insert_value(recovered_file.partition.clone(), value, SystemTime::now()).await?;
                                 }
                             }
                         },
@@ -554,18 +591,22 @@ where
                 }
                 _ = tokio::time::sleep_until(next_policy_check) => {
                     next_policy_check = tokio::time::Instant::now() + Duration::from_millis(100);
-                    if let Some(writer) = self.writers.get_mut(&self.current_writer_name) {
-                        if let Some(stats) = writer.stats() {
-                            if self.rolling_policy.should_roll(&stats){
-                            if let Some(future) = writer.close()? {
-                                self.futures.push(future);
-                            }
-                            self.max_file_index += 1;
-                            let new_writer = self.new_writer();
-                            self.current_writer_name = new_writer.name();
-                            self.writers.insert(new_writer.name(), new_writer);
+                    let to_roll: Vec<_> = self.active_writers
+                        .iter()
+                        .filter(|(_, active)| {
+                            self.writers.get(&active.name)
+                                .and_then(|writer| writer.stats())
+                                .map(|stats| self.rolling_policy.should_roll(&stats))
+                                .unwrap_or(false)
+                        })
+                        .map(|(partition, _)| partition.clone())
+                        .collect();
+                    if !to_roll.is_empty() {
+                        for partition in to_roll {
+                            self.close_writer(&partition)?;
                         }
-                    }
+                        // the next writer for each rolled partition will use the new index
+                        self.max_file_index += 1;
                     }
                 }
                 else => {
@@ -576,16 +617,95 @@ where
         Ok(())
     }
 
-    fn new_writer(&mut self) -> R {
-        R::new(
+    fn new_writer(&mut self, partition: Option<&str>) -> Result<R> {
+        let partition = partition.map(Path::parse).transpose()?;
+        let filename = format!("{:0>5}-{:0>3}", self.max_file_index, self.subtask_id);
+        let location: Path = self
+            .path
+            .parts()
+            .chain(partition.iter().flat_map(|p| p.parts()))
+            .chain(std::iter::once(filename.as_str().into()))
+            .collect();
+
+        Ok(R::new(
             self.object_store.clone(),
-            format!(
-                "{}/{:0>5}-{:0>3}",
-                self.path, self.max_file_index, self.subtask_id
-            )
-            .into(),
+            location,
             &self.properties,
-        )
+        ))
+    }
+
+    /// Inserts a value into the open writer for its partition, creating one if necessary
+    async fn insert_value(
+        &mut self,
+        partition: Option<String>,
+        value: T,
+        time: SystemTime,
+    ) -> Result<()> {
+        let name = match self.active_writers.get_mut(&partition) {
+            Some(active) => {
+                active.last_write_at = Instant::now();
+                active.name.clone()
+            }
+            None => {
+                if self.active_writers.len() >= MAX_OPEN_WRITERS {
+                    self.close_least_recently_written()?;
+                }
+                let writer = self.new_writer(partition.as_deref())?;
+                let name = writer.name();
+                self.writers.insert(name.clone(), writer);
+                self.partitions.insert(name.clone(), partition.clone());
+                self.active_writers.insert(
+                    partition,
+                    ActiveWriter {
+                        name: name.clone(),
+                        last_write_at: Instant::now(),
+                    },
+                );
+                name
+            }
+        };
+        let Some(writer) = self.writers.get_mut(&name) else {
+            bail!("expect the writer {} to be initialized", name);
+        };
+        if let Some(future) = writer.insert_value(value, time).await? {
+            self.futures.push(future);
+        }
+        Ok(())
+    }
+
+    fn close_least_recently_written(&mut self) -> Result<()> {
+        let Some(partition) = self
+            .active_writers
+            .iter()
+            .min_by_key(|(_, active)| active.last_write_at)
+            .map(|(partition, _)| partition.clone())
+        else {
+            return Ok(());
+        };
+        self.close_writer(&partition)?;
+        // the next writer for the closed partition can't reuse its file name
+        self.max_file_index += 1;
+        Ok(())
+    }
+
+    fn close_writer(&mut self, partition: &Option<String>) -> Result<()> {
+        let Some(active) = self.active_writers.remove(partition) else {
+            return Ok(());
+        };
+        if let Some(writer) = self.writers.get_mut(&active.name) {
+            if let Some(future) = writer.close()? {
+                self.futures.push(future);
+            }
+        }
+        Ok(())
+    }
+
+    fn close_active_writers(&mut self) -> Result<()> {
+        let partitions: Vec<_> = self.active_writers.keys().cloned().collect();
+        for partition in partitions {
+            self.close_writer(&partition)?;
+        }
+        Ok(())
     }
 
     async fn flush_futures(&mut self) -> Result<()> {
@@ -615,6 +735,7 @@ where
                     // need the file to finish to be checkpointed first.
                     self.add_part_to_finish(file_to_write);
                     self.writers.remove(&name);
+                    self.partitions.remove(&name);
                 }
                 Ok(())
             }
@@ -622,6 +743,7 @@ where
                 let file_to_write = writer.get_finished_file();
                 self.add_part_to_finish(file_to_write);
                 self.writers.remove(&name);
+                self.partitions.remove(&name);
                 Ok(())
             }
         }
@@ -652,12 +774,7 @@ where
     }
 
     async fn stop(&mut self) -> Result<()> {
-        if let Some(writer) = self.writers.get_mut(&self.current_writer_name) {
-            let close_future: Option<BoxedTryFuture<MultipartCallbackWithName>> = writer.close()?;
-            if let Some(future) = close_future {
-                self.futures.push(future);
-            }
-        }
+        self.close_active_writers()?;
         while let Some(result) = self.futures.next().await {
             let MultipartCallbackWithName { callback, name } = result?;
             self.process_callback(name, callback)?;
@@ -668,9 +785,11 @@ where
     async fn take_checkpoint(&mut self, _subtask_id: usize) -> Result<()> {
         for (filename, writer) in self.writers.iter_mut() {
             let buffered_data = writer.currently_buffered_data();
+            let partition = self.partitions.get(filename).cloned().flatten();
             let in_progress_checkpoint =
                 CheckpointData::InProgressFileCheckpoint(InProgressFileCheckpoint {
                     filename: filename.clone(),
+                    partition,
                     data: writer.get_in_progress_checkpoint(),
                     buffered_data,
                 });
@@ -681,6 +800,7 @@ where
                 .send(CheckpointData::InProgressFileCheckpoint(
                     InProgressFileCheckpoint {
                         filename: file_to_finish.filename.clone(),
+                        partition: None,
                         data: FileCheckpointData::MultiPartWriterUploadCompleted {
                             multi_part_upload_id: file_to_finish.multi_part_upload_id.clone(),
                             completed_parts: file_to_finish.completed_parts.clone(),
@@ -993,12 +1113,12 @@ pub trait BatchBuilder: Send {
 pub trait BatchBufferingWriter: Send {
     type BatchData;
     fn new(config: &FileSystemTable) -> Self;
-    fn suffix() -> String;
-    fn add_batch_data(&mut self, data: Self::BatchData) -> Option<Vec<u8>>;
+    fn suffix(config: &FileSystemTable) -> String;
+    fn add_batch_data(&mut self, data: Self::BatchData) -> Result<Option<Vec<u8>>>;
     fn buffer_length(&self) -> usize;
     fn evict_current_buffer(&mut self) -> Vec<u8>;
     fn get_trailing_bytes_for_checkpoint(&mut self) -> Option<Vec<u8>>;
-    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Option<Vec<u8>>;
+    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Result<Option<Vec<u8>>>;
 }
 
 pub struct BatchMultipartWriter<
@@ -1019,7 +1139,7 @@ impl<BB: BatchBuilder, BBW: BatchBufferingWriter<BatchData = BB::BatchData>> Mul
     fn new(object_store: Arc<dyn ObjectStore>, path: Path, config: &FileSystemTable) -> Self {
         let batch_builder = BB::new(config);
         let batch_buffering_writer = BBW::new(config);
-        let path = format!("{}.{}", path, BBW::suffix()).into();
+        let path = format!("{}.{}", path, BBW::suffix(config)).into();
         Self {
             batch_builder,
             batch_buffering_writer,
@@ -1050,12 +1170,16 @@ impl<BB: BatchBuilder, BBW: BatchBufferingWriter<BatchData = BB::BatchData>> Mul
 
         if let Some(batch) = self.batch_builder.insert(value.clone()) {
             let prev_size = self.batch_buffering_writer.buffer_length();
-            if let Some(bytes) = self.batch_buffering_writer.add_batch_data(batch) {
-                stats.bytes_written += bytes.len() - prev_size;
+            let part = self.batch_buffering_writer.add_batch_data(batch)?;
+            // evicted parts may be compressed, so they can be smaller than the buffer they replace
+            stats.bytes_written = stats.bytes_written
+                + part.as_ref().map(|bytes| bytes.len()).unwrap_or_default()
+                + self.batch_buffering_writer.buffer_length()
+                - prev_size;
+            if let Some(bytes) = part {
                 stats.parts_written += 1;
                 self.multipart_manager.write_next_part(bytes)
             } else {
-                stats.bytes_written += self.batch_buffering_writer.buffer_length() - prev_size;
                 Ok(None)
             }
         } else {
@@ -1120,7 +1244,7 @@ impl<BB: BatchBuilder, BBW: BatchBufferingWriter<BatchData = BB::BatchData>>
         } else {
             None
         };
-        if let Some(bytes) = self.batch_buffering_writer.close(final_batch) {
+        if let Some(bytes) = self.batch_buffering_writer.close(final_batch)? {
             self.multipart_manager.write_next_part(bytes)
         } else if self.multipart_manager.all_uploads_finished() {
             // Return a finished file future
@@ -1236,6 +1360,7 @@ impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
     async fn commit(
         &mut self,
         _task_info: &TaskInfo,
+        _epoch: u32,
         pre_commit: Vec<Self::PreCommit>,
     ) -> Result<()> {
         self.sender
@@ -1279,6 +1404,7 @@ impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
                 }
                 CheckpointData::InProgressFileCheckpoint(InProgressFileCheckpoint {
                     filename,
+                    partition,
                     data,
                     buffered_data,
                 }) => {
@@ -1298,6 +1424,7 @@ impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
                     } else {
                         active_files.push(InProgressFileCheckpoint {
                             filename,
+                            partition,
                             data,
                             buffered_data,
                         })
@@ -1308,3 +1435,242 @@ impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
         bail!("checkpoint receiver closed unexpectedly")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    use anyhow::Result;
+    use arroyo_types::Record;
+    use async_trait::async_trait;
+    use object_store::{local::LocalFileSystem, path::Path, ObjectStore, UploadPart};
+    use serde::Serialize;
+
+    use crate::connectors::two_phase_committer::TwoPhaseCommitter;
+
+    use super::{
+        BoxedTryFuture, FileCheckpointData, FileSettings, FileSystemSink, FileSystemTable,
+        FileToFinish, MultiPartWriter, MultiPartWriterStats, MultipartCallback,
+        MultipartCallbackWithName, Partitioning, MAX_OPEN_WRITERS,
+    };
+
+    #[derive(Clone, Debug, bincode::Encode, bincode::Decode, PartialEq, Serialize)]
+    struct Event {
+        country: Option<String>,
+        id: i64,
+    }
+
+    /// Buffers everything it's given until it's closed, without uploading anything
+    struct BufferingWriter {
+        name: String,
+        values: Vec<Event>,
+    }
+
+    #[async_trait]
+    impl MultiPartWriter for BufferingWriter {
+        type InputType = Event;
+
+        fn new(_object_store: Arc<dyn ObjectStore>, path: Path, _: &FileSystemTable) -> Self {
+            Self {
+                name: path.to_string(),
+                values: vec![],
+            }
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        async fn insert_value(
+            &mut self,
+            value: Event,
+            _time: SystemTime,
+        ) -> Result<Option<BoxedTryFuture<MultipartCallbackWithName>>> {
+            self.values.push(value);
+            Ok(None)
+        }
+
+        fn handle_initialization(
+            &mut self,
+            _multipart_id: String,
+        ) -> Result<Vec<BoxedTryFuture<MultipartCallbackWithName>>> {
+            unreachable!("no uploads are started")
+        }
+
+        fn handle_completed_part(
+            &mut self,
+            _part_idx: usize,
+            _upload_part: UploadPart,
+        ) -> Result<Option<FileToFinish>> {
+            unreachable!("no uploads are started")
+        }
+
+        fn get_in_progress_checkpoint(&mut self) -> FileCheckpointData {
+            FileCheckpointData::Empty
+        }
+
+        fn currently_buffered_data(&mut self) -> Vec<Event> {
+            self.values.clone()
+        }
+
+        fn close(&mut self) -> Result<Option<BoxedTryFuture<MultipartCallbackWithName>>> {
+            let name = self.name.clone();
+            Ok(Some(Box::pin(async move {
+                Ok(MultipartCallbackWithName {
+                    callback: MultipartCallback::UploadsFinished,
+                    name,
+                })
+            })))
+        }
+
+        fn stats(&self) -> Option<MultiPartWriterStats> {
+            None
+        }
+
+        fn get_finished_file(&mut self) -> FileToFinish {
+            FileToFinish {
+                filename: self.name.clone(),
+                multi_part_upload_id: "".to_string(),
+                completed_parts: vec![],
+            }
+        }
+    }
+
+    fn sink(fields: &str) -> FileSystemSink<(), Event, BufferingWriter> {
+        FileSystemSink::new(
+            Arc::new(LocalFileSystem::new()),
+            Path::from("tables/events"),
+            FileSystemTable {
+                source: None,
+                write_target: None,
+                file_settings: Some(FileSettings {
+                    inactivity_rollover_seconds: None,
+                    max_parts: None,
+                    rollover_seconds: None,
+                    target_file_size: None,
+                    target_part_size: None,
+                }),
+                format_settings: None,
+                partitioning: Some(Partitioning {
+                    time_pattern: None,
+                    partition_fields: Some(fields.to_string()),
+                }),
+                iceberg: None,
+            },
+        )
+    }
+
+    fn record(country: Option<&str>, id: i64) -> Record<(), Event> {
+        Record {
+            timestamp: SystemTime::now(),
+            key: None,
+            value: Event {
+                country: country.map(|c| c.to_string()),
+                id,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_sink() {
+        let task_info = arroyo_types::get_test_task_info();
+        let mut sink = sink("country");
+        sink.init(&task_info, vec![]).await.unwrap();
+
+        for record in [
+            record(Some("US"), 1),
+            record(Some("DE"), 2),
+            record(Some("US"), 3),
+            record(None, 4),
+        ] {
+            sink.insert_record(&record).await.unwrap();
+        }
+
+        // open files are recovered into the partition they were written to
+        let (recovery, pre_commits) = sink.checkpoint(&task_info, false).await.unwrap();
+        assert!(pre_commits.is_empty());
+        let mut active: Vec<_> = recovery
+            .active_files
+            .iter()
+            .map(|f| {
+                (
+                    f.filename.as_str(),
+                    f.partition.as_deref(),
+                    f.buffered_data.iter().map(|e| e.id).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        active.sort();
+        assert_eq!(
+            active,
+            vec![
+                (
+                    "tables/events/country=DE/00000-000",
+                    Some("country=DE"),
+                    vec![2]
+                ),
+                (
+                    "tables/events/country=US/00000-000",
+                    Some("country=US"),
+                    vec![1, 3]
+                ),
+                (
+                    "tables/events/country=__HIVE_DEFAULT_PARTITION__/00000-000",
+                    Some("country=__HIVE_DEFAULT_PARTITION__"),
+                    vec![4]
+                ),
+            ]
+        );
+
+        // stopping closes every file, and they're finished by the commit
+        let (recovery, pre_commits) = sink.checkpoint(&task_info, true).await.unwrap();
+        assert!(recovery.active_files.is_empty());
+        let mut files: Vec<_> = pre_commits.keys().cloned().collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "tables/events/country=DE/00000-000",
+                "tables/events/country=US/00000-000",
+                "tables/events/country=__HIVE_DEFAULT_PARTITION__/00000-000",
+            ]
+        );
+
+        sink.commit(&task_info, 1, pre_commits.into_values().collect())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_open_writers() {
+        let task_info = arroyo_types::get_test_task_info();
+        let mut sink = sink("id");
+        sink.init(&task_info, vec![]).await.unwrap();
+
+        for id in 0..=MAX_OPEN_WRITERS as i64 {
+            sink.insert_record(&record(None, id)).await.unwrap();
+        }
+
+        // the least recently written partition was closed to make room for the last one
+        let (recovery, pre_commits) = sink.checkpoint(&task_info, false).await.unwrap();
+        assert_eq!(recovery.active_files.len(), MAX_OPEN_WRITERS);
+        assert_eq!(
+            pre_commits.into_keys().collect::<Vec<_>>(),
+            vec!["tables/events/id=0/00000-000"]
+        );
+
+        // reopening the closed partition closes the next least recently written one, and the
+        // index moves on so that the new file doesn't reuse the closed file's name
+        sink.insert_record(&record(None, 0)).await.unwrap();
+        let (recovery, pre_commits) = sink.checkpoint(&task_info, false).await.unwrap();
+        assert_eq!(
+            pre_commits.into_keys().collect::<Vec<_>>(),
+            vec!["tables/events/id=1/00000-000"]
+        );
+        assert!(recovery
+            .active_files
+            .iter()
+            .any(|f| f.filename == "tables/events/id=0/00002-000"));
+    }
+}
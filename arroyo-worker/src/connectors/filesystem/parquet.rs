@@ -1,5 +1,7 @@
 use std::{fs::File, io::Write, marker::PhantomData, sync::Arc};
 
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
 use arrow_array::RecordBatch;
 use arroyo_types::RecordBatchBuilder;
 use parquet::{
@@ -9,6 +11,7 @@ use parquet::{
 };
 
 use super::{
+    iceberg::{iceberg_arrow_schema, to_iceberg_batch},
     local::{CurrentFileRecovery, FilePreCommit, LocalWriter},
     BatchBufferingWriter, BatchBuilder, FileSettings, FileSystemTable,
 };
@@ -124,9 +127,21 @@ pub struct RecordBatchBufferingWriter<R: RecordBatchBuilder> {
     writer: Option<ArrowWriter<SharedBuffer>>,
     shared_buffer: SharedBuffer,
     target_part_size: usize,
+    // set for iceberg tables, whose data files use microsecond timestamps
+    iceberg_schema: Option<SchemaRef>,
     phantom: PhantomData<R>,
 }
 
+impl<R: RecordBatchBuilder> RecordBatchBufferingWriter<R> {
+    fn convert(&self, batch: RecordBatch) -> RecordBatch {
+        match &self.iceberg_schema {
+            Some(schema) => to_iceberg_batch(&batch, schema.clone())
+                .expect("failed to convert batch for iceberg table"),
+            None => batch,
+        }
+    }
+}
+
 impl<R: RecordBatchBuilder> BatchBufferingWriter for RecordBatchBufferingWriter<R> {
     type BatchData = RecordBatch;
 
@@ -142,9 +157,15 @@ impl<R: RecordBatchBuilder> BatchBufferingWriter for RecordBatchBufferingWriter<
         };
         let shared_buffer = SharedBuffer::new(target_part_size);
         let writer_properties = writer_properties_from_table(config);
+        let iceberg_schema = config
+            .iceberg
+            .as_ref()
+            .map(|_| iceberg_arrow_schema(&R::default().schema()));
         let writer = ArrowWriter::try_new(
             shared_buffer.clone(),
-            R::default().schema(),
+            iceberg_schema
+                .clone()
+                .unwrap_or_else(|| R::default().schema()),
             Some(writer_properties),
         )
         .unwrap();
@@ -153,22 +174,24 @@ impl<R: RecordBatchBuilder> BatchBufferingWriter for RecordBatchBufferingWriter<
             writer: Some(writer),
             shared_buffer,
             target_part_size,
+            iceberg_schema,
             phantom: PhantomData,
         }
     }
 
-    fn suffix() -> String {
+    fn suffix(_config: &FileSystemTable) -> String {
         "parquet".to_string()
     }
 
-    fn add_batch_data(&mut self, data: Self::BatchData) -> Option<Vec<u8>> {
+    fn add_batch_data(&mut self, data: Self::BatchData) -> Result<Option<Vec<u8>>> {
+        let data = self.convert(data);
         let writer = self.writer.as_mut().unwrap();
-        writer.write(&data).unwrap();
-        writer.flush().unwrap();
+        writer.write(&data)?;
+        writer.flush()?;
         if self.buffer_length() > self.target_part_size {
-            Some(self.evict_current_buffer())
+            Ok(Some(self.evict_current_buffer()))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -197,14 +220,15 @@ impl<R: RecordBatchBuilder> BatchBufferingWriter for RecordBatchBufferingWriter<
         Some(copied_bytes)
     }
 
-    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Option<Vec<u8>> {
+    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Result<Option<Vec<u8>>> {
+        let final_batch = final_batch.map(|batch| self.convert(batch));
         let mut writer = self.writer.take().unwrap();
         if let Some(batch) = final_batch {
-            writer.write(&batch).unwrap();
+            writer.write(&batch)?;
         }
-        writer.close().unwrap();
+        writer.close()?;
         let buffer = self.shared_buffer.buffer.try_lock().unwrap();
-        Some(buffer.to_vec())
+        Ok(Some(buffer.to_vec()))
     }
 }
 
@@ -239,8 +263,8 @@ impl<V: RecordBatchBuilder + 'static> LocalWriter<V::Data> for ParquetLocalWrite
         }
     }
 
-    fn file_suffix() -> &'static str {
-        "parquet"
+    fn file_suffix(_table_properties: &FileSystemTable) -> String {
+        "parquet".to_string()
     }
 
     fn write(&mut self, value: V::Data) -> anyhow::Result<()> {
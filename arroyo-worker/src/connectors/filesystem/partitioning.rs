@@ -0,0 +1,355 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+pub use arroyo_rpc::partition_spec::{parse_partition_spec, PartitionField, Transform};
+
+use crate::formats::fields::extract_fields;
+
+use super::FileSystemTable;
+
+/// Directory name Hive uses for null partition values
+pub const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// Computes the Hive-style partition directory of a record (like `dt=2023-10-01/country=US`)
+/// from its event time and the values of its partition fields.
+#[derive(Debug, Clone)]
+pub struct Partitioner {
+    time_pattern: Option<String>,
+    fields: Vec<PartitionField>,
+    // the fields of the record that partitions are computed from
+    sources: Vec<String>,
+}
+
+/// Converts between the values of a partition transform and the directory names of partitions
+pub trait PartitionPath {
+    /// Returns the directory name for the transformed value, or None if it's null
+    fn path_value(&self, value: &Value) -> Option<String>;
+
+    /// Parses a partition directory name back into the Iceberg partition value: days or hours
+    /// since the epoch for time transforms, the bucket for bucket transforms, and the unescaped
+    /// string for identity transforms
+    fn parse_path_value(&self, value: &str) -> Option<PartitionValue>;
+}
+
+impl PartitionPath for Transform {
+    fn path_value(&self, value: &Value) -> Option<String> {
+        match (self, value) {
+            (_, Value::Null) => None,
+            (Transform::Identity, Value::String(s)) => Some(escape_path_value(s)),
+            (Transform::Identity, other) => Some(escape_path_value(&other.to_string())),
+            (Transform::Day, v) => {
+                let micros = timestamp_micros(v)?;
+                Some(
+                    Utc.timestamp_micros(micros)
+                        .single()?
+                        .format("%Y-%m-%d")
+                        .to_string(),
+                )
+            }
+            (Transform::Hour, v) => {
+                let micros = timestamp_micros(v)?;
+                Some(
+                    Utc.timestamp_micros(micros)
+                        .single()?
+                        .format("%Y-%m-%d-%H")
+                        .to_string(),
+                )
+            }
+            (Transform::Bucket(n), v) => {
+                // ints are hashed as longs, and timestamps as longs of microseconds
+                let hash = match v {
+                    Value::String(s) => murmur3_32(s.as_bytes()),
+                    Value::Number(n) => murmur3_32(&n.as_i64()?.to_le_bytes()),
+                    Value::Object(_) => murmur3_32(&timestamp_micros(v)?.to_le_bytes()),
+                    _ => return None,
+                };
+                Some(((hash & i32::MAX as u32) % n).to_string())
+            }
+        }
+    }
+
+    fn parse_path_value(&self, value: &str) -> Option<PartitionValue> {
+        if value == HIVE_DEFAULT_PARTITION {
+            return None;
+        }
+
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        match self {
+            Transform::Identity => Some(PartitionValue::String(unescape_path_value(value))),
+            Transform::Day => {
+                let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+                Some(PartitionValue::Int((date - epoch).num_days() as i32))
+            }
+            Transform::Hour => {
+                let time =
+                    NaiveDateTime::parse_from_str(&format!("{}:00", value), "%Y-%m-%d-%H:%M")
+                        .ok()?;
+                let epoch = epoch.and_hms_opt(0, 0, 0).unwrap();
+                Some(PartitionValue::Int((time - epoch).num_hours() as i32))
+            }
+            Transform::Bucket(_) => value.parse().ok().map(PartitionValue::Int),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartitionValue {
+    Int(i32),
+    String(String),
+}
+
+/// Reads a timestamp serialized as RFC3339, epoch millis, or a serde `SystemTime` as micros
+fn timestamp_micros(value: &Value) -> Option<i64> {
+    match value {
+        Value::String(s) => Some(DateTime::parse_from_rfc3339(s).ok()?.timestamp_micros()),
+        Value::Number(n) => n.as_i64()?.checked_mul(1000),
+        Value::Object(o) => {
+            let secs = o.get("secs_since_epoch")?.as_i64()?;
+            let nanos = o.get("nanos_since_epoch")?.as_i64()?;
+            Some(secs * 1_000_000 + nanos / 1000)
+        }
+        _ => None,
+    }
+}
+
+/// The 32-bit x86 variant of murmur3 with a seed of 0, as used by Iceberg's bucket transform
+fn murmur3_32(data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mut h: u32 = 0;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (i, b) in tail.iter().enumerate() {
+            k |= (*b as u32) << (8 * i);
+        }
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+    }
+
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^= h >> 16;
+    h
+}
+
+impl Partitioner {
+    /// Returns None if the table is not partitioned
+    pub fn from_table(table: &FileSystemTable) -> Option<Self> {
+        if let Some(iceberg) = &table.iceberg {
+            let fields =
+                parse_partition_spec(iceberg.partition_spec.as_deref().unwrap_or_default())
+                    .expect("invalid iceberg partition spec");
+            return (!fields.is_empty()).then(|| Self::new(None, fields));
+        }
+
+        let partitioning = table.partitioning.as_ref()?;
+
+        let time_pattern = partitioning
+            .time_pattern
+            .as_ref()
+            .map(|p| p.trim_matches('/').to_string())
+            .filter(|p| !p.is_empty());
+
+        let fields: Vec<PartitionField> = partitioning
+            .partition_fields
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+            .map(|f| PartitionField {
+                source: f.to_string(),
+                name: f.to_string(),
+                transform: Transform::Identity,
+            })
+            .collect();
+
+        if time_pattern.is_none() && fields.is_empty() {
+            return None;
+        }
+
+        Some(Self::new(time_pattern, fields))
+    }
+
+    fn new(time_pattern: Option<String>, fields: Vec<PartitionField>) -> Self {
+        let sources = fields.iter().map(|f| f.source.clone()).collect();
+        Self {
+            time_pattern,
+            fields,
+            sources,
+        }
+    }
+
+    pub fn partition<T: Serialize>(&self, value: &T, time: SystemTime) -> String {
+        let mut parts = vec![];
+
+        if let Some(pattern) = &self.time_pattern {
+            parts.push(DateTime::<Utc>::from(time).format(pattern).to_string());
+        }
+
+        if !self.fields.is_empty() {
+            let values = extract_fields(value, &self.sources).unwrap_or_default();
+            for field in &self.fields {
+                let field_value = values
+                    .get(&field.source)
+                    .and_then(|v| field.transform.path_value(v))
+                    .unwrap_or_else(|| HIVE_DEFAULT_PARTITION.to_string());
+                parts.push(format!("{}={}", field.name, field_value));
+            }
+        }
+
+        parts.join("/")
+    }
+}
+
+/// Escapes characters that aren't allowed in partition values the same way Hive does
+fn escape_path_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_ascii_control() || "\"#%'*/:=?\\{[]^".contains(c) {
+            escaped.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape_path_value(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((b, tail)) = rest.split_first() {
+        if *b == b'%' && tail.len() >= 2 {
+            if let Ok(c) = u8::from_str_radix(std::str::from_utf8(&tail[..2]).unwrap_or(""), 16) {
+                bytes.push(c);
+                rest = &tail[2..];
+                continue;
+            }
+        }
+        bytes.push(*b);
+        rest = tail;
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use serde::Serialize;
+
+    use serde_json::json;
+
+    use super::{murmur3_32, PartitionPath, PartitionValue, Partitioner, Transform};
+    use crate::connectors::filesystem::{FileSystemTable, Partitioning};
+
+    #[derive(Serialize)]
+    struct Event {
+        country: Option<String>,
+        id: i64,
+    }
+
+    fn partitioner(time_pattern: Option<&str>, fields: Option<&str>) -> Option<Partitioner> {
+        Partitioner::from_table(&FileSystemTable {
+            write_target: None,
+            source: None,
+            format_settings: None,
+            file_settings: None,
+            iceberg: None,
+            partitioning: Some(Partitioning {
+                time_pattern: time_pattern.map(|s| s.to_string()),
+                partition_fields: fields.map(|s| s.to_string()),
+            }),
+        })
+    }
+
+    #[test]
+    fn test_partitioning() {
+        // 2023-10-01T13:20:00Z
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1696166400);
+
+        assert!(partitioner(None, Some(" ")).is_none());
+
+        let p = partitioner(Some("dt=%Y-%m-%d/hour=%H/"), Some("country, id")).unwrap();
+        let event = Event {
+            country: Some("US".to_string()),
+            id: 5,
+        };
+        assert_eq!(
+            p.partition(&event, time),
+            "dt=2023-10-01/hour=13/country=US/id=5"
+        );
+
+        let p = partitioner(None, Some("country")).unwrap();
+        let event = Event {
+            country: None,
+            id: 5,
+        };
+        assert_eq!(
+            p.partition(&event, time),
+            "country=__HIVE_DEFAULT_PARTITION__"
+        );
+
+        let event = Event {
+            country: Some("a/b=c".to_string()),
+            id: 5,
+        };
+        assert_eq!(p.partition(&event, time), "country=a%2Fb%3Dc");
+    }
+
+    #[test]
+    fn test_partition_transforms() {
+        // test vectors from the iceberg spec
+        assert_eq!(murmur3_32(&34i64.to_le_bytes()), 2017239379);
+        assert_eq!(murmur3_32("iceberg".as_bytes()), 1210000089);
+
+        // timestamps are bucketed by their microseconds, and bools aren't bucketed
+        let time =
+            serde_json::to_value(SystemTime::UNIX_EPOCH + Duration::from_micros(34)).unwrap();
+        assert_eq!(
+            Transform::Bucket(16).path_value(&time),
+            Transform::Bucket(16).path_value(&json!(34))
+        );
+        assert_eq!(Transform::Bucket(16).path_value(&json!(true)), None);
+
+        let ts = json!("2023-10-01T13:20:00Z");
+        let day = Transform::Day.path_value(&ts).unwrap();
+        assert_eq!(day, "2023-10-01");
+        assert_eq!(
+            Transform::Day.parse_path_value(&day),
+            Some(PartitionValue::Int(19631))
+        );
+
+        let hour = Transform::Hour.path_value(&ts).unwrap();
+        assert_eq!(hour, "2023-10-01-13");
+        assert_eq!(
+            Transform::Hour.parse_path_value(&hour),
+            Some(PartitionValue::Int(19631 * 24 + 13))
+        );
+
+        assert_eq!(
+            Transform::Identity.parse_path_value("a%2Fb%3Dc"),
+            Some(PartitionValue::String("a/b=c".to_string()))
+        );
+        assert_eq!(
+            Transform::Bucket(16).parse_path_value("__HIVE_DEFAULT_PARTITION__"),
+            None
+        );
+    }
+}
@@ -71,8 +71,8 @@ impl<K: Key, T: SchemaData + Serialize> FileSink<K, T> {
         }
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, _ctx: &mut Context<(), ()>) {
-        let Some(row) = self.serializer.to_vec(&record.value) else {
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        let Some(row) = self.serializer.to_vec_or_report(&record.value, ctx).await else {
             return;
         };
         // row as a line
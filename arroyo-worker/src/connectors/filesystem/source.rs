@@ -0,0 +1,867 @@
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::datatypes::*;
+use arrow::util::display::array_value_to_string;
+use arroyo_macro::source_fn;
+use arroyo_rpc::grpc::{StopMode, TableDescriptor};
+use arroyo_rpc::types::Format;
+use arroyo_rpc::{ControlMessage, OperatorConfig};
+use arroyo_storage::{split_glob, StorageError, StorageProvider};
+use arroyo_types::*;
+use bincode::{Decode, Encode};
+use bytes::Bytes;
+use chrono::{TimeZone, Utc};
+use futures::future::BoxFuture;
+use futures::{StreamExt, TryFutureExt};
+use parquet::arrow::async_reader::{
+    fetch_parquet_metadata, AsyncFileReader, ParquetRecordBatchStreamBuilder,
+};
+use parquet::errors::ParquetError;
+use parquet::file::metadata::ParquetMetaData;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use tokio::select;
+use tracing::{debug, info};
+
+use crate::connectors::bad_data::BadDataHandler;
+use crate::engine::{Context, StreamNode};
+use crate::formats::DataDeserializer;
+use crate::SourceFinishType;
+
+use super::FileSystemTable;
+
+/// Reads line-delimited JSON (or raw string) and Parquet files from a directory or glob on any
+/// storage supported by `StorageProvider`. Files are distributed across subtasks by hashing their
+/// paths, and the number of lines or rows read from each file is checkpointed so that restarts
+/// resume exactly where they left off.
+///
+/// With a watch interval, the path is listed again periodically and new files are read. Files
+/// are treated as immutable: once a file has been read to the end it's marked finished and never
+/// read again, so data appended to it afterwards is not picked up. Finished files are dropped
+/// from state once they no longer appear in the listing.
+#[derive(StreamNode, Clone)]
+pub struct FileSystemSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: DeserializeOwned + Data,
+{
+    base_url: String,
+    pattern: Option<Regex>,
+    watch_interval: Option<Duration>,
+    format: Format,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
+    files: HashMap<String, FileReadState>,
+    _t: PhantomData<(K, T)>,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
+pub struct FileReadState {
+    path: String,
+    /// lines for line-delimited files, rows for parquet files
+    records_read: usize,
+    finished: bool,
+}
+
+pub fn tables() -> Vec<TableDescriptor> {
+    vec![arroyo_state::global_table("f", "file system source state")]
+}
+
+/// Files are distributed across subtasks by the hash of their path, which must be stable so that
+/// restored subtasks keep reading the files they have state for
+fn is_assigned(path: &str, task_info: &TaskInfo) -> bool {
+    (stable_hash(path) % task_info.parallelism as u64) as usize == task_info.task_index
+}
+
+fn json_value(array: &ArrayRef, row: usize) -> Value {
+    if array.is_null(row) {
+        return Value::Null;
+    }
+
+    match array.data_type() {
+        DataType::Boolean => array.as_boolean().value(row).into(),
+        DataType::Int8 => array.as_primitive::<Int8Type>().value(row).into(),
+        DataType::Int16 => array.as_primitive::<Int16Type>().value(row).into(),
+        DataType::Int32 => array.as_primitive::<Int32Type>().value(row).into(),
+        DataType::Int64 => array.as_primitive::<Int64Type>().value(row).into(),
+        DataType::UInt8 => array.as_primitive::<UInt8Type>().value(row).into(),
+        DataType::UInt16 => array.as_primitive::<UInt16Type>().value(row).into(),
+        DataType::UInt32 => array.as_primitive::<UInt32Type>().value(row).into(),
+        DataType::UInt64 => array.as_primitive::<UInt64Type>().value(row).into(),
+        DataType::Float32 => array.as_primitive::<Float32Type>().value(row).into(),
+        DataType::Float64 => array.as_primitive::<Float64Type>().value(row).into(),
+        DataType::Utf8 => array.as_string::<i32>().value(row).into(),
+        DataType::LargeUtf8 => array.as_string::<i64>().value(row).into(),
+        DataType::Binary => array.as_binary::<i32>().value(row).into(),
+        DataType::LargeBinary => array.as_binary::<i64>().value(row).into(),
+        DataType::Timestamp(unit, _) => {
+            let nanos = match unit {
+                TimeUnit::Second => array
+                    .as_primitive::<TimestampSecondType>()
+                    .value(row)
+                    .saturating_mul(1_000_000_000),
+                TimeUnit::Millisecond => array
+                    .as_primitive::<TimestampMillisecondType>()
+                    .value(row)
+                    .saturating_mul(1_000_000),
+                TimeUnit::Microsecond => array
+                    .as_primitive::<TimestampMicrosecondType>()
+                    .value(row)
+                    .saturating_mul(1_000),
+                TimeUnit::Nanosecond => array.as_primitive::<TimestampNanosecondType>().value(row),
+            };
+            // parquet sources deserialize timestamps as RFC3339
+            Utc.timestamp_nanos(nanos).to_rfc3339().into()
+        }
+        DataType::Struct(fields) => {
+            let array = array.as_struct();
+            Value::Object(
+                fields
+                    .iter()
+                    .zip(array.columns())
+                    .map(|(f, c)| (f.name().clone(), json_value(c, row)))
+                    .collect(),
+            )
+        }
+        DataType::List(_) => {
+            let values = array.as_list::<i32>().value(row);
+            Value::Array((0..values.len()).map(|i| json_value(&values, i)).collect())
+        }
+        _ => array_value_to_string(array, row)
+            .map(Value::String)
+            .unwrap_or(Value::Null),
+    }
+}
+
+fn read_error(path: &str, e: StorageError) -> UserError {
+    UserError::new(
+        "Failed to read file",
+        format!("could not read {}: {}", path, e),
+    )
+}
+
+/// Reads parquet files through a `StorageProvider`, fetching byte ranges as the parquet reader
+/// needs them rather than the whole file
+struct StorageFileReader {
+    storage: StorageProvider,
+    path: String,
+    size: usize,
+}
+
+impl AsyncFileReader for StorageFileReader {
+    fn get_bytes(&mut self, range: Range<usize>) -> BoxFuture<'_, parquet::errors::Result<Bytes>> {
+        Box::pin(async move {
+            self.storage
+                .get_range(self.path.as_str(), range)
+                .await
+                .map_err(|e| ParquetError::External(Box::new(e)))
+        })
+    }
+
+    fn get_metadata(&mut self) -> BoxFuture<'_, parquet::errors::Result<Arc<ParquetMetaData>>> {
+        Box::pin(async move {
+            let (storage, path) = (&self.storage, self.path.as_str());
+            let metadata = fetch_parquet_metadata(
+                |range| {
+                    storage
+                        .get_range(path, range)
+                        .map_err(|e| ParquetError::External(Box::new(e)))
+                },
+                self.size,
+                None,
+            )
+            .await?;
+            Ok(Arc::new(metadata))
+        })
+    }
+}
+
+#[source_fn(out_k = (), out_t = T)]
+impl<K, T> FileSystemSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: DeserializeOwned + Data,
+{
+    pub fn new(path: &str, watch_interval: Option<Duration>, format: Format) -> Self {
+        let (base_url, pattern) =
+            split_glob(path).expect("source paths are validated when the table is created");
+
+        Self {
+            base_url,
+            pattern,
+            watch_interval,
+            deserializer: DataDeserializer::new(format.clone()),
+            format,
+            bad_data: BadDataHandler::default(),
+            files: HashMap::new(),
+            _t: PhantomData,
+        }
+    }
+
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for FileSystemSource");
+        let bad_data = BadDataHandler::from_config(&config);
+        let table: FileSystemTable = serde_json::from_value(config.table)
+            .expect("Invalid table config for FileSystemSource");
+        let source = table
+            .source
+            .expect("found non-source FileSystem config in source operator");
+
+        Self {
+            bad_data,
+            ..Self::new(
+                &source.path,
+                source
+                    .watch_interval_seconds
+                    .map(|s| Duration::from_secs(s.max(1) as u64)),
+                config
+                    .format
+                    .expect("Format must be set for FileSystemSource"),
+            )
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("filesystem-{}", self.base_url)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        tables()
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+
+    /// Lists the unfinished files assigned to this subtask, in a stable order. Finished files
+    /// that are no longer listed are forgotten, so that state doesn't grow without bound.
+    async fn list_files(
+        &mut self,
+        ctx: &Context<(), T>,
+        storage: &StorageProvider,
+    ) -> Result<Vec<String>, UserError> {
+        let key = storage.key().unwrap_or_default();
+
+        let listed: HashSet<String> = storage
+            .list()
+            .await
+            .map_err(|e| {
+                UserError::new(
+                    "Failed to list files",
+                    format!("could not list {}: {}", self.base_url, e),
+                )
+            })?
+            .into_iter()
+            .filter(|path| {
+                let relative = path
+                    .strip_prefix(key)
+                    .unwrap_or(path)
+                    .trim_start_matches('/');
+
+                // skip marker and metadata files like _SUCCESS and .crc files
+                let name = relative.rsplit('/').next().unwrap_or_default();
+                if name.starts_with('.') || name.starts_with('_') {
+                    return false;
+                }
+
+                self.pattern
+                    .as_ref()
+                    .map(|p| p.is_match(relative))
+                    .unwrap_or(true)
+            })
+            .filter(|path| is_assigned(path, &ctx.task_info))
+            .collect();
+
+        self.files
+            .retain(|path, file| !file.finished || listed.contains(path));
+
+        let mut files: Vec<String> = listed
+            .into_iter()
+            .filter(|path| !self.files.get(path).map(|f| f.finished).unwrap_or(false))
+            .collect();
+
+        files.sort();
+        Ok(files)
+    }
+
+    async fn run_int(&mut self, ctx: &mut Context<(), T>) -> Result<SourceFinishType, UserError> {
+        let storage = StorageProvider::for_url(&self.base_url)
+            .await
+            .map_err(|e| {
+                UserError::new(
+                    "Failed to create storage provider",
+                    format!("invalid path {}: {}", self.base_url, e),
+                )
+            })?;
+
+        // state is restored to every subtask, and each subtask only reads the files it's assigned
+        let mut s = ctx
+            .state
+            .get_global_keyed_state::<String, FileReadState>('f')
+            .await;
+        self.files = s
+            .get_all()
+            .into_iter()
+            .filter(|f| is_assigned(&f.path, &ctx.task_info))
+            .map(|f| (f.path.clone(), f.clone()))
+            .collect();
+
+        loop {
+            let files = self.list_files(ctx, &storage).await?;
+            if files.is_empty() {
+                ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+            }
+
+            for path in files {
+                if let Some(finish) = self.read_file(ctx, &storage, &path).await? {
+                    return Ok(finish);
+                }
+            }
+
+            let Some(interval) = self.watch_interval else {
+                info!("finished reading files from {}", self.base_url);
+                return Ok(SourceFinishType::Final);
+            };
+
+            let sleep = tokio::time::sleep(interval);
+            tokio::pin!(sleep);
+            loop {
+                select! {
+                    _ = &mut sleep => break,
+                    control_message = ctx.control_rx.recv() => {
+                        if let Some(finish) = self.handle_control_message(ctx, control_message).await? {
+                            return Ok(finish);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn read_file(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        storage: &StorageProvider,
+        path: &str,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        let skip = self
+            .files
+            .get(path)
+            .map(|f| f.records_read)
+            .unwrap_or_default();
+
+        debug!("reading {} starting from record {}", path, skip);
+
+        let finish = if let Format::Parquet(_) = &self.format {
+            self.read_parquet(ctx, storage, path, skip).await?
+        } else {
+            self.read_lines(ctx, storage, path, skip).await?
+        };
+
+        if finish.is_none() {
+            self.file_state(path).finished = true;
+        }
+
+        Ok(finish)
+    }
+
+    /// Reads a line-delimited file as a stream, so that only the current chunk and any partial
+    /// line at its end are held in memory
+    async fn read_lines(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        storage: &StorageProvider,
+        path: &str,
+        skip: usize,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        let mut chunks = storage
+            .get_stream(path)
+            .await
+            .map_err(|e| read_error(path, e))?;
+
+        let mut buf: Vec<u8> = vec![];
+        let mut lines_read = 0;
+        let mut done = false;
+        while !done {
+            match chunks.next().await {
+                Some(chunk) => buf.extend_from_slice(&chunk.map_err(|e| read_error(path, e))?),
+                None => {
+                    // the last line may not end with a newline
+                    done = true;
+                    if buf.is_empty() {
+                        break;
+                    }
+                    buf.push(b'\n');
+                }
+            }
+
+            let mut start = 0;
+            while let Some(end) = buf[start..].iter().position(|b| *b == b'\n') {
+                let line = &buf[start..start + end];
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                start += end + 1;
+                lines_read += 1;
+
+                if lines_read <= skip {
+                    continue;
+                }
+
+                if !line.is_empty() {
+                    let values = self.deserializer.deserialize_slice(line).await;
+                    for value in self
+                        .bad_data
+                        .handle(ctx, line, values)
+                        .await
+                        .unwrap_or_default()
+                    {
+                        ctx.collector
+                            .collect(Record {
+                                timestamp: SystemTime::now(),
+                                key: None,
+                                value,
+                            })
+                            .await;
+                    }
+                }
+
+                if let Some(finish) = self.record_progress(ctx, path, lines_read).await? {
+                    return Ok(Some(finish));
+                }
+            }
+            buf.drain(..start);
+        }
+
+        Ok(None)
+    }
+
+    /// Reads a parquet file as a stream of record batches, fetching only the parts of the file
+    /// that are needed for the current row group
+    async fn read_parquet(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        storage: &StorageProvider,
+        path: &str,
+        skip: usize,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        let size = storage.size(path).await.map_err(|e| read_error(path, e))?;
+
+        let reader = StorageFileReader {
+            storage: storage.clone(),
+            path: path.to_string(),
+            size,
+        };
+
+        let mut batches = ParquetRecordBatchStreamBuilder::new(reader)
+            .await
+            .and_then(|b| b.build())
+            .map_err(|e| {
+                UserError::new(
+                    "Failed to read parquet file",
+                    format!("{} is not a valid parquet file: {}", path, e),
+                )
+            })?;
+
+        let mut rows_read = 0;
+        while let Some(batch) = batches.next().await {
+            let batch = batch.map_err(|e| {
+                UserError::new(
+                    "Failed to read parquet file",
+                    format!("error while reading {}: {}", path, e),
+                )
+            })?;
+
+            if rows_read + batch.num_rows() <= skip {
+                rows_read += batch.num_rows();
+                continue;
+            }
+
+            let schema = batch.schema();
+            for row in skip.saturating_sub(rows_read)..batch.num_rows() {
+                let value: Map<String, Value> = schema
+                    .fields()
+                    .iter()
+                    .zip(batch.columns())
+                    .map(|(f, c)| (f.name().clone(), json_value(c, row)))
+                    .collect();
+
+                let raw = serde_json::to_vec(&value).unwrap();
+                let result = serde_json::from_value(Value::Object(value)).map_err(|e| {
+                    UserError::new("Deserialization failed", format!("{}: {}", path, e))
+                });
+
+                if let Some(value) = self.bad_data.handle(ctx, &raw, result).await {
+                    ctx.collector
+                        .collect(Record {
+                            timestamp: SystemTime::now(),
+                            key: None,
+                            value,
+                        })
+                        .await;
+                }
+
+                if let Some(finish) = self.record_progress(ctx, path, rows_read + row + 1).await? {
+                    return Ok(Some(finish));
+                }
+            }
+
+            rows_read += batch.num_rows();
+        }
+
+        Ok(None)
+    }
+
+    fn file_state(&mut self, path: &str) -> &mut FileReadState {
+        self.files
+            .entry(path.to_string())
+            .or_insert_with(|| FileReadState {
+                path: path.to_string(),
+                records_read: 0,
+                finished: false,
+            })
+    }
+
+    /// Records how far we've read into a file and handles any pending control message, so that
+    /// checkpoints taken while reading capture our exact position
+    async fn record_progress(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        path: &str,
+        records_read: usize,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        self.file_state(path).records_read = records_read;
+
+        match ctx.control_rx.try_recv() {
+            Ok(control_message) => {
+                self.handle_control_message(ctx, Some(control_message))
+                    .await
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        control_message: Option<ControlMessage>,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        match control_message {
+            Some(ControlMessage::Checkpoint(c)) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                let mut s = ctx
+                    .state
+                    .get_global_keyed_state::<String, FileReadState>('f')
+                    .await;
+
+                // state is shared by all subtasks, so each only writes (and removes) the files
+                // assigned to it
+                let removed: Vec<String> = s
+                    .get_all()
+                    .into_iter()
+                    .filter(|f| {
+                        is_assigned(&f.path, &ctx.task_info) && !self.files.contains_key(&f.path)
+                    })
+                    .map(|f| f.path.clone())
+                    .collect();
+                for mut path in removed {
+                    s.remove(&mut path).await;
+                }
+
+                for (path, file) in &self.files {
+                    if is_assigned(path, &ctx.task_info) {
+                        s.insert(path.clone(), file.clone()).await;
+                    }
+                }
+
+                if self.checkpoint(c, ctx).await {
+                    return Ok(Some(SourceFinishType::Immediate));
+                }
+            }
+            Some(ControlMessage::Stop { mode }) => {
+                info!("Stopping filesystem source: {:?}", mode);
+
+                return Ok(Some(match mode {
+                    StopMode::Graceful => SourceFinishType::Graceful,
+                    StopMode::Immediate => SourceFinishType::Immediate,
+                }));
+            }
+            Some(ControlMessage::Commit { .. }) => {
+                return Err(UserError::new(
+                    "FileSystem source does not support committing",
+                    "",
+                ));
+            }
+            Some(ControlMessage::LoadCompacted { compacted }) => {
+                ctx.load_compacted(compacted).await;
+            }
+            None => {}
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use arroyo_rpc::grpc::{CheckpointMetadata, OperatorCheckpointMetadata, StopMode};
+    use arroyo_rpc::types::{Format, JsonFormat};
+    use arroyo_rpc::{CheckpointCompleted, ControlMessage, ControlResp};
+    use arroyo_state::{BackingStore, StateBackend};
+    use arroyo_types::{to_micros, CheckpointBarrier, Message, TaskInfo};
+    use rand::Rng;
+    use serde::{Deserialize, Serialize};
+    use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+    use crate::engine::{Context, OutQueue, QueueItem};
+
+    use super::{tables, FileSystemSourceFunc};
+
+    #[derive(Debug, Clone, bincode::Encode, bincode::Decode, Serialize, Deserialize, PartialEq)]
+    struct TestData {
+        i: u64,
+    }
+
+    struct SourceWithReads {
+        to_control_tx: Sender<ControlMessage>,
+        from_control_rx: Receiver<ControlResp>,
+        data_recv: Receiver<QueueItem>,
+    }
+
+    impl SourceWithReads {
+        async fn next_message(&mut self) -> Message<(), TestData> {
+            loop {
+                let item = tokio::time::timeout(Duration::from_secs(10), self.data_recv.recv())
+                    .await
+                    .expect("timed out waiting for a message")
+                    .expect("option shouldn't be missing");
+
+                let msg: Message<(), TestData> = item.into();
+                if !matches!(msg, Message::Watermark(_)) {
+                    return msg;
+                }
+            }
+        }
+
+        async fn assert_next_record_values(&mut self, expected: impl IntoIterator<Item = u64>) {
+            for expected_value in expected {
+                match self.next_message().await {
+                    Message::Record(record) => assert_eq!(expected_value, record.value.i),
+                    msg => unreachable!("expected a record, got {:?}", msg),
+                }
+            }
+        }
+
+        async fn assert_next_message_checkpoint(&mut self, expected_epoch: u32) {
+            match self.next_message().await {
+                Message::Barrier(barrier) => assert_eq!(expected_epoch, barrier.epoch),
+                msg => unreachable!("expected a barrier, got {:?}", msg),
+            }
+        }
+
+        async fn assert_control_checkpoint(&mut self, expected_epoch: u32) -> CheckpointCompleted {
+            loop {
+                let control_response = self
+                    .from_control_rx
+                    .recv()
+                    .await
+                    .expect("should be a valid message");
+
+                if let ControlResp::CheckpointCompleted(checkpoint) = control_response {
+                    assert_eq!(expected_epoch, checkpoint.checkpoint_epoch);
+                    return checkpoint;
+                }
+            }
+        }
+    }
+
+    fn test_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "arroyo-filesystem-source-{}",
+            rand::thread_rng().gen::<u64>()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_file(dir: &PathBuf, name: &str, values: impl IntoIterator<Item = u64>) {
+        let lines: Vec<String> = values
+            .into_iter()
+            .map(|i| serde_json::to_string(&TestData { i }).unwrap())
+            .collect();
+        std::fs::write(dir.join(name), lines.join("\n")).unwrap();
+    }
+
+    /// Starts a source reading `*.json` in `dir`, sending `control` to it before it starts
+    async fn start_source(
+        dir: &PathBuf,
+        watch_interval: Option<Duration>,
+        task_info: TaskInfo,
+        restore_from: Option<u32>,
+        control: Vec<ControlMessage>,
+    ) -> SourceWithReads {
+        let mut source: FileSystemSourceFunc<(), TestData> = FileSystemSourceFunc::new(
+            &format!("{}/*.json", dir.to_str().unwrap()),
+            watch_interval,
+            Format::Json(JsonFormat::default()),
+        );
+
+        let (to_control_tx, control_rx) = channel(128);
+        let (command_tx, from_control_rx) = channel(128);
+        let (data_tx, data_recv) = channel(128);
+
+        for message in control {
+            to_control_tx.send(message).await.unwrap();
+        }
+
+        let checkpoint_metadata = restore_from.map(|epoch| CheckpointMetadata {
+            job_id: task_info.job_id.to_string(),
+            epoch,
+            min_epoch: 1,
+            start_time: to_micros(SystemTime::now()),
+            finish_time: to_micros(SystemTime::now()),
+            operator_ids: vec![task_info.operator_id.clone()],
+        });
+
+        let mut ctx: Context<(), TestData> = Context::new(
+            task_info,
+            checkpoint_metadata,
+            control_rx,
+            command_tx,
+            1,
+            vec![vec![OutQueue::new(data_tx, false)]],
+            tables(),
+        )
+        .await;
+
+        tokio::spawn(async move {
+            source.on_start(&mut ctx).await;
+            source.run(&mut ctx).await;
+        });
+
+        SourceWithReads {
+            to_control_tx,
+            from_control_rx,
+            data_recv,
+        }
+    }
+
+    fn test_task_info() -> TaskInfo {
+        let mut task_info = arroyo_types::get_test_task_info();
+        task_info.job_id = format!("filesystem-job-{}", rand::thread_rng().gen::<u64>());
+        task_info
+    }
+
+    #[tokio::test]
+    async fn test_read_files() {
+        let dir = test_dir();
+        write_file(&dir, "a.json", 0..5);
+        write_file(&dir, "b.json", 5..10);
+        write_file(&dir, "c.csv", 10..15);
+
+        let mut reader = start_source(&dir, None, test_task_info(), None, vec![]).await;
+        reader.assert_next_record_values(0..10).await;
+
+        // the source finishes once every file has been read, closing its output
+        while let Some(item) = reader.data_recv.recv().await {
+            let msg: Message<(), TestData> = item.into();
+            assert!(
+                matches!(msg, Message::Watermark(_)),
+                "expected no more records, got {:?}",
+                msg
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_from_checkpoint() {
+        let dir = test_dir();
+        write_file(&dir, "a.json", 0..5);
+        write_file(&dir, "b.json", 5..10);
+        let task_info = test_task_info();
+
+        // the barrier is handled after the first record, and the source stops once it's taken
+        let barrier = ControlMessage::Checkpoint(CheckpointBarrier {
+            epoch: 1,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: true,
+        });
+        let mut reader = start_source(&dir, None, task_info.clone(), None, vec![barrier]).await;
+
+        reader.assert_next_record_values(0..1).await;
+        let checkpoint_completed = reader.assert_control_checkpoint(1).await;
+        reader.assert_next_message_checkpoint(1).await;
+
+        StateBackend::write_operator_checkpoint_metadata(OperatorCheckpointMetadata {
+            job_id: task_info.job_id.clone(),
+            operator_id: task_info.operator_id.clone(),
+            epoch: 1,
+            start_time: 0,
+            finish_time: 0,
+            min_watermark: Some(0),
+            max_watermark: Some(0),
+            has_state: true,
+            tables: tables(),
+            backend_data: checkpoint_completed.subtask_metadata.backend_data,
+            bytes: checkpoint_completed.subtask_metadata.bytes,
+        })
+        .await;
+
+        StateBackend::complete_checkpoint(CheckpointMetadata {
+            job_id: task_info.job_id.clone(),
+            epoch: 1,
+            min_epoch: 1,
+            start_time: 0,
+            finish_time: 0,
+            operator_ids: vec![task_info.operator_id.clone()],
+        })
+        .await;
+
+        let mut reader = start_source(&dir, None, task_info, Some(1), vec![]).await;
+        reader.assert_next_record_values(1..10).await;
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let dir = test_dir();
+        write_file(&dir, "a.json", 0..5);
+
+        let mut reader = start_source(
+            &dir,
+            Some(Duration::from_secs(1)),
+            test_task_info(),
+            None,
+            vec![],
+        )
+        .await;
+        reader.assert_next_record_values(0..5).await;
+
+        // files that have already been read aren't read again
+        write_file(&dir, "b.json", 5..10);
+        reader.assert_next_record_values(5..10).await;
+
+        reader
+            .to_control_tx
+            .send(ControlMessage::Stop {
+                mode: StopMode::Graceful,
+            })
+            .await
+            .unwrap();
+    }
+}
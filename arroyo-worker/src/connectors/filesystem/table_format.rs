@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use arroyo_types::{Data, Key, Record, RecordBatchBuilder, TaskInfo};
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+
+use crate::connectors::two_phase_committer::TwoPhaseCommitter;
+
+use super::{FileSystemDataRecovery, FileToFinish, ParquetFileSystemSink};
+
+/// A table format, like Delta Lake or Iceberg, that the parquet files written by a
+/// [`TableFormatSink`] are registered in once their checkpoint completes
+#[async_trait]
+pub trait TableFormat: Send + 'static {
+    fn name(&self) -> String;
+
+    /// Adds `files` to the table on behalf of `app_id`, doing nothing if `app_id` has already
+    /// committed `epoch` (or a later one) to the table
+    async fn commit(&mut self, app_id: &str, epoch: u32, files: &[FileToFinish]) -> Result<()>;
+}
+
+/// Writes parquet files like the [`ParquetFileSystemSink`], then commits them to a table format.
+/// Commits are made under an app id per subtask along with the checkpoint epoch, so commits that
+/// are replayed after a failure are skipped.
+pub struct TableFormatSink<
+    K: Key,
+    T: Data + Sync,
+    R: RecordBatchBuilder<Data = T> + 'static,
+    F: TableFormat,
+> {
+    sink: ParquetFileSystemSink<K, T, R>,
+    table: F,
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
+pub struct TablePreCommit {
+    subtask_index: usize,
+    file: FileToFinish,
+}
+
+impl<K: Key, T: Data + Sync, R: RecordBatchBuilder<Data = T> + 'static, F: TableFormat>
+    TableFormatSink<K, T, R, F>
+{
+    pub(crate) fn new(sink: ParquetFileSystemSink<K, T, R>, table: F) -> Self {
+        Self { sink, table }
+    }
+}
+
+#[async_trait]
+impl<K: Key, T: Data + Sync, R: RecordBatchBuilder<Data = T> + 'static, F: TableFormat>
+    TwoPhaseCommitter<K, T> for TableFormatSink<K, T, R, F>
+{
+    type DataRecovery = FileSystemDataRecovery<T>;
+    type PreCommit = TablePreCommit;
+
+    fn name(&self) -> String {
+        self.table.name()
+    }
+
+    async fn init(
+        &mut self,
+        task_info: &TaskInfo,
+        data_recovery: Vec<Self::DataRecovery>,
+    ) -> Result<()> {
+        self.sink.init(task_info, data_recovery).await
+    }
+
+    async fn insert_record(&mut self, record: &Record<K, T>) -> Result<()> {
+        self.sink.insert_record(record).await
+    }
+
+    async fn commit(
+        &mut self,
+        task_info: &TaskInfo,
+        epoch: u32,
+        pre_commit: Vec<Self::PreCommit>,
+    ) -> Result<()> {
+        self.sink
+            .commit(
+                task_info,
+                epoch,
+                pre_commit.iter().map(|p| p.file.clone()).collect(),
+            )
+            .await?;
+
+        // after a restart subtask 0 commits the files of every subtask, so the files are
+        // committed under the app id of the subtask that wrote them
+        let mut files_by_subtask: BTreeMap<usize, Vec<FileToFinish>> = BTreeMap::new();
+        for TablePreCommit {
+            subtask_index,
+            file,
+        } in pre_commit
+        {
+            if !file.completed_parts.is_empty() {
+                files_by_subtask
+                    .entry(subtask_index)
+                    .or_default()
+                    .push(file);
+            }
+        }
+
+        for (subtask_index, files) in files_by_subtask {
+            let app_id = format!(
+                "arroyo-{}-{}-{}",
+                task_info.job_id, task_info.operator_id, subtask_index
+            );
+            self.table.commit(&app_id, epoch, &files).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn checkpoint(
+        &mut self,
+        task_info: &TaskInfo,
+        stopping: bool,
+    ) -> Result<(Self::DataRecovery, HashMap<String, Self::PreCommit>)> {
+        let (recovery, pre_commits) = self.sink.checkpoint(task_info, stopping).await?;
+        let pre_commits = pre_commits
+            .into_iter()
+            .map(|(name, file)| {
+                (
+                    name,
+                    TablePreCommit {
+                        subtask_index: task_info.task_index,
+                        file,
+                    },
+                )
+            })
+            .collect();
+        Ok((recovery, pre_commits))
+    }
+}
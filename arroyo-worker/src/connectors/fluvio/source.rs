@@ -1,4 +1,6 @@
+use crate::connectors::bad_data::BadDataHandler;
 use crate::engine::{Context, StreamNode};
+use crate::formats::DataDeserializer;
 use crate::SourceFinishType;
 use anyhow::anyhow;
 use arroyo_macro::source_fn;
@@ -31,7 +33,8 @@ where
     topic: String,
     endpoint: Option<String>,
     offset_mode: SourceOffset,
-    format: Format,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
     _t: PhantomData<(K, T)>,
 }
 
@@ -61,7 +64,8 @@ where
             topic: topic.to_string(),
             endpoint: endpoint.map(|e| e.to_string()),
             offset_mode,
-            format,
+            deserializer: DataDeserializer::new(format),
+            bad_data: BadDataHandler::default(),
             _t: PhantomData,
         }
     }
@@ -69,6 +73,7 @@ where
     pub fn from_config(config: &str) -> Self {
         let config: OperatorConfig =
             serde_json::from_str(config).expect("Invalid config for FluvioSource");
+        let bad_data = BadDataHandler::from_config(&config);
         let table: FluvioTable =
             serde_json::from_value(config.table).expect("Invalid table config for FluvioSource");
         let TableType::Source { offset, .. } = &table.type_ else {
@@ -79,7 +84,10 @@ where
             topic: table.topic,
             endpoint: table.endpoint.clone(),
             offset_mode: *offset,
-            format: config.format.expect("Format must be specified for fluvio"),
+            deserializer: DataDeserializer::new(
+                config.format.expect("Format must be specified for fluvio"),
+            ),
+            bad_data,
             _t: PhantomData,
         }
     }
@@ -194,11 +202,14 @@ where
                 message = streams.next() => {
                     match message {
                         Some((_, Ok(msg))) => {
-                            ctx.collector.collect(Record {
-                                timestamp: from_millis(msg.timestamp().max(0) as u64),
-                                key: None,
-                                value: crate::formats::deserialize_slice(&self.format, msg.value())?,
-                            }).await;
+                            let values = self.deserializer.deserialize_slice(msg.value()).await;
+                            for value in self.bad_data.handle(ctx, msg.value(), values).await.unwrap_or_default() {
+                                ctx.collector.collect(Record {
+                                    timestamp: from_millis(msg.timestamp().max(0) as u64),
+                                    key: None,
+                                    value,
+                                }).await;
+                            }
                             offsets.insert(msg.partition(), msg.offset());
                         },
                         Some((p, Err(e))) => {
@@ -1,41 +1,55 @@
 use std::collections::HashMap;
 
-use rdkafka::Offset;
 use serde::{Deserialize, Serialize};
 use typify::import_types;
 
 pub mod sink;
 pub mod source;
 
-import_types!(schema = "../connector-schemas/kafka/connection.json");
 import_types!(schema = "../connector-schemas/kafka/table.json");
 
-impl SourceOffset {
-    fn get_offset(&self) -> Offset {
-        match self {
-            SourceOffset::Earliest => Offset::Beginning,
-            SourceOffset::Latest => Offset::End,
-        }
-    }
+pub use arroyo_rpc::kafka::{
+    client_configs, KafkaConfig, KafkaConfigAuthentication, SchemaRegistry,
+};
+
+/// Parses start offsets like `0:1500,1:1620`, where each entry may also be prefixed with a topic
+/// (like `events:0:1500`) to only apply to the partition of that topic
+pub fn parse_start_offsets(offsets: &str) -> Result<HashMap<(Option<String>, i32), i64>, String> {
+    offsets
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let parts: Vec<_> = entry.split(':').collect();
+            let (topic, partition, offset) = match parts.as_slice() {
+                [partition, offset] => (None, partition, offset),
+                [topic, partition, offset] => (Some(topic.to_string()), partition, offset),
+                _ => return Err(format!("invalid start offset '{}'", entry)),
+            };
+            let partition = partition
+                .parse()
+                .map_err(|_| format!("invalid partition in start offset '{}'", entry))?;
+            let offset = offset
+                .parse()
+                .map_err(|_| format!("invalid offset in start offset '{}'", entry))?;
+            Ok(((topic, partition), offset))
+        })
+        .collect()
 }
 
-pub fn client_configs(connection: &KafkaConfig) -> HashMap<String, String> {
-    let mut client_configs: HashMap<String, String> = HashMap::new();
-
-    match &connection.authentication {
-        KafkaConfigAuthentication::None {} => {}
-        KafkaConfigAuthentication::Sasl {
-            mechanism,
-            password,
-            protocol,
-            username,
-        } => {
-            client_configs.insert("sasl.mechanism".to_string(), mechanism.to_string());
-            client_configs.insert("security.protocol".to_string(), protocol.to_string());
-            client_configs.insert("sasl.username".to_string(), username.to_string());
-            client_configs.insert("sasl.password".to_string(), password.to_string());
-        }
-    };
-
-    client_configs
+#[cfg(test)]
+mod tests {
+    use super::parse_start_offsets;
+
+    #[test]
+    fn test_parse_start_offsets() {
+        let offsets = parse_start_offsets("0:1500, 1:1620,events:1:20").unwrap();
+        assert_eq!(offsets.len(), 3);
+        assert_eq!(offsets[&(None, 0)], 1500);
+        assert_eq!(offsets[&(None, 1)], 1620);
+        assert_eq!(offsets[&(Some("events".to_string()), 1)], 20);
+
+        assert!(parse_start_offsets("0").is_err());
+        assert!(parse_start_offsets("0:latest").is_err());
+    }
 }
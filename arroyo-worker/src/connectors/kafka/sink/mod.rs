@@ -1,9 +1,12 @@
+use crate::connectors::template::{parse_template, render_template, TemplatePart};
 use crate::engine::{Context, StreamNode};
+use crate::formats::fields::extract_fields;
 use crate::formats::DataSerializer;
 use crate::SchemaData;
 use anyhow::Result;
 use arroyo_macro::process_fn;
 use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableWriteBehavior};
+use arroyo_rpc::schema_resolver::ConfluentSchemaRegistry;
 use arroyo_rpc::types::Format;
 use arroyo_rpc::{CheckpointEvent, ControlMessage, OperatorConfig};
 use arroyo_types::*;
@@ -12,6 +15,7 @@ use std::marker::PhantomData;
 
 use tracing::{error, warn};
 
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
 use rdkafka::util::Timeout;
 
@@ -21,9 +25,13 @@ use arroyo_types::CheckpointBarrier;
 use rdkafka::error::KafkaError;
 use rdkafka_sys::RDKafkaErrorCode;
 use serde::Serialize;
+use serde_json::Value;
 use std::time::{Duration, SystemTime};
 
-use super::{client_configs, KafkaConfig, KafkaTable, SinkCommitMode, TableType};
+use super::{
+    client_configs, KafkaConfig, KafkaTable, SchemaRegistry, SinkCommitMode, SinkPartitioner,
+    TableType,
+};
 
 #[cfg(test)]
 mod test;
@@ -37,9 +45,117 @@ pub struct KafkaSinkFunc<K: Key + Serialize, T: SchemaData> {
     write_futures: Vec<DeliveryFuture>,
     client_config: HashMap<String, String>,
     serializer: DataSerializer<T>,
+    schema_registry: Option<SchemaRegistry>,
+    key: MessageKey,
+    headers_field: Option<String>,
+    partitioning: Partitioning,
+    // the columns that keys, headers and partitions are read from
+    value_fields: Vec<String>,
     _t: PhantomData<K>,
 }
 
+/// Where the key of each message comes from
+enum MessageKey {
+    /// The key of the dataflow, serialized as JSON
+    Dataflow,
+    /// The value of a column
+    Field(String),
+    /// A template with the values of columns substituted in
+    Template(Vec<TemplatePart>),
+}
+
+impl MessageKey {
+    fn new(key_field: Option<String>, key_template: Option<String>) -> Self {
+        match (key_field, key_template) {
+            (Some(field), _) => MessageKey::Field(field),
+            (None, Some(template)) => MessageKey::Template(parse_template(&template)),
+            (None, None) => MessageKey::Dataflow,
+        }
+    }
+}
+
+/// Strings are written as-is and other values as JSON
+fn value_bytes(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.as_bytes().to_vec()),
+        other => Some(other.to_string().into_bytes()),
+    }
+}
+
+/// Converts a map or struct column to headers. Maps may be serialized either as objects or as
+/// lists of key/value pairs.
+fn headers_from_value(value: &Value) -> Option<OwnedHeaders> {
+    let entries: Vec<(String, Option<Vec<u8>>)> = match value {
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(k, v)| (k.clone(), value_bytes(v)))
+            .collect(),
+        Value::Array(entries) => entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Value::Array(pair) if pair.len() == 2 => {
+                    Some((pair[0].as_str()?.to_string(), value_bytes(&pair[1])))
+                }
+                Value::Object(pair) => Some((
+                    pair.get("key")?.as_str()?.to_string(),
+                    pair.get("value").and_then(value_bytes),
+                )),
+                _ => None,
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    Some(
+        entries
+            .iter()
+            .fold(OwnedHeaders::new(), |headers, (key, value)| {
+                headers.insert(Header {
+                    key,
+                    value: value.as_deref(),
+                })
+            }),
+    )
+}
+
+/// Returns the columns that the key, headers and partition of each message are read from
+fn value_fields(
+    key: &MessageKey,
+    headers_field: &Option<String>,
+    partitioning: &Partitioning,
+) -> Vec<String> {
+    let mut fields = vec![];
+    match key {
+        MessageKey::Dataflow => {}
+        MessageKey::Field(field) => fields.push(field.clone()),
+        MessageKey::Template(parts) => fields.extend(parts.iter().filter_map(|p| match p {
+            TemplatePart::Field(field) => Some(field.clone()),
+            TemplatePart::Literal(_) => None,
+        })),
+    }
+    fields.extend(headers_field.clone());
+    if let Partitioning::Field { field, .. } = partitioning {
+        fields.push(field.clone());
+    }
+    fields
+}
+
+/// How messages are assigned to partitions
+enum Partitioning {
+    /// Use the partitioner configured for the producer
+    Default,
+    RoundRobin {
+        partitions: i32,
+        next: i32,
+    },
+    /// Use the partition in a column; records without a valid partition are reported and dropped
+    Field {
+        field: String,
+        partitions: i32,
+    },
+}
+
 enum ConsistencyMode {
     AtLeastOnce,
     ExactlyOnce {
@@ -78,6 +194,11 @@ impl<K: Key + Serialize, T: SchemaData> KafkaSinkFunc<K, T> {
                 .map(|(key, value)| (key.to_string(), value.to_string()))
                 .collect(),
             serializer: DataSerializer::new(format),
+            schema_registry: None,
+            key: MessageKey::Dataflow,
+            headers_field: None,
+            partitioning: Partitioning::Default,
+            value_fields: vec![],
             _t: PhantomData,
         }
     }
@@ -89,20 +210,55 @@ impl<K: Key + Serialize, T: SchemaData> KafkaSinkFunc<K, T> {
             .expect("Invalid connection config for KafkaSink");
         let table: KafkaTable =
             serde_json::from_value(config.table).expect("Invalid table config for KafkaSource");
-        let TableType::Sink { commit_mode } = table.type_ else {
+        let TableType::Sink {
+            commit_mode,
+            key_field,
+            key_template,
+            headers_field,
+            partitioner,
+            partition_field,
+        } = table.type_
+        else {
             panic!("found non-sink kafka config in sink operator");
         };
 
+        let mut client_config = client_configs(&connection);
+        let partitioning = match partitioner.unwrap_or(SinkPartitioner::Default) {
+            SinkPartitioner::Default => Partitioning::Default,
+            SinkPartitioner::Hash => {
+                // the same partitioner as the Java client, so that messages are co-partitioned
+                // with topics written by other producers
+                client_config.insert("partitioner".to_string(), "murmur2_random".to_string());
+                Partitioning::Default
+            }
+            SinkPartitioner::RoundRobin => Partitioning::RoundRobin {
+                partitions: 0,
+                next: 0,
+            },
+            SinkPartitioner::Field => Partitioning::Field {
+                field: partition_field
+                    .expect("partition_field must be set for the field partitioner"),
+                partitions: 0,
+            },
+        };
+        let key = MessageKey::new(key_field, key_template);
+        let value_fields = value_fields(&key, &headers_field, &partitioning);
+
         Self {
             topic: table.topic,
             bootstrap_servers: connection.bootstrap_servers.to_string(),
             producer: None,
             consistency_mode: commit_mode.unwrap_or(SinkCommitMode::AtLeastOnce).into(),
             write_futures: vec![],
-            client_config: client_configs(&connection),
+            client_config,
             serializer: DataSerializer::new(
                 config.format.expect("Format must be defined for KafkaSink"),
             ),
+            schema_registry: connection.schema_registry,
+            key,
+            headers_field,
+            partitioning,
+            value_fields,
             _t: PhantomData,
         }
     }
@@ -115,8 +271,72 @@ impl<K: Key + Serialize, T: SchemaData + Serialize> KafkaSinkFunc<K, T> {
     }
 
     async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        if let Some((schema, schema_type)) = self.serializer.confluent_schema() {
+            let Some(schema_registry) = &self.schema_registry else {
+                panic!("Format is configured to use Confluent Schema Registry, but no schema registry is configured for the connection");
+            };
+
+            let schema_registry = match ConfluentSchemaRegistry::new(
+                &schema_registry.endpoint,
+                &self.topic,
+                schema_registry.api_key.clone(),
+                schema_registry.api_secret.clone(),
+            ) {
+                Ok(schema_registry) => schema_registry,
+                Err(e) => {
+                    ctx.report_error(
+                        "Failed to construct schema registry client".to_string(),
+                        e.to_string(),
+                    )
+                    .await;
+                    panic!("Failed to construct schema registry client: {:?}", e);
+                }
+            };
+
+            match schema_registry.write_schema(schema, schema_type).await {
+                Ok(id) => self.serializer.set_schema_id(id),
+                Err(e) => {
+                    ctx.report_error("Failed to register schema".to_string(), e.to_string())
+                        .await;
+                    panic!("Failed to register schema with schema registry: {:?}", e);
+                }
+            }
+        }
+
         self.init_producer(&ctx.task_info)
             .expect("Producer creation failed");
+
+        if matches!(self.partitioning, Partitioning::Default) {
+            return;
+        }
+
+        let metadata = self
+            .producer
+            .as_ref()
+            .unwrap()
+            .client()
+            .fetch_metadata(Some(&self.topic), Timeout::After(Duration::from_secs(30)))
+            .expect("failed to fetch metadata for topic");
+        let partition_count = metadata
+            .topics()
+            .first()
+            .map(|t| t.partitions().len() as i32)
+            .unwrap_or_default();
+        if partition_count == 0 {
+            panic!("topic {} has no partitions", self.topic);
+        }
+
+        match &mut self.partitioning {
+            Partitioning::Default => {}
+            Partitioning::RoundRobin { partitions, next } => {
+                *partitions = partition_count;
+                // start each subtask at a different partition to spread out the load
+                *next = ctx.task_info.task_index as i32 % partition_count;
+            }
+            Partitioning::Field { partitions, .. } => {
+                *partitions = partition_count;
+            }
+        }
     }
 
     fn is_committing(&self) -> bool {
@@ -211,14 +431,26 @@ impl<K: Key + Serialize, T: SchemaData + Serialize> KafkaSinkFunc<K, T> {
         }
     }
 
-    async fn publish(&mut self, k: Option<String>, v: Vec<u8>) {
-        let mut rec = {
-            if let Some(k) = k.as_ref() {
-                FutureRecord::to(&self.topic).key(k).payload(&v)
-            } else {
-                FutureRecord::to(&self.topic).payload(&v)
-            }
-        };
+    async fn publish(
+        &mut self,
+        k: Option<Vec<u8>>,
+        v: Option<Vec<u8>>,
+        headers: Option<OwnedHeaders>,
+        partition: Option<i32>,
+    ) {
+        let mut rec = FutureRecord::<Vec<u8>, Vec<u8>>::to(&self.topic);
+        if let Some(v) = v.as_ref() {
+            rec = rec.payload(v);
+        }
+        if let Some(k) = k.as_ref() {
+            rec = rec.key(k);
+        }
+        if let Some(headers) = headers {
+            rec = rec.headers(headers);
+        }
+        if let Some(partition) = partition {
+            rec = rec.partition(partition);
+        }
 
         loop {
             match self.producer.as_mut().unwrap().send_result(rec) {
@@ -239,16 +471,97 @@ impl<K: Key + Serialize, T: SchemaData + Serialize> KafkaSinkFunc<K, T> {
         }
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, _ctx: &mut Context<(), ()>) {
-        let k = record
-            .key
-            .as_ref()
-            .map(|k| serde_json::to_string(k).unwrap());
-        let v = self.serializer.to_vec(&record.value);
+    /// Returns the columns of the value that keys, headers and partitions are read from; only
+    /// those are converted, as the record is serialized separately
+    fn extract_value_fields(&self, value: &T) -> Value {
+        if self.value_fields.is_empty() {
+            Value::Null
+        } else {
+            extract_fields(value, &self.value_fields).unwrap()
+        }
+    }
+
+    fn message_key(&self, key: Option<&K>, value: &Value) -> Option<Vec<u8>> {
+        match &self.key {
+            MessageKey::Dataflow => key.map(|k| serde_json::to_vec(k).unwrap()),
+            MessageKey::Field(field) => value.get(field).and_then(value_bytes),
+            MessageKey::Template(parts) => Some(render_template(parts, value).into_bytes()),
+        }
+    }
 
-        if let Some(v) = v {
-            self.publish(k, v).await;
+    /// Whether the message key of an update's old value differs from that of its new value.
+    /// Updates that change the dataflow key are split into a retraction and an append before
+    /// they reach the sink, but keys read from columns are checked against both values.
+    fn key_changed(&self, key: Option<&K>, old: &T, new: &T) -> bool {
+        if matches!(self.key, MessageKey::Dataflow) {
+            return false;
         }
+        self.message_key(key, &self.extract_value_fields(old))
+            != self.message_key(key, &self.extract_value_fields(new))
+    }
+
+    /// Writes a message for the value. Tombstones are written without a payload, but take their
+    /// key, headers and partition from the value like other messages.
+    async fn write(
+        &mut self,
+        key: Option<&K>,
+        value: &T,
+        tombstone: bool,
+        ctx: &mut Context<(), ()>,
+    ) {
+        let v = if tombstone {
+            None
+        } else {
+            let Some(v) = self.serializer.to_vec_or_report(value, ctx).await else {
+                return;
+            };
+            Some(v)
+        };
+
+        let value = self.extract_value_fields(value);
+        let k = self.message_key(key, &value);
+
+        let headers = self
+            .headers_field
+            .as_ref()
+            .and_then(|field| headers_from_value(value.get(field)?));
+
+        let partition = match &mut self.partitioning {
+            Partitioning::Default => None,
+            Partitioning::RoundRobin { partitions, next } => {
+                let partition = *next;
+                *next = (*next + 1) % *partitions;
+                Some(partition)
+            }
+            Partitioning::Field { field, partitions } => {
+                let partition = value.get(field.as_str()).unwrap_or(&Value::Null);
+                match partition
+                    .as_i64()
+                    .and_then(|p| i32::try_from(p).ok())
+                    .filter(|p| (0..*partitions).contains(p))
+                {
+                    Some(partition) => Some(partition),
+                    None => {
+                        ctx.report_user_error(UserError::new(
+                            "Invalid partition",
+                            format!(
+                                "partition_field '{}' must be a partition of topic {} between 0 and {}, but was {}",
+                                field, self.topic, *partitions - 1, partition
+                            ),
+                        ))
+                        .await;
+                        return;
+                    }
+                }
+            }
+        };
+
+        self.publish(k, v, headers, partition).await;
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        self.write(record.key.as_ref(), &record.value, false, ctx)
+            .await;
     }
 
     async fn handle_commit(&mut self, epoch: u32, ctx: &mut crate::engine::Context<(), ()>) {
@@ -302,3 +615,81 @@ impl<K: Key + Serialize, T: SchemaData + Serialize> KafkaSinkFunc<K, T> {
         }
     }
 }
+
+/// Writes updating data, keyed by the primary key of the table, as a changelog for compacted
+/// topics: appends and updates write the new value, and retractions and updates that change the
+/// key write a tombstone for the old key
+#[derive(StreamNode)]
+pub struct KafkaUpsertSinkFunc<K: Key + Serialize, T: SchemaData> {
+    sink: KafkaSinkFunc<K, T>,
+}
+
+impl<K: Key + Serialize, T: SchemaData> KafkaUpsertSinkFunc<K, T> {
+    pub fn new(
+        servers: &str,
+        topic: &str,
+        format: Format,
+        client_config: Vec<(&str, &str)>,
+    ) -> Self {
+        Self {
+            sink: KafkaSinkFunc::new(servers, topic, format, client_config),
+        }
+    }
+
+    pub fn from_config(config: &str) -> Self {
+        Self {
+            sink: KafkaSinkFunc::from_config(config),
+        }
+    }
+}
+
+#[process_fn(in_k = K, in_t = UpdatingData<T>)]
+impl<K: Key + Serialize, T: SchemaData + Serialize> KafkaUpsertSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("kafka-upsert-producer-{}", self.sink.topic)
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        self.sink.on_start(ctx).await;
+    }
+
+    fn tables(&self) -> Vec<arroyo_rpc::grpc::TableDescriptor> {
+        self.sink.tables()
+    }
+
+    async fn handle_checkpoint(&mut self, barrier: &CheckpointBarrier, ctx: &mut Context<(), ()>) {
+        self.sink.handle_checkpoint(barrier, ctx).await;
+    }
+
+    async fn process_element(
+        &mut self,
+        record: &Record<K, UpdatingData<T>>,
+        ctx: &mut Context<(), ()>,
+    ) {
+        match &record.value {
+            UpdatingData::Append(value) => {
+                self.sink
+                    .write(record.key.as_ref(), value, false, ctx)
+                    .await;
+            }
+            UpdatingData::Update { old, new } => {
+                // the old key would otherwise keep its value after compaction
+                if self.sink.key_changed(record.key.as_ref(), old, new) {
+                    self.sink.write(record.key.as_ref(), old, true, ctx).await;
+                }
+                self.sink.write(record.key.as_ref(), new, false, ctx).await;
+            }
+            UpdatingData::Retract(value) => {
+                self.sink.write(record.key.as_ref(), value, true, ctx).await;
+            }
+        }
+    }
+
+    async fn handle_commit(&mut self, epoch: u32, ctx: &mut crate::engine::Context<(), ()>) {
+        self.sink.handle_commit(epoch, ctx).await;
+    }
+
+    async fn on_close(&mut self, ctx: &mut crate::engine::Context<(), ()>) {
+        self.sink.on_close(ctx).await;
+    }
+}
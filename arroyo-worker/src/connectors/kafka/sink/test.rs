@@ -13,7 +13,7 @@ use rdkafka::producer::Producer;
 use rdkafka::{ClientConfig, Message};
 use tokio::sync::mpsc::channel;
 
-use super::KafkaSinkFunc;
+use super::{headers_from_value, KafkaSinkFunc, KafkaUpsertSinkFunc, MessageKey};
 
 pub struct KafkaTopicTester {
     topic: String,
@@ -54,27 +54,27 @@ impl KafkaTopicTester {
             Format::Json(JsonFormat::default()),
             vec![],
         );
-        let (_, control_rx) = channel(128);
-        let (command_tx, _) = channel(128);
-        let (data_tx, _recv) = channel(128);
-
-        let task_info = arroyo_types::get_test_task_info();
-
-        let mut ctx: Context<(), ()> = Context::new(
-            task_info,
-            None,
-            control_rx,
-            command_tx,
-            1,
-            vec![vec![OutQueue::new(data_tx, false)]],
-            vec![],
-        )
-        .await;
+        let mut ctx = sink_context().await;
         kafka.on_start(&mut ctx).await;
 
         KafkaSinkWithWrites { sink: kafka, ctx }
     }
 
+    async fn get_upsert_sink_with_writes(
+        &self,
+    ) -> (KafkaUpsertSinkFunc<String, TestOutStruct>, Context<(), ()>) {
+        let mut kafka = KafkaUpsertSinkFunc::new(
+            &self.server,
+            &self.topic,
+            Format::Json(JsonFormat::default()),
+            vec![],
+        );
+        let mut ctx = sink_context().await;
+        kafka.on_start(&mut ctx).await;
+
+        (kafka, ctx)
+    }
+
     fn get_consumer(&mut self, job_id: &str) -> StreamConsumer {
         let base_consumer: StreamConsumer = ClientConfig::new()
             .set("bootstrap.servers", self.server.to_string())
@@ -91,6 +91,25 @@ impl KafkaTopicTester {
     }
 }
 
+async fn sink_context() -> Context<(), ()> {
+    let (_, control_rx) = channel(128);
+    let (command_tx, _) = channel(128);
+    let (data_tx, _recv) = channel(128);
+
+    let task_info = arroyo_types::get_test_task_info();
+
+    Context::new(
+        task_info,
+        None,
+        control_rx,
+        command_tx,
+        1,
+        vec![vec![OutQueue::new(data_tx, false)]],
+        vec![],
+    )
+    .await
+}
+
 async fn get_data(consumer: &mut StreamConsumer) -> Record<String, String> {
     let owned_message = consumer
         .recv()
@@ -142,6 +161,10 @@ impl SchemaData for TestOutStruct {
     fn to_raw_string(&self) -> Option<Vec<u8>> {
         unimplemented!()
     }
+
+    fn to_raw_bytes(&self) -> Option<Vec<u8>> {
+        unimplemented!()
+    }
 }
 
 struct KafkaSinkWithWrites {
@@ -226,3 +249,94 @@ async fn test_kafka() {
         assert_eq!(record.value, result);
     }
 }
+
+#[tokio::test]
+async fn test_kafka_upsert() {
+    let mut kafka_topic_tester = KafkaTopicTester {
+        topic: "arroyo-sink-upsert".to_string(),
+        server: "0.0.0.0:9092".to_string(),
+    };
+
+    kafka_topic_tester.create_topic("upsert", 1).await;
+    let (mut sink, mut ctx) = kafka_topic_tester.get_upsert_sink_with_writes().await;
+    let mut consumer = kafka_topic_tester.get_consumer("2");
+
+    let updates = [
+        UpdatingData::Append("a".to_string().into()),
+        UpdatingData::Update {
+            old: "a".to_string().into(),
+            new: "b".to_string().into(),
+        },
+        UpdatingData::Retract("b".to_string().into()),
+    ];
+
+    for update in updates {
+        let mut record = Record {
+            timestamp: SystemTime::now(),
+            key: Some("k".to_string()),
+            value: update,
+        };
+        sink.process_element(&mut record, &mut ctx).await;
+    }
+    sink.sink
+        .producer
+        .as_ref()
+        .unwrap()
+        .flush(Duration::from_secs(1))
+        .unwrap();
+
+    let mut messages = vec![];
+    for _ in 0..3 {
+        let message = consumer.recv().await.unwrap().detach();
+        assert_eq!(message.key(), Some("\"k\"".as_bytes()));
+        messages.push(
+            message
+                .payload()
+                .map(|p| serde_json::from_slice::<TestOutStruct>(p).unwrap().t),
+        );
+    }
+
+    // the retraction is written as a tombstone
+    assert_eq!(
+        messages,
+        vec![Some("a".to_string()), Some("b".to_string()), None]
+    );
+}
+
+#[test]
+fn test_upsert_key_changed() {
+    let mut sink: KafkaSinkFunc<String, TestOutStruct> = KafkaSinkFunc::new(
+        "localhost:9092",
+        "arroyo-sink-upsert",
+        Format::Json(JsonFormat::default()),
+        vec![],
+    );
+    let key = "k".to_string();
+    let (a, b): (TestOutStruct, TestOutStruct) = ("a".to_string().into(), "b".to_string().into());
+
+    // updates keep their dataflow key
+    assert!(!sink.key_changed(Some(&key), &a, &b));
+
+    sink.key = MessageKey::Field("t".to_string());
+    sink.value_fields = vec!["t".to_string()];
+    assert!(sink.key_changed(Some(&key), &a, &b));
+    assert!(!sink.key_changed(Some(&key), &a, &a));
+}
+
+#[test]
+fn test_headers_from_map() {
+    use rdkafka::message::Headers;
+
+    for value in [
+        serde_json::json!({"trace_id": "abc", "span_id": null}),
+        serde_json::json!([["trace_id", "abc"], ["span_id", null]]),
+        serde_json::json!([{"key": "trace_id", "value": "abc"}, {"key": "span_id"}]),
+    ] {
+        let headers = headers_from_value(&value).unwrap();
+        assert_eq!(headers.count(), 2);
+        let trace_id = headers.iter().find(|h| h.key == "trace_id").unwrap();
+        assert_eq!(trace_id.value, Some("abc".as_bytes()));
+        let span_id = headers.iter().find(|h| h.key == "span_id").unwrap();
+        assert_eq!(span_id.value, None);
+    }
+}
@@ -1,30 +1,44 @@
+use crate::connectors::bad_data::BadDataHandler;
 use crate::engine::{Context, StreamNode};
-use crate::formats;
+use crate::formats::metadata::MetadataValue;
+use crate::formats::DataDeserializer;
 use crate::SourceFinishType;
+use anyhow::anyhow;
 use arroyo_macro::source_fn;
-use arroyo_rpc::grpc::TableDescriptor;
-use arroyo_rpc::types::Format;
-use arroyo_rpc::OperatorConfig;
-use arroyo_rpc::{grpc::StopMode, ControlMessage, ControlResp};
+use arroyo_rpc::grpc::{TableDescriptor, TableWriteBehavior};
+use arroyo_rpc::schema_resolver::{ConfluentSchemaRegistry, FailingSchemaResolver, SchemaResolver};
+use arroyo_rpc::types::{Format, PrimitiveType};
+use arroyo_rpc::{grpc::StopMode, CheckpointEvent, ControlMessage, ControlResp};
+use arroyo_rpc::{MetadataField, OperatorConfig};
 use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
 use arroyo_types::*;
 use bincode::{Decode, Encode};
 use governor::{Quota, RateLimiter};
 use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{BorrowedMessage, Headers};
 use rdkafka::{ClientConfig, Message as KMessage, Offset, TopicPartitionList};
+use regex::Regex;
 use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::PhantomData;
 use std::num::NonZeroU32;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::select;
 use tracing::{debug, error, info, warn};
 
-use super::{client_configs, KafkaConfig, KafkaTable, ReadMode, TableType};
+use super::{
+    client_configs, parse_start_offsets, KafkaConfig, KafkaTable, ReadMode, SourceOffset,
+    Subscription, TableType,
+};
 
 #[cfg(test)]
 mod test;
 
+// how often sources check for new topics and partitions
+const TOPIC_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(StreamNode, Clone)]
 pub struct KafkaSourceFunc<K, T>
 where
@@ -32,25 +46,102 @@ where
     T: DeserializeOwned + Data,
 {
     topic: String,
+    topics: Topics,
     bootstrap_servers: String,
-    offset_mode: super::SourceOffset,
-    format: Format,
+    offset_mode: SourceOffset,
+    start_timestamp: Option<i64>,
+    start_offsets: HashMap<(Option<String>, i32), i64>,
+    group_id: Option<String>,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
     client_configs: HashMap<String, String>,
     messages_per_second: NonZeroU32,
+    metadata_fields: Vec<MetadataField>,
     _t: PhantomData<(K, T)>,
 }
 
+/// The topics that a source reads from
+#[derive(Clone, Debug)]
+enum Topics {
+    List(Vec<String>),
+    /// All topics with matching names, including ones created while the source is running
+    Pattern(Regex),
+}
+
+impl Topics {
+    fn new(topic: &str, subscription: Subscription) -> Self {
+        match subscription {
+            Subscription::Topics => Topics::List(
+                topic
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect(),
+            ),
+            Subscription::Pattern => Topics::Pattern(
+                Regex::new(&format!("^(?:{})$", topic)).expect("invalid kafka topic pattern"),
+            ),
+        }
+    }
+
+    fn matches(&self, topic: &str) -> bool {
+        match self {
+            Topics::List(topics) => topics.iter().any(|t| t == topic),
+            // internal topics (like __consumer_offsets) are only read if they're listed
+            Topics::Pattern(pattern) => !topic.starts_with("__") && pattern.is_match(topic),
+        }
+    }
+
+    fn single(&self) -> Option<&str> {
+        match self {
+            Topics::List(topics) if topics.len() == 1 => Some(&topics[0]),
+            _ => None,
+        }
+    }
+
+    /// The subtask that reads a partition. The partitions of a single topic are assigned the way
+    /// they always have been, so restored pipelines keep reading the partitions they have state
+    /// for; with several topics, the partitions of each are spread across the subtasks starting
+    /// from a subtask picked by the topic name, so that the assignment of existing partitions
+    /// doesn't change as topics are added.
+    fn assigned_subtask(&self, topic: &str, partition: i32, parallelism: usize) -> usize {
+        if self.single().is_some() {
+            return partition as usize % parallelism;
+        }
+
+        let hash = stable_hash(topic);
+        ((hash % parallelism as u64) as usize + partition as usize) % parallelism
+    }
+}
+
+/// The offsets of sources that could only read a single topic, which are still read when
+/// restoring from old checkpoints
 #[derive(Copy, Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
 pub struct KafkaState {
     partition: i32,
     offset: i64,
 }
 
+#[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
+pub struct KafkaTopicState {
+    topic: String,
+    partition: i32,
+    offset: i64,
+}
+
 pub fn tables() -> Vec<TableDescriptor> {
-    vec![arroyo_state::global_table("k", "kafka source state")]
+    vec![
+        arroyo_state::global_table("k", "kafka source state"),
+        // commit writes, so that the offsets of a checkpoint are committed to Kafka only once
+        // the checkpoint has completed
+        TableDescriptor {
+            write_behavior: TableWriteBehavior::CommitWrites as i32,
+            ..arroyo_state::global_table("t", "kafka source topic partition state")
+        },
+    ]
 }
 
-#[source_fn(out_k = (), out_t = T)]
+#[source_fn(out_k = (), out_t = T, commits = true)]
 impl<K, T> KafkaSourceFunc<K, T>
 where
     K: DeserializeOwned + Data,
@@ -59,21 +150,27 @@ where
     pub fn new(
         servers: &str,
         topic: &str,
-        offset_mode: super::SourceOffset,
+        offset_mode: SourceOffset,
         format: Format,
         messages_per_second: u32,
         client_configs: Vec<(&str, &str)>,
     ) -> Self {
         Self {
             topic: topic.to_string(),
+            topics: Topics::new(topic, Subscription::Topics),
             bootstrap_servers: servers.to_string(),
             offset_mode,
-            format,
+            start_timestamp: None,
+            start_offsets: HashMap::new(),
+            group_id: None,
+            deserializer: DataDeserializer::new(format),
+            bad_data: BadDataHandler::default(),
             client_configs: client_configs
                 .iter()
                 .map(|(key, value)| (key.to_string(), value.to_string()))
                 .collect(),
             messages_per_second: NonZeroU32::new(messages_per_second).unwrap(),
+            metadata_fields: vec![],
             _t: PhantomData,
         }
     }
@@ -81,11 +178,20 @@ where
     pub fn from_config(config: &str) -> Self {
         let config: OperatorConfig =
             serde_json::from_str(config).expect("Invalid config for KafkaSource");
+        let bad_data = BadDataHandler::from_config(&config);
         let connection: KafkaConfig = serde_json::from_value(config.connection)
             .expect("Invalid connection config for KafkaSource");
         let table: KafkaTable =
             serde_json::from_value(config.table).expect("Invalid table config for KafkaSource");
-        let TableType::Source { offset, read_mode } = &table.type_ else {
+        let TableType::Source {
+            offset,
+            read_mode,
+            subscription,
+            start_timestamp,
+            start_offsets,
+            group_id,
+        } = &table.type_
+        else {
             panic!("found non-source kafka config in source operator");
         };
         let mut client_configs = client_configs(&connection);
@@ -93,11 +199,37 @@ where
             client_configs.insert("isolation.level".to_string(), "read_committed".to_string());
         }
 
+        let schema_resolver: Arc<dyn SchemaResolver + Sync> =
+            if let Some(schema_registry) = &connection.schema_registry {
+                Arc::new(
+                    ConfluentSchemaRegistry::new(
+                        &schema_registry.endpoint,
+                        &table.topic,
+                        schema_registry.api_key.clone(),
+                        schema_registry.api_secret.clone(),
+                    )
+                    .expect("failed to construct confluent schema resolver"),
+                )
+            } else {
+                Arc::new(FailingSchemaResolver::new())
+            };
+
         Self {
+            topics: Topics::new(&table.topic, subscription.unwrap_or(Subscription::Topics)),
             topic: table.topic,
             bootstrap_servers: connection.bootstrap_servers.to_string(),
             offset_mode: *offset,
-            format: config.format.expect("Format must be set for Kafka source"),
+            start_timestamp: *start_timestamp,
+            start_offsets: start_offsets
+                .as_ref()
+                .map(|offsets| parse_start_offsets(offsets).expect("invalid kafka start offsets"))
+                .unwrap_or_default(),
+            group_id: group_id.clone(),
+            deserializer: DataDeserializer::with_schema_resolver(
+                config.format.expect("Format must be set for Kafka source"),
+                schema_resolver,
+            ),
+            bad_data,
             client_configs,
             messages_per_second: NonZeroU32::new(
                 config
@@ -106,10 +238,56 @@ where
                     .unwrap_or(u32::MAX),
             )
             .unwrap(),
+            metadata_fields: config.metadata_fields,
             _t: PhantomData,
         }
     }
 
+    /// The values of the metadata fields for a message, by field name
+    fn metadata_values(
+        &self,
+        msg: &BorrowedMessage,
+        timestamp: i64,
+    ) -> Vec<(String, MetadataValue)> {
+        self.metadata_fields
+            .iter()
+            .map(|field| {
+                let value = match field.key.as_str() {
+                    "topic" => MetadataValue::Json(json!(msg.topic())),
+                    "partition" => MetadataValue::Json(json!(msg.partition())),
+                    "offset" => MetadataValue::Json(json!(msg.offset())),
+                    "key" => MetadataValue::Json(match (msg.key(), &field.data_type) {
+                        (None, _) => Value::Null,
+                        (Some(key), PrimitiveType::Bytes) => json!(key),
+                        (Some(key), _) => json!(String::from_utf8_lossy(key)),
+                    }),
+                    "timestamp" => MetadataValue::Timestamp(from_millis(timestamp as u64)),
+                    "headers" => {
+                        let headers: Map<String, Value> = msg
+                            .headers()
+                            .map(|headers| {
+                                headers
+                                    .iter()
+                                    .map(|h| {
+                                        (
+                                            h.key.to_string(),
+                                            h.value
+                                                .map(|v| json!(String::from_utf8_lossy(v)))
+                                                .unwrap_or(Value::Null),
+                                        )
+                                    })
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        MetadataValue::Json(json!(Value::Object(headers).to_string()))
+                    }
+                    key => unreachable!("unknown kafka metadata key {}", key),
+                };
+                (field.field_name.clone(), value)
+            })
+            .collect()
+    }
+
     fn name(&self) -> String {
         format!("kafka-{}", self.topic)
     }
@@ -131,49 +309,67 @@ where
             .set("enable.auto.commit", "false")
             .set(
                 "group.id",
-                format!(
-                    "arroyo-{}-{}-consumer",
-                    ctx.task_info.job_id, ctx.task_info.operator_id
-                ),
+                self.group_id.clone().unwrap_or_else(|| {
+                    format!(
+                        "arroyo-{}-{}-consumer",
+                        ctx.task_info.job_id, ctx.task_info.operator_id
+                    )
+                }),
             )
             .create()?;
 
-        let mut s: GlobalKeyedState<i32, KafkaState, _> =
-            ctx.state.get_global_keyed_state('k').await;
-        let state: Vec<&KafkaState> = s.get_all();
+        let mut state: HashMap<(String, i32), i64> = {
+            let mut s: GlobalKeyedState<(String, i32), KafkaTopicState, _> =
+                ctx.state.get_global_keyed_state('t').await;
+            s.get_all()
+                .into_iter()
+                .map(|s| ((s.topic.clone(), s.partition), s.offset))
+                .collect()
+        };
+
+        if let Some(topic) = self.topics.single() {
+            let mut s: GlobalKeyedState<i32, KafkaState, _> =
+                ctx.state.get_global_keyed_state('k').await;
+            for legacy in s.get_all() {
+                state
+                    .entry((topic.to_string(), legacy.partition))
+                    .or_insert(legacy.offset);
+            }
+        }
 
         // did we restore any partitions?
         let has_state = !state.is_empty();
 
-        let state: HashMap<i32, KafkaState> = state.iter().map(|s| (s.partition, **s)).collect();
-        let metadata = consumer.fetch_metadata(Some(&self.topic), Duration::from_secs(30))?;
+        let partitions = self.fetch_partitions(&consumer, Duration::from_secs(30))?;
 
         info!("Fetched metadata for topic {}", self.topic);
 
-        let our_partitions: HashMap<_, _> = {
-            let partitions = metadata.topics()[0].partitions();
-            partitions
-                .iter()
-                .enumerate()
-                .filter(|(i, _)| i % ctx.task_info.parallelism == ctx.task_info.task_index)
-                .map(|(_, p)| {
-                    let offset = state
-                        .get(&p.id())
-                        .map(|s| Offset::Offset(s.offset))
-                        .unwrap_or_else(|| {
-                            if has_state {
-                                // if we've restored partitions and we don't know about this one, that means it's
-                                // new, and we want to start from the beginning so we don't drop data
-                                Offset::Beginning
-                            } else {
-                                self.offset_mode.get_offset()
-                            }
-                        });
+        let mut our_partitions = HashMap::new();
+        let mut unstarted = vec![];
+        for tp in partitions {
+            let (topic, partition) = &tp;
+            if self
+                .topics
+                .assigned_subtask(topic, *partition, ctx.task_info.parallelism)
+                != ctx.task_info.task_index
+            {
+                continue;
+            }
 
-                    ((self.topic.clone(), p.id()), offset)
-                })
-                .collect()
-        };
+            match state.get(&tp) {
+                Some(offset) => {
+                    our_partitions.insert(tp, Offset::Offset(*offset));
+                }
+                None if has_state => {
+                    // if we've restored partitions and we don't know about this one, that means it's
+                    // new, and we want to start from the beginning so we don't drop data
+                    our_partitions.insert(tp, Offset::Beginning);
+                }
+                None => unstarted.push(tp),
+            }
+        }
+
+        our_partitions.extend(self.initial_offsets(&consumer, unstarted)?);
 
         let topic_partitions = TopicPartitionList::from_topic_map(&our_partitions)?;
 
@@ -182,6 +378,118 @@ where
         Ok(consumer)
     }
 
+    /// The offsets that partitions are read from when the source is started without state
+    fn initial_offsets(
+        &self,
+        consumer: &StreamConsumer,
+        partitions: Vec<(String, i32)>,
+    ) -> anyhow::Result<HashMap<(String, i32), Offset>> {
+        match self.offset_mode {
+            SourceOffset::Earliest => Ok(partitions
+                .into_iter()
+                .map(|tp| (tp, Offset::Beginning))
+                .collect()),
+            SourceOffset::Latest => {
+                Ok(partitions.into_iter().map(|tp| (tp, Offset::End)).collect())
+            }
+            SourceOffset::Timestamp => {
+                if partitions.is_empty() {
+                    return Ok(HashMap::new());
+                }
+
+                let timestamp = self.start_timestamp.ok_or_else(|| {
+                    anyhow!("start_timestamp must be set to start from a timestamp")
+                })?;
+                let query: HashMap<_, _> = partitions
+                    .into_iter()
+                    .map(|tp| (tp, Offset::Offset(timestamp)))
+                    .collect();
+
+                // partitions without any messages after the timestamp are read from the end
+                Ok(consumer
+                    .offsets_for_times(
+                        TopicPartitionList::from_topic_map(&query)?,
+                        Duration::from_secs(30),
+                    )?
+                    .elements()
+                    .iter()
+                    .map(|e| ((e.topic().to_string(), e.partition()), e.offset()))
+                    .collect())
+            }
+            SourceOffset::Offsets => Ok(partitions
+                .into_iter()
+                .map(|(topic, partition)| {
+                    let offset = self
+                        .start_offsets
+                        .get(&(Some(topic.clone()), partition))
+                        .or_else(|| self.start_offsets.get(&(None, partition)))
+                        .map(|offset| Offset::Offset(*offset))
+                        .unwrap_or(Offset::Beginning);
+                    ((topic, partition), offset)
+                })
+                .collect()),
+        }
+    }
+
+    /// Fetches the partitions of all of the topics that this source reads from
+    fn fetch_partitions(
+        &self,
+        consumer: &StreamConsumer,
+        timeout: Duration,
+    ) -> anyhow::Result<Vec<(String, i32)>> {
+        let metadata = consumer.fetch_metadata(self.topics.single(), timeout)?;
+
+        Ok(metadata
+            .topics()
+            .iter()
+            .filter(|t| self.topics.matches(t.name()))
+            .flat_map(|t| {
+                t.partitions()
+                    .iter()
+                    .map(|p| (t.name().to_string(), p.id()))
+            })
+            .collect())
+    }
+
+    /// Assigns the partitions of topics that have been created (or have grown) since the consumer
+    /// was created. These are read from the beginning, so that no messages are dropped.
+    fn assign_new_partitions(
+        &self,
+        consumer: &StreamConsumer,
+        task_info: &TaskInfo,
+    ) -> anyhow::Result<()> {
+        let assigned: HashSet<(String, i32)> = consumer
+            .assignment()?
+            .elements()
+            .iter()
+            .map(|e| (e.topic().to_string(), e.partition()))
+            .collect();
+
+        let new_partitions: HashMap<_, _> = self
+            .fetch_partitions(consumer, Duration::from_secs(5))?
+            .into_iter()
+            .filter(|(topic, partition)| {
+                self.topics
+                    .assigned_subtask(topic, *partition, task_info.parallelism)
+                    == task_info.task_index
+                    && !assigned.contains(&(topic.clone(), *partition))
+            })
+            .map(|tp| (tp, Offset::Beginning))
+            .collect();
+
+        if !new_partitions.is_empty() {
+            info!(
+                "Assigning {} new partitions to Kafka consumer {}-{}",
+                new_partitions.len(),
+                task_info.operator_id,
+                task_info.task_index
+            );
+            consumer.incremental_assign(&TopicPartitionList::from_topic_map(&new_partitions)?)?;
+        }
+
+        Ok(())
+    }
+
     async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
         match self.run_int(ctx).await {
             Ok(r) => r,
@@ -201,6 +509,38 @@ where
         }
     }
 
+    /// Commits the offsets of a completed checkpoint to Kafka. These are just used for progress
+    /// tracking by metrics and consumer group monitoring, so it's not a fatal error if it fails;
+    /// the offsets the source restores from are stored in state.
+    async fn commit_offsets(
+        &mut self,
+        consumer: &StreamConsumer,
+        pending_commits: &mut BTreeMap<u32, TopicPartitionList>,
+        epoch: u32,
+        ctx: &mut Context<(), T>,
+    ) {
+        // nothing is committed if nothing was read before the checkpoint, or if the commit is
+        // being replayed after a restore
+        if let Some(topic_partitions) = pending_commits.remove(&epoch) {
+            if let Err(e) = consumer.commit(&topic_partitions, CommitMode::Async) {
+                warn!("Failed to commit offset to Kafka {:?}", e);
+            }
+        }
+        // the offsets of earlier checkpoints are behind the ones just committed
+        pending_commits.retain(|checkpoint_epoch, _| *checkpoint_epoch > epoch);
+
+        ctx.control_tx
+            .send(ControlResp::CheckpointEvent(CheckpointEvent {
+                checkpoint_epoch: epoch,
+                operator_id: ctx.task_info.operator_id.clone(),
+                subtask_index: ctx.task_info.task_index as u32,
+                time: SystemTime::now(),
+                event_type: arroyo_rpc::grpc::TaskCheckpointEventType::FinishedCommit.into(),
+            }))
+            .await
+            .expect("sent commit event");
+    }
+
     async fn run_int(&mut self, ctx: &mut Context<(), T>) -> Result<SourceFinishType, UserError> {
         let consumer = self
             .get_consumer(ctx)
@@ -208,7 +548,15 @@ where
             .map_err(|e| UserError::new("Could not create Kafka consumer", format!("{:?}", e)))?;
 
         let rate_limiter = RateLimiter::direct(Quota::per_second(self.messages_per_second));
-        let mut offsets = HashMap::new();
+        let mut offsets: HashMap<String, HashMap<i32, i64>> = HashMap::new();
+        // offsets of in-progress checkpoints by epoch, each committed to Kafka once its checkpoint
+        // completes; a checkpoint can start before the commit of the previous one arrives
+        let mut pending_commits: BTreeMap<u32, TopicPartitionList> = BTreeMap::new();
+
+        // partitions may be added to the topics while the source is running, and pattern sources
+        // also need to look for new topics
+        let mut refresh_interval = tokio::time::interval(TOPIC_REFRESH_INTERVAL);
+        refresh_interval.tick().await;
 
         if consumer.assignment().unwrap().count() == 0 {
             warn!("Kafka Consumer {}-{} is subscribed to no partitions, as there are more subtasks than partitions... setting idle",
@@ -226,12 +574,28 @@ where
                                     .ok_or_else(|| UserError::new("Failed to read timestamp from Kafka record",
                                         "The message read from Kafka did not contain a message timestamp"))?;
 
-                                ctx.collector.collect(Record {
-                                    timestamp: from_millis(timestamp as u64),
-                                    key: None,
-                                    value: formats::deserialize_slice(&self.format, v)?,
-                                }).await;
-                                offsets.insert(msg.partition(), msg.offset());
+                                let values = if self.metadata_fields.is_empty() {
+                                    self.deserializer.deserialize_slice(v).await
+                                } else {
+                                    let metadata = self.metadata_values(&msg, timestamp);
+                                    self.deserializer.deserialize_slice_with_metadata(v, &metadata).await
+                                };
+                                for value in self.bad_data.handle(ctx, v, values).await.unwrap_or_default() {
+                                    ctx.collector.collect(Record {
+                                        timestamp: from_millis(timestamp as u64),
+                                        key: None,
+                                        value,
+                                    }).await;
+                                }
+                                match offsets.get_mut(msg.topic()) {
+                                    Some(partitions) => {
+                                        partitions.insert(msg.partition(), msg.offset());
+                                    }
+                                    None => {
+                                        offsets.insert(msg.topic().to_string(),
+                                            HashMap::from([(msg.partition(), msg.offset())]));
+                                    }
+                                }
                                 rate_limiter.until_ready().await;
                             }
                         },
@@ -244,24 +608,35 @@ where
                     match control_message {
                         Some(ControlMessage::Checkpoint(c)) => {
                             debug!("starting checkpointing {}", ctx.task_info.task_index);
+                            // the offsets of bad messages are only checkpointed once they're in the dead letter table
+                            self.bad_data.flush(ctx).await;
                             let mut topic_partitions = TopicPartitionList::new();
-                            let mut s = ctx.state.get_global_keyed_state('k').await;
-                            for (partition, offset) in &offsets {
-                                let partition2 = partition;
-                                s.insert(*partition, KafkaState {
-                                    partition: *partition2,
-                                    offset: *offset + 1,
-                                }).await;
-                                topic_partitions.add_partition_offset(
-                                    &self.topic, *partition, Offset::Offset(*offset)).unwrap();
+                            let mut s = ctx.state.get_global_keyed_state('t').await;
+                            for (topic, partitions) in &offsets {
+                                for (partition, offset) in partitions {
+                                    s.insert((topic.clone(), *partition), KafkaTopicState {
+                                        topic: topic.clone(),
+                                        partition: *partition,
+                                        offset: *offset + 1,
+                                    }).await;
+                                    // the committed offset is the next one to read, as for other consumers
+                                    topic_partitions.add_partition_offset(
+                                        topic, *partition, Offset::Offset(*offset + 1)).unwrap();
+                                }
                             }
 
-                            if let Err(e) = consumer.commit(&topic_partitions, CommitMode::Async) {
-                                // This is just used for progress tracking for metrics, so it's not a fatal error if it
-                                // fails. The actual offset is stored in state.
-                                warn!("Failed to commit offset to Kafka {:?}", e);
+                            if topic_partitions.count() > 0 {
+                                pending_commits.insert(c.epoch, topic_partitions);
                             }
                             if self.checkpoint(c, ctx).await {
+                                // wait for the final checkpoint to complete so that its offsets are committed
+                                if !pending_commits.is_empty() {
+                                    if let Some(ControlMessage::Commit { epoch }) = ctx.control_rx.recv().await {
+                                        self.commit_offsets(&consumer, &mut pending_commits, epoch, ctx).await;
+                                    } else {
+                                        warn!("no commit message received, not committing offsets to Kafka");
+                                    }
+                                }
                                 return Ok(SourceFinishType::Immediate);
                             }
                         },
@@ -277,8 +652,8 @@ where
                                 }
                             }
                         }
-                        Some(ControlMessage::Commit { epoch: _ }) => {
-                            unreachable!("sources shouldn't receive commit messages");
+                        Some(ControlMessage::Commit { epoch }) => {
+                            self.commit_offsets(&consumer, &mut pending_commits, epoch, ctx).await;
                         }
                         Some(ControlMessage::LoadCompacted {compacted}) => {
                             ctx.load_compacted(compacted).await;
@@ -288,6 +663,11 @@ where
                         }
                     }
                 }
+                _ = refresh_interval.tick() => {
+                    if let Err(e) = self.assign_new_partitions(&consumer, &ctx.task_info) {
+                        warn!("Failed to check for new Kafka topics: {:?}", e);
+                    }
+                }
             }
         }
     }
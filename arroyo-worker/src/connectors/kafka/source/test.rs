@@ -4,7 +4,7 @@ use std::time::{Duration, SystemTime};
 
 use crate::connectors::kafka::source;
 use crate::engine::{Context, OutQueue, QueueItem};
-use arroyo_rpc::grpc::{CheckpointMetadata, OperatorCheckpointMetadata};
+use arroyo_rpc::grpc::{CheckpointMetadata, OperatorCheckpointMetadata, TaskCheckpointEventType};
 use arroyo_rpc::types::{Format, JsonFormat};
 use arroyo_rpc::{CheckpointCompleted, ControlMessage, ControlResp};
 use arroyo_types::{to_micros, CheckpointBarrier, Message, TaskInfo};
@@ -14,7 +14,7 @@ use rdkafka::ClientConfig;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
-use super::KafkaSourceFunc;
+use super::{KafkaSourceFunc, Subscription, Topics};
 
 #[derive(Debug, Clone, bincode::Encode, bincode::Decode, Serialize, Deserialize, PartialEq)]
 struct TestData {
@@ -60,7 +60,7 @@ impl KafkaTopicTester {
         task_info: TaskInfo,
         restore_from: Option<u32>,
     ) -> KafkaSourceWithReads {
-        let mut kafka: KafkaSourceFunc<(), TestData> = KafkaSourceFunc::new(
+        let kafka: KafkaSourceFunc<(), TestData> = KafkaSourceFunc::new(
             &self.server,
             &self.topic,
             crate::connectors::kafka::SourceOffset::Earliest,
@@ -68,39 +68,7 @@ impl KafkaTopicTester {
             100,
             vec![],
         );
-        let (to_control_tx, control_rx) = channel(128);
-        let (command_tx, from_control_rx) = channel(128);
-        let (data_tx, recv) = channel(128);
-
-        let checkpoint_metadata = restore_from.map(|epoch| CheckpointMetadata {
-            job_id: task_info.job_id.to_string(),
-            epoch,
-            min_epoch: 1,
-            start_time: to_micros(SystemTime::now()),
-            finish_time: to_micros(SystemTime::now()),
-            operator_ids: vec![task_info.operator_id.clone()],
-        });
-
-        let mut ctx: Context<(), TestData> = Context::new(
-            task_info,
-            checkpoint_metadata,
-            control_rx,
-            command_tx,
-            1,
-            vec![vec![OutQueue::new(data_tx, false)]],
-            source::tables(),
-        )
-        .await;
-
-        tokio::spawn(async move {
-            kafka.on_start(&mut ctx).await;
-            kafka.run(&mut ctx).await;
-        });
-        KafkaSourceWithReads {
-            to_control_tx,
-            from_control_rx,
-            data_recv: recv,
-        }
+        run_source(kafka, task_info, restore_from).await
     }
 
     fn get_producer(&mut self) -> KafkaTopicProducer {
@@ -119,6 +87,46 @@ impl KafkaTopicTester {
         }
     }
 }
+
+async fn run_source(
+    mut kafka: KafkaSourceFunc<(), TestData>,
+    task_info: TaskInfo,
+    restore_from: Option<u32>,
+) -> KafkaSourceWithReads {
+    let (to_control_tx, control_rx) = channel(128);
+    let (command_tx, from_control_rx) = channel(128);
+    let (data_tx, recv) = channel(128);
+
+    let checkpoint_metadata = restore_from.map(|epoch| CheckpointMetadata {
+        job_id: task_info.job_id.to_string(),
+        epoch,
+        min_epoch: 1,
+        start_time: to_micros(SystemTime::now()),
+        finish_time: to_micros(SystemTime::now()),
+        operator_ids: vec![task_info.operator_id.clone()],
+    });
+
+    let mut ctx: Context<(), TestData> = Context::new(
+        task_info,
+        checkpoint_metadata,
+        control_rx,
+        command_tx,
+        1,
+        vec![vec![OutQueue::new(data_tx, false)]],
+        source::tables(),
+    )
+    .await;
+
+    tokio::spawn(async move {
+        kafka.on_start(&mut ctx).await;
+        kafka.run(&mut ctx).await;
+    });
+    KafkaSourceWithReads {
+        to_control_tx,
+        from_control_rx,
+        data_recv: recv,
+    }
+}
 struct KafkaTopicProducer {
     base_producer: BaseProducer,
     topic: String,
@@ -140,6 +148,19 @@ struct KafkaSourceWithReads {
 }
 
 impl KafkaSourceWithReads {
+    async fn next_record_value(&mut self) -> u64 {
+        let msg: Message<(), TestData> = self
+            .data_recv
+            .recv()
+            .await
+            .expect("option shouldn't be missing")
+            .into();
+        match msg {
+            Message::Record(record) => record.value.i,
+            msg => unreachable!("expected a record, got {:?}", msg),
+        }
+    }
+
     async fn assert_next_message_record_value(&mut self, expected_value: u64) {
         match self.data_recv.recv().await {
             Some(item) => {
@@ -185,6 +206,23 @@ impl KafkaSourceWithReads {
             }
         }
     }
+
+    async fn assert_control_commit(&mut self, expected_epoch: u32) {
+        loop {
+            let control_response = self
+                .from_control_rx
+                .recv()
+                .await
+                .expect("should be a valid message");
+
+            if let ControlResp::CheckpointEvent(event) = control_response {
+                if event.event_type == TaskCheckpointEventType::FinishedCommit {
+                    assert_eq!(expected_epoch, event.checkpoint_epoch);
+                    return;
+                }
+            }
+        }
+    }
 }
 
 #[tokio::test]
@@ -216,6 +254,13 @@ async fn test_kafka() {
     });
     reader.to_control_tx.send(barrier).await.unwrap();
     let checkpoint_completed = reader.assert_control_checkpoint(1).await;
+    // offsets are committed to kafka once the checkpoint has completed
+    reader
+        .to_control_tx
+        .send(ControlMessage::Commit { epoch: 1 })
+        .await
+        .unwrap();
+    reader.assert_control_commit(1).await;
     producer.send_data(TestData { i: 20 });
 
     reader.assert_next_message_checkpoint(1).await;
@@ -264,3 +309,80 @@ async fn test_kafka() {
     producer.send_data(TestData { i: 21 });
     reader.assert_next_message_record_value(21).await;
 }
+
+#[tokio::test]
+async fn test_kafka_topic_pattern() {
+    let server = "0.0.0.0:9092".to_string();
+    let mut producers = vec![];
+    for topic in ["arroyo-pattern-a", "arroyo-pattern-b"] {
+        let mut tester = KafkaTopicTester {
+            topic: topic.to_string(),
+            server: server.clone(),
+        };
+        tester.create_topic().await;
+        producers.push(tester.get_producer());
+    }
+
+    let mut task_info = arroyo_types::get_test_task_info();
+    task_info.job_id = format!("kafka-job-{}", rand::thread_rng().gen::<u64>());
+
+    let mut kafka: KafkaSourceFunc<(), TestData> = KafkaSourceFunc::new(
+        &server,
+        "arroyo-pattern-.*",
+        crate::connectors::kafka::SourceOffset::Earliest,
+        Format::Json(JsonFormat::default()),
+        100,
+        vec![],
+    );
+    kafka.topics = Topics::new("arroyo-pattern-.*", Subscription::Pattern);
+    let mut reader = run_source(kafka, task_info, None).await;
+
+    for (i, producer) in producers.iter_mut().enumerate() {
+        producer.send_data(TestData { i: i as u64 });
+    }
+
+    // the order of messages from different topics isn't defined
+    let mut values = vec![
+        reader.next_record_value().await,
+        reader.next_record_value().await,
+    ];
+    values.sort();
+    assert_eq!(values, vec![0, 1]);
+}
+
+#[test]
+fn test_topics() {
+    let topics = Topics::new("events_a, events_b", Subscription::Topics);
+    assert!(topics.matches("events_b"));
+    assert!(!topics.matches("events_c"));
+    assert_eq!(topics.single(), None);
+    assert_eq!(
+        Topics::new("events_a", Subscription::Topics).single(),
+        Some("events_a")
+    );
+
+    let pattern = Topics::new("events_.*", Subscription::Pattern);
+    assert!(pattern.matches("events_c"));
+    assert!(!pattern.matches("tenant_events_c"));
+    assert_eq!(pattern.single(), None);
+    assert!(!Topics::new(".*", Subscription::Pattern).matches("__consumer_offsets"));
+}
+
+#[test]
+fn test_assigned_subtask() {
+    // the partitions of a single topic are assigned by partition number
+    let single = Topics::new("events", Subscription::Topics);
+    let subtasks: Vec<_> = (0..6)
+        .map(|p| single.assigned_subtask("events", p, 4))
+        .collect();
+    assert_eq!(subtasks, vec![0, 1, 2, 3, 0, 1]);
+
+    // partitions of each topic are spread evenly across the subtasks
+    let pattern = Topics::new("events_.*", Subscription::Pattern);
+    let mut subtasks: Vec<_> = (0..4)
+        .map(|p| pattern.assigned_subtask("events_a", p, 4))
+        .collect();
+    subtasks.sort();
+    assert_eq!(subtasks, vec![0, 1, 2, 3]);
+    assert_eq!(pattern.assigned_subtask("events_a", 2, 1), 0);
+}
@@ -75,14 +75,14 @@ impl<K: Key + Serialize, T: SchemaData> KinesisSinkFunc<K, T> {
         }
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, _ctx: &mut Context<(), ()>) {
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
         let k = record
             .key
             .as_ref()
             .map(|k| serde_json::to_string(k).unwrap())
             .unwrap_or_else(|| Uuid::new_v4().to_string());
 
-        let Some(v) = self.serializer.to_vec(&record.value) else {
+        let Some(v) = self.serializer.to_vec_or_report(&record.value, ctx).await else {
             return;
         };
 
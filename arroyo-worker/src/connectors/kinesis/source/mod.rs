@@ -9,7 +9,6 @@ use std::{
 
 use anyhow::{anyhow, bail, Context as AnyhowContext, Result};
 use arroyo_macro::{source_fn, StreamNode};
-use arroyo_rpc::types::Format;
 use arroyo_rpc::{
     grpc::{StopMode, TableDescriptor},
     ControlMessage, OperatorConfig,
@@ -34,7 +33,10 @@ use tokio::{
 };
 use tracing::{debug, info, warn};
 
-use crate::{engine::Context, formats, SourceFinishType};
+use crate::{
+    connectors::bad_data::BadDataHandler, engine::Context, formats::DataDeserializer,
+    SourceFinishType,
+};
 
 use super::{KinesisTable, SourceOffset, TableType};
 
@@ -48,7 +50,8 @@ pub enum KinesisOffset {
 #[derive(StreamNode)]
 pub struct KinesisSourceFunc<K: Data, T: Data + DeserializeOwned> {
     stream_name: String,
-    format: Format,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
     kinesis_client: Option<KinesisClient>,
     aws_region: Option<String>,
     shards: HashMap<String, ShardState>,
@@ -178,6 +181,7 @@ impl<K: Data, T: Data + DeserializeOwned> KinesisSourceFunc<K, T> {
     pub fn from_config(config: &str) -> Self {
         let config: OperatorConfig =
             serde_json::from_str(config).expect("Invalid config for KinesisSource");
+        let bad_data = BadDataHandler::from_config(&config);
         let table: KinesisTable =
             serde_json::from_value(config.table).expect("Invalid table config for KinesisSource");
         let kinesis_config = KinesisSourceConfig::new_from_table(&table);
@@ -188,7 +192,8 @@ impl<K: Data, T: Data + DeserializeOwned> KinesisSourceFunc<K, T> {
             aws_region: table.aws_region,
             config: kinesis_config,
             shards: HashMap::new(),
-            format: config.format.unwrap(),
+            deserializer: DataDeserializer::new(config.format.unwrap()),
+            bad_data,
             _phantom: PhantomData,
         }
     }
@@ -469,14 +474,19 @@ impl<K: Data, T: Data + DeserializeOwned> KinesisSourceFunc<K, T> {
         let records = get_records_output.records.unwrap_or_default();
         for record in records {
             let data = record.data.unwrap().into_inner();
-            let value = formats::deserialize_slice(&self.format, &data)?;
-            let timestamp = record.approximate_arrival_timestamp.unwrap();
-            let output_record = Record {
-                timestamp: from_nanos(timestamp.as_nanos() as u128),
-                key: None,
-                value,
+            let values = self.deserializer.deserialize_slice(&data).await;
+            let Some(values) = self.bad_data.handle(ctx, &data, values).await else {
+                continue;
             };
-            ctx.collect(output_record).await;
+            let timestamp = record.approximate_arrival_timestamp.unwrap();
+            for value in values {
+                let output_record = Record {
+                    timestamp: from_nanos(timestamp.as_nanos() as u128),
+                    key: None,
+                    value,
+                };
+                ctx.collect(output_record).await;
+            }
         }
         Ok(get_records_output.next_shard_iterator)
     }
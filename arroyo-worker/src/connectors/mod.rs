@@ -1,12 +1,17 @@
+pub mod bad_data;
 pub mod blackhole;
 pub mod filesystem;
 pub mod fluvio;
 pub mod impulse;
 pub mod kafka;
 pub mod kinesis;
+pub mod mqtt;
 pub mod nexmark;
 pub mod polling_http;
+pub mod postgres;
+pub mod redis;
 pub mod sse;
+pub mod template;
 pub mod two_phase_committer;
 pub mod webhook;
 pub mod websocket;
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use arroyo_rpc::mqtt::{mqtt_options, MqttOptions};
+use arroyo_types::TaskInfo;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, Receiver};
+use tokio::task::JoinHandle;
+use typify::import_types;
+
+pub mod sink;
+pub mod source;
+
+import_types!(schema = "../connector-schemas/mqtt/table.json");
+
+pub use arroyo_rpc::mqtt::{MqttConfig, ProtocolVersion};
+
+/// The number of requests that can be queued for the event loop before publishing blocks
+const REQUEST_CAPACITY: usize = 1000;
+
+/// How long to wait before reconnecting after the connection to the broker fails
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// A client for either version of the protocol, which sends requests to its event loop
+pub enum MqttClient {
+    V3(rumqttc::AsyncClient),
+    V5(rumqttc::v5::AsyncClient),
+}
+
+/// Drives the connection to the broker; nothing is sent or received unless it's polled
+pub enum MqttEventLoop {
+    V3(rumqttc::EventLoop),
+    V5(rumqttc::v5::EventLoop),
+}
+
+pub enum MqttEvent {
+    Connected,
+    Message { topic: String, payload: Vec<u8> },
+    PubAck,
+    SubscriptionRejected(String),
+    Other,
+}
+
+impl MqttClient {
+    /// Creates a client with an id that's unique to the subtask, so that parallel subtasks don't
+    /// disconnect each other
+    pub fn new(config: &MqttConfig, task_info: &TaskInfo) -> Result<(Self, MqttEventLoop)> {
+        let client_id = format!(
+            "{}-{}-{}-{}",
+            config.client_prefix.as_deref().unwrap_or("arroyo"),
+            task_info.job_id,
+            task_info.operator_id,
+            task_info.task_index
+        );
+
+        Ok(match mqtt_options(config, client_id)? {
+            MqttOptions::V3(options) => {
+                let (client, eventloop) = rumqttc::AsyncClient::new(options, REQUEST_CAPACITY);
+                (MqttClient::V3(client), MqttEventLoop::V3(eventloop))
+            }
+            MqttOptions::V5(options) => {
+                let (client, eventloop) = rumqttc::v5::AsyncClient::new(options, REQUEST_CAPACITY);
+                (MqttClient::V5(client), MqttEventLoop::V5(eventloop))
+            }
+        })
+    }
+
+    pub async fn subscribe(&self, filter: &str, qos: MqttTableQos) -> Result<()> {
+        match self {
+            MqttClient::V3(client) => client.subscribe(filter, qos.v3()).await?,
+            MqttClient::V5(client) => client.subscribe(filter, qos.v5()).await?,
+        }
+        Ok(())
+    }
+
+    pub async fn publish(
+        &self,
+        topic: &str,
+        qos: MqttTableQos,
+        retain: bool,
+        payload: Vec<u8>,
+    ) -> Result<()> {
+        match self {
+            MqttClient::V3(client) => client.publish(topic, qos.v3(), retain, payload).await?,
+            MqttClient::V5(client) => client.publish(topic, qos.v5(), retain, payload).await?,
+        }
+        Ok(())
+    }
+
+    pub async fn disconnect(&self) -> Result<()> {
+        match self {
+            MqttClient::V3(client) => client.disconnect().await?,
+            MqttClient::V5(client) => client.disconnect().await?,
+        }
+        Ok(())
+    }
+}
+
+impl MqttEventLoop {
+    /// Polls the event loop on its own task, so that the connection makes progress (and sends
+    /// keep-alives) whatever the operator is doing, and sends the events to the returned channel.
+    /// After a connection error, the next poll reconnects once RECONNECT_DELAY has passed.
+    pub fn spawn(mut self) -> (Receiver<Result<MqttEvent>>, JoinHandle<()>) {
+        let (tx, rx) = channel(REQUEST_CAPACITY);
+        let handle = tokio::spawn(async move {
+            loop {
+                let event = self.poll().await;
+                if matches!(event, Ok(MqttEvent::Other)) {
+                    continue;
+                }
+
+                let failed = event.is_err();
+                if tx.send(event).await.is_err() {
+                    return;
+                }
+                if failed {
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        });
+
+        (rx, handle)
+    }
+
+    async fn poll(&mut self) -> Result<MqttEvent> {
+        use rumqttc::v5::mqttbytes::v5::SubscribeReasonCode as V5SubscribeReasonCode;
+        use rumqttc::SubscribeReasonCode as V3SubscribeReasonCode;
+
+        Ok(match self {
+            MqttEventLoop::V3(eventloop) => match eventloop.poll().await? {
+                rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) => MqttEvent::Connected,
+                rumqttc::Event::Incoming(rumqttc::Packet::Publish(publish)) => MqttEvent::Message {
+                    topic: publish.topic,
+                    payload: publish.payload.to_vec(),
+                },
+                rumqttc::Event::Incoming(rumqttc::Packet::PubAck(_)) => MqttEvent::PubAck,
+                rumqttc::Event::Incoming(rumqttc::Packet::SubAck(ack)) => {
+                    if ack
+                        .return_codes
+                        .iter()
+                        .any(|code| matches!(code, V3SubscribeReasonCode::Failure))
+                    {
+                        MqttEvent::SubscriptionRejected("failure return code".to_string())
+                    } else {
+                        MqttEvent::Other
+                    }
+                }
+                _ => MqttEvent::Other,
+            },
+            MqttEventLoop::V5(eventloop) => match eventloop.poll().await? {
+                rumqttc::v5::Event::Incoming(rumqttc::v5::Incoming::ConnAck(_)) => {
+                    MqttEvent::Connected
+                }
+                rumqttc::v5::Event::Incoming(rumqttc::v5::Incoming::Publish(publish)) => {
+                    MqttEvent::Message {
+                        topic: String::from_utf8_lossy(&publish.topic).to_string(),
+                        payload: publish.payload.to_vec(),
+                    }
+                }
+                rumqttc::v5::Event::Incoming(rumqttc::v5::Incoming::PubAck(_)) => MqttEvent::PubAck,
+                rumqttc::v5::Event::Incoming(rumqttc::v5::Incoming::SubAck(ack)) => {
+                    match ack
+                        .return_codes
+                        .iter()
+                        .find(|code| !matches!(code, V5SubscribeReasonCode::Success(_)))
+                    {
+                        Some(code) => MqttEvent::SubscriptionRejected(format!("{:?}", code)),
+                        None => MqttEvent::Other,
+                    }
+                }
+                _ => MqttEvent::Other,
+            },
+        })
+    }
+}
+
+impl MqttTableQos {
+    fn v3(&self) -> rumqttc::QoS {
+        match self {
+            MqttTableQos::AtMostOnce => rumqttc::QoS::AtMostOnce,
+            MqttTableQos::AtLeastOnce => rumqttc::QoS::AtLeastOnce,
+        }
+    }
+
+    fn v5(&self) -> rumqttc::v5::mqttbytes::QoS {
+        match self {
+            MqttTableQos::AtMostOnce => rumqttc::v5::mqttbytes::QoS::AtMostOnce,
+            MqttTableQos::AtLeastOnce => rumqttc::v5::mqttbytes::QoS::AtLeastOnce,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::engine::{Context, StreamNode};
+use crate::formats::DataSerializer;
+use crate::SchemaData;
+use anyhow::Result;
+use arroyo_macro::process_fn;
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::*;
+use serde::Serialize;
+use std::marker::PhantomData;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::Receiver;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{MqttClient, MqttConfig, MqttEvent, MqttTable, MqttTableQos, TableType};
+
+#[derive(StreamNode)]
+pub struct MqttSinkFunc<K: Key + Serialize, T: SchemaData> {
+    config: MqttConfig,
+    topic: String,
+    qos: MqttTableQos,
+    retain: bool,
+    serializer: DataSerializer<T>,
+    client: Option<MqttClient>,
+    events: Option<Receiver<Result<MqttEvent>>>,
+    eventloop: Option<JoinHandle<()>>,
+    /// Messages published with QoS 1 that the broker hasn't acknowledged yet
+    unacked: usize,
+    _t: PhantomData<K>,
+}
+
+impl<K: Key + Serialize, T: SchemaData + Serialize> MqttSinkFunc<K, T> {
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for MqttSink");
+        let connection: MqttConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for MqttSink");
+        let table: MqttTable =
+            serde_json::from_value(config.table).expect("Invalid table config for MqttSink");
+        let TableType::Sink { retain } = &table.type_ else {
+            panic!("found non-sink MQTT config in sink operator");
+        };
+
+        Self {
+            config: connection,
+            retain: retain.unwrap_or(false),
+            topic: table.topic,
+            qos: table.qos.unwrap_or(MqttTableQos::AtLeastOnce),
+            serializer: DataSerializer::new(
+                config
+                    .format
+                    .expect("Format must be specified for MqttSink"),
+            ),
+            client: None,
+            events: None,
+            eventloop: None,
+            unacked: 0,
+            _t: PhantomData,
+        }
+    }
+
+    fn handle_event(&mut self, event: Result<MqttEvent>) {
+        match event {
+            Ok(MqttEvent::Connected) => {
+                info!("Connected to {}", self.config.url);
+            }
+            Ok(MqttEvent::PubAck) => {
+                self.unacked = self.unacked.saturating_sub(1);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                // the event loop reconnects and resends unacknowledged messages
+                warn!(
+                    "Lost connection to {}, reconnecting: {:?}",
+                    self.config.url, e
+                );
+            }
+        }
+    }
+
+    /// Handles the events that are ready without waiting, so that the channel doesn't fill up and
+    /// block the event loop
+    fn drain_events(&mut self) {
+        loop {
+            match self.events.as_mut().unwrap().try_recv() {
+                Ok(event) => self.handle_event(event),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => panic!("MQTT event loop stopped"),
+            }
+        }
+    }
+
+    /// Waits until the broker has acknowledged everything published with QoS 1
+    async fn flush(&mut self) {
+        self.drain_events();
+        while self.unacked > 0 {
+            let event = self
+                .events
+                .as_mut()
+                .unwrap()
+                .recv()
+                .await
+                .expect("MQTT event loop stopped");
+            self.handle_event(event);
+        }
+    }
+}
+
+#[process_fn(in_k = K, in_t = T)]
+impl<K: Key + Serialize, T: SchemaData + Serialize> MqttSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("mqtt-sink-{}", self.topic)
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        match MqttClient::new(&self.config, &ctx.task_info) {
+            Ok((client, eventloop)) => {
+                let (events, eventloop) = eventloop.spawn();
+                self.client = Some(client);
+                self.events = Some(events);
+                self.eventloop = Some(eventloop);
+            }
+            Err(e) => {
+                ctx.report_error("Failed to create MQTT client".to_string(), e.to_string())
+                    .await;
+                panic!("Failed to create MQTT client: {:?}", e);
+            }
+        }
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        self.drain_events();
+
+        let Some(payload) = self.serializer.to_vec_or_report(&record.value, ctx).await else {
+            return;
+        };
+
+        if let Err(e) = self
+            .client
+            .as_ref()
+            .unwrap()
+            .publish(&self.topic, self.qos, self.retain, payload)
+            .await
+        {
+            panic!("Failed to publish to MQTT topic {}: {:?}", self.topic, e);
+        }
+
+        if let MqttTableQos::AtLeastOnce = self.qos {
+            self.unacked += 1;
+        }
+    }
+
+    async fn handle_checkpoint(&mut self, _: &CheckpointBarrier, _: &mut Context<(), ()>) {
+        self.flush().await;
+    }
+
+    async fn on_close(&mut self, _: &mut Context<(), ()>) {
+        self.flush().await;
+        if let Err(e) = self.client.as_ref().unwrap().disconnect().await {
+            warn!("Failed to disconnect from {}: {:?}", self.config.url, e);
+        }
+        if let Some(eventloop) = self.eventloop.take() {
+            eventloop.abort();
+        }
+    }
+}
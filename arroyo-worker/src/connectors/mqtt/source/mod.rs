@@ -0,0 +1,213 @@
+use crate::connectors::bad_data::BadDataHandler;
+use crate::engine::{Context, StreamNode};
+use crate::formats::metadata::MetadataValue;
+use crate::formats::DataDeserializer;
+use crate::SourceFinishType;
+use arroyo_macro::source_fn;
+use arroyo_rpc::types::Format;
+use arroyo_rpc::{grpc::StopMode, ControlMessage};
+use arroyo_rpc::{MetadataField, OperatorConfig};
+use arroyo_types::*;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use tokio::select;
+use tracing::{debug, info, warn};
+
+use super::{MqttClient, MqttConfig, MqttEvent, MqttTable, MqttTableQos, TableType};
+
+#[cfg(test)]
+mod test;
+
+/// Reads the messages published to the topics matching a topic filter. MQTT can't replay
+/// messages, so those published while the job isn't running are not read.
+#[derive(StreamNode, Clone)]
+pub struct MqttSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: DeserializeOwned + Data,
+{
+    config: MqttConfig,
+    topic: String,
+    qos: MqttTableQos,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
+    metadata_fields: Vec<MetadataField>,
+    _t: PhantomData<(K, T)>,
+}
+
+#[source_fn(out_k = (), out_t = T)]
+impl<K, T> MqttSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: DeserializeOwned + Data,
+{
+    pub fn new(config: MqttConfig, topic: &str, qos: MqttTableQos, format: Format) -> Self {
+        Self {
+            config,
+            topic: topic.to_string(),
+            qos,
+            deserializer: DataDeserializer::new(format),
+            bad_data: BadDataHandler::default(),
+            metadata_fields: vec![],
+            _t: PhantomData,
+        }
+    }
+
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for MqttSource");
+        let bad_data = BadDataHandler::from_config(&config);
+        let connection: MqttConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for MqttSource");
+        let table: MqttTable =
+            serde_json::from_value(config.table).expect("Invalid table config for MqttSource");
+        let TableType::Source {} = &table.type_ else {
+            panic!("found non-source MQTT config in source operator");
+        };
+
+        Self {
+            config: connection,
+            topic: table.topic,
+            qos: table.qos.unwrap_or(MqttTableQos::AtLeastOnce),
+            deserializer: DataDeserializer::new(
+                config
+                    .format
+                    .expect("Format must be specified for MqttSource"),
+            ),
+            bad_data,
+            metadata_fields: config.metadata_fields,
+            _t: PhantomData,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("mqtt-{}", self.topic)
+    }
+
+    async fn handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if self.checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping MQTT source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+        }
+        None
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        // every subscriber receives all matching messages, so only the first subtask reads
+        if ctx.task_info.task_index != 0 {
+            ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+            loop {
+                let msg = ctx.control_rx.recv().await;
+                if let Some(r) = self.handle_control_message(ctx, msg).await {
+                    return r;
+                }
+            }
+        }
+
+        let (client, eventloop) = match MqttClient::new(&self.config, &ctx.task_info) {
+            Ok(client) => client,
+            Err(e) => {
+                ctx.report_error("Failed to create MQTT client".to_string(), e.to_string())
+                    .await;
+                panic!("Failed to create MQTT client: {:?}", e);
+            }
+        };
+
+        let (mut events, eventloop) = eventloop.spawn();
+
+        let mut connected = false;
+        let finish = loop {
+            select! {
+                event = events.recv() => {
+                    match event.expect("MQTT event loop stopped") {
+                        // sessions aren't persisted, so we subscribe again after reconnecting
+                        Ok(MqttEvent::Connected) => {
+                            info!("Connected to {}; subscribing to {}", self.config.url, self.topic);
+                            connected = true;
+                            if let Err(e) = client.subscribe(&self.topic, self.qos).await {
+                                ctx.report_error("Failed to subscribe to MQTT topic".to_string(), e.to_string()).await;
+                                panic!("Failed to subscribe to {}: {:?}", self.topic, e);
+                            }
+                        }
+                        Ok(MqttEvent::Message { topic, payload }) => {
+                            let values = if self.metadata_fields.is_empty() {
+                                self.deserializer.deserialize_slice(&payload).await
+                            } else {
+                                let metadata: Vec<_> = self
+                                    .metadata_fields
+                                    .iter()
+                                    .map(|field| match field.key.as_str() {
+                                        "topic" => {
+                                            (field.field_name.clone(), MetadataValue::Json(json!(topic)))
+                                        }
+                                        key => unreachable!("unknown MQTT metadata key {}", key),
+                                    })
+                                    .collect();
+                                self.deserializer.deserialize_slice_with_metadata(&payload, &metadata).await
+                            };
+
+                            for value in self.bad_data.handle(ctx, &payload, values).await.unwrap_or_default() {
+                                ctx.collector.collect(Record {
+                                    timestamp: SystemTime::now(),
+                                    key: None,
+                                    value,
+                                }).await;
+                            }
+                        }
+                        Ok(MqttEvent::SubscriptionRejected(reason)) => {
+                            ctx.report_error("MQTT broker rejected the subscription".to_string(), format!("{}: {}", self.topic, reason)).await;
+                            panic!("Subscription to {} was rejected: {}", self.topic, reason);
+                        }
+                        Ok(MqttEvent::PubAck | MqttEvent::Other) => {}
+                        Err(e) if connected => {
+                            warn!("Lost connection to {}, reconnecting: {:?}", self.config.url, e);
+                        }
+                        Err(e) => {
+                            ctx.report_error("Failed to connect to MQTT broker".to_string(), e.to_string()).await;
+                            panic!("Failed to connect to {}: {:?}", self.config.url, e);
+                        }
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.handle_control_message(ctx, control_message).await {
+                        break r;
+                    }
+                }
+            }
+        };
+
+        if let Err(e) = client.disconnect().await {
+            warn!("Failed to disconnect from {}: {:?}", self.config.url, e);
+        }
+        eventloop.abort();
+        finish
+    }
+}
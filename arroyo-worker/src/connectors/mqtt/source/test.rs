@@ -0,0 +1,181 @@
+use rand::Rng;
+use std::time::{Duration, SystemTime};
+
+use crate::engine::{Context, OutQueue, QueueItem};
+use arroyo_rpc::types::{Format, JsonFormat};
+use arroyo_rpc::{ControlMessage, ControlResp};
+use arroyo_types::{CheckpointBarrier, Message};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use super::MqttSourceFunc;
+use crate::connectors::mqtt::{MqttConfig, MqttTableQos};
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode, Serialize, Deserialize, PartialEq)]
+struct TestData {
+    i: u64,
+}
+
+const BROKER_URL: &str = "mqtt://localhost:1883";
+
+struct MqttSourceWithReads {
+    to_control_tx: Sender<ControlMessage>,
+    from_control_rx: Receiver<ControlResp>,
+    data_recv: Receiver<QueueItem>,
+}
+
+impl MqttSourceWithReads {
+    async fn new(topic: &str) -> Self {
+        let config = MqttConfig {
+            url: BROKER_URL.to_string(),
+            protocol_version: None,
+            username: None,
+            password: None,
+            client_prefix: None,
+            tls: None,
+        };
+        let mut mqtt: MqttSourceFunc<(), TestData> = MqttSourceFunc::new(
+            config,
+            topic,
+            MqttTableQos::AtLeastOnce,
+            Format::Json(JsonFormat::default()),
+        );
+
+        let (to_control_tx, control_rx) = channel(128);
+        let (command_tx, from_control_rx) = channel(128);
+        let (data_tx, recv) = channel(128);
+
+        let mut task_info = arroyo_types::get_test_task_info();
+        task_info.job_id = format!("mqtt-job-{}", rand::thread_rng().gen::<u64>());
+
+        let mut ctx: Context<(), TestData> = Context::new(
+            task_info,
+            None,
+            control_rx,
+            command_tx,
+            1,
+            vec![vec![OutQueue::new(data_tx, false)]],
+            vec![],
+        )
+        .await;
+
+        tokio::spawn(async move {
+            mqtt.on_start(&mut ctx).await;
+            mqtt.run(&mut ctx).await;
+        });
+
+        Self {
+            to_control_tx,
+            from_control_rx,
+            data_recv: recv,
+        }
+    }
+
+    async fn assert_next_message_record_value(&mut self, expected_value: u64) {
+        let msg: Message<(), TestData> = self
+            .data_recv
+            .recv()
+            .await
+            .expect("option shouldn't be missing")
+            .into();
+        if let Message::Record(record) = msg {
+            assert_eq!(expected_value, record.value.i);
+        } else {
+            unreachable!("expected a record, got {:?}", msg);
+        }
+    }
+
+    async fn assert_next_message_checkpoint(&mut self, expected_epoch: u32) {
+        let msg: Message<(), TestData> = self
+            .data_recv
+            .recv()
+            .await
+            .expect("option shouldn't be missing")
+            .into();
+        if let Message::Barrier(barrier) = msg {
+            assert_eq!(expected_epoch, barrier.epoch);
+        } else {
+            unreachable!("expected a barrier, got {:?}", msg);
+        }
+    }
+
+    async fn assert_control_checkpoint(&mut self, expected_epoch: u32) {
+        loop {
+            let control_response = self
+                .from_control_rx
+                .recv()
+                .await
+                .expect("should be a valid message");
+
+            if let ControlResp::CheckpointCompleted(checkpoint) = control_response {
+                assert_eq!(expected_epoch, checkpoint.checkpoint_epoch);
+                return;
+            }
+        }
+    }
+}
+
+/// Requires an MQTT broker, like Mosquitto, listening on localhost:1883
+#[tokio::test]
+#[ignore]
+async fn test_mqtt() {
+    let prefix = format!("arroyo-test/{}", rand::thread_rng().gen::<u64>());
+    let mut reader = MqttSourceWithReads::new(&format!("{}/+/telemetry", prefix)).await;
+
+    let mut options = rumqttc::MqttOptions::new("arroyo-test-publisher", "localhost", 1883);
+    options.set_keep_alive(Duration::from_secs(5));
+    let (publisher, mut eventloop) = rumqttc::AsyncClient::new(options, 100);
+    tokio::spawn(async move { while eventloop.poll().await.is_ok() {} });
+
+    // give the source time to subscribe, as messages published before then aren't delivered
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    for i in 1u64..10 {
+        let device = if i % 2 == 0 { "device-a" } else { "device-b" };
+        publisher
+            .publish(
+                format!("{}/{}/telemetry", prefix, device),
+                rumqttc::QoS::AtLeastOnce,
+                false,
+                serde_json::to_vec(&TestData { i }).unwrap(),
+            )
+            .await
+            .unwrap();
+        reader.assert_next_message_record_value(i).await;
+    }
+
+    // doesn't match the filter
+    publisher
+        .publish(
+            format!("{}/device-a/status", prefix),
+            rumqttc::QoS::AtLeastOnce,
+            false,
+            serde_json::to_vec(&TestData { i: 100 }).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    reader
+        .to_control_tx
+        .send(ControlMessage::Checkpoint(CheckpointBarrier {
+            epoch: 1,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+        }))
+        .await
+        .unwrap();
+    reader.assert_control_checkpoint(1).await;
+    reader.assert_next_message_checkpoint(1).await;
+
+    publisher
+        .publish(
+            format!("{}/device-a/telemetry", prefix),
+            rumqttc::QoS::AtLeastOnce,
+            false,
+            serde_json::to_vec(&TestData { i: 10 }).unwrap(),
+        )
+        .await
+        .unwrap();
+    reader.assert_next_message_record_value(10).await;
+}
@@ -16,14 +16,15 @@ use tokio::select;
 use tokio::time::MissedTickBehavior;
 
 use arroyo_rpc::grpc::StopMode;
-use arroyo_rpc::types::Format;
 use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
 use tracing::{debug, info, warn};
 use typify::import_types;
 
 use crate::{
+    connectors::bad_data::BadDataHandler,
     engine::{Context, StreamNode},
-    formats, SchemaData, SourceFinishType,
+    formats::DataDeserializer,
+    SchemaData, SourceFinishType,
 };
 
 import_types!(schema = "../connector-schemas/polling_http/table.json");
@@ -39,7 +40,8 @@ where
 {
     state: PollingHttpSourceState<T>,
     client: reqwest::Client,
-    format: Format,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
     endpoint: url::Url,
     method: reqwest::Method,
     body: Option<Bytes>,
@@ -63,6 +65,7 @@ where
     pub fn from_config(config: &str) -> Self {
         let config: OperatorConfig =
             serde_json::from_str(config).expect("Invalid config for WebhookSink");
+        let bad_data = BadDataHandler::from_config(&config);
         let table: PollingHttpTable =
             serde_json::from_value(config.table).expect("Invalid table config for WebhookSink");
 
@@ -86,9 +89,12 @@ where
                 .timeout(Duration::from_secs(5))
                 .build()
                 .expect("could not construct http client"),
-            format: config
-                .format
-                .expect("polling http source must have a format configured"),
+            deserializer: DataDeserializer::new(
+                config
+                    .format
+                    .expect("polling http source must have a format configured"),
+            ),
+            bad_data,
             endpoint: url::Url::from_str(&table.endpoint).expect("invalid endpoint"),
             method: match table.method {
                 None | Some(Method::Get) => reqwest::Method::GET,
@@ -162,7 +168,7 @@ where
         None
     }
 
-    async fn request(&mut self) -> Result<T, UserError> {
+    async fn request(&mut self) -> Result<Vec<u8>, UserError> {
         let mut request = self
             .client
             .request(self.method.clone(), self.endpoint.clone());
@@ -213,7 +219,7 @@ where
                 }
             }
 
-            Ok(formats::deserialize_slice(&self.format, &buf)?)
+            Ok(buf)
         } else {
             let status = resp.status();
             let bytes = resp.bytes().await;
@@ -246,21 +252,28 @@ where
                 select! {
                     _ = timer.tick()  => {
                         match self.request().await {
-                            Ok(value) => {
-                                if self.emit_behavior == EmitBehavior::Changed {
-                                    if Some(&value) == self.state.last_message.as_ref() {
-                                        continue;
+                            Ok(buf) => {
+                                let values = self.deserializer.deserialize_slice(&buf).await;
+                                let Some(values) = self.bad_data.handle(ctx, &buf, values).await else {
+                                    continue;
+                                };
+
+                                for value in values {
+                                    if self.emit_behavior == EmitBehavior::Changed {
+                                        if Some(&value) == self.state.last_message.as_ref() {
+                                            continue;
+                                        }
                                     }
-                                }
 
-                                // TODO: should be possible to get rid of this clone
-                                self.state.last_message = Some(value.clone());
+                                    // TODO: should be possible to get rid of this clone
+                                    self.state.last_message = Some(value.clone());
 
-                                ctx.collect(Record {
-                                    timestamp: SystemTime::now(),
-                                    key: None,
-                                    value,
-                                }).await;
+                                    ctx.collect(Record {
+                                        timestamp: SystemTime::now(),
+                                        key: None,
+                                        value,
+                                    }).await;
+                                }
                             }
                             Err(e) => {
                                 ctx.report_user_error(e).await;
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use typify::import_types;
+
+mod pgoutput;
+mod replication;
+pub mod sink;
+pub mod source;
+
+import_types!(schema = "../connector-schemas/postgres/connection.json");
+import_types!(schema = "../connector-schemas/postgres/table.json");
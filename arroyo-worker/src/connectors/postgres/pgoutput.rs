@@ -0,0 +1,320 @@
+// Decoding of the messages written by Postgres' `pgoutput` logical decoding plugin, in version 1
+// of its protocol; see https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html
+
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Begin {
+        final_lsn: u64,
+    },
+    Commit {
+        commit_lsn: u64,
+    },
+    Relation(Relation),
+    Insert {
+        relation_id: u32,
+        new: Vec<TupleValue>,
+    },
+    Update {
+        relation_id: u32,
+        old: Option<Vec<TupleValue>>,
+        new: Vec<TupleValue>,
+    },
+    Delete {
+        relation_id: u32,
+        old: Vec<TupleValue>,
+    },
+    /// Messages that don't describe row changes, like truncates, types and origins
+    Other(u8),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Relation {
+    pub id: u32,
+    pub namespace: String,
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub type_oid: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TupleValue {
+    Null,
+    /// A TOASTed value that wasn't changed, and so isn't included in the message
+    UnchangedToast,
+    Text(String),
+}
+
+pub fn parse(msg: &[u8]) -> Result<Message, String> {
+    let mut reader = Reader { buf: msg };
+
+    Ok(match reader.u8()? {
+        b'B' => {
+            let final_lsn = reader.u64()?;
+            Message::Begin { final_lsn }
+        }
+        b'C' => {
+            let _flags = reader.u8()?;
+            let commit_lsn = reader.u64()?;
+            Message::Commit { commit_lsn }
+        }
+        b'R' => {
+            let id = reader.u32()?;
+            let namespace = reader.string()?;
+            let name = reader.string()?;
+            let _replica_identity = reader.u8()?;
+            let columns = (0..reader.u16()?)
+                .map(|_| {
+                    let _flags = reader.u8()?;
+                    let name = reader.string()?;
+                    let type_oid = reader.u32()?;
+                    let _type_modifier = reader.u32()?;
+                    Ok(Column { name, type_oid })
+                })
+                .collect::<Result<_, String>>()?;
+
+            Message::Relation(Relation {
+                id,
+                namespace: if namespace.is_empty() {
+                    // the pg_catalog namespace is sent as an empty string
+                    "pg_catalog".to_string()
+                } else {
+                    namespace
+                },
+                name,
+                columns,
+            })
+        }
+        b'I' => {
+            let relation_id = reader.u32()?;
+            reader.expect(b'N')?;
+            Message::Insert {
+                relation_id,
+                new: reader.tuple()?,
+            }
+        }
+        b'U' => {
+            let relation_id = reader.u32()?;
+            let old = match reader.u8()? {
+                b'K' | b'O' => {
+                    let old = reader.tuple()?;
+                    reader.expect(b'N')?;
+                    Some(old)
+                }
+                b'N' => None,
+                other => return Err(format!("unexpected tuple type '{}'", other as char)),
+            };
+
+            Message::Update {
+                relation_id,
+                old,
+                new: reader.tuple()?,
+            }
+        }
+        b'D' => {
+            let relation_id = reader.u32()?;
+            match reader.u8()? {
+                b'K' | b'O' => {}
+                other => return Err(format!("unexpected tuple type '{}'", other as char)),
+            }
+
+            Message::Delete {
+                relation_id,
+                old: reader.tuple()?,
+            }
+        }
+        other => Message::Other(other),
+    })
+}
+
+/// Parses an LSN in its text representation, like `16/B374D848`
+pub fn parse_lsn(lsn: &str) -> Result<u64, String> {
+    let (high, low) = lsn
+        .split_once('/')
+        .ok_or_else(|| format!("invalid LSN '{}'", lsn))?;
+    let high = u64::from_str_radix(high, 16).map_err(|_| format!("invalid LSN '{}'", lsn))?;
+    let low = u64::from_str_radix(low, 16).map_err(|_| format!("invalid LSN '{}'", lsn))?;
+    Ok((high << 32) | low)
+}
+
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFFFFFF)
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.buf.len() < n {
+            return Err("unexpected end of message".to_string());
+        }
+        let (bytes, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        match self.u8()? {
+            b if b == expected => Ok(()),
+            b => Err(format!(
+                "expected '{}' but found '{}'",
+                expected as char, b as char
+            )),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let end = self
+            .buf
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| "unterminated string".to_string())?;
+        let s = String::from_utf8_lossy(&self.buf[..end]).to_string();
+        self.buf = &self.buf[end + 1..];
+        Ok(s)
+    }
+
+    fn tuple(&mut self) -> Result<Vec<TupleValue>, String> {
+        (0..self.u16()?)
+            .map(|_| {
+                Ok(match self.u8()? {
+                    b'n' => TupleValue::Null,
+                    b'u' => TupleValue::UnchangedToast,
+                    b't' => {
+                        let len = self.u32()? as usize;
+                        TupleValue::Text(String::from_utf8_lossy(self.take(len)?).to_string())
+                    }
+                    other => return Err(format!("unsupported value type '{}'", other as char)),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuple(values: &[Option<&str>]) -> Vec<u8> {
+        let mut buf = (values.len() as u16).to_be_bytes().to_vec();
+        for value in values {
+            match value {
+                Some(v) => {
+                    buf.push(b't');
+                    buf.extend((v.len() as u32).to_be_bytes());
+                    buf.extend(v.as_bytes());
+                }
+                None => buf.push(b'n'),
+            }
+        }
+        buf
+    }
+
+    #[test]
+    fn test_parse_relation() {
+        let mut msg = vec![b'R'];
+        msg.extend(16385u32.to_be_bytes());
+        msg.extend(b"public\0orders\0");
+        msg.push(b'f');
+        msg.extend(2u16.to_be_bytes());
+        for (name, oid) in [("id", 23u32), ("amount", 1700)] {
+            msg.push(1);
+            msg.extend(name.as_bytes());
+            msg.push(0);
+            msg.extend(oid.to_be_bytes());
+            msg.extend((-1i32).to_be_bytes());
+        }
+
+        assert_eq!(
+            parse(&msg).unwrap(),
+            Message::Relation(Relation {
+                id: 16385,
+                namespace: "public".to_string(),
+                name: "orders".to_string(),
+                columns: vec![
+                    Column {
+                        name: "id".to_string(),
+                        type_oid: 23
+                    },
+                    Column {
+                        name: "amount".to_string(),
+                        type_oid: 1700
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_changes() {
+        let mut insert = vec![b'I'];
+        insert.extend(16385u32.to_be_bytes());
+        insert.push(b'N');
+        insert.extend(tuple(&[Some("1"), None]));
+        assert_eq!(
+            parse(&insert).unwrap(),
+            Message::Insert {
+                relation_id: 16385,
+                new: vec![TupleValue::Text("1".to_string()), TupleValue::Null],
+            }
+        );
+
+        let mut update = vec![b'U'];
+        update.extend(16385u32.to_be_bytes());
+        update.push(b'O');
+        update.extend(tuple(&[Some("1"), Some("10.5")]));
+        update.push(b'N');
+        update.extend(tuple(&[Some("1"), Some("12")]));
+        assert_eq!(
+            parse(&update).unwrap(),
+            Message::Update {
+                relation_id: 16385,
+                old: Some(vec![
+                    TupleValue::Text("1".to_string()),
+                    TupleValue::Text("10.5".to_string())
+                ]),
+                new: vec![
+                    TupleValue::Text("1".to_string()),
+                    TupleValue::Text("12".to_string())
+                ],
+            }
+        );
+
+        let mut delete = vec![b'D'];
+        delete.extend(16385u32.to_be_bytes());
+        delete.push(b'O');
+        delete.extend(tuple(&[Some("1"), Some("12")]));
+        assert!(matches!(parse(&delete).unwrap(), Message::Delete { .. }));
+
+        assert!(parse(&insert[..insert.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_lsn() {
+        assert_eq!(parse_lsn("16/B374D848").unwrap(), 0x16_B374_D848);
+        assert_eq!(format_lsn(0x16_B374_D848), "16/B374D848");
+        assert!(parse_lsn("B374D848").is_err());
+    }
+}
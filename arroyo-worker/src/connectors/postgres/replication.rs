@@ -0,0 +1,285 @@
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use arroyo_rpc::postgres::{quote_ident, quote_literal};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use fallible_iterator::FallibleIterator;
+use postgres_protocol::authentication::{
+    md5_hash,
+    sasl::{ChannelBinding, ScramSha256, SCRAM_SHA_256},
+};
+use postgres_protocol::message::{backend::Message, frontend};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::pgoutput::format_lsn;
+use super::PostgresConfig;
+
+// postgres-protocol doesn't parse the response that starts streaming
+const COPY_BOTH_RESPONSE_TAG: u8 = b'W';
+
+// status updates report times relative to the Postgres epoch, 2000-01-01
+const POSTGRES_EPOCH: Duration = Duration::from_secs(946_684_800);
+
+/// A connection using the replication protocol, which tokio-postgres doesn't support. It's used
+/// to create replication slots, which can export a snapshot that stays usable until the
+/// connection runs another command or is closed, and to stream changes from them.
+pub struct ReplicationConnection {
+    stream: TcpStream,
+    read_buf: BytesMut,
+}
+
+/// A slot created with an exported snapshot
+pub struct CreatedSlot {
+    /// The LSN from which the slot's changes start, which the snapshot is consistent with
+    pub consistent_point: String,
+    /// The name of the snapshot, which can be imported with `SET TRANSACTION SNAPSHOT`
+    pub snapshot_name: String,
+}
+
+/// A message streamed from a replication slot
+pub enum ReplicationMessage {
+    /// A pgoutput message
+    XLogData(Bytes),
+    /// Sent periodically by the server, which closes the connection if `reply` is set and no
+    /// status update is sent in time
+    Keepalive { reply: bool },
+}
+
+impl ReplicationConnection {
+    pub async fn connect(config: &PostgresConfig) -> Result<Self> {
+        let stream =
+            TcpStream::connect((config.host.as_str(), config.port.unwrap_or(5432) as u16)).await?;
+        let mut conn = Self {
+            stream,
+            read_buf: BytesMut::new(),
+        };
+
+        let mut buf = BytesMut::new();
+        frontend::startup_message(
+            [
+                ("user", config.username.as_str()),
+                ("database", config.database.as_str()),
+                ("replication", "database"),
+            ],
+            &mut buf,
+        )?;
+        conn.send(&buf).await?;
+
+        let password = config.password.as_deref().unwrap_or_default().as_bytes();
+        let mut scram: Option<ScramSha256> = None;
+        loop {
+            let mut buf = BytesMut::new();
+            match conn.recv().await? {
+                Message::AuthenticationOk => {}
+                Message::AuthenticationCleartextPassword => {
+                    frontend::password_message(password, &mut buf)?;
+                    conn.send(&buf).await?;
+                }
+                Message::AuthenticationMd5Password(body) => {
+                    let hash = md5_hash(config.username.as_bytes(), password, body.salt());
+                    frontend::password_message(hash.as_bytes(), &mut buf)?;
+                    conn.send(&buf).await?;
+                }
+                Message::AuthenticationSasl(body) => {
+                    if !body.mechanisms().any(|m| Ok(m == SCRAM_SHA_256))? {
+                        bail!("the server doesn't support SCRAM-SHA-256 authentication");
+                    }
+                    let s = ScramSha256::new(password, ChannelBinding::unsupported());
+                    frontend::sasl_initial_response(SCRAM_SHA_256, s.message(), &mut buf)?;
+                    conn.send(&buf).await?;
+                    scram = Some(s);
+                }
+                Message::AuthenticationSaslContinue(body) => {
+                    let s = scram
+                        .as_mut()
+                        .ok_or_else(|| anyhow!("unexpected SASL message"))?;
+                    s.update(body.data())?;
+                    frontend::sasl_response(s.message(), &mut buf)?;
+                    conn.send(&buf).await?;
+                }
+                Message::AuthenticationSaslFinal(body) => {
+                    scram
+                        .as_mut()
+                        .ok_or_else(|| anyhow!("unexpected SASL message"))?
+                        .finish(body.data())?;
+                }
+                Message::ErrorResponse(body) => bail!(error_message(body.fields())?),
+                Message::ReadyForQuery(_) => return Ok(conn),
+                Message::ParameterStatus(_)
+                | Message::BackendKeyData(_)
+                | Message::NoticeResponse(_) => {}
+                _ => bail!("unexpected message while connecting for replication"),
+            }
+        }
+    }
+
+    /// Creates a logical replication slot using pgoutput, exporting a snapshot of the database at
+    /// the point the slot starts from
+    pub async fn create_slot(&mut self, slot_name: &str) -> Result<CreatedSlot> {
+        let mut buf = BytesMut::new();
+        frontend::query(
+            &format!(
+                "CREATE_REPLICATION_SLOT \"{}\" LOGICAL pgoutput EXPORT_SNAPSHOT",
+                slot_name.replace('"', "\"\"")
+            ),
+            &mut buf,
+        )?;
+        self.send(&buf).await?;
+
+        let mut row = None;
+        loop {
+            match self.recv().await? {
+                Message::DataRow(body) => {
+                    // slot_name, consistent_point, snapshot_name, output_plugin
+                    let values: Vec<Option<String>> = body
+                        .ranges()
+                        .map(|r| {
+                            Ok(r.map(|r| String::from_utf8_lossy(&body.buffer()[r]).to_string()))
+                        })
+                        .collect()?;
+                    row = Some(values);
+                }
+                Message::ErrorResponse(body) => bail!(error_message(body.fields())?),
+                Message::ReadyForQuery(_) => break,
+                _ => {}
+            }
+        }
+
+        match row.as_deref() {
+            Some([_, Some(consistent_point), Some(snapshot_name), ..]) => Ok(CreatedSlot {
+                consistent_point: consistent_point.clone(),
+                snapshot_name: snapshot_name.clone(),
+            }),
+            _ => bail!("unexpected response when creating replication slot"),
+        }
+    }
+
+    /// Starts streaming the changes in the slot for the publication with pgoutput. The server
+    /// starts from the position last confirmed for the slot, if that's later than `start_lsn`.
+    pub async fn start_replication(
+        &mut self,
+        slot_name: &str,
+        publication: &str,
+        start_lsn: u64,
+    ) -> Result<()> {
+        let mut buf = BytesMut::new();
+        frontend::query(
+            &format!(
+                "START_REPLICATION SLOT {} LOGICAL {} (proto_version '1', publication_names {})",
+                quote_ident(slot_name),
+                format_lsn(start_lsn),
+                quote_literal(&quote_ident(publication))
+            ),
+            &mut buf,
+        )?;
+        self.send(&buf).await?;
+
+        loop {
+            if self.read_buf.first() == Some(&COPY_BOTH_RESPONSE_TAG) {
+                if self.read_buf.len() >= 5 {
+                    let len = (&self.read_buf[1..5]).get_u32() as usize;
+                    if self.read_buf.len() > len {
+                        self.read_buf.advance(len + 1);
+                        return Ok(());
+                    }
+                }
+            } else if let Some(message) = Message::parse(&mut self.read_buf)? {
+                match message {
+                    Message::ErrorResponse(body) => bail!(error_message(body.fields())?),
+                    Message::ParameterStatus(_) | Message::NoticeResponse(_) => {}
+                    _ => bail!("unexpected message while starting replication"),
+                }
+                continue;
+            }
+            self.read().await?;
+        }
+    }
+
+    /// Receives the next message streamed after `start_replication`
+    pub async fn recv_replication(&mut self) -> Result<ReplicationMessage> {
+        loop {
+            match self.recv().await? {
+                Message::CopyData(body) => {
+                    let data = body.into_bytes();
+                    return match data.first() {
+                        // the start and end of the WAL range and the send time precede the data
+                        Some(b'w') if data.len() >= 25 => {
+                            Ok(ReplicationMessage::XLogData(data.slice(25..)))
+                        }
+                        // the end of the WAL and the send time precede the reply flag
+                        Some(b'k') if data.len() >= 18 => Ok(ReplicationMessage::Keepalive {
+                            reply: data[17] == 1,
+                        }),
+                        _ => bail!("unexpected replication message"),
+                    };
+                }
+                Message::ErrorResponse(body) => bail!(error_message(body.fields())?),
+                Message::CopyDone => bail!("the server stopped replication"),
+                Message::ParameterStatus(_) | Message::NoticeResponse(_) => {}
+                _ => bail!("unexpected message during replication"),
+            }
+        }
+    }
+
+    /// Reports that changes up to the position have been processed, which lets the server
+    /// discard the WAL before it and start from it when replication restarts
+    pub async fn send_status(&mut self, lsn: u64) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH + POSTGRES_EPOCH)
+            .unwrap_or_default();
+
+        let mut body = BytesMut::new();
+        body.put_u8(b'r');
+        // written, flushed and applied
+        body.put_u64(lsn);
+        body.put_u64(lsn);
+        body.put_u64(lsn);
+        body.put_i64(now.as_micros() as i64);
+        // no reply requested
+        body.put_u8(0);
+
+        let mut buf = BytesMut::new();
+        frontend::CopyData::new(body)?.write(&mut buf);
+        self.send(&buf).await
+    }
+
+    async fn send(&mut self, buf: &[u8]) -> Result<()> {
+        self.stream.write_all(buf).await?;
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<Message> {
+        loop {
+            if let Some(message) = Message::parse(&mut self.read_buf)? {
+                return Ok(message);
+            }
+            self.read().await?;
+        }
+    }
+
+    /// Reads more data from the server; cancelling it doesn't lose any data
+    async fn read(&mut self) -> Result<()> {
+        if self.stream.read_buf(&mut self.read_buf).await? == 0 {
+            bail!("connection closed by the server");
+        }
+        Ok(())
+    }
+}
+
+fn error_message<'a>(
+    mut fields: impl FallibleIterator<
+        Item = postgres_protocol::message::backend::ErrorField<'a>,
+        Error = io::Error,
+    >,
+) -> Result<String> {
+    let mut message = None;
+    while let Some(field) = fields.next()? {
+        // the human-readable message
+        if field.type_() == b'M' {
+            message = Some(field.value().to_string());
+        }
+    }
+    Ok(message.unwrap_or_else(|| "unknown error".to_string()))
+}
@@ -0,0 +1,745 @@
+use crate::engine::{Context, StreamNode};
+use anyhow::{anyhow, Result};
+use arroyo_macro::process_fn;
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableWriteBehavior};
+use arroyo_rpc::postgres::{quote_ident, quote_literal, quote_qualified};
+use arroyo_rpc::{CheckpointEvent, ControlMessage, OperatorConfig};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
+use arroyo_types::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use tokio_postgres::{Client, NoTls};
+use tracing::{info, warn};
+
+use super::{PostgresConfig, PostgresTable, PostgresTableCommitMode};
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+#[derive(StreamNode)]
+pub struct PostgresSinkFunc<K: Key + Serialize, T: Data + Serialize> {
+    connection: PostgresConfig,
+    table_name: String,
+    exactly_once: bool,
+    batch_size: usize,
+    client: Option<Client>,
+    in_transaction: bool,
+    inserts: Vec<Value>,
+    /// The latest upsert (or delete, if None) for each key that hasn't been written yet, along
+    /// with the key columns themselves
+    updates: HashMap<String, (Value, Option<Value>)>,
+    /// In exactly-once mode, the transaction prepared at the last checkpoint, which is committed
+    /// once the checkpoint completes. Rows aren't written until then, as they could wait on locks
+    /// held by the prepared transaction.
+    pending_commit: Option<String>,
+    _t: PhantomData<(K, T)>,
+}
+
+impl<K: Key + Serialize, T: Data + Serialize> PostgresSinkFunc<K, T> {
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for PostgresSink");
+        let connection: PostgresConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for PostgresSink");
+        let table: PostgresTable =
+            serde_json::from_value(config.table).expect("Invalid table config for PostgresSink");
+
+        Self {
+            connection,
+            table_name: table.table_name,
+            exactly_once: matches!(
+                table.commit_mode,
+                Some(PostgresTableCommitMode::ExactlyOnce)
+            ),
+            batch_size: table
+                .batch_size
+                .map(|size| size as usize)
+                .unwrap_or(DEFAULT_BATCH_SIZE),
+            client: None,
+            in_transaction: false,
+            inserts: vec![],
+            updates: HashMap::new(),
+            pending_commit: None,
+            _t: PhantomData,
+        }
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let mut config = tokio_postgres::Config::new();
+        config
+            .host(&self.connection.host)
+            .port(self.connection.port.unwrap_or(5432) as u16)
+            .dbname(&self.connection.database)
+            .user(&self.connection.username);
+        if let Some(password) = &self.connection.password {
+            config.password(password);
+        }
+
+        let (client, connection) = config.connect(NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Postgres connection closed: {}", e);
+            }
+        });
+
+        Ok(client)
+    }
+
+    /// Finishes the transactions prepared by previous runs of this subtask: those of checkpoints
+    /// up to the one we restored from completed, so they're committed, and later ones are rolled
+    /// back. Subtask 0 also finishes those of subtasks that no longer exist because the
+    /// parallelism was reduced. Each subtask only touches transactions that no other running
+    /// subtask can prepare, so none can be finished while another subtask is using it.
+    async fn recover(&self, task_info: &TaskInfo, restored_epoch: Option<u32>) -> Result<()> {
+        let client = self.client.as_ref().unwrap();
+        let prefix = transaction_prefix(task_info);
+        let rows = client
+            .query(
+                "SELECT gid FROM pg_prepared_xacts
+                WHERE database = current_database() AND left(gid, length($1)) = $1",
+                &[&prefix],
+            )
+            .await?;
+
+        for row in rows {
+            let gid: String = row.get(0);
+            let Some((index, epoch)) = parse_transaction_id(&prefix, &gid) else {
+                warn!(
+                    "Ignoring prepared transaction {} with an unexpected name",
+                    gid
+                );
+                continue;
+            };
+
+            let owned = index == task_info.task_index
+                || (task_info.task_index == 0 && index >= task_info.parallelism);
+            if !owned {
+                continue;
+            }
+
+            let completed = matches!(restored_epoch, Some(r) if epoch <= r);
+            info!(
+                "{} transaction {} prepared by a previous run of the sink",
+                if completed {
+                    "Committing"
+                } else {
+                    "Rolling back"
+                },
+                gid
+            );
+            client
+                .batch_execute(&format!(
+                    "{} {}",
+                    if completed {
+                        "COMMIT PREPARED"
+                    } else {
+                        "ROLLBACK PREPARED"
+                    },
+                    quote_literal(&gid)
+                ))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn insert(&mut self, value: &T) {
+        self.inserts.push(serde_json::to_value(value).unwrap());
+        if self.inserts.len() >= self.batch_size && self.pending_commit.is_none() {
+            self.flush().await;
+        }
+    }
+
+    async fn upsert(&mut self, key: Value, value: Option<Value>) {
+        self.updates.insert(key.to_string(), (key, value));
+        if self.updates.len() >= self.batch_size && self.pending_commit.is_none() {
+            self.flush().await;
+        }
+    }
+
+    async fn flush(&mut self) {
+        if let Err(e) = self.try_flush().await {
+            panic!(
+                "Failed to write to Postgres table {}: {:?}",
+                self.table_name, e
+            );
+        }
+    }
+
+    /// Writes the buffered rows inside the open transaction, which is committed at the next
+    /// checkpoint
+    async fn try_flush(&mut self) -> Result<()> {
+        if self.inserts.is_empty() && self.updates.is_empty() {
+            return Ok(());
+        }
+
+        self.begin().await?;
+        let client = self.client.as_ref().unwrap();
+
+        if let Some(first) = self.inserts.first() {
+            let statement = insert_statement(&self.table_name, &object_keys(first)?, &[]);
+            let rows = Value::Array(std::mem::take(&mut self.inserts));
+            client.execute(&statement, &[&rows]).await?;
+        }
+
+        let mut upserts = vec![];
+        let mut deletes = vec![];
+        let mut keys = vec![];
+        for (_, (key, value)) in self.updates.drain() {
+            if keys.is_empty() {
+                keys = object_keys(&key)?;
+            }
+            match value {
+                Some(value) => upserts.push(value),
+                None => deletes.push(key),
+            }
+        }
+
+        if let Some(first) = upserts.first() {
+            let statement = insert_statement(&self.table_name, &object_keys(first)?, &keys);
+            client
+                .execute(&statement, &[&Value::Array(upserts)])
+                .await?;
+        }
+
+        if !deletes.is_empty() {
+            let statement = delete_statement(&self.table_name, &keys);
+            client
+                .execute(&statement, &[&Value::Array(deletes)])
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn begin(&mut self) -> Result<()> {
+        if !self.in_transaction {
+            self.client.as_ref().unwrap().batch_execute("BEGIN").await?;
+            self.in_transaction = true;
+        }
+        Ok(())
+    }
+
+    /// Flushes the buffered rows and commits them
+    async fn commit(&mut self) -> Result<()> {
+        self.try_flush().await?;
+
+        if self.in_transaction {
+            self.client
+                .as_ref()
+                .unwrap()
+                .batch_execute("COMMIT")
+                .await?;
+            self.in_transaction = false;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes the buffered rows and prepares the transaction for the checkpoint, so that it can
+    /// be committed once the checkpoint completes, even by a later run of the sink
+    async fn prepare(&mut self, task_info: &TaskInfo, epoch: u32) -> Result<()> {
+        self.try_flush().await?;
+
+        if self.in_transaction {
+            let gid = format!(
+                "{}{}-{}",
+                transaction_prefix(task_info),
+                task_info.task_index,
+                epoch
+            );
+            self.client
+                .as_ref()
+                .unwrap()
+                .batch_execute(&format!("PREPARE TRANSACTION {}", quote_literal(&gid)))
+                .await?;
+            self.in_transaction = false;
+            self.pending_commit = Some(gid);
+        }
+
+        Ok(())
+    }
+
+    async fn commit_prepared(&mut self) -> Result<()> {
+        if let Some(gid) = self.pending_commit.take() {
+            self.client
+                .as_ref()
+                .unwrap()
+                .batch_execute(&format!("COMMIT PREPARED {}", quote_literal(&gid)))
+                .await?;
+        }
+
+        // rows were held back while the transaction was waiting to be committed
+        if self.inserts.len() >= self.batch_size || self.updates.len() >= self.batch_size {
+            self.try_flush().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prepared transactions are named `arroyo-<job>-<operator>-<subtask>-<epoch>`, so that the
+/// transactions of a sink can be found after a restart
+fn transaction_prefix(task_info: &TaskInfo) -> String {
+    format!("arroyo-{}-{}-", task_info.job_id, task_info.operator_id)
+}
+
+/// Returns the subtask index and epoch of a transaction named with the sink's prefix
+fn parse_transaction_id(prefix: &str, gid: &str) -> Option<(usize, u32)> {
+    let (index, epoch) = gid.strip_prefix(prefix)?.split_once('-')?;
+    Some((index.parse().ok()?, epoch.parse().ok()?))
+}
+
+#[process_fn(in_k = K, in_t = T)]
+impl<K: Key + Serialize, T: Data + Serialize> PostgresSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("postgres-sink-{}", self.table_name)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        if self.exactly_once {
+            vec![TableDescriptor {
+                name: "e".to_string(),
+                description: "postgres sink epochs".to_string(),
+                table_type: arroyo_rpc::grpc::TableType::Global as i32,
+                delete_behavior: TableDeleteBehavior::None as i32,
+                write_behavior: TableWriteBehavior::CommitWrites as i32,
+                retention_micros: 0,
+            }]
+        } else {
+            vec![]
+        }
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        match self.connect().await {
+            Ok(client) => {
+                self.client = Some(client);
+            }
+            Err(e) => {
+                ctx.report_error("Failed to connect to Postgres".to_string(), e.to_string())
+                    .await;
+                panic!("Failed to connect to Postgres: {:?}", e);
+            }
+        }
+
+        if self.exactly_once {
+            let mut epochs: GlobalKeyedState<usize, u32, _> =
+                ctx.state.get_global_keyed_state('e').await;
+            let restored_epoch = epochs.get_all().into_iter().max().copied();
+
+            if let Err(e) = self.recover(&ctx.task_info, restored_epoch).await {
+                ctx.report_error(
+                    "Failed to recover Postgres sink transactions".to_string(),
+                    e.to_string(),
+                )
+                .await;
+                panic!("Failed to recover prepared transactions: {:?}", e);
+            }
+        }
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, _ctx: &mut Context<(), ()>) {
+        self.insert(&record.value).await;
+    }
+
+    async fn handle_checkpoint(&mut self, barrier: &CheckpointBarrier, ctx: &mut Context<(), ()>) {
+        if self.exactly_once {
+            self.prepare(&ctx.task_info, barrier.epoch)
+                .await
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to prepare transaction for Postgres table {}: {:?}",
+                        self.table_name, e
+                    )
+                });
+
+            ctx.state
+                .get_global_keyed_state('e')
+                .await
+                .insert(ctx.task_info.task_index, barrier.epoch)
+                .await;
+        } else {
+            self.commit().await.unwrap_or_else(|e| {
+                panic!(
+                    "Failed to commit to Postgres table {}: {:?}",
+                    self.table_name, e
+                )
+            });
+        }
+    }
+
+    async fn handle_commit(&mut self, epoch: u32, ctx: &mut Context<(), ()>) {
+        if let Err(e) = self.commit_prepared().await {
+            ctx.report_error("Failed to commit to Postgres".to_string(), e.to_string())
+                .await;
+            panic!(
+                "Failed to commit to Postgres table {}: {:?}",
+                self.table_name, e
+            );
+        }
+
+        let checkpoint_event = arroyo_rpc::ControlResp::CheckpointEvent(CheckpointEvent {
+            checkpoint_epoch: epoch,
+            operator_id: ctx.task_info.operator_id.clone(),
+            subtask_index: ctx.task_info.task_index as u32,
+            time: SystemTime::now(),
+            event_type: arroyo_rpc::grpc::TaskCheckpointEventType::FinishedCommit.into(),
+        });
+        ctx.control_tx
+            .send(checkpoint_event)
+            .await
+            .expect("sent commit event");
+    }
+
+    async fn on_close(&mut self, ctx: &mut Context<(), ()>) {
+        if self.pending_commit.is_some() {
+            if let Some(ControlMessage::Commit { epoch }) = ctx.control_rx.recv().await {
+                self.handle_commit(epoch, ctx).await;
+            } else {
+                warn!("no commit message received, not committing");
+                return;
+            }
+        }
+
+        if let Err(e) = self.commit().await {
+            warn!(
+                "Failed to commit final writes to Postgres table {}: {:?}",
+                self.table_name, e
+            );
+        }
+    }
+}
+
+/// Writes updating data keyed by the primary key, as upserts and deletes. Updates that change
+/// the primary key are split into a retraction and an append before they reach the sink, so the
+/// record's key is the key of both the old and new rows.
+#[derive(StreamNode)]
+pub struct PostgresUpsertSinkFunc<K: Key + Serialize, T: Data + Serialize> {
+    sink: PostgresSinkFunc<K, T>,
+}
+
+impl<K: Key + Serialize, T: Data + Serialize> PostgresUpsertSinkFunc<K, T> {
+    pub fn from_config(config: &str) -> Self {
+        Self {
+            sink: PostgresSinkFunc::from_config(config),
+        }
+    }
+}
+
+#[process_fn(in_k = K, in_t = UpdatingData<T>)]
+impl<K: Key + Serialize, T: Data + Serialize> PostgresUpsertSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("postgres-upsert-sink-{}", self.sink.table_name)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        self.sink.tables()
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        self.sink.on_start(ctx).await;
+    }
+
+    async fn process_element(
+        &mut self,
+        record: &Record<K, UpdatingData<T>>,
+        _ctx: &mut Context<(), ()>,
+    ) {
+        let key =
+            serde_json::to_value(record.key.as_ref().expect("upserts must be keyed")).unwrap();
+        match &record.value {
+            UpdatingData::Append(value) => {
+                let value = serde_json::to_value(value).unwrap();
+                self.sink.upsert(key, Some(value)).await;
+            }
+            UpdatingData::Update { new, .. } => {
+                let new = serde_json::to_value(new).unwrap();
+                self.sink.upsert(key, Some(new)).await;
+            }
+            UpdatingData::Retract(_) => {
+                self.sink.upsert(key, None).await;
+            }
+        }
+    }
+
+    async fn handle_checkpoint(&mut self, barrier: &CheckpointBarrier, ctx: &mut Context<(), ()>) {
+        self.sink.handle_checkpoint(barrier, ctx).await;
+    }
+
+    async fn handle_commit(&mut self, epoch: u32, ctx: &mut Context<(), ()>) {
+        self.sink.handle_commit(epoch, ctx).await;
+    }
+
+    async fn on_close(&mut self, ctx: &mut Context<(), ()>) {
+        self.sink.on_close(ctx).await;
+    }
+}
+
+fn object_keys(value: &Value) -> Result<Vec<String>> {
+    value
+        .as_object()
+        .map(|fields| fields.keys().cloned().collect())
+        .ok_or_else(|| anyhow!("can only write records with fields, not {}", value))
+}
+
+/// Inserts the rows of a JSON array passed as the only parameter; if there are key columns,
+/// conflicting rows are updated instead
+fn insert_statement(table: &str, columns: &[String], keys: &[String]) -> String {
+    let table = quote_qualified(table);
+    let column_list = columns
+        .iter()
+        .map(|c| quote_ident(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut statement = format!(
+        "INSERT INTO {table} ({column_list}) SELECT {column_list} FROM json_populate_recordset(null::{table}, $1)"
+    );
+
+    if !keys.is_empty() {
+        let updates: Vec<_> = columns
+            .iter()
+            .filter(|c| !keys.contains(c))
+            .map(|c| format!("{0} = EXCLUDED.{0}", quote_ident(c)))
+            .collect();
+
+        let key_list = keys
+            .iter()
+            .map(|k| quote_ident(k))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        if updates.is_empty() {
+            statement.push_str(&format!(" ON CONFLICT ({key_list}) DO NOTHING"));
+        } else {
+            statement.push_str(&format!(
+                " ON CONFLICT ({key_list}) DO UPDATE SET {}",
+                updates.join(", ")
+            ));
+        }
+    }
+
+    statement
+}
+
+/// Deletes the rows matching the keys in a JSON array passed as the only parameter
+fn delete_statement(table: &str, keys: &[String]) -> String {
+    let table = quote_qualified(table);
+    let conditions: Vec<_> = keys
+        .iter()
+        .map(|k| format!("target.{0} = deleted.{0}", quote_ident(k)))
+        .collect();
+
+    format!(
+        "DELETE FROM {table} AS target USING json_populate_recordset(null::{table}, $1) AS deleted WHERE {}",
+        conditions.join(" AND ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{delete_statement, insert_statement, parse_transaction_id, PostgresUpsertSinkFunc};
+    use crate::engine::{Context, OutQueue};
+    use arroyo_rpc::{ControlResp, OperatorConfig};
+    use arroyo_types::{
+        get_test_task_info, CheckpointBarrier, DatabaseConfig, Record, UpdatingData,
+    };
+    use serde::Serialize;
+    use std::time::SystemTime;
+    use tokio::sync::mpsc::{channel, Receiver};
+    use tokio_postgres::{Client, NoTls};
+
+    #[derive(Clone, Debug, bincode::Encode, bincode::Decode, PartialEq, Eq, Hash, Serialize)]
+    struct Key {
+        id: i32,
+    }
+
+    #[derive(Clone, Debug, bincode::Encode, bincode::Decode, PartialEq, Serialize)]
+    struct Row {
+        id: i32,
+        count: i64,
+    }
+
+    fn strings(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_insert_statement() {
+        assert_eq!(
+            insert_statement("orders", &strings(&["id", "amount"]), &[]),
+            "INSERT INTO \"orders\" (\"id\", \"amount\") SELECT \"id\", \"amount\" \
+            FROM json_populate_recordset(null::\"orders\", $1)"
+        );
+    }
+
+    #[test]
+    fn test_upsert_statement() {
+        assert_eq!(
+            insert_statement(
+                "public.counts",
+                &strings(&["id", "count"]),
+                &strings(&["id"])
+            ),
+            "INSERT INTO \"public\".\"counts\" (\"id\", \"count\") SELECT \"id\", \"count\" \
+            FROM json_populate_recordset(null::\"public\".\"counts\", $1) \
+            ON CONFLICT (\"id\") DO UPDATE SET \"count\" = EXCLUDED.\"count\""
+        );
+
+        assert_eq!(
+            insert_statement("ids", &strings(&["id"]), &strings(&["id"])),
+            "INSERT INTO \"ids\" (\"id\") SELECT \"id\" FROM json_populate_recordset(null::\"ids\", $1) \
+            ON CONFLICT (\"id\") DO NOTHING"
+        );
+    }
+
+    #[test]
+    fn test_parse_transaction_id() {
+        let prefix = "arroyo-job-1-sink-";
+        assert_eq!(
+            parse_transaction_id(prefix, "arroyo-job-1-sink-10-3"),
+            Some((10, 3))
+        );
+        assert_eq!(parse_transaction_id(prefix, "arroyo-job-1-sink-x-3"), None);
+        assert_eq!(parse_transaction_id(prefix, "arroyo-job-2-sink-0-3"), None);
+    }
+
+    #[test]
+    fn test_delete_statement() {
+        assert_eq!(
+            delete_statement("counts", &strings(&["id", "region"])),
+            "DELETE FROM \"counts\" AS target USING json_populate_recordset(null::\"counts\", $1) AS deleted \
+            WHERE target.\"id\" = deleted.\"id\" AND target.\"region\" = deleted.\"region\""
+        );
+    }
+
+    async fn sink_context(
+        sink: &PostgresUpsertSinkFunc<Key, Row>,
+    ) -> (Context<(), ()>, Receiver<ControlResp>) {
+        let (_, control_rx) = channel(128);
+        let (control_tx, control_resp_rx) = channel(128);
+        let (data_tx, _) = channel(128);
+
+        let ctx = Context::new(
+            get_test_task_info(),
+            None,
+            control_rx,
+            control_tx,
+            1,
+            vec![vec![OutQueue::new(data_tx, false)]],
+            sink.tables(),
+        )
+        .await;
+
+        (ctx, control_resp_rx)
+    }
+
+    fn record(key: i32, value: UpdatingData<Row>) -> Record<Key, UpdatingData<Row>> {
+        Record {
+            timestamp: SystemTime::now(),
+            key: Some(Key { id: key }),
+            value,
+        }
+    }
+
+    async fn rows(client: &Client) -> Vec<(i32, i64)> {
+        client
+            .query(
+                "SELECT id, count FROM arroyo_upsert_sink_test ORDER BY id",
+                &[],
+            )
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_exactly_once_upserts() {
+        let database = DatabaseConfig::load();
+        let (client, connection) = tokio_postgres::Config::new()
+            .host(&database.host)
+            .port(database.port)
+            .dbname(&database.name)
+            .user(&database.user)
+            .password(&database.password)
+            .connect(NoTls)
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        client
+            .batch_execute(
+                "DROP TABLE IF EXISTS arroyo_upsert_sink_test;
+                CREATE TABLE arroyo_upsert_sink_test (id INT PRIMARY KEY, count BIGINT)",
+            )
+            .await
+            .unwrap();
+
+        let config = OperatorConfig {
+            connection: serde_json::json!({
+                "host": database.host,
+                "port": database.port,
+                "database": database.name,
+                "username": database.user,
+                "password": database.password,
+            }),
+            table: serde_json::json!({
+                "table_name": "arroyo_upsert_sink_test",
+                "commit_mode": "exactly_once",
+            }),
+            format: None,
+            bad_data: None,
+            dead_letter: None,
+            rate_limit: None,
+            metadata_fields: vec![],
+        };
+        let mut sink: PostgresUpsertSinkFunc<Key, Row> =
+            PostgresUpsertSinkFunc::from_config(&serde_json::to_string(&config).unwrap());
+        let (mut ctx, mut control_resp_rx) = sink_context(&sink).await;
+        sink.on_start(&mut ctx).await;
+
+        for (key, value) in [
+            (1, UpdatingData::Append(Row { id: 1, count: 1 })),
+            (2, UpdatingData::Append(Row { id: 2, count: 1 })),
+            (3, UpdatingData::Append(Row { id: 3, count: 1 })),
+            (3, UpdatingData::Retract(Row { id: 3, count: 1 })),
+            (
+                2,
+                UpdatingData::Update {
+                    old: Row { id: 2, count: 1 },
+                    new: Row { id: 2, count: 2 },
+                },
+            ),
+        ] {
+            sink.process_element(&record(key, value), &mut ctx).await;
+        }
+
+        let barrier = CheckpointBarrier {
+            epoch: 1,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+        };
+        sink.handle_checkpoint(&barrier, &mut ctx).await;
+
+        // the writes are only visible once the checkpoint has completed
+        assert_eq!(rows(&client).await, vec![]);
+
+        sink.handle_commit(1, &mut ctx).await;
+        assert_eq!(rows(&client).await, vec![(1, 1), (2, 2)]);
+        assert!(matches!(
+            control_resp_rx.recv().await,
+            Some(ControlResp::CheckpointEvent(_))
+        ));
+
+        client
+            .batch_execute("DROP TABLE arroyo_upsert_sink_test")
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,782 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::datatypes::DataType;
+use arroyo_macro::source_fn;
+use arroyo_rpc::grpc::{StopMode, TableDescriptor, TableWriteBehavior};
+use arroyo_rpc::postgres::{quote_ident, quote_qualified, split_qualified};
+use arroyo_rpc::{CheckpointEvent, ControlMessage, ControlResp, OperatorConfig};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
+use arroyo_types::{Message, Record, UserError, Watermark};
+use bincode::{Decode, Encode};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use futures::{pin_mut, TryStreamExt};
+use serde_json::{json, Map, Number, Value};
+use tokio::select;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, NoTls};
+use tracing::{debug, info, warn};
+
+use crate::{
+    connectors::bad_data::BadDataHandler,
+    engine::{Context, StreamNode},
+    SchemaData, SourceFinishType,
+};
+
+use super::pgoutput::{self, parse_lsn, Relation, TupleValue};
+use super::replication::{CreatedSlot, ReplicationConnection, ReplicationMessage};
+use super::{PostgresConfig, PostgresTable};
+
+// type oids of the columns that aren't read as strings
+const BOOL: u32 = 16;
+const INT8: u32 = 20;
+const INT2: u32 = 21;
+const INT4: u32 = 23;
+const OID: u32 = 26;
+const FLOAT4: u32 = 700;
+const FLOAT8: u32 = 701;
+const TIMESTAMP: u32 = 1114;
+const TIMESTAMPTZ: u32 = 1184;
+const NUMERIC: u32 = 1700;
+
+/// Reads the changes to a table from a logical replication slot, as debezium-style records.
+///
+/// Changes are streamed from the slot over a replication connection, and the slot's position is
+/// only confirmed to the server once the checkpoint that stored it has completed, so that a
+/// restored source reads every change after that checkpoint again.
+///
+/// The initial snapshot is read under the snapshot exported when the slot is created, so that it
+/// contains exactly the transactions committed before the first change in the slot.
+#[derive(StreamNode)]
+pub struct PostgresSourceFunc<K, T>
+where
+    K: Send + 'static,
+    T: SchemaData,
+{
+    connection: PostgresConfig,
+    table_name: String,
+    slot_name: String,
+    publication: String,
+    snapshot: bool,
+    bad_data: BadDataHandler,
+    /// The types of the fields that columns are deserialized into
+    column_types: HashMap<String, DataType>,
+    client: Option<Client>,
+    replication: Option<ReplicationConnection>,
+    /// The relations described by the server since replication started
+    relations: HashMap<u32, Relation>,
+    /// Whether the changes of the current transaction were already read before a restore
+    skip: bool,
+    state: PostgresSourceState,
+    /// The position stored by the last checkpoint, which is confirmed once it completes
+    pending_ack: Option<(u32, u64)>,
+    /// The position of the last completed checkpoint, which is confirmed to the server
+    acked: u64,
+    _t: PhantomData<(K, T)>,
+}
+
+#[derive(Copy, Clone, Debug, Default, Encode, Decode, PartialEq, PartialOrd)]
+pub struct PostgresSourceState {
+    /// The commit LSN of the last transaction that was read; earlier transactions are skipped
+    lsn: u64,
+    snapshot_done: bool,
+}
+
+#[source_fn(out_k = (), out_t = T, commits = true)]
+impl<K, T> PostgresSourceFunc<K, T>
+where
+    K: Send + 'static,
+    T: SchemaData,
+{
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for PostgresSource");
+        let bad_data = BadDataHandler::from_config(&config);
+        let connection: PostgresConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for PostgresSource");
+        let table: PostgresTable =
+            serde_json::from_value(config.table).expect("Invalid table config for PostgresSource");
+
+        let default_name = format!(
+            "arroyo_{}",
+            table
+                .table_name
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+                .to_lowercase()
+        );
+
+        Self {
+            connection,
+            slot_name: table.slot_name.unwrap_or_else(|| default_name.clone()),
+            publication: table.publication.unwrap_or(default_name),
+            table_name: table.table_name,
+            snapshot: table.snapshot.unwrap_or(false),
+            bad_data,
+            column_types: column_types(&T::schema()),
+            client: None,
+            replication: None,
+            relations: HashMap::new(),
+            skip: false,
+            state: PostgresSourceState::default(),
+            pending_ack: None,
+            acked: 0,
+            _t: PhantomData,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("postgres-source-{}", self.table_name)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        // commit writes, so that the position of a checkpoint is confirmed to the server only
+        // once the checkpoint has completed
+        vec![TableDescriptor {
+            write_behavior: TableWriteBehavior::CommitWrites as i32,
+            ..arroyo_state::global_table("s", "postgres source state")
+        }]
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), T>) {
+        let s: GlobalKeyedState<(), PostgresSourceState, _> =
+            ctx.state.get_global_keyed_state('s').await;
+
+        if let Some(state) = s.get(&()) {
+            self.state = *state;
+            // the checkpoint we restored from has completed
+            self.acked = state.lsn;
+        }
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let mut config = tokio_postgres::Config::new();
+        config
+            .host(&self.connection.host)
+            .port(self.connection.port.unwrap_or(5432) as u16)
+            .dbname(&self.connection.database)
+            .user(&self.connection.username);
+        if let Some(password) = &self.connection.password {
+            config.password(password);
+        }
+
+        let (client, connection) = config.connect(NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Postgres connection closed: {}", e);
+            }
+        });
+
+        Ok(client)
+    }
+
+    /// Connects, checks the table, and creates the publication and replication slot if they
+    /// don't exist yet. If the snapshot still has to be read, the slot is (re)created with an
+    /// exported snapshot, which is returned along with the replication connection that keeps it
+    /// alive.
+    async fn init(&mut self) -> Result<(ReplicationConnection, Option<CreatedSlot>)> {
+        let client = self.connect().await?;
+
+        let replica_identity: i8 = client
+            .query_one(
+                "SELECT relreplident FROM pg_class WHERE oid = $1::text::regclass",
+                &[&quote_qualified(&self.table_name)],
+            )
+            .await?
+            .get(0);
+        if replica_identity != b'f' as i8 {
+            bail!(
+                "table '{}' must have REPLICA IDENTITY FULL so that updates and deletes include the previous row",
+                self.table_name
+            );
+        }
+
+        if client
+            .query_opt(
+                "SELECT 1 FROM pg_publication WHERE pubname = $1",
+                &[&self.publication],
+            )
+            .await?
+            .is_none()
+        {
+            info!(
+                "Creating publication {} for {}",
+                self.publication, self.table_name
+            );
+            client
+                .batch_execute(&format!(
+                    "CREATE PUBLICATION {} FOR TABLE {}",
+                    quote_ident(&self.publication),
+                    quote_qualified(&self.table_name)
+                ))
+                .await?;
+        }
+
+        let slot_exists = client
+            .query_opt(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+                &[&self.slot_name],
+            )
+            .await?
+            .is_some();
+        let needs_snapshot = self.snapshot && !self.state.snapshot_done;
+
+        if slot_exists && needs_snapshot {
+            // the snapshot can only be exported when the slot is created, so a slot left behind
+            // by a run that didn't finish reading the snapshot is recreated
+            info!(
+                "Recreating replication slot {} to read a snapshot",
+                self.slot_name
+            );
+            client
+                .execute("SELECT pg_drop_replication_slot($1)", &[&self.slot_name])
+                .await?;
+        }
+
+        self.client = Some(client);
+
+        let mut replication = ReplicationConnection::connect(&self.connection).await?;
+        if !slot_exists || needs_snapshot {
+            info!("Creating replication slot {}", self.slot_name);
+            let slot = replication.create_slot(&self.slot_name).await?;
+            if needs_snapshot {
+                return Ok((replication, Some(slot)));
+            }
+        }
+
+        Ok((replication, None))
+    }
+
+    /// Reads the existing rows of the table as inserts, under the snapshot exported when the slot
+    /// was created. Control messages are handled between rows, so that checkpoints and stops
+    /// aren't held up by large tables; returns how the source should finish if it was stopped.
+    ///
+    /// The exported snapshot can't be read again after a restore, so a pipeline restored from a
+    /// checkpoint taken before the snapshot was done reads the snapshot again from the start.
+    async fn read_snapshot(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        slot: &CreatedSlot,
+    ) -> Result<Option<SourceFinishType>> {
+        info!(
+            "Reading snapshot of {} at {}",
+            self.table_name, slot.consistent_point
+        );
+        // the client is taken while rows are streamed from it, so that control messages can be
+        // handled in between
+        let client = self.client.take().unwrap();
+
+        let columns: Vec<(String, u32)> = client
+            .query(
+                "SELECT attname::text, atttypid FROM pg_attribute
+                WHERE attrelid = $1::text::regclass AND attnum > 0 AND NOT attisdropped
+                ORDER BY attnum",
+                &[&quote_qualified(&self.table_name)],
+            )
+            .await?
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+
+        client
+            .batch_execute(&format!(
+                "BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY;
+                SET TRANSACTION SNAPSHOT '{}'",
+                slot.snapshot_name.replace('\'', "''")
+            ))
+            .await?;
+
+        let select = format!(
+            "SELECT {} FROM {}",
+            columns
+                .iter()
+                .map(|(name, _)| format!("{}::text", quote_ident(name)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            quote_qualified(&self.table_name)
+        );
+        let rows = client
+            .query_raw(&select, std::iter::empty::<&dyn ToSql>())
+            .await?;
+        pin_mut!(rows);
+
+        loop {
+            select! {
+                row = rows.try_next() => {
+                    let Some(row) = row? else {
+                        break;
+                    };
+
+                    let after: Map<String, Value> = columns
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (name, type_oid))| {
+                            let value = row
+                                .get::<_, Option<String>>(i)
+                                .map(|text| {
+                                    text_to_json(*type_oid, &text, self.column_types.get(name))
+                                })
+                                .unwrap_or(Value::Null);
+                            (name.clone(), value)
+                        })
+                        .collect();
+
+                    self.emit(ctx, json!({"before": null, "after": after, "op": "c"}))
+                        .await;
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.our_handle_control_message(ctx, control_message).await {
+                        return Ok(Some(r));
+                    }
+                }
+            }
+        }
+
+        client.batch_execute("COMMIT").await?;
+        self.client = Some(client);
+
+        // the slot only has transactions that committed after the snapshot, but skip up to its
+        // position anyway
+        self.state.lsn = parse_lsn(&slot.consistent_point).map_err(|e| anyhow!(e))?;
+        self.state.snapshot_done = true;
+        Ok(None)
+    }
+
+    /// Handles a message streamed from the slot. Transactions that were already read before a
+    /// restore are skipped, as the server streams from the last confirmed position.
+    async fn handle_replication_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        message: ReplicationMessage,
+    ) -> Result<()> {
+        let data = match message {
+            ReplicationMessage::XLogData(data) => data,
+            ReplicationMessage::Keepalive { reply } => {
+                if reply {
+                    self.replication
+                        .as_mut()
+                        .unwrap()
+                        .send_status(self.acked)
+                        .await?;
+                }
+                return Ok(());
+            }
+        };
+
+        let message = pgoutput::parse(&data)
+            .map_err(|e| anyhow!("failed to decode replication message: {}", e))?;
+
+        let (relation_id, change) = match message {
+            pgoutput::Message::Begin { final_lsn } => {
+                self.skip = final_lsn <= self.state.lsn;
+                return Ok(());
+            }
+            pgoutput::Message::Commit { commit_lsn } => {
+                if !self.skip {
+                    self.state.lsn = commit_lsn;
+                }
+                return Ok(());
+            }
+            pgoutput::Message::Relation(relation) => {
+                self.relations.insert(relation.id, relation);
+                return Ok(());
+            }
+            _ if self.skip => return Ok(()),
+            pgoutput::Message::Insert { relation_id, new } => (relation_id, (None, Some(new), "c")),
+            pgoutput::Message::Update {
+                relation_id,
+                old,
+                new,
+            } => (relation_id, (old, Some(new), "u")),
+            pgoutput::Message::Delete { relation_id, old } => (relation_id, (Some(old), None, "d")),
+            pgoutput::Message::Other(_) => return Ok(()),
+        };
+
+        let relation = self
+            .relations
+            .get(&relation_id)
+            .ok_or_else(|| anyhow!("received change for unknown relation {}", relation_id))?;
+        if !self.is_table(relation) {
+            return Ok(());
+        }
+
+        let (old, new, op) = change;
+        let before = old.map(|old| tuple_to_json(relation, old, None, &self.column_types));
+        let after =
+            new.map(|new| tuple_to_json(relation, new, before.as_ref(), &self.column_types));
+
+        self.emit(ctx, json!({"before": before, "after": after, "op": op}))
+            .await;
+        Ok(())
+    }
+
+    fn is_table(&self, relation: &Relation) -> bool {
+        match &split_qualified(&self.table_name)[..] {
+            [name] => relation.namespace == "public" && &relation.name == name,
+            [namespace, name] => &relation.namespace == namespace && &relation.name == name,
+            _ => false,
+        }
+    }
+
+    async fn emit(&self, ctx: &mut Context<(), T>, change: Value) {
+        let msg = serde_json::to_vec(&change).unwrap();
+        let value = serde_json::from_value(change).map_err(|e| {
+            UserError::new(
+                "Deserialization failed",
+                format!("Failed to deserialize change into schema: {:?}", e),
+            )
+        });
+
+        if let Some(value) = self.bad_data.handle(ctx, &msg, value).await {
+            ctx.collect(Record {
+                timestamp: SystemTime::now(),
+                key: None,
+                value,
+            })
+            .await;
+        }
+    }
+
+    /// Confirms the position of a completed checkpoint to the server, which lets it discard the
+    /// WAL before it
+    async fn handle_commit(&mut self, epoch: u32, ctx: &mut Context<(), T>) {
+        match self.pending_ack.take() {
+            Some((checkpoint_epoch, lsn)) if checkpoint_epoch == epoch => {
+                self.acked = lsn;
+                if let Some(replication) = &mut self.replication {
+                    if let Err(e) = replication.send_status(lsn).await {
+                        warn!("Failed to confirm replication slot position {:?}", e);
+                    }
+                }
+            }
+            Some((checkpoint_epoch, _)) => {
+                warn!(
+                    "received commit for epoch {} but the pending position is for epoch {}, not confirming",
+                    epoch, checkpoint_epoch
+                );
+            }
+            // only the first subtask reads from the slot
+            None => {}
+        }
+
+        ctx.control_tx
+            .send(ControlResp::CheckpointEvent(CheckpointEvent {
+                checkpoint_epoch: epoch,
+                operator_id: ctx.task_info.operator_id.clone(),
+                subtask_index: ctx.task_info.task_index as u32,
+                time: SystemTime::now(),
+                event_type: arroyo_rpc::grpc::TaskCheckpointEventType::FinishedCommit.into(),
+            }))
+            .await
+            .expect("sent commit event");
+    }
+
+    async fn our_handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if ctx.task_info.task_index == 0 {
+                    let mut s: GlobalKeyedState<(), PostgresSourceState, _> =
+                        ctx.state.get_global_keyed_state('s').await;
+                    s.insert((), self.state).await;
+                    self.pending_ack = Some((c.epoch, self.state.lsn));
+                }
+
+                if self.checkpoint(c, ctx).await {
+                    // wait for the final checkpoint to complete so that its position is confirmed
+                    if let Some(ControlMessage::Commit { epoch }) = ctx.control_rx.recv().await {
+                        self.handle_commit(epoch, ctx).await;
+                    } else {
+                        warn!("no commit message received, not confirming slot position");
+                    }
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping postgres source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { epoch } => {
+                self.handle_commit(epoch, ctx).await;
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+        }
+        None
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        // a replication slot can only be read by one consumer, so only read on the first task
+        if ctx.task_info.task_index != 0 {
+            ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+            loop {
+                let msg = ctx.control_rx.recv().await;
+                if let Some(r) = self.our_handle_control_message(ctx, msg).await {
+                    return r;
+                }
+            }
+        }
+
+        let (mut replication, exported) = match self.init().await {
+            Ok(init) => init,
+            Err(e) => {
+                ctx.report_error(
+                    "Failed to start replication from Postgres".to_string(),
+                    e.to_string(),
+                )
+                .await;
+                panic!("Failed to start replication from Postgres: {:?}", e);
+            }
+        };
+
+        if let Some(slot) = exported {
+            match self.read_snapshot(ctx, &slot).await {
+                Ok(Some(finish)) => return finish,
+                Ok(None) => {}
+                Err(e) => {
+                    ctx.report_error(
+                        "Failed to read snapshot from Postgres".to_string(),
+                        e.to_string(),
+                    )
+                    .await;
+                    panic!("Failed to read snapshot of {}: {:?}", self.table_name, e);
+                }
+            }
+        }
+
+        // starting replication releases the exported snapshot, which is no longer needed
+        if let Err(e) = replication
+            .start_replication(&self.slot_name, &self.publication, self.acked)
+            .await
+        {
+            ctx.report_error(
+                "Failed to start replication from Postgres".to_string(),
+                e.to_string(),
+            )
+            .await;
+            panic!(
+                "Failed to start replication from slot {}: {:?}",
+                self.slot_name, e
+            );
+        }
+        self.replication = Some(replication);
+
+        loop {
+            select! {
+                message = self.replication.as_mut().unwrap().recv_replication() => {
+                    let result = match message {
+                        Ok(message) => self.handle_replication_message(ctx, message).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = result {
+                        ctx.report_error(
+                            "Failed to read changes from Postgres".to_string(),
+                            e.to_string(),
+                        )
+                        .await;
+                        panic!("Failed to read changes from slot {}: {:?}", self.slot_name, e);
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.our_handle_control_message(ctx, control_message).await {
+                        return r;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the types of the fields of the rows in debezium-style records
+fn column_types(schema: &arrow::datatypes::Schema) -> HashMap<String, DataType> {
+    match schema.field_with_name("after").map(|f| f.data_type()) {
+        Ok(DataType::Struct(fields)) => fields
+            .iter()
+            .map(|f| (f.name().clone(), f.data_type().clone()))
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn tuple_to_json(
+    relation: &Relation,
+    values: Vec<TupleValue>,
+    old: Option<&Value>,
+    column_types: &HashMap<String, DataType>,
+) -> Value {
+    let fields: Map<String, Value> = relation
+        .columns
+        .iter()
+        .zip(values)
+        .map(|(column, value)| {
+            let value = match value {
+                TupleValue::Null => Value::Null,
+                // unchanged values are only left out of the new row of an update
+                TupleValue::UnchangedToast => old
+                    .and_then(|old| old.get(&column.name))
+                    .cloned()
+                    .unwrap_or(Value::Null),
+                TupleValue::Text(text) => {
+                    text_to_json(column.type_oid, &text, column_types.get(&column.name))
+                }
+            };
+            (column.name.clone(), value)
+        })
+        .collect();
+
+    Value::Object(fields)
+}
+
+/// Converts a value in Postgres' text representation into JSON, which is deserialized into a
+/// field of the given type. NUMERIC values are only converted to numbers for numeric fields, as
+/// JSON numbers can't hold all of their precision; other fields get the exact text.
+fn text_to_json(type_oid: u32, text: &str, field_type: Option<&DataType>) -> Value {
+    let value = match type_oid {
+        BOOL => Some(Value::Bool(text == "t")),
+        INT2 | INT4 | INT8 | OID => text.parse::<i64>().ok().map(Value::from),
+        FLOAT4 | FLOAT8 => text
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        NUMERIC => match field_type {
+            Some(
+                DataType::Int8
+                | DataType::Int16
+                | DataType::Int32
+                | DataType::Int64
+                | DataType::UInt8
+                | DataType::UInt16
+                | DataType::UInt32
+                | DataType::UInt64,
+            ) => numeric_to_integer(text),
+            Some(DataType::Float16 | DataType::Float32 | DataType::Float64) => text
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            _ => None,
+        },
+        TIMESTAMP => NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(|t| Value::String(Utc.from_utc_datetime(&t).to_rfc3339())),
+        TIMESTAMPTZ => DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .ok()
+            .map(|t| Value::String(t.with_timezone(&Utc).to_rfc3339())),
+        _ => None,
+    };
+
+    value.unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+/// Converts a NUMERIC value without a fractional part to an integer. Values of columns with a
+/// scale are written with it, like `12.00`, so zero fractional digits are ignored.
+fn numeric_to_integer(text: &str) -> Option<Value> {
+    let integer = match text.split_once('.') {
+        Some((integer, fraction)) if fraction.bytes().all(|b| b == b'0') => integer,
+        Some(_) => return None,
+        None => text,
+    };
+
+    match integer.parse::<i64>() {
+        Ok(i) => Some(Value::from(i)),
+        Err(_) => integer.parse::<u64>().ok().map(Value::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_to_json() {
+        assert_eq!(text_to_json(BOOL, "t", None), json!(true));
+        assert_eq!(text_to_json(INT8, "-42", None), json!(-42));
+        assert_eq!(
+            text_to_json(NUMERIC, "10.50", Some(&DataType::Float64)),
+            json!(10.5)
+        );
+        assert_eq!(
+            text_to_json(NUMERIC, "12", Some(&DataType::Int64)),
+            json!(12)
+        );
+        assert_eq!(
+            text_to_json(NUMERIC, "12.00", Some(&DataType::Int64)),
+            json!(12)
+        );
+        // fractional values can't be read into integer fields
+        assert_eq!(
+            text_to_json(NUMERIC, "12.50", Some(&DataType::Int64)),
+            json!("12.50")
+        );
+        assert_eq!(
+            text_to_json(NUMERIC, "NaN", Some(&DataType::Float64)),
+            json!("NaN")
+        );
+        // text fields keep all of the digits
+        assert_eq!(
+            text_to_json(
+                NUMERIC,
+                "12345678901234567890.123456789",
+                Some(&DataType::Utf8)
+            ),
+            json!("12345678901234567890.123456789")
+        );
+        assert_eq!(
+            text_to_json(TIMESTAMP, "2023-09-01 12:30:00.5", None),
+            json!("2023-09-01T12:30:00.500+00:00")
+        );
+        assert_eq!(
+            text_to_json(TIMESTAMPTZ, "2023-09-01 14:30:00+02", None),
+            json!("2023-09-01T12:30:00+00:00")
+        );
+        assert_eq!(text_to_json(25, "hello", None), json!("hello"));
+    }
+
+    #[test]
+    fn test_unchanged_toast() {
+        let relation = Relation {
+            id: 1,
+            namespace: "public".to_string(),
+            name: "docs".to_string(),
+            columns: vec![
+                pgoutput::Column {
+                    name: "id".to_string(),
+                    type_oid: INT4,
+                },
+                pgoutput::Column {
+                    name: "body".to_string(),
+                    type_oid: 25,
+                },
+            ],
+        };
+
+        let old = json!({"id": 1, "body": "a long document"});
+        assert_eq!(
+            tuple_to_json(
+                &relation,
+                vec![
+                    TupleValue::Text("2".to_string()),
+                    TupleValue::UnchangedToast
+                ],
+                Some(&old),
+                &HashMap::new()
+            ),
+            json!({"id": 2, "body": "a long document"})
+        );
+    }
+}
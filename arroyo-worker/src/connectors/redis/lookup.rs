@@ -0,0 +1,361 @@
+use crate::connectors::bad_data::BadDataHandler;
+use crate::connectors::template::{parse_template, render_template, TemplatePart};
+use crate::engine::{Context, StreamNode};
+use crate::formats::metadata::MetadataValue;
+use crate::formats::DataDeserializer;
+use crate::SchemaData;
+use anyhow::Result;
+use arrow::datatypes::{DataType, Schema};
+use arroyo_macro::process_fn;
+use arroyo_rpc::types::{Format, JsonFormat};
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::*;
+use redis::aio::ConnectionManager;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use super::{connect, RedisConfig, RedisTable, RedisTableTarget};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+const MAX_CACHE_ENTRIES: usize = 100_000;
+
+/// Looks up the row stored under the key rendered from the join key of each record, emitting the
+/// record along with the row, or None if the key doesn't exist. Records are buffered and their
+/// keys read in a single round trip per batch, which is flushed when full, periodically, and
+/// before watermarks and checkpoints are forwarded.
+#[derive(StreamNode)]
+pub struct RedisLookupFunc<K: Key + Serialize, T: Data, R: SchemaData> {
+    connection: RedisConfig,
+    target: RedisTableTarget,
+    key_template: String,
+    key_parts: Vec<TemplatePart>,
+    deserializer: DataDeserializer<R>,
+    bad_data: BadDataHandler,
+    client: Option<ConnectionManager>,
+    batch_size: usize,
+    buffer: Vec<Record<K, T>>,
+    cache: Option<LookupCache>,
+    _t: PhantomData<(K, T)>,
+}
+
+/// Caches the values read for keys, including keys that don't exist, for a fixed time
+struct LookupCache {
+    ttl: Duration,
+    entries: HashMap<String, (Instant, Option<Vec<u8>>)>,
+}
+
+impl LookupCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&self, key: &str, now: Instant) -> Option<&Option<Vec<u8>>> {
+        self.entries
+            .get(key)
+            .filter(|(inserted, _)| now.duration_since(*inserted) < self.ttl)
+            .map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, key: String, value: Option<Vec<u8>>, now: Instant) {
+        if self.entries.len() >= MAX_CACHE_ENTRIES {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (inserted, _)| now.duration_since(*inserted) < ttl);
+            if self.entries.len() >= MAX_CACHE_ENTRIES {
+                self.entries.clear();
+            }
+        }
+        self.entries.insert(key, (now, value));
+    }
+}
+
+impl<K: Key + Serialize, T: Data, R: SchemaData> RedisLookupFunc<K, T, R> {
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for RedisLookup");
+        let connection: RedisConfig = serde_json::from_value(config.connection.clone())
+            .expect("Invalid connection config for RedisLookup");
+        let table: RedisTable = serde_json::from_value(config.table.clone())
+            .expect("Invalid table config for RedisLookup");
+
+        Self {
+            connection,
+            target: table.target,
+            key_parts: parse_template(&table.key_template),
+            key_template: table.key_template,
+            // hashes are converted to JSON objects before being deserialized
+            deserializer: DataDeserializer::new(
+                config
+                    .format
+                    .clone()
+                    .unwrap_or_else(|| Format::Json(JsonFormat::default())),
+            ),
+            bad_data: BadDataHandler::from_config(&config),
+            client: None,
+            batch_size: table
+                .batch_size
+                .map(|size| size as usize)
+                .unwrap_or(DEFAULT_BATCH_SIZE),
+            buffer: vec![],
+            cache: table
+                .cache_ttl_seconds
+                .map(|ttl| LookupCache::new(Duration::from_secs(ttl as u64))),
+            _t: PhantomData,
+        }
+    }
+
+    /// Reads the values stored under the keys in a single round trip, as bytes in the table's
+    /// format
+    async fn get_all(&mut self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let client = self.client.as_mut().unwrap();
+        Ok(match self.target {
+            RedisTableTarget::Hash => {
+                let mut pipeline = redis::pipe();
+                for key in keys {
+                    pipeline.hgetall(key);
+                }
+                let hashes: Vec<HashMap<String, String>> = pipeline.query_async(client).await?;
+                hashes
+                    .into_iter()
+                    .map(|fields| (!fields.is_empty()).then(|| hash_to_json(fields, &R::schema())))
+                    .collect()
+            }
+            // GET rather than MGET would be sent for a single key if this used AsyncCommands::get
+            _ => redis::cmd("MGET").arg(keys).query_async(client).await?,
+        })
+    }
+
+    /// Looks up the keys of the buffered records, emitting each record with its row
+    async fn flush(&mut self, ctx: &mut Context<K, (T, Option<R>)>) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let records = std::mem::take(&mut self.buffer);
+
+        let keys: Vec<(Vec<(String, MetadataValue)>, String)> = records
+            .iter()
+            .map(|record| {
+                let key_value = serde_json::to_value(record.key.as_ref().unwrap()).unwrap();
+                let key = render_template(&self.key_parts, &key_value);
+                let key_fields = match key_value {
+                    Value::Object(fields) => fields
+                        .into_iter()
+                        .map(|(name, value)| (name, MetadataValue::Json(value)))
+                        .collect(),
+                    other => panic!("lookup keys must have fields, not {}", other),
+                };
+                (key_fields, key)
+            })
+            .collect();
+
+        let now = Instant::now();
+        let mut values: HashMap<String, Option<Vec<u8>>> = HashMap::new();
+        let mut missing = vec![];
+        let mut seen = HashSet::new();
+        for (_, key) in &keys {
+            if !seen.insert(key.as_str()) {
+                continue;
+            }
+            match self.cache.as_ref().and_then(|cache| cache.get(key, now)) {
+                Some(value) => {
+                    values.insert(key.clone(), value.clone());
+                }
+                None => missing.push(key.clone()),
+            }
+        }
+
+        match self.get_all(&missing).await {
+            Ok(fetched) => {
+                for (key, value) in missing.into_iter().zip(fetched) {
+                    if let Some(cache) = &mut self.cache {
+                        cache.insert(key.clone(), value.clone(), now);
+                    }
+                    values.insert(key, value);
+                }
+            }
+            Err(e) => {
+                ctx.report_error(
+                    "Failed to read from Redis".to_string(),
+                    format!("{}: {}", missing.join(", "), e),
+                )
+                .await;
+                panic!("Failed to read Redis keys {:?}: {:?}", missing, e);
+            }
+        }
+
+        for (record, (key_fields, key)) in records.into_iter().zip(keys) {
+            let row = match values.get(&key).cloned().flatten() {
+                Some(bytes) => {
+                    // the key columns may not be stored in the value, so they're taken from the
+                    // join
+                    let result = self
+                        .deserializer
+                        .deserialize_slice_with_metadata(&bytes, &key_fields)
+                        .await
+                        .and_then(|rows| {
+                            rows.into_iter().next().ok_or_else(|| {
+                                UserError::new(
+                                    "Deserialization failed",
+                                    format!("Redis key {} holds no rows", key),
+                                )
+                            })
+                        });
+                    self.bad_data.handle(ctx, &bytes, result).await
+                }
+                None => None,
+            };
+
+            ctx.collect(Record {
+                timestamp: record.timestamp,
+                key: record.key,
+                value: (record.value, row),
+            })
+            .await;
+        }
+    }
+}
+
+#[process_fn(in_k = K, in_t = T, out_k = K, out_t = (T, Option<R>), tick_ms = 100)]
+impl<K: Key + Serialize, T: Data, R: SchemaData> RedisLookupFunc<K, T, R> {
+    fn name(&self) -> String {
+        format!("redis-lookup-{}", self.key_template)
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<K, (T, Option<R>)>) {
+        match connect(&self.connection).await {
+            Ok(client) => {
+                self.client = Some(client);
+            }
+            Err(e) => {
+                ctx.report_error("Failed to connect to Redis".to_string(), e.to_string())
+                    .await;
+                panic!("Failed to connect to Redis: {:?}", e);
+            }
+        }
+    }
+
+    async fn process_element(
+        &mut self,
+        record: &Record<K, T>,
+        ctx: &mut Context<K, (T, Option<R>)>,
+    ) {
+        self.buffer.push(record.clone());
+        if self.buffer.len() >= self.batch_size {
+            self.flush(ctx).await;
+        }
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut Context<K, (T, Option<R>)>) {
+        self.flush(ctx).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut Context<K, (T, Option<R>)>,
+    ) {
+        // buffered records must be emitted before the watermark that follows them
+        self.flush(ctx).await;
+        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
+            .await;
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: &CheckpointBarrier,
+        ctx: &mut Context<K, (T, Option<R>)>,
+    ) {
+        self.flush(ctx).await;
+    }
+
+    async fn on_close(&mut self, ctx: &mut Context<K, (T, Option<R>)>) {
+        self.flush(ctx).await;
+    }
+}
+
+/// Converts the fields of a hash to a JSON object. Hashes store every value as a string, so
+/// values of columns that aren't strings are parsed as JSON, as they were written by the sink.
+fn hash_to_json(fields: HashMap<String, String>, schema: &Schema) -> Vec<u8> {
+    let row: Map<String, Value> = fields
+        .into_iter()
+        .map(|(name, value)| {
+            let is_string = schema
+                .field_with_name(&name)
+                .map(|f| matches!(f.data_type(), DataType::Utf8 | DataType::LargeUtf8))
+                .unwrap_or(true);
+
+            let value = if is_string {
+                Value::String(value)
+            } else {
+                serde_json::from_str(&value).unwrap_or(Value::String(value))
+            };
+            (name, value)
+        })
+        .collect();
+
+    serde_json::to_vec(&Value::Object(row)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_to_json, LookupCache};
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_lookup_cache_expires() {
+        let mut cache = LookupCache::new(Duration::from_secs(10));
+        let now = Instant::now();
+        cache.insert("present".to_string(), Some(b"{}".to_vec()), now);
+        cache.insert("missing".to_string(), None, now);
+
+        assert_eq!(cache.get("present", now), Some(&Some(b"{}".to_vec())));
+        assert_eq!(cache.get("missing", now), Some(&None));
+        assert_eq!(cache.get("other", now), None);
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(cache.get("present", later), None);
+        assert_eq!(cache.get("missing", later), None);
+    }
+
+    #[test]
+    fn test_hash_to_json() {
+        let schema = Schema::new(vec![
+            Field::new("user_id", DataType::Utf8, false),
+            Field::new("score", DataType::Float64, false),
+            Field::new(
+                "updated",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]);
+
+        let fields = HashMap::from([
+            ("user_id".to_string(), "123".to_string()),
+            ("score".to_string(), "0.5".to_string()),
+            ("updated".to_string(), "2023-08-01T00:00:00Z".to_string()),
+        ]);
+
+        let row: Value = serde_json::from_slice(&hash_to_json(fields, &schema)).unwrap();
+        assert_eq!(
+            row,
+            json!({
+                "user_id": "123",
+                "score": 0.5,
+                "updated": "2023-08-01T00:00:00Z",
+            })
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::{anyhow, Result};
+use redis::aio::ConnectionManager;
+use redis::IntoConnectionInfo;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use typify::import_types;
+
+pub mod lookup;
+pub mod sink;
+
+import_types!(schema = "../connector-schemas/redis/connection.json");
+import_types!(schema = "../connector-schemas/redis/table.json");
+
+/// Opens a connection that transparently reconnects if the server goes away
+async fn connect(config: &RedisConfig) -> Result<ConnectionManager> {
+    let mut info = config
+        .address
+        .as_str()
+        .into_connection_info()
+        .map_err(|e| anyhow!("invalid Redis address '{}': {}", config.address, e))?;
+    if config.username.is_some() {
+        info.redis.username = config.username.clone();
+    }
+    if config.password.is_some() {
+        info.redis.password = config.password.clone();
+    }
+
+    Ok(ConnectionManager::new(redis::Client::open(info)?).await?)
+}
+
+/// The value of a column as it's stored in a hash field or stream entry: strings are written
+/// as-is and other values as JSON
+fn field_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
@@ -0,0 +1,294 @@
+use crate::connectors::template::{parse_template, render_template, TemplatePart};
+use crate::engine::{Context, StreamNode};
+use crate::formats::DataSerializer;
+use crate::SchemaData;
+use anyhow::Result;
+use arroyo_macro::process_fn;
+use arroyo_rpc::types::{Format, JsonFormat};
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::*;
+use redis::aio::ConnectionManager;
+use redis::Pipeline;
+use serde::Serialize;
+use serde_json::Value;
+use std::marker::PhantomData;
+use tracing::warn;
+
+use super::{connect, field_value, RedisConfig, RedisTable, RedisTableTarget};
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+#[derive(StreamNode)]
+pub struct RedisSinkFunc<K: Key + Serialize, T: SchemaData> {
+    connection: RedisConfig,
+    target: RedisTableTarget,
+    key_template: String,
+    key_parts: Vec<TemplatePart>,
+    ttl_seconds: Option<usize>,
+    batch_size: usize,
+    serializer: DataSerializer<T>,
+    client: Option<ConnectionManager>,
+    /// Commands that haven't been sent yet, which are written in a single round trip
+    pipeline: Pipeline,
+    pending: usize,
+    _t: PhantomData<K>,
+}
+
+impl<K: Key + Serialize, T: SchemaData + Serialize> RedisSinkFunc<K, T> {
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for RedisSink");
+        let connection: RedisConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for RedisSink");
+        let table: RedisTable =
+            serde_json::from_value(config.table).expect("Invalid table config for RedisSink");
+
+        Self {
+            connection,
+            target: table.target,
+            key_parts: parse_template(&table.key_template),
+            key_template: table.key_template,
+            ttl_seconds: table.ttl_seconds.map(|ttl| ttl as usize),
+            batch_size: table
+                .batch_size
+                .map(|size| size as usize)
+                .unwrap_or(DEFAULT_BATCH_SIZE),
+            // hash and stream tables write individual columns rather than serialized rows
+            serializer: DataSerializer::new(
+                config
+                    .format
+                    .unwrap_or_else(|| Format::Json(JsonFormat::default())),
+            ),
+            client: None,
+            pipeline: redis::pipe(),
+            pending: 0,
+            _t: PhantomData,
+        }
+    }
+
+    /// Renders the key that a row is stored under
+    fn key(&self, value: &T) -> String {
+        render_template(&self.key_parts, &serde_json::to_value(value).unwrap())
+    }
+
+    async fn write(&mut self, value: &T, ctx: &mut Context<(), ()>) {
+        let row = serde_json::to_value(value).unwrap();
+        let key = render_template(&self.key_parts, &row);
+
+        match self.target {
+            RedisTableTarget::String => {
+                let Some(bytes) = self.serializer.to_vec_or_report(value, ctx).await else {
+                    return;
+                };
+                self.pipeline.set(&key, bytes).ignore();
+            }
+            RedisTableTarget::Hash => {
+                let (fields, nulls) = hash_fields(&row);
+                if !nulls.is_empty() {
+                    self.pipeline.hdel(&key, nulls).ignore();
+                }
+                if !fields.is_empty() {
+                    self.pipeline.hset_multiple(&key, &fields).ignore();
+                }
+            }
+            RedisTableTarget::List => {
+                let Some(bytes) = self.serializer.to_vec_or_report(value, ctx).await else {
+                    return;
+                };
+                self.pipeline.rpush(&key, bytes).ignore();
+            }
+            RedisTableTarget::Stream => {
+                let (fields, _) = hash_fields(&row);
+                if fields.is_empty() {
+                    return;
+                }
+                self.pipeline.xadd(&key, "*", &fields).ignore();
+            }
+        }
+
+        if let Some(ttl) = self.ttl_seconds {
+            self.pipeline.expire(&key, ttl).ignore();
+        }
+
+        self.push().await;
+    }
+
+    /// Removes the key for a retracted row; list and stream entries are append-only, so
+    /// retractions of them are ignored
+    async fn delete(&mut self, value: &T) {
+        if matches!(
+            self.target,
+            RedisTableTarget::List | RedisTableTarget::Stream
+        ) {
+            return;
+        }
+
+        self.pipeline.del(self.key(value)).ignore();
+        self.push().await;
+    }
+
+    async fn push(&mut self) {
+        self.pending += 1;
+        if self.pending >= self.batch_size {
+            self.flush().await;
+        }
+    }
+
+    async fn flush(&mut self) {
+        if let Err(e) = self.try_flush().await {
+            panic!(
+                "Failed to write to Redis keys {}: {:?}",
+                self.key_template, e
+            );
+        }
+    }
+
+    async fn try_flush(&mut self) -> Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+
+        let pipeline = std::mem::replace(&mut self.pipeline, redis::pipe());
+        self.pending = 0;
+        pipeline
+            .query_async::<_, ()>(self.client.as_mut().unwrap())
+            .await?;
+        Ok(())
+    }
+}
+
+#[process_fn(in_k = K, in_t = T)]
+impl<K: Key + Serialize, T: SchemaData + Serialize> RedisSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("redis-sink-{}", self.key_template)
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        match connect(&self.connection).await {
+            Ok(client) => {
+                self.client = Some(client);
+            }
+            Err(e) => {
+                ctx.report_error("Failed to connect to Redis".to_string(), e.to_string())
+                    .await;
+                panic!("Failed to connect to Redis: {:?}", e);
+            }
+        }
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        self.write(&record.value, ctx).await;
+    }
+
+    async fn handle_checkpoint(&mut self, _: &CheckpointBarrier, _ctx: &mut Context<(), ()>) {
+        self.flush().await;
+    }
+
+    async fn on_close(&mut self, _ctx: &mut Context<(), ()>) {
+        if let Err(e) = self.try_flush().await {
+            warn!(
+                "Failed to write final records to Redis keys {}: {:?}",
+                self.key_template, e
+            );
+        }
+    }
+}
+
+/// Writes updating data, overwriting the key of each updated row and removing the key of each
+/// retracted one. If an update changes the columns the key is rendered from, the old key is
+/// removed as well.
+#[derive(StreamNode)]
+pub struct RedisUpsertSinkFunc<K: Key + Serialize, T: SchemaData> {
+    sink: RedisSinkFunc<K, T>,
+}
+
+impl<K: Key + Serialize, T: SchemaData + Serialize> RedisUpsertSinkFunc<K, T> {
+    pub fn from_config(config: &str) -> Self {
+        Self {
+            sink: RedisSinkFunc::from_config(config),
+        }
+    }
+}
+
+#[process_fn(in_k = K, in_t = UpdatingData<T>)]
+impl<K: Key + Serialize, T: SchemaData + Serialize> RedisUpsertSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("redis-upsert-sink-{}", self.sink.key_template)
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        self.sink.on_start(ctx).await;
+    }
+
+    async fn process_element(
+        &mut self,
+        record: &Record<K, UpdatingData<T>>,
+        ctx: &mut Context<(), ()>,
+    ) {
+        match &record.value {
+            UpdatingData::Append(value) => {
+                self.sink.write(value, ctx).await;
+            }
+            UpdatingData::Update { old, new } => {
+                if self.sink.key(old) != self.sink.key(new) {
+                    self.sink.delete(old).await;
+                }
+                self.sink.write(new, ctx).await;
+            }
+            UpdatingData::Retract(value) => {
+                self.sink.delete(value).await;
+            }
+        }
+    }
+
+    async fn handle_checkpoint(&mut self, barrier: &CheckpointBarrier, ctx: &mut Context<(), ()>) {
+        self.sink.handle_checkpoint(barrier, ctx).await;
+    }
+
+    async fn on_close(&mut self, ctx: &mut Context<(), ()>) {
+        self.sink.on_close(ctx).await;
+    }
+}
+
+/// Splits a row into the field/value pairs of its non-null columns, and the names of its null
+/// columns
+fn hash_fields(row: &Value) -> (Vec<(String, String)>, Vec<String>) {
+    let mut fields = vec![];
+    let mut nulls = vec![];
+    for (name, value) in row.as_object().into_iter().flatten() {
+        match field_value(value) {
+            Some(value) => fields.push((name.clone(), value)),
+            None => nulls.push(name.clone()),
+        }
+    }
+    (fields, nulls)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::hash_fields;
+    use serde_json::json;
+
+    #[test]
+    fn test_hash_fields() {
+        let (fields, nulls) = hash_fields(&json!({
+            "user_id": "u1",
+            "score": 0.5,
+            "tags": ["a", "b"],
+            "segment": null,
+        }));
+
+        // the order of hash fields doesn't matter
+        assert_eq!(
+            fields.into_iter().collect::<HashMap<_, _>>(),
+            HashMap::from([
+                ("user_id".to_string(), "u1".to_string()),
+                ("score".to_string(), "0.5".to_string()),
+                ("tags".to_string(), "[\"a\",\"b\"]".to_string()),
+            ])
+        );
+        assert_eq!(nulls, vec!["segment".to_string()]);
+    }
+}
@@ -1,5 +1,7 @@
+use crate::connectors::bad_data::BadDataHandler;
 use crate::engine::Context;
-use crate::{formats, SourceFinishType};
+use crate::formats::DataDeserializer;
+use crate::SourceFinishType;
 use arroyo_macro::{source_fn, StreamNode};
 use arroyo_rpc::grpc::{StopMode, TableDescriptor};
 use arroyo_rpc::types::Format;
@@ -13,7 +15,7 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::marker::PhantomData;
-use std::time::{Duration, Instant, SystemTime};
+use std::time::SystemTime;
 use tokio::select;
 use tracing::{debug, info};
 use typify::import_types;
@@ -34,7 +36,8 @@ where
     url: String,
     headers: Vec<(String, String)>,
     events: Vec<String>,
-    format: Format,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
     state: SSESourceState,
     _t: PhantomData<(K, T)>,
 }
@@ -53,7 +56,8 @@ where
                 .map(|(k, v)| (k.to_string(), v.to_string()))
                 .collect(),
             events: events.into_iter().map(|s| s.to_string()).collect(),
-            format,
+            deserializer: DataDeserializer::new(format),
+            bad_data: BadDataHandler::default(),
             state: SSESourceState::default(),
             _t: PhantomData,
         }
@@ -62,6 +66,7 @@ where
     pub fn from_config(config: &str) -> Self {
         let config: OperatorConfig =
             serde_json::from_str(config).expect("Invalid config for SSESource");
+        let bad_data = BadDataHandler::from_config(&config);
         let table: SseTable =
             serde_json::from_value(config.table).expect("Invalid table config for SSESource");
 
@@ -75,7 +80,10 @@ where
                 .events
                 .map(|e| e.split(',').map(|e| e.to_string()).collect())
                 .unwrap_or_else(std::vec::Vec::new),
-            format: config.format.expect("SSESource requires a format"),
+            deserializer: DataDeserializer::new(
+                config.format.expect("SSESource requires a format"),
+            ),
+            bad_data,
             state: SSESourceState::default(),
             _t: PhantomData,
         }
@@ -150,9 +158,6 @@ where
         let mut stream = client.build().stream();
         let events: HashSet<_> = self.events.iter().cloned().collect();
 
-        let mut last_reported_error = Instant::now();
-        let mut errors = 0;
-
         // since there's no way to partition across an event source, only read on the first task
         if ctx.task_info.task_index == 0 {
             loop {
@@ -167,28 +172,13 @@ where
                                         }
 
                                         if events.is_empty() || events.contains(&event.event_type) {
-                                            match formats::deserialize_slice(&self.format, &event.data.as_bytes()) {
-                                                Ok(value) => {
-                                                    ctx.collector.collect(Record {
-                                                        timestamp: SystemTime::now(),
-                                                        key: None,
-                                                        value,
-                                                    }).await;
-                                                }
-                                                Err(e) => {
-                                                    errors += 1;
-                                                    if last_reported_error.elapsed() > Duration::from_secs(30) {
-                                                        ctx.control_tx.send(
-                                                            ControlResp::Error {
-                                                                operator_id: ctx.task_info.operator_id.clone(),
-                                                                task_index: ctx.task_info.task_index,
-                                                                message: format!("{} x {}", e.name, errors),
-                                                                details: e.details,
-                                                        }).await.unwrap();
-                                                        errors = 0;
-                                                        last_reported_error = Instant::now();
-                                                    }
-                                                }
+                                            let values = self.deserializer.deserialize_slice(event.data.as_bytes()).await;
+                                            for value in self.bad_data.handle(ctx, event.data.as_bytes(), values).await.unwrap_or_default() {
+                                                ctx.collector.collect(Record {
+                                                    timestamp: SystemTime::now(),
+                                                    key: None,
+                                                    value,
+                                                }).await;
                                             }
 
                                         }
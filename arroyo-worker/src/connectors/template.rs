@@ -0,0 +1,68 @@
+use serde_json::Value;
+
+/// A part of a template like `{tenant}:{user_id}`, which renders keys from the columns of rows
+#[derive(Debug, PartialEq)]
+pub enum TemplatePart {
+    Literal(String),
+    Field(String),
+}
+
+/// Parses a template like `{tenant}:{user_id}` into literals and column references
+pub fn parse_template(template: &str) -> Vec<TemplatePart> {
+    let mut parts = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        if start > 0 {
+            parts.push(TemplatePart::Literal(rest[..start].to_string()));
+        }
+        parts.push(TemplatePart::Field(
+            rest[start + 1..start + end].to_string(),
+        ));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Literal(rest.to_string()));
+    }
+    parts
+}
+
+/// Substitutes the values of the columns of a row into a parsed template. Strings are inserted
+/// as-is, other values as JSON, and nulls and missing columns as nothing.
+pub fn render_template(parts: &[TemplatePart], value: &Value) -> String {
+    let mut rendered = String::new();
+    for part in parts {
+        match part {
+            TemplatePart::Literal(literal) => rendered.push_str(literal),
+            TemplatePart::Field(field) => match value.get(field) {
+                Some(Value::String(s)) => rendered.push_str(s),
+                Some(Value::Null) | None => {}
+                Some(other) => rendered.push_str(&other.to_string()),
+            },
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_template, render_template, TemplatePart};
+
+    #[test]
+    fn test_key_template() {
+        let parts = parse_template("{tenant}:{user_id}");
+        assert_eq!(
+            parts,
+            vec![
+                TemplatePart::Field("tenant".to_string()),
+                TemplatePart::Literal(":".to_string()),
+                TemplatePart::Field("user_id".to_string()),
+            ]
+        );
+
+        let value = serde_json::json!({"tenant": "acme", "user_id": 42});
+        assert_eq!(render_template(&parts, &value), "acme:42");
+    }
+}
@@ -48,9 +48,12 @@ pub trait TwoPhaseCommitter<K: Key, T: Data + Sync>: Send + 'static {
     ) -> Result<()>;
     async fn insert_record(&mut self, record: &Record<K, T>) -> Result<()>;
     // TODO: figure out how to have the relevant vectors be of pointers across async boundaries.
+    /// Commits the pre-commits of the checkpoint `epoch`. This may be called again for the same
+    /// epoch after a restart if the job failed while committing.
     async fn commit(
         &mut self,
         task_info: &TaskInfo,
+        epoch: u32,
         pre_commit: Vec<Self::PreCommit>,
     ) -> Result<()>;
     async fn checkpoint(
@@ -162,7 +165,7 @@ impl<K: Key, T: Data + Sync, TPC: TwoPhaseCommitter<K, T>> TwoPhaseCommitterOper
         let pre_commits = self.pre_commits.clone();
         self.pre_commits.clear();
         self.committer
-            .commit(&ctx.task_info, pre_commits)
+            .commit(&ctx.task_info, epoch, pre_commits)
             .await
             .expect("committer committed");
         let checkpoint_event = arroyo_rpc::ControlResp::CheckpointEvent(CheckpointEvent {
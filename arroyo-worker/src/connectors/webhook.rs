@@ -98,7 +98,7 @@ where
             .await
             .expect("websink semaphore closed");
 
-        let Some(body) = self.serializer.to_vec(&record.value) else {
+        let Some(body) = self.serializer.to_vec_or_report(&record.value, ctx).await else {
             return;
         };
 
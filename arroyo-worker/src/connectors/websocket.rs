@@ -4,7 +4,6 @@ use std::{
 };
 
 use arroyo_macro::source_fn;
-use arroyo_rpc::types::Format;
 use arroyo_rpc::{
     grpc::{StopMode, TableDescriptor},
     ControlMessage, OperatorConfig,
@@ -21,8 +20,10 @@ use tracing::{debug, info};
 use typify::import_types;
 
 use crate::{
+    connectors::bad_data::BadDataHandler,
     engine::{Context, StreamNode},
-    formats, SourceFinishType,
+    formats::DataDeserializer,
+    SourceFinishType,
 };
 
 import_types!(schema = "../connector-schemas/websocket/table.json");
@@ -38,7 +39,8 @@ where
 {
     url: String,
     subscription_message: Option<String>,
-    format: Format,
+    deserializer: DataDeserializer<T>,
+    bad_data: BadDataHandler,
     state: WebsocketSourceState,
     _t: PhantomData<(K, T)>,
 }
@@ -52,13 +54,17 @@ where
     pub fn from_config(config: &str) -> Self {
         let config: OperatorConfig =
             serde_json::from_str(config).expect("Invalid config for WebsocketSource");
+        let bad_data = BadDataHandler::from_config(&config);
         let table: WebsocketTable =
             serde_json::from_value(config.table).expect("Invalid table config for WebsocketSource");
 
         Self {
             url: table.endpoint,
             subscription_message: table.subscription_message.map(|s| s.into()),
-            format: config.format.expect("WebsocketSource requires a format"),
+            deserializer: DataDeserializer::new(
+                config.format.expect("WebsocketSource requires a format"),
+            ),
+            bad_data,
             state: WebsocketSourceState::default(),
             _t: PhantomData,
         }
@@ -160,10 +166,12 @@ where
                             Some(Ok(msg)) => {
                                 let data = match msg {
                                     tungstenite::Message::Text(t) => {
-                                        formats::deserialize_slice(&self.format, &t.as_bytes()).map(|t| Some(t))
+                                        let values = self.deserializer.deserialize_slice(t.as_bytes()).await;
+                                        Ok(self.bad_data.handle(ctx, t.as_bytes(), values).await)
                                     },
                                     tungstenite::Message::Binary(bs) => {
-                                        formats::deserialize_slice(&self.format, &bs).map(|t| Some(t))
+                                        let values = self.deserializer.deserialize_slice(&bs).await;
+                                        Ok(self.bad_data.handle(ctx, &bs, values).await)
                                     },
                                     tungstenite::Message::Ping(d) => {
                                         tx.send(tungstenite::Message::Pong(d)).await
@@ -185,12 +193,14 @@ where
                                 };
 
                                 match data {
-                                    Ok(Some(t)) => {
-                                        ctx.collector.collect(Record {
-                                            timestamp: SystemTime::now(),
-                                            key: None,
-                                            value: t,
-                                        }).await;
+                                    Ok(Some(values)) => {
+                                        for value in values {
+                                            ctx.collector.collect(Record {
+                                                timestamp: SystemTime::now(),
+                                                key: None,
+                                                value,
+                                            }).await;
+                                        }
                                     }
                                     Ok(None) => {}
                                     Err(e) => {
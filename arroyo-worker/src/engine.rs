@@ -426,6 +426,23 @@ impl<K: Key, T: Data> Context<K, T> {
     }
 }
 
+/// Commits are sent to every source, but most sources don't commit anything; this drops them
+/// before they reach those sources, so they only have to handle the other control messages
+pub fn without_commits(mut control_rx: Receiver<ControlMessage>) -> Receiver<ControlMessage> {
+    let (tx, rx) = channel(16);
+    tokio::spawn(async move {
+        while let Some(msg) = control_rx.recv().await {
+            if matches!(msg, ControlMessage::Commit { .. }) {
+                continue;
+            }
+            if tx.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
 #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
 pub struct TimerValue<K: Key, T: Decode + Encode + Clone + PartialEq + Eq> {
     pub time: SystemTime,
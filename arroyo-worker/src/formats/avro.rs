@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use apache_avro::{from_avro_datum, to_avro_datum, types::Value as AvroValue, Schema};
+use arrow::datatypes::{DataType, Field, Fields};
+use arroyo_rpc::schema_resolver::SchemaResolver;
+use arroyo_rpc::types::AvroFormat;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::metadata::{Metadata, WithMetadata};
+use super::parse_confluent_header;
+
+pub struct AvroDecoder {
+    confluent_schema_registry: bool,
+    reader_schema: Option<Schema>,
+    writer_schemas: Mutex<HashMap<u32, Arc<Schema>>>,
+    resolver: Arc<dyn SchemaResolver + Sync>,
+}
+
+impl AvroDecoder {
+    pub fn new(format: &AvroFormat, resolver: Arc<dyn SchemaResolver + Sync>) -> Self {
+        let reader_schema = format
+            .schema
+            .as_ref()
+            .map(|s| Schema::parse_str(s).expect("invalid avro schema for table"));
+
+        Self {
+            confluent_schema_registry: format.confluent_schema_registry,
+            reader_schema,
+            writer_schemas: Mutex::new(HashMap::new()),
+            resolver,
+        }
+    }
+
+    async fn writer_schema(&self, id: u32) -> Result<Arc<Schema>, String> {
+        let cached = self.writer_schemas.lock().unwrap().get(&id).cloned();
+        if let Some(schema) = cached {
+            return Ok(schema);
+        }
+
+        let schema = self
+            .resolver
+            .resolve_schema(id)
+            .await?
+            .ok_or_else(|| format!("Schema with id {} not found in registry", id))?;
+
+        let schema = Arc::new(
+            Schema::parse_str(&schema)
+                .map_err(|e| format!("Schema {} from registry is not valid avro: {:?}", id, e))?,
+        );
+
+        self.writer_schemas
+            .lock()
+            .unwrap()
+            .insert(id, schema.clone());
+
+        Ok(schema)
+    }
+
+    pub async fn deserialize<T: DeserializeOwned>(
+        &self,
+        msg: &[u8],
+        metadata: &Metadata,
+    ) -> Result<T, String> {
+        let value = if self.confluent_schema_registry {
+            let (id, mut datum) = parse_confluent_header(msg)?;
+            let writer_schema = self.writer_schema(id).await?;
+
+            // resolve the writer's schema against our own, which allows producers to evolve
+            // their schemas in compatible ways
+            from_avro_datum(&writer_schema, &mut datum, self.reader_schema.as_ref())
+        } else {
+            let schema = self.reader_schema.as_ref().ok_or_else(|| {
+                "Avro tables without a schema registry must have a schema defined".to_string()
+            })?;
+
+            from_avro_datum(schema, &mut &msg[..], None)
+        }
+        .map_err(|e| format!("Failed to decode Avro message: {:?}", e))?;
+
+        T::deserialize(WithMetadata::new(avro_to_json(value)?, metadata))
+            .map_err(|e| format!("Failed to deserialize Avro record into schema: {:?}", e))
+    }
+}
+
+// Records are deserialized into the generated structs by way of JSON, so that they can make use of
+// the same serde annotations as the JSON format. Arrays and maps are represented in SQL as
+// JSON-encoded text, so those that are fields of records are converted to strings.
+fn avro_to_json(value: AvroValue) -> Result<Value, String> {
+    to_json(value, false)
+}
+
+fn to_json(value: AvroValue, in_collection: bool) -> Result<Value, String> {
+    Ok(match value {
+        AvroValue::Null => Value::Null,
+        AvroValue::Boolean(b) => json!(b),
+        AvroValue::Int(i) | AvroValue::TimeMillis(i) => json!(i),
+        AvroValue::Long(i) | AvroValue::TimeMicros(i) | AvroValue::TimestampMillis(i) => json!(i),
+        // timestamps are represented as millis in the generated structs
+        AvroValue::TimestampMicros(i) => json!(i / 1000),
+        AvroValue::Date(days) => json!(days as i64 * 24 * 60 * 60 * 1000),
+        AvroValue::Float(f) => json!(f),
+        AvroValue::Double(f) => json!(f),
+        AvroValue::Bytes(bs) | AvroValue::Fixed(_, bs) => json!(bs),
+        AvroValue::String(s) | AvroValue::Enum(_, s) => json!(s),
+        AvroValue::Uuid(u) => json!(u.to_string()),
+        AvroValue::Union(_, v) => to_json(*v, in_collection)?,
+        AvroValue::Array(vs) => {
+            let array = Value::Array(
+                vs.into_iter()
+                    .map(|v| to_json(v, true))
+                    .collect::<Result<_, String>>()?,
+            );
+            stringify_if(array, !in_collection)
+        }
+        AvroValue::Map(m) => {
+            let map = Value::Object(
+                m.into_iter()
+                    .map(|(k, v)| Ok((k, to_json(v, true)?)))
+                    .collect::<Result<_, String>>()?,
+            );
+            stringify_if(map, !in_collection)
+        }
+        AvroValue::Record(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(k, v)| Ok((k, to_json(v, in_collection)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        v => return Err(format!("Unsupported Avro value: {:?}", v)),
+    })
+}
+
+fn stringify_if(value: Value, stringify: bool) -> Value {
+    if stringify {
+        Value::String(value.to_string())
+    } else {
+        value
+    }
+}
+
+pub fn serialize_avro<T: Serialize>(schema: &Schema, record: &T) -> Result<Vec<u8>, String> {
+    let value = to_avro_value(schema, record)?;
+    to_avro_datum(schema, value).map_err(|e| format!("Failed to serialize avro: {:?}", e))
+}
+
+/// Converts a record into an avro value resolved against the schema
+pub fn to_avro_value<T: Serialize>(schema: &Schema, record: &T) -> Result<AvroValue, String> {
+    apache_avro::to_value(record)
+        .map_err(|e| format!("Failed to convert record to avro: {:?}", e))
+        .and_then(|mut v| {
+            parse_collections(schema, &mut v)?;
+            Ok(v)
+        })?
+        .resolve(schema)
+        .map_err(|e| format!("Failed to convert record to avro: {:?}", e))
+}
+
+/// Parses the JSON-encoded text of fields that the schema defines as arrays or maps back into
+/// values, so that they can be resolved against the schema
+fn parse_collections(schema: &Schema, value: &mut AvroValue) -> Result<(), String> {
+    match (schema, value) {
+        (Schema::Record(record), AvroValue::Record(fields)) => {
+            for (name, v) in fields.iter_mut() {
+                if let Some(field) = record.fields.iter().find(|f| f.name == *name) {
+                    parse_collections(&field.schema, v)?;
+                }
+            }
+        }
+        (Schema::Union(union), value) => {
+            let value = match value {
+                AvroValue::Union(_, v) => &mut **v,
+                v => v,
+            };
+            for variant in union.variants() {
+                parse_collections(variant, value)?;
+            }
+        }
+        (Schema::Array(_) | Schema::Map(_), value) => {
+            if let AvroValue::String(s) = value {
+                let json: Value = serde_json::from_str(s)
+                    .map_err(|e| format!("Field does not contain valid JSON: {:?}", e))?;
+                *value = apache_avro::to_value(json)
+                    .map_err(|e| format!("Failed to convert record to avro: {:?}", e))?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn field_to_avro(namespace: &str, field: &Field) -> Result<Value, String> {
+    let typ = match field.data_type() {
+        DataType::Null => json!("null"),
+        DataType::Boolean => json!("boolean"),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            json!("int")
+        }
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => json!("long"),
+        DataType::Float16 | DataType::Float32 => json!("float"),
+        DataType::Float64 => json!("double"),
+        DataType::Utf8 | DataType::LargeUtf8 => json!("string"),
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+            json!("bytes")
+        }
+        DataType::Timestamp(_, _) => {
+            json!({ "type": "long", "logicalType": "timestamp-millis" })
+        }
+        DataType::List(t) | DataType::FixedSizeList(t, _) | DataType::LargeList(t) => {
+            let items = field_to_avro(namespace, t)?;
+            json!({ "type": "array", "items": items["type"] })
+        }
+        DataType::Struct(fields) => {
+            arrow_to_avro_json(&format!("{}_{}", namespace, field.name()), fields)?
+        }
+        DataType::Date32
+        | DataType::Date64
+        | DataType::Time32(_)
+        | DataType::Time64(_)
+        | DataType::Duration(_)
+        | DataType::Interval(_)
+        | DataType::Union(_, _)
+        | DataType::Dictionary(_, _)
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _)
+        | DataType::Map(_, _)
+        | DataType::RunEndEncoded(_, _) => {
+            return Err(format!(
+                "field '{}' has type {:?}, which can't be written as avro",
+                field.name(),
+                field.data_type()
+            ));
+        }
+    };
+
+    Ok(if field.is_nullable() {
+        json!({
+            "name": field.name(),
+            "type": ["null", typ],
+            "default": null,
+        })
+    } else {
+        json!({
+            "name": field.name(),
+            "type": typ,
+        })
+    })
+}
+
+fn arrow_to_avro_json(name: &str, fields: &Fields) -> Result<Value, String> {
+    let fields = fields
+        .iter()
+        .map(|f| field_to_avro(name, f))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(json!({
+        "type": "record",
+        "name": name,
+        "fields": fields,
+    }))
+}
+
+/// Generates the schema that records are written with when the table doesn't configure one. Types
+/// that can't be represented are rejected when the table is created, so this only fails if a
+/// pipeline skipped that check.
+pub fn arrow_to_avro_schema(name: &str, fields: &Fields) -> Result<Schema, String> {
+    Schema::parse(&arrow_to_avro_json(name, fields)?)
+        .map_err(|e| format!("generated avro schema is invalid: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use apache_avro::Schema;
+    use arroyo_rpc::schema_resolver::FailingSchemaResolver;
+    use arroyo_rpc::types::AvroFormat;
+    use serde::{Deserialize, Serialize};
+
+    use super::{serialize_avro, AvroDecoder};
+    use crate::formats::confluent_header;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestData {
+        id: i64,
+        name: String,
+        score: Option<f64>,
+    }
+
+    const WRITER_SCHEMA: &str = r#"
+    {
+        "type": "record",
+        "name": "TestData",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "name", "type": "string"},
+            {"name": "extra", "type": "int"}
+        ]
+    }"#;
+
+    const READER_SCHEMA: &str = r#"
+    {
+        "type": "record",
+        "name": "TestData",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "name", "type": "string"},
+            {"name": "score", "type": ["null", "double"], "default": null}
+        ]
+    }"#;
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        let format = AvroFormat {
+            confluent_schema_registry: false,
+            schema: Some(READER_SCHEMA.to_string()),
+        };
+
+        let decoder = AvroDecoder::new(&format, Arc::new(FailingSchemaResolver::new()));
+        let schema = Schema::parse_str(READER_SCHEMA).unwrap();
+
+        let data = TestData {
+            id: 5,
+            name: "hello".to_string(),
+            score: Some(1.5),
+        };
+
+        let bytes = serialize_avro(&schema, &data).unwrap();
+        let result: TestData = decoder.deserialize(&bytes, &[]).await.unwrap();
+        assert_eq!(data, result);
+    }
+
+    #[tokio::test]
+    async fn test_collections() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct WithCollections {
+            id: i64,
+            tags: String,
+            counts: Option<String>,
+        }
+
+        let schema_str = r#"
+        {
+            "type": "record",
+            "name": "WithCollections",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "tags", "type": {"type": "array", "items": "string"}},
+                {"name": "counts", "type": ["null", {"type": "map", "values": "long"}], "default": null}
+            ]
+        }"#;
+
+        let decoder = AvroDecoder::new(
+            &AvroFormat {
+                confluent_schema_registry: false,
+                schema: Some(schema_str.to_string()),
+            },
+            Arc::new(FailingSchemaResolver::new()),
+        );
+        let schema = Schema::parse_str(schema_str).unwrap();
+
+        let data = WithCollections {
+            id: 1,
+            tags: r#"["a","b"]"#.to_string(),
+            counts: Some(r#"{"x":3}"#.to_string()),
+        };
+
+        let bytes = serialize_avro(&schema, &data).unwrap();
+        let result: WithCollections = decoder.deserialize(&bytes, &[]).await.unwrap();
+        assert_eq!(data, result);
+
+        let invalid = WithCollections {
+            tags: "not json".to_string(),
+            ..data
+        };
+        assert!(serialize_avro(&schema, &invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schema_resolution() {
+        #[derive(Serialize)]
+        struct Writer {
+            id: i64,
+            name: String,
+            extra: i32,
+        }
+
+        let writer_schema = Schema::parse_str(WRITER_SCHEMA).unwrap();
+        let mut msg = confluent_header(1);
+        msg.extend(
+            serialize_avro(
+                &writer_schema,
+                &Writer {
+                    id: 10,
+                    name: "a".to_string(),
+                    extra: 3,
+                },
+            )
+            .unwrap(),
+        );
+
+        let decoder = AvroDecoder::new(
+            &AvroFormat {
+                confluent_schema_registry: true,
+                schema: Some(READER_SCHEMA.to_string()),
+            },
+            Arc::new(FailingSchemaResolver::new()),
+        );
+
+        // seed the cache, as we don't have a registry available
+        decoder
+            .writer_schemas
+            .lock()
+            .unwrap()
+            .insert(1, Arc::new(writer_schema));
+
+        let result: TestData = decoder.deserialize(&msg, &[]).await.unwrap();
+        assert_eq!(
+            TestData {
+                id: 10,
+                name: "a".to_string(),
+                score: None
+            },
+            result
+        );
+    }
+}
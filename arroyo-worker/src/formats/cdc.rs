@@ -0,0 +1,296 @@
+use arroyo_rpc::types::JsonEnvelope;
+use serde_json::{json, Map, Value};
+
+// Maxwell and Canal messages are converted to and from the Debezium envelope
+// (`{"before": ..., "after": ..., "op": "c" | "u" | "d"}`), which is how updating data is
+// represented by the rest of the pipeline.
+
+/// Converts a change message into zero or more Debezium-style messages. Messages that don't
+/// describe data changes (like DDL statements or bootstrap markers) produce no output.
+pub fn to_debezium(envelope: JsonEnvelope, msg: Value) -> Result<Vec<Value>, String> {
+    match envelope {
+        JsonEnvelope::Maxwell => maxwell_to_debezium(msg),
+        JsonEnvelope::Canal => canal_to_debezium(msg),
+    }
+}
+
+/// Converts a serialized `Debezium` record into the given envelope
+pub fn from_debezium(envelope: JsonEnvelope, msg: Value) -> Result<Value, String> {
+    let (before, after, op) = split_debezium(msg)?;
+
+    Ok(match envelope {
+        JsonEnvelope::Maxwell => match op.as_str() {
+            "c" => json!({"type": "insert", "data": after}),
+            "u" => json!({"type": "update", "data": after, "old": before}),
+            "d" => json!({"type": "delete", "data": before}),
+            op => return Err(format!("invalid debezium op '{}'", op)),
+        },
+        JsonEnvelope::Canal => match op.as_str() {
+            "c" => json!({"type": "INSERT", "isDdl": false, "data": [after]}),
+            "u" => json!({"type": "UPDATE", "isDdl": false, "data": [after], "old": [before]}),
+            "d" => json!({"type": "DELETE", "isDdl": false, "data": [before]}),
+            op => return Err(format!("invalid debezium op '{}'", op)),
+        },
+    })
+}
+
+fn split_debezium(msg: Value) -> Result<(Value, Value, String), String> {
+    let Value::Object(mut msg) = msg else {
+        return Err("expected a debezium record".to_string());
+    };
+
+    let op = match msg.remove("op") {
+        Some(Value::String(op)) => op,
+        _ => return Err("debezium record is missing `op`".to_string()),
+    };
+
+    Ok((
+        msg.remove("before").unwrap_or(Value::Null),
+        msg.remove("after").unwrap_or(Value::Null),
+        op,
+    ))
+}
+
+fn debezium(before: Option<Value>, after: Option<Value>, op: &str) -> Value {
+    json!({
+        "before": before,
+        "after": after,
+        "op": op,
+    })
+}
+
+/// Change messages only include the previous values of columns that were modified, so the full
+/// previous row is the new row with those values replaced
+fn previous_row(data: &Value, old: Option<&Value>) -> Value {
+    let mut before = data.clone();
+    if let (Value::Object(before), Some(Value::Object(old))) = (&mut before, old) {
+        for (k, v) in old {
+            before.insert(k.clone(), v.clone());
+        }
+    }
+    before
+}
+
+fn get_type(msg: &Map<String, Value>) -> Result<&str, String> {
+    msg.get("type")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| "change message is missing `type`".to_string())
+}
+
+fn maxwell_to_debezium(msg: Value) -> Result<Vec<Value>, String> {
+    let Value::Object(msg) = msg else {
+        return Err("expected a Maxwell message to be a JSON object".to_string());
+    };
+
+    let op = match get_type(&msg)? {
+        "insert" | "bootstrap-insert" => "c",
+        "update" => "u",
+        "delete" => "d",
+        // DDL and bootstrap-start/complete messages
+        _ => return Ok(vec![]),
+    };
+
+    let data = msg
+        .get("data")
+        .filter(|d| d.is_object())
+        .ok_or_else(|| "Maxwell message is missing `data`".to_string())?;
+
+    Ok(vec![match op {
+        "c" => debezium(None, Some(data.clone()), op),
+        "u" => debezium(
+            Some(previous_row(data, msg.get("old"))),
+            Some(data.clone()),
+            op,
+        ),
+        _ => debezium(Some(data.clone()), None, op),
+    }])
+}
+
+fn canal_to_debezium(msg: Value) -> Result<Vec<Value>, String> {
+    let Value::Object(msg) = msg else {
+        return Err("expected a Canal message to be a JSON object".to_string());
+    };
+
+    if msg.get("isDdl").and_then(|d| d.as_bool()).unwrap_or(false) {
+        return Ok(vec![]);
+    }
+
+    let op = match get_type(&msg)? {
+        "INSERT" => "c",
+        "UPDATE" => "u",
+        "DELETE" => "d",
+        _ => return Ok(vec![]),
+    };
+
+    let Some(Value::Array(data)) = msg.get("data") else {
+        return Err("Canal message is missing `data`".to_string());
+    };
+
+    let old = match msg.get("old") {
+        Some(Value::Array(old)) => old.as_slice(),
+        _ => &[],
+    };
+
+    let mysql_types = msg.get("mysqlType").and_then(|t| t.as_object());
+
+    data.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            if !row.is_object() {
+                return Err("expected Canal rows to be JSON objects".to_string());
+            }
+
+            let row = coerce_canal_row(row, mysql_types);
+            Ok(match op {
+                "c" => debezium(None, Some(row), op),
+                "u" => {
+                    let old = old.get(i).map(|o| coerce_canal_row(o, mysql_types));
+                    debezium(Some(previous_row(&row, old.as_ref())), Some(row), op)
+                }
+                _ => debezium(Some(row), None, op),
+            })
+        })
+        .collect()
+}
+
+/// Canal writes all values as strings; we use the MySQL column types included in the message to
+/// turn integer, float and boolean columns back into JSON values. Decimals are left as strings,
+/// as they can't be represented as JSON numbers without losing precision.
+fn coerce_canal_row(row: &Value, mysql_types: Option<&Map<String, Value>>) -> Value {
+    let (Value::Object(row), Some(mysql_types)) = (row, mysql_types) else {
+        return row.clone();
+    };
+
+    Value::Object(
+        row.iter()
+            .map(|(k, v)| {
+                let v = match (v, mysql_types.get(k).and_then(|t| t.as_str())) {
+                    (Value::String(s), Some(t)) => {
+                        coerce_canal_value(s, t).unwrap_or_else(|| Value::String(s.clone()))
+                    }
+                    _ => v.clone(),
+                };
+                (k.clone(), v)
+            })
+            .collect(),
+    )
+}
+
+fn coerce_canal_value(value: &str, mysql_type: &str) -> Option<Value> {
+    let mysql_type = mysql_type.to_lowercase();
+
+    // MySQL's BOOLEAN is an alias for tinyint(1)
+    if mysql_type.starts_with("tinyint(1)") {
+        return value.parse::<i64>().ok().map(|v| Value::Bool(v != 0));
+    }
+
+    let base = mysql_type.split(['(', ' ']).next().unwrap_or_default();
+
+    match base {
+        "tinyint" | "smallint" | "mediumint" | "int" | "integer" | "bigint" => value
+            .parse::<i64>()
+            .map(Value::from)
+            .or_else(|_| value.parse::<u64>().map(Value::from))
+            .ok(),
+        "float" | "double" | "real" => value.parse::<f64>().ok().map(Value::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arroyo_rpc::types::JsonEnvelope;
+    use serde_json::json;
+
+    use super::{from_debezium, to_debezium};
+
+    #[test]
+    fn test_maxwell() {
+        let msg = json!({
+            "database": "shop",
+            "table": "orders",
+            "type": "update",
+            "ts": 1696000000,
+            "data": {"id": 1, "status": "shipped"},
+            "old": {"status": "pending"}
+        });
+
+        assert_eq!(
+            to_debezium(JsonEnvelope::Maxwell, msg).unwrap(),
+            vec![json!({
+                "before": {"id": 1, "status": "pending"},
+                "after": {"id": 1, "status": "shipped"},
+                "op": "u"
+            })]
+        );
+
+        let ddl = json!({"type": "table-create", "database": "shop", "table": "orders"});
+        assert!(to_debezium(JsonEnvelope::Maxwell, ddl).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_canal() {
+        let msg = json!({
+            "database": "shop",
+            "table": "orders",
+            "type": "DELETE",
+            "isDdl": false,
+            "mysqlType": {
+                "id": "int(11)",
+                "price": "decimal(20,2)",
+                "weight": "double",
+                "active": "tinyint(1)",
+                "name": "varchar(255)"
+            },
+            "data": [
+                {
+                    "id": "1",
+                    "price": "12345678901234567.89",
+                    "weight": "0.5",
+                    "active": "1",
+                    "name": "a"
+                },
+                {"id": "2", "price": null, "weight": null, "active": "0", "name": "b"}
+            ],
+            "old": null
+        });
+
+        assert_eq!(
+            to_debezium(JsonEnvelope::Canal, msg).unwrap(),
+            vec![
+                json!({
+                    "before": {
+                        "id": 1,
+                        "price": "12345678901234567.89",
+                        "weight": 0.5,
+                        "active": true,
+                        "name": "a"
+                    },
+                    "after": null,
+                    "op": "d"
+                }),
+                json!({
+                    "before": {
+                        "id": 2,
+                        "price": null,
+                        "weight": null,
+                        "active": false,
+                        "name": "b"
+                    },
+                    "after": null,
+                    "op": "d"
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_debezium() {
+        let record = json!({"before": {"id": 1}, "after": {"id": 2}, "op": "u"});
+
+        assert_eq!(
+            from_debezium(JsonEnvelope::Canal, record).unwrap(),
+            json!({"type": "UPDATE", "isDdl": false, "data": [{"id": 2}], "old": [{"id": 1}]})
+        );
+    }
+}
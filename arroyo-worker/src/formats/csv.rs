@@ -0,0 +1,716 @@
+use std::str::FromStr;
+
+use arroyo_rpc::types::CsvFormat;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use serde::de::value::{Error as DeError, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, Error as _, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, SerializeStruct, Serializer};
+use serde::{forward_to_deserialize_any, Deserializer, Serialize};
+
+use super::metadata::{Metadata, WithMetadata};
+
+pub struct CsvCodec {
+    format: CsvFormat,
+}
+
+impl CsvCodec {
+    pub fn new(format: &CsvFormat) -> Self {
+        Self {
+            format: format.clone(),
+        }
+    }
+
+    fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.format.delimiter as u8)
+            .quote(self.format.quote as u8)
+            .escape(self.format.escape.map(|c| c as u8))
+            .double_quote(self.format.escape.is_none())
+            .has_headers(self.format.header);
+        builder
+    }
+
+    fn writer_builder(&self) -> WriterBuilder {
+        let mut builder = WriterBuilder::new();
+        builder
+            .delimiter(self.format.delimiter as u8)
+            .quote(self.format.quote as u8)
+            .escape(self.format.escape.map(|c| c as u8).unwrap_or(b'\\'))
+            .double_quote(self.format.escape.is_none())
+            .has_headers(false);
+        builder
+    }
+
+    pub fn header(&self) -> bool {
+        self.format.header
+    }
+
+    /// Deserializes the first record of a message. If the format has a header, the message must
+    /// start with a header row, which is used to match columns to fields by name.
+    pub fn deserialize<T: DeserializeOwned>(
+        &self,
+        msg: &[u8],
+        metadata: &Metadata,
+    ) -> Result<T, String> {
+        let mut reader = self.reader_builder().from_reader(msg);
+
+        let headers = if self.format.header {
+            Some(
+                reader
+                    .headers()
+                    .map_err(|e| format!("Failed to read CSV header: {}", e))?
+                    .clone(),
+            )
+        } else {
+            None
+        };
+
+        let mut record = StringRecord::new();
+        if !reader
+            .read_record(&mut record)
+            .map_err(|e| format!("Failed to read CSV record: {}", e))?
+        {
+            return Err("Message does not contain a CSV record".to_string());
+        }
+
+        // only fields matching the null value are nulls; with a non-empty null value, empty
+        // fields are empty strings
+        let fields = record.iter().map(|f| FieldDeserializer {
+            value: f,
+            null: f == self.format.null_value,
+        });
+
+        match &headers {
+            Some(headers) => T::deserialize(WithMetadata::new(
+                MapDeserializer::<_, DeError>::new(headers.iter().zip(fields)),
+                metadata,
+            )),
+            None => T::deserialize(SeqDeserializer::<_, DeError>::new(fields)),
+        }
+        .map_err(|e| format!("Failed to deserialize CSV record into schema: {}", e))
+    }
+
+    /// Serializes the record into its field names and values, with nulls written as the
+    /// format's null value
+    fn to_row<T: Serialize>(&self, record: &T) -> Result<(Vec<&'static str>, Vec<String>), String> {
+        let (names, values) = record
+            .serialize(RecordSerializer)
+            .map_err(|e| format!("Failed to serialize record as CSV: {}", e))?;
+
+        let values = values
+            .into_iter()
+            .map(|v| v.unwrap_or_else(|| self.format.null_value.clone()))
+            .collect();
+
+        Ok((names, values))
+    }
+
+    fn write_row<I: AsRef<[u8]>>(&self, buf: &mut Vec<u8>, fields: &[I]) -> Result<(), String> {
+        let mut writer = self.writer_builder().from_writer(buf);
+        writer
+            .write_record(fields)
+            .and_then(|_| writer.flush().map_err(csv::Error::from))
+            .map_err(|e| format!("Failed to write CSV row: {}", e))
+    }
+
+    /// Writes the header row for the given record's fields, terminated by a newline
+    pub fn write_header<T: Serialize>(&self, buf: &mut Vec<u8>, record: &T) -> Result<(), String> {
+        let (headers, _) = self.to_row(record)?;
+        self.write_row(buf, &headers)
+    }
+
+    /// Writes a record as a CSV row, terminated by a newline
+    pub fn write_record<T: Serialize>(&self, buf: &mut Vec<u8>, record: &T) -> Result<(), String> {
+        let (_, fields) = self.to_row(record)?;
+        self.write_row(buf, &fields)
+    }
+
+    /// Serializes a record as a message, preceded by a header row if the format has one
+    pub fn serialize<T: Serialize>(&self, record: &T) -> Result<Vec<u8>, String> {
+        let (headers, fields) = self.to_row(record)?;
+
+        let mut buf = vec![];
+        if self.format.header {
+            self.write_row(&mut buf, &headers)?;
+        }
+        self.write_row(&mut buf, &fields)?;
+
+        // messages don't need a trailing newline
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+        }
+        Ok(buf)
+    }
+}
+
+/// Serializes a record, which must be a struct, into its field names and values
+struct RecordSerializer;
+
+type Row = (Vec<&'static str>, Vec<Option<String>>);
+
+fn not_a_struct() -> DeError {
+    ser::Error::custom("CSV records must be structs")
+}
+
+impl Serializer for RecordSerializer {
+    type Ok = Row;
+    type Error = DeError;
+    type SerializeSeq = Impossible<Row, DeError>;
+    type SerializeTuple = Impossible<Row, DeError>;
+    type SerializeTupleStruct = Impossible<Row, DeError>;
+    type SerializeTupleVariant = Impossible<Row, DeError>;
+    type SerializeMap = Impossible<Row, DeError>;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = Impossible<Row, DeError>;
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<StructSerializer, DeError> {
+        Ok(StructSerializer {
+            names: Vec::with_capacity(len),
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Row, DeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, DeError> {
+        Err(ser::Error::custom("serializing maps is not supported"))
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_char(self, _: char) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_str(self, _: &str) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_none(self) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_unit(self) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Row, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, DeError> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, DeError> {
+        Err(not_a_struct())
+    }
+}
+
+struct StructSerializer {
+    names: Vec<&'static str>,
+    values: Vec<Option<String>>,
+}
+
+impl SerializeStruct for StructSerializer {
+    type Ok = Row;
+    type Error = DeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), DeError> {
+        self.names.push(key);
+        self.values.push(value.serialize(FieldSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Row, DeError> {
+        Ok((self.names, self.values))
+    }
+}
+
+/// Serializes a single field as its text, or `None` if it's null. Numbers are formatted as the
+/// csv crate formats them.
+struct FieldSerializer;
+
+fn nested_field() -> DeError {
+    ser::Error::custom("nested values can't be written as CSV fields")
+}
+
+// serde_json writes finite floats in their shortest round-tripping form, as csv does
+fn format_float<F: Serialize + ToString>(v: F, finite: bool) -> Result<Option<String>, DeError> {
+    Ok(Some(if finite {
+        serde_json::to_string(&v).map_err(<DeError as ser::Error>::custom)?
+    } else {
+        v.to_string()
+    }))
+}
+
+macro_rules! serialize_display {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<Option<String>, DeError> {
+                Ok(Some(v.to_string()))
+            }
+        )*
+    };
+}
+
+impl Serializer for FieldSerializer {
+    type Ok = Option<String>;
+    type Error = DeError;
+    type SerializeSeq = Impossible<Option<String>, DeError>;
+    type SerializeTuple = Impossible<Option<String>, DeError>;
+    type SerializeTupleStruct = Impossible<Option<String>, DeError>;
+    type SerializeTupleVariant = Impossible<Option<String>, DeError>;
+    type SerializeMap = Impossible<Option<String>, DeError>;
+    type SerializeStruct = Impossible<Option<String>, DeError>;
+    type SerializeStructVariant = Impossible<Option<String>, DeError>;
+
+    serialize_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<String>, DeError> {
+        format_float(v, v.is_finite())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<String>, DeError> {
+        format_float(v, v.is_finite())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Option<String>, DeError> {
+        Ok(Some(String::from_utf8_lossy(v).into_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Option<String>, DeError> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Option<String>, DeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<String>, DeError> {
+        Ok(Some(String::new()))
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Option<String>, DeError> {
+        Ok(Some(String::new()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Option<String>, DeError> {
+        Ok(Some(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Option<String>, DeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Option<String>, DeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, DeError> {
+        Err(nested_field())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, DeError> {
+        Err(nested_field())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, DeError> {
+        Err(nested_field())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, DeError> {
+        Err(nested_field())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, DeError> {
+        Err(nested_field())
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, DeError> {
+        Err(nested_field())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, DeError> {
+        Err(nested_field())
+    }
+}
+
+/// Deserializes a single CSV field, which is `None` for nullable fields if it held the format's
+/// null value. As with the csv crate's deserializer, self-describing types are inferred from the
+/// field's text.
+struct FieldDeserializer<'a> {
+    value: &'a str,
+    null: bool,
+}
+
+impl<'a> FieldDeserializer<'a> {
+    fn parse<T: FromStr>(&self, ty: &str) -> Result<T, DeError> {
+        self.value
+            .trim()
+            .parse()
+            .map_err(|_| DeError::custom(format!("'{}' is not a valid {}", self.value, ty)))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.parse::<$ty>(stringify!($ty))?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for FieldDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.null {
+            return visitor.visit_none();
+        }
+
+        let s = self.value;
+        if let Ok(b) = s.parse::<bool>() {
+            visitor.visit_bool(b)
+        } else if let Ok(n) = s.parse::<u64>() {
+            visitor.visit_u64(n)
+        } else if let Ok(n) = s.parse::<i64>() {
+            visitor.visit_i64(n)
+        } else if let Ok(n) = s.parse::<f64>() {
+            visitor.visit_f64(n)
+        } else {
+            visitor.visit_borrowed_str(s)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        if self.null {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_borrowed_str(self.value)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_borrowed_bytes(self.value.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_enum(IntoDeserializer::<DeError>::into_deserializer(self.value))
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_i128 => visit_i128(i128),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_u128 => visit_u128(u128),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+    }
+
+    forward_to_deserialize_any! {
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for FieldDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use arroyo_rpc::types::CsvFormat;
+    use serde::{Deserialize, Serialize};
+
+    use super::CsvCodec;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestData {
+        id: i64,
+        name: String,
+        score: Option<f64>,
+    }
+
+    #[test]
+    fn test_options() {
+        let codec = CsvCodec::new(&CsvFormat {
+            delimiter: '|',
+            quote: '\'',
+            null_value: "NULL".to_string(),
+            ..Default::default()
+        });
+
+        let result: TestData = codec.deserialize(b"5|'a|b'|NULL", &[]).unwrap();
+        assert_eq!(
+            TestData {
+                id: 5,
+                name: "a|b".to_string(),
+                score: None
+            },
+            result
+        );
+
+        assert_eq!(b"5|'a|b'|NULL".to_vec(), codec.serialize(&result).unwrap());
+    }
+
+    #[test]
+    fn test_header() {
+        let codec = CsvCodec::new(&CsvFormat {
+            header: true,
+            ..Default::default()
+        });
+
+        let result: TestData = codec
+            .deserialize(b"score,name,id\n1.5,hello,3", &[])
+            .unwrap();
+        let expected = TestData {
+            id: 3,
+            name: "hello".to_string(),
+            score: Some(1.5),
+        };
+        assert_eq!(expected, result);
+
+        assert_eq!(
+            "id,name,score\n3,hello,1.5",
+            String::from_utf8(codec.serialize(&expected).unwrap()).unwrap()
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct NullableText {
+        id: i64,
+        name: Option<String>,
+    }
+
+    #[test]
+    fn test_nullable_text() {
+        let codec = CsvCodec::new(&CsvFormat {
+            null_value: "NULL".to_string(),
+            ..Default::default()
+        });
+
+        let null: NullableText = codec.deserialize(b"1,NULL", &[]).unwrap();
+        assert_eq!(NullableText { id: 1, name: None }, null);
+        assert_eq!(b"1,NULL".to_vec(), codec.serialize(&null).unwrap());
+
+        let empty: NullableText = codec.deserialize(b"2,", &[]).unwrap();
+        assert_eq!(
+            NullableText {
+                id: 2,
+                name: Some(String::new())
+            },
+            empty
+        );
+        assert_eq!(b"2,".to_vec(), codec.serialize(&empty).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_record() {
+        let codec = CsvCodec::new(&CsvFormat::default());
+
+        let err = codec.deserialize::<TestData>(b"x,a,1.5", &[]).unwrap_err();
+        assert!(err.contains("'x' is not a valid i64"), "{}", err);
+
+        assert!(codec
+            .serialize(&BTreeMap::from([("id", 1)]))
+            .unwrap_err()
+            .contains("maps is not supported"));
+    }
+}
@@ -0,0 +1,199 @@
+use serde::ser::{self, Impossible, SerializeStruct, Serializer};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Serializes only the named top-level fields of a struct into a JSON object, for operators that
+/// need a few columns of each record without converting the whole record
+pub fn extract_fields<T: Serialize + ?Sized>(
+    record: &T,
+    fields: &[String],
+) -> Result<Value, serde_json::Error> {
+    record
+        .serialize(FieldExtractor { fields })
+        .map(Value::Object)
+}
+
+type FieldValues = Map<String, Value>;
+
+struct FieldExtractor<'a> {
+    fields: &'a [String],
+}
+
+fn not_a_struct() -> serde_json::Error {
+    ser::Error::custom("fields can only be extracted from structs")
+}
+
+macro_rules! not_a_struct {
+    ($($method:ident($($arg:ty),*)),* $(,)?) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<FieldValues, serde_json::Error> {
+                Err(not_a_struct())
+            }
+        )*
+    };
+}
+
+impl<'a> Serializer for FieldExtractor<'a> {
+    type Ok = FieldValues;
+    type Error = serde_json::Error;
+    type SerializeSeq = Impossible<FieldValues, serde_json::Error>;
+    type SerializeTuple = Impossible<FieldValues, serde_json::Error>;
+    type SerializeTupleStruct = Impossible<FieldValues, serde_json::Error>;
+    type SerializeTupleVariant = Impossible<FieldValues, serde_json::Error>;
+    type SerializeMap = Impossible<FieldValues, serde_json::Error>;
+    type SerializeStruct = StructFieldExtractor<'a>;
+    type SerializeStructVariant = Impossible<FieldValues, serde_json::Error>;
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<StructFieldExtractor<'a>, serde_json::Error> {
+        Ok(StructFieldExtractor {
+            fields: self.fields,
+            values: Map::new(),
+        })
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<FieldValues, serde_json::Error> {
+        value.serialize(self)
+    }
+
+    not_a_struct!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(&'static str),
+        serialize_unit_variant(&'static str, u32, &'static str),
+    );
+
+    fn serialize_some<T: ?Sized + Serialize>(
+        self,
+        _: &T,
+    ) -> Result<FieldValues, serde_json::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<FieldValues, serde_json::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, serde_json::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, serde_json::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, serde_json::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, serde_json::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, serde_json::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, serde_json::Error> {
+        Err(not_a_struct())
+    }
+}
+
+struct StructFieldExtractor<'a> {
+    fields: &'a [String],
+    values: FieldValues,
+}
+
+impl<'a> SerializeStruct for StructFieldExtractor<'a> {
+    type Ok = FieldValues;
+    type Error = serde_json::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), serde_json::Error> {
+        if self.fields.iter().any(|f| f == key) {
+            self.values
+                .insert(key.to_string(), serde_json::to_value(value)?);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<FieldValues, serde_json::Error> {
+        Ok(self.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::extract_fields;
+
+    #[derive(Serialize)]
+    struct Event {
+        id: i64,
+        country: Option<String>,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_extract_fields() {
+        let event = Event {
+            id: 5,
+            country: Some("US".to_string()),
+            tags: vec!["a".to_string()],
+        };
+
+        assert_eq!(
+            extract_fields(&event, &["country".to_string(), "missing".to_string()]).unwrap(),
+            json!({"country": "US"})
+        );
+        assert!(extract_fields(&5, &["id".to_string()]).is_err());
+    }
+}
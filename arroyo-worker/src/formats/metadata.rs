@@ -0,0 +1,279 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+
+use arroyo_types::to_nanos;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::Value;
+
+/// The value of a field that doesn't come from the message payload, like a Kafka offset
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Json(Value),
+    /// Timestamps are read by the timestamp fields directly, rather than in the encoding that the
+    /// payload's timestamps use, so they keep their precision whatever the format
+    Timestamp(SystemTime),
+}
+
+/// The metadata fields of a record, by field name
+pub type Metadata = [(String, MetadataValue)];
+
+/// Wraps the deserializer of a record so that the metadata fields are read along with the
+/// fields of the payload. Payload fields with the name of a metadata field are skipped.
+pub struct WithMetadata<'a, D> {
+    inner: D,
+    metadata: &'a Metadata,
+}
+
+impl<'a, D> WithMetadata<'a, D> {
+    pub fn new(inner: D, metadata: &'a Metadata) -> Self {
+        Self { inner, metadata }
+    }
+}
+
+impl<'de, 'a, D: Deserializer<'de>> Deserializer<'de> for WithMetadata<'a, D> {
+    type Error = D::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_any(MetadataVisitor {
+            inner: visitor,
+            metadata: self.metadata,
+        })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_map(MetadataVisitor {
+            inner: visitor,
+            metadata: self.metadata,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_struct(
+            name,
+            fields,
+            MetadataVisitor {
+                inner: visitor,
+                metadata: self.metadata,
+            },
+        )
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct enum identifier ignored_any
+    }
+}
+
+struct MetadataVisitor<'a, V> {
+    inner: V,
+    metadata: &'a Metadata,
+}
+
+impl<'de, 'a, V: Visitor<'de>> Visitor<'de> for MetadataVisitor<'a, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        self.inner.visit_map(MetadataMapAccess {
+            inner: map,
+            metadata: self.metadata,
+            next: None,
+            value: None,
+        })
+    }
+
+    // records that are read as sequences have no field names, so there's nothing to add
+    // metadata fields to
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        self.inner.visit_seq(seq)
+    }
+}
+
+/// Reads the entries of the payload, followed by the metadata fields
+struct MetadataMapAccess<'a, A> {
+    inner: A,
+    metadata: &'a Metadata,
+    /// The index of the next metadata field, once the payload's entries have been read
+    next: Option<usize>,
+    value: Option<&'a MetadataValue>,
+}
+
+impl<'de, 'a, A: de::MapAccess<'de>> de::MapAccess<'de> for MetadataMapAccess<'a, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        mut seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        if self.next.is_none() {
+            loop {
+                match self.inner.next_key_seed(KeySeed {
+                    inner: seed,
+                    metadata: self.metadata,
+                })? {
+                    Some(Ok(key)) => return Ok(Some(key)),
+                    Some(Err(unused)) => {
+                        self.inner.next_value::<IgnoredAny>()?;
+                        seed = unused;
+                    }
+                    None => break,
+                }
+            }
+            self.next = Some(0);
+        }
+
+        let i = self.next.unwrap();
+        let Some((name, value)) = self.metadata.get(i) else {
+            return Ok(None);
+        };
+        self.next = Some(i + 1);
+        self.value = Some(value);
+        seed.deserialize(name.as_str().into_deserializer())
+            .map(Some)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, A::Error> {
+        match self.value.take() {
+            Some(MetadataValue::Json(value)) => {
+                seed.deserialize(value.clone()).map_err(de::Error::custom)
+            }
+            Some(MetadataValue::Timestamp(t)) => seed.deserialize(MetadataTimestamp {
+                nanos: to_nanos(*t) as u64,
+                _e: PhantomData,
+            }),
+            None => self.inner.next_value_seed(seed),
+        }
+    }
+}
+
+/// Reads the key of a payload entry, handing the seed back instead of using it if the key is
+/// the name of a metadata field
+struct KeySeed<'a, K> {
+    inner: K,
+    metadata: &'a Metadata,
+}
+
+impl<'de, 'a, K: DeserializeSeed<'de>> DeserializeSeed<'de> for KeySeed<'a, K> {
+    type Value = Result<K::Value, K>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, 'a, K: DeserializeSeed<'de>> Visitor<'de> for KeySeed<'a, K> {
+    type Value = Result<K::Value, K>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        if self.metadata.iter().any(|(name, _)| name == v) {
+            return Ok(Err(self.inner));
+        }
+        self.inner.deserialize(v.into_deserializer()).map(Ok)
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        if self.metadata.iter().any(|(name, _)| name == v) {
+            return Ok(Err(self.inner));
+        }
+        self.inner
+            .deserialize(de::value::BorrowedStrDeserializer::new(v))
+            .map(Ok)
+    }
+}
+
+/// Presents a metadata timestamp to the timestamp visitors as a newtype struct holding
+/// nanoseconds since the epoch. Payloads are only ever read as strings, numbers and maps by
+/// those visitors, so this can't be confused with a value of the payload.
+struct MetadataTimestamp<E> {
+    nanos: u64,
+    _e: PhantomData<E>,
+}
+
+impl<'de, E: de::Error> Deserializer<'de> for MetadataTimestamp<E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_newtype_struct(self.nanos.into_deserializer())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetadataValue, WithMetadata};
+    use arroyo_types::from_micros;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::time::SystemTime;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Record {
+        value: String,
+        offset: i64,
+        #[serde(with = "crate::formats::timestamp_as_rfc3339")]
+        timestamp: SystemTime,
+        #[serde(default)]
+        #[serde(with = "crate::formats::opt_timestamp_as_millis")]
+        received: Option<SystemTime>,
+    }
+
+    #[test]
+    fn test_with_metadata() {
+        // metadata timestamps keep their precision, whatever the encoding of the field
+        let t = from_micros(1_696_000_000_000_001);
+        let metadata = vec![
+            ("offset".to_string(), MetadataValue::Json(json!(5))),
+            ("timestamp".to_string(), MetadataValue::Timestamp(t)),
+            ("received".to_string(), MetadataValue::Timestamp(t)),
+        ];
+
+        // payload fields with the name of a metadata field are replaced
+        let mut deserializer =
+            serde_json::Deserializer::from_str(r#"{"offset": "not an offset", "value": "a"}"#);
+        let record = Record::deserialize(WithMetadata::new(&mut deserializer, &metadata)).unwrap();
+
+        assert_eq!(
+            record,
+            Record {
+                value: "a".to_string(),
+                offset: 5,
+                timestamp: t,
+                received: Some(t),
+            }
+        );
+    }
+
+    #[test]
+    fn test_payload_maps_are_not_timestamps() {
+        let result: Result<Record, _> = serde_json::from_value(json!({
+            "value": "a",
+            "offset": 1,
+            "timestamp": { "__arroyo_timestamp_nanos": 0 },
+        }));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,937 @@
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+use apache_avro::Schema;
+use arrow::datatypes::{Field, Fields};
+use arroyo_rpc::schema_resolver::{ConfluentSchemaType, FailingSchemaResolver, SchemaResolver};
+use arroyo_rpc::types::{Format, JsonEnvelope, JsonFormat};
+use arroyo_types::{Data, Key, UserError};
+use serde::de::value::{Error as DeError, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Deserializer};
+use serde_json::{json, Map, Value};
+use smallvec::{smallvec, SmallVec};
+
+use crate::engine::Context;
+use crate::SchemaData;
+
+use self::metadata::{Metadata, WithMetadata};
+
+pub mod avro;
+pub mod cdc;
+pub mod csv;
+pub mod fields;
+pub mod metadata;
+pub mod protobuf;
+pub mod timestamps;
+
+// Confluent wire format: a zero magic byte followed by a 4-byte big-endian schema id
+const CONFLUENT_MAGIC_BYTE: u8 = 0;
+const CONFLUENT_HEADER_LEN: usize = 5;
+
+pub fn confluent_header(schema_id: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(CONFLUENT_HEADER_LEN);
+    buf.push(CONFLUENT_MAGIC_BYTE);
+    buf.extend(schema_id.to_be_bytes());
+    buf
+}
+
+pub fn parse_confluent_header(msg: &[u8]) -> Result<(u32, &[u8]), String> {
+    if msg.len() < CONFLUENT_HEADER_LEN || msg[0] != CONFLUENT_MAGIC_BYTE {
+        return Err(
+            "Message is not in the Confluent Schema Registry wire format (missing magic byte)"
+                .to_string(),
+        );
+    }
+
+    let id = u32::from_be_bytes(msg[1..CONFLUENT_HEADER_LEN].try_into().unwrap());
+    Ok((id, &msg[CONFLUENT_HEADER_LEN..]))
+}
+
+fn deserialize_slice_json<T: DeserializeOwned>(
+    format: &JsonFormat,
+    msg: &[u8],
+    metadata: &Metadata,
+) -> Result<T, String> {
+    let msg = if format.confluent_schema_registry {
+        parse_confluent_header(msg)?.1
+    } else {
+        msg
+    };
+
+    if format.unstructured {
+        let j = if format.include_schema {
+            // we need to deserialize it to pull out the payload
+            let v: Value = serde_json::from_slice(&msg)
+                .map_err(|e| format!("Failed to deserialize json: {:?}", e))?;
+            let payload = v.get("payload").ok_or_else(|| {
+                "`include_schema` set to true, but record does not have a payload field".to_string()
+            })?;
+
+            json! {
+                { "value": serde_json::to_string(payload).unwrap() }
+            }
+        } else {
+            json! {
+                { "value": String::from_utf8_lossy(msg) }
+            }
+        };
+
+        // TODO: this is inefficient, because we know that T is RawJson in this case and can much more directly
+        //  produce that value. However, without specialization I don't know how to get the compiler to emit
+        //  the optimized code for that case.
+        T::deserialize(WithMetadata::new(j, metadata))
+            .map_err(|e| format!("Failed to deserialize JSON into schema: {:?}", e))
+    } else {
+        let mut deserializer = serde_json::Deserializer::from_slice(msg);
+        T::deserialize(WithMetadata::new(&mut deserializer, metadata))
+            .and_then(|t| deserializer.end().map(|_| t))
+            .map_err(|e| format!("Failed to deserialize JSON into schema: {:?}", e))
+    }
+}
+
+fn deserialize_slice_envelope<T: DeserializeOwned>(
+    format: &JsonFormat,
+    envelope: JsonEnvelope,
+    msg: &[u8],
+    metadata: &Metadata,
+) -> Result<Vec<T>, String> {
+    let msg = if format.confluent_schema_registry {
+        parse_confluent_header(msg)?.1
+    } else {
+        msg
+    };
+
+    let value: Value =
+        serde_json::from_slice(msg).map_err(|e| format!("Failed to deserialize json: {:?}", e))?;
+
+    cdc::to_debezium(envelope, value)?
+        .into_iter()
+        .map(|v| {
+            T::deserialize(WithMetadata::new(v, metadata))
+                .map_err(|e| format!("Failed to deserialize JSON into schema: {:?}", e))
+        })
+        .collect()
+}
+
+fn deserialize_raw_string<T: DeserializeOwned>(
+    msg: &[u8],
+    metadata: &Metadata,
+) -> Result<T, String> {
+    let json = json! {
+        { "value": String::from_utf8_lossy(msg) }
+    };
+    T::deserialize(WithMetadata::new(json, metadata))
+        .map_err(|e| format!("Failed to deserialize raw string into schema: {}", e))
+}
+
+fn deserialize_raw_bytes<T: DeserializeOwned>(
+    msg: &[u8],
+    metadata: &Metadata,
+) -> Result<T, String> {
+    T::deserialize(WithMetadata::new(
+        MapDeserializer::<_, DeError>::new(std::iter::once(("value", BytesDeserializer(msg)))),
+        metadata,
+    ))
+    .map_err(|e| format!("Failed to deserialize raw bytes into schema: {}", e))
+}
+
+/// Deserializes a message as the byte array field of a raw_bytes table, which may be nullable
+struct BytesDeserializer<'a>(&'a [u8]);
+
+impl<'de, 'a> IntoDeserializer<'de, DeError> for BytesDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for BytesDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_seq(SeqDeserializer::new(self.0.iter().copied()))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+pub struct DataDeserializer<T: DeserializeOwned> {
+    format: Arc<Format>,
+    avro: Option<Arc<avro::AvroDecoder>>,
+    protobuf: Option<Arc<protobuf::ProtobufCodec>>,
+    csv: Option<Arc<csv::CsvCodec>>,
+    _t: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Clone for DataDeserializer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            format: self.format.clone(),
+            avro: self.avro.clone(),
+            protobuf: self.protobuf.clone(),
+            csv: self.csv.clone(),
+            _t: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> DataDeserializer<T> {
+    pub fn new(format: Format) -> Self {
+        Self::with_schema_resolver(format, Arc::new(FailingSchemaResolver::new()))
+    }
+
+    pub fn with_schema_resolver(
+        format: Format,
+        schema_resolver: Arc<dyn SchemaResolver + Sync>,
+    ) -> Self {
+        let avro = match &format {
+            Format::Avro(avro) => Some(Arc::new(avro::AvroDecoder::new(avro, schema_resolver))),
+            _ => None,
+        };
+
+        let protobuf = match &format {
+            Format::Protobuf(protobuf) => Some(Arc::new(
+                protobuf::ProtobufCodec::new(protobuf).expect("invalid protobuf schema for table"),
+            )),
+            _ => None,
+        };
+
+        let csv = match &format {
+            Format::Csv(csv) => Some(Arc::new(csv::CsvCodec::new(csv))),
+            _ => None,
+        };
+
+        Self {
+            format: Arc::new(format),
+            avro,
+            protobuf,
+            csv,
+            _t: PhantomData,
+        }
+    }
+
+    /// Deserializes a message into records. Most formats produce exactly one record per message,
+    /// but change data capture envelopes may contain several changes, or none.
+    pub async fn deserialize_slice(&self, msg: &[u8]) -> Result<SmallVec<[T; 1]>, UserError> {
+        self.deserialize_slice_with_metadata(msg, &[]).await
+    }
+
+    /// Deserializes a message into records like `deserialize_slice`, and sets the given fields
+    /// (which don't come from the message payload, like Kafka offsets) on each of them
+    pub async fn deserialize_slice_with_metadata(
+        &self,
+        msg: &[u8],
+        metadata: &Metadata,
+    ) -> Result<SmallVec<[T; 1]>, UserError> {
+        self.deserialize_slice_as(msg, metadata)
+            .await
+            .map_err(|e| deserialization_error(msg, e))
+    }
+
+    async fn deserialize_slice_as(
+        &self,
+        msg: &[u8],
+        metadata: &Metadata,
+    ) -> Result<SmallVec<[T; 1]>, String> {
+        match &*self.format {
+            Format::Json(
+                json @ JsonFormat {
+                    envelope: Some(envelope),
+                    ..
+                },
+            ) => deserialize_slice_envelope(json, *envelope, msg, metadata).map(SmallVec::from_vec),
+            Format::Json(json) => deserialize_slice_json(json, msg, metadata).map(|t| smallvec![t]),
+            Format::Avro(_) => self
+                .avro
+                .as_ref()
+                .unwrap()
+                .deserialize(msg, metadata)
+                .await
+                .map(|t| smallvec![t]),
+            Format::Protobuf(_) => self
+                .protobuf
+                .as_ref()
+                .unwrap()
+                .deserialize(msg, metadata)
+                .map(|t| smallvec![t]),
+            Format::Csv(_) => self
+                .csv
+                .as_ref()
+                .unwrap()
+                .deserialize(msg, metadata)
+                .map(|t| smallvec![t]),
+            Format::Parquet(_) => todo!(),
+            Format::RawString(_) => deserialize_raw_string(msg, metadata).map(|t| smallvec![t]),
+            Format::RawBytes(_) => deserialize_raw_bytes(msg, metadata).map(|t| smallvec![t]),
+        }
+    }
+}
+
+fn deserialization_error(msg: &[u8], e: String) -> UserError {
+    UserError::new(
+        "Deserialization failed",
+        format!(
+            "Failed to deserialize: '{}': {}",
+            String::from_utf8_lossy(msg),
+            e
+        ),
+    )
+}
+
+pub struct DataSerializer<T: SchemaData> {
+    kafka_schema: Value,
+    json_schema: Value,
+    avro_schema: Option<Schema>,
+    protobuf: Option<protobuf::ProtobufCodec>,
+    csv: Option<csv::CsvCodec>,
+    schema_id: Option<u32>,
+    format: Format,
+    _t: PhantomData<T>,
+}
+
+impl<T: SchemaData> DataSerializer<T> {
+    pub fn new(format: Format) -> Self {
+        // schemas are validated when the table is created, so these only fail for pipelines that
+        // were planned without those checks
+        let avro_schema = match &format {
+            Format::Avro(avro) => Some(match &avro.schema {
+                Some(s) => Schema::parse_str(s).expect("invalid avro schema for sink"),
+                None => avro::arrow_to_avro_schema(T::name(), T::schema().fields())
+                    .unwrap_or_else(|e| panic!("{}", e)),
+            }),
+            _ => None,
+        };
+
+        let protobuf = match &format {
+            Format::Protobuf(protobuf) => Some(
+                protobuf::ProtobufCodec::new(protobuf).expect("invalid protobuf schema for sink"),
+            ),
+            _ => None,
+        };
+
+        let csv = match &format {
+            Format::Csv(csv) => Some(csv::CsvCodec::new(csv)),
+            _ => None,
+        };
+
+        Self {
+            kafka_schema: arrow_to_kafka_json(T::name(), T::schema().fields()),
+            json_schema: arrow_to_json_schema(T::schema().fields()),
+            avro_schema,
+            protobuf,
+            csv,
+            schema_id: None,
+            format,
+            _t: PhantomData,
+        }
+    }
+
+    /// Returns the schema that needs to be registered with the Confluent Schema Registry before
+    /// records can be written, if the format requires one
+    pub fn confluent_schema(&self) -> Option<(String, ConfluentSchemaType)> {
+        match &self.format {
+            Format::Json(json) if json.confluent_schema_registry => Some((
+                serde_json::to_string(&self.json_schema).unwrap(),
+                ConfluentSchemaType::Json,
+            )),
+            Format::Avro(avro) if avro.confluent_schema_registry => Some((
+                serde_json::to_string(self.avro_schema.as_ref().unwrap()).unwrap(),
+                ConfluentSchemaType::Avro,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Sets the id of the registered schema, which will be written into the header of each record
+    pub fn set_schema_id(&mut self, schema_id: u32) {
+        self.schema_id = Some(schema_id);
+    }
+
+    /// Serializes a record, returning None if there is nothing to write (like a raw_string record
+    /// with a null value), and an error if the record can't be encoded in the format
+    pub fn to_vec(&self, record: &T) -> Result<Option<Vec<u8>>, UserError> {
+        Ok(match &self.format {
+            Format::Json(json) => {
+                let mut v = if json.confluent_schema_registry {
+                    confluent_header(
+                        self.schema_id
+                            .expect("schema must be registered before writing json records"),
+                    )
+                } else {
+                    vec![]
+                };
+
+                if json.include_schema {
+                    let record = json! {{
+                        "schema": self.kafka_schema,
+                        "payload": record
+                    }};
+
+                    serde_json::to_writer(&mut v, &record).unwrap();
+                } else if let Some(envelope) = json.envelope {
+                    let record = serde_json::to_value(record)
+                        .map_err(|e| e.to_string())
+                        .and_then(|record| cdc::from_debezium(envelope, record))
+                        .map_err(serialization_error)?;
+                    serde_json::to_writer(&mut v, &record).unwrap();
+                } else {
+                    serde_json::to_writer(&mut v, record).unwrap();
+                }
+
+                Some(v)
+            }
+            Format::Avro(avro) => {
+                let mut buf = if avro.confluent_schema_registry {
+                    confluent_header(
+                        self.schema_id
+                            .expect("schema must be registered before writing avro records"),
+                    )
+                } else {
+                    vec![]
+                };
+
+                let datum = avro::serialize_avro(self.avro_schema.as_ref().unwrap(), record)
+                    .map_err(serialization_error)?;
+                buf.extend(datum);
+
+                Some(buf)
+            }
+            Format::Protobuf(_) => Some(
+                self.protobuf
+                    .as_ref()
+                    .unwrap()
+                    .serialize(record)
+                    .map_err(serialization_error)?,
+            ),
+            Format::Csv(_) => Some(
+                self.csv
+                    .as_ref()
+                    .unwrap()
+                    .serialize(record)
+                    .map_err(serialization_error)?,
+            ),
+            Format::Parquet(_) => todo!(),
+            Format::RawString(_) => record.to_raw_string(),
+            Format::RawBytes(_) => record.to_raw_bytes(),
+        })
+    }
+
+    /// Serializes a record like `to_vec`, but reports records that can't be encoded as errors
+    /// and skips them, rather than failing the job
+    pub async fn to_vec_or_report<K: Key, D: Data>(
+        &self,
+        record: &T,
+        ctx: &mut Context<K, D>,
+    ) -> Option<Vec<u8>> {
+        match self.to_vec(record) {
+            Ok(v) => v,
+            Err(e) => {
+                ctx.report_user_error(e).await;
+                None
+            }
+        }
+    }
+}
+
+fn serialization_error(e: String) -> UserError {
+    UserError::new("Serialization failed", e)
+}
+
+#[derive(Debug)]
+pub struct MilliSecondsSystemTimeVisitor;
+
+// Custom datetime serializer for use with Debezium JSON
+pub mod timestamp_as_millis {
+    use std::{fmt, time::SystemTime};
+
+    use arroyo_types::{from_millis, to_millis};
+    use serde::{de, Deserializer, Serializer};
+
+    use super::timestamps::visit_metadata_timestamp;
+    use super::MilliSecondsSystemTimeVisitor;
+
+    pub fn serialize<S>(t: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(to_millis(*t))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(MilliSecondsSystemTimeVisitor)
+    }
+
+    impl<'de> de::Visitor<'de> for MilliSecondsSystemTimeVisitor {
+        type Value = SystemTime;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a unix timestamp in milliseconds")
+        }
+
+        /// Deserialize a timestamp in milliseconds since the epoch
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            if value >= 0 {
+                Ok(from_millis(value as u64))
+            } else {
+                Err(serde::de::Error::custom("millis must be positive"))
+            }
+        }
+
+        /// Deserialize a timestamp in milliseconds since the epoch
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(from_millis(value))
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            visit_metadata_timestamp(deserializer)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OptMilliSecondsSystemTimeVisitor;
+
+// Custom datetime serializer for use with Debezium JSON
+pub mod opt_timestamp_as_millis {
+    use std::{fmt, time::SystemTime};
+
+    use arroyo_types::to_millis;
+    use serde::{de, Deserializer, Serializer};
+
+    use super::{MilliSecondsSystemTimeVisitor, OptMilliSecondsSystemTimeVisitor};
+
+    pub fn serialize<S>(t: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some(t) = t {
+            serializer.serialize_some(&to_millis(*t))
+        } else {
+            serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptMilliSecondsSystemTimeVisitor)
+    }
+
+    impl<'de> de::Visitor<'de> for OptMilliSecondsSystemTimeVisitor {
+        type Value = Option<SystemTime>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a unix timestamp in milliseconds")
+        }
+
+        /// Deserialize a timestamp in milliseconds since the epoch
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Ok(Some(
+                deserializer.deserialize_any(MilliSecondsSystemTimeVisitor)?,
+            ))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+    }
+}
+
+// Custom deserializer for fields encoded as RFC3339 date time strings, relying on Chrono's deserialization
+// capabilities (note we can't use chrono::DateTime as the field type currently, because all times in SQL-land
+// currently need to be SystemTime)
+pub mod timestamp_as_rfc3339 {
+    use std::{fmt, time::SystemTime};
+
+    use arroyo_types::from_nanos;
+    use chrono::{DateTime, Utc};
+    use serde::{de, Deserializer, Serializer};
+
+    use super::timestamps::visit_metadata_timestamp;
+
+    pub fn serialize<S>(t: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let dt: DateTime<Utc> = (*t).into();
+        serializer.serialize_str(&dt.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(Rfc3339SystemTimeVisitor)
+    }
+
+    pub(super) struct Rfc3339SystemTimeVisitor;
+
+    impl<'de> de::Visitor<'de> for Rfc3339SystemTimeVisitor {
+        type Value = SystemTime;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an RFC3339 timestamp")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let raw: DateTime<Utc> = value.parse().map_err(E::custom)?;
+            Ok(from_nanos(raw.timestamp_nanos() as u128))
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            visit_metadata_timestamp(deserializer)
+        }
+    }
+}
+
+pub mod opt_timestamp_as_rfc3339 {
+    use std::{fmt, time::SystemTime};
+
+    use chrono::{DateTime, Utc};
+    use serde::{de, Deserializer, Serializer};
+
+    use super::timestamp_as_rfc3339::Rfc3339SystemTimeVisitor;
+
+    pub fn serialize<S>(t: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some(t) = *t {
+            let dt: DateTime<Utc> = t.into();
+            serializer.serialize_some(&dt.to_rfc3339())
+        } else {
+            serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptRfc3339SystemTimeVisitor)
+    }
+
+    struct OptRfc3339SystemTimeVisitor;
+
+    impl<'de> de::Visitor<'de> for OptRfc3339SystemTimeVisitor {
+        type Value = Option<SystemTime>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an optional RFC3339 timestamp")
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Ok(Some(
+                deserializer.deserialize_any(Rfc3339SystemTimeVisitor)?,
+            ))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+    }
+}
+
+fn field_to_json_schema(field: &Field) -> Value {
+    match field.data_type() {
+        arrow::datatypes::DataType::Null => {
+            json! {{ "type": "null" }}
+        }
+        arrow::datatypes::DataType::Boolean => {
+            json! {{ "type": "boolean" }}
+        }
+        arrow::datatypes::DataType::Int8
+        | arrow::datatypes::DataType::Int16
+        | arrow::datatypes::DataType::Int32
+        | arrow::datatypes::DataType::Int64
+        | arrow::datatypes::DataType::UInt8
+        | arrow::datatypes::DataType::UInt16
+        | arrow::datatypes::DataType::UInt32
+        | arrow::datatypes::DataType::UInt64 => {
+            // TODO: integer bounds
+            json! {{ "type": "integer" }}
+        }
+        arrow::datatypes::DataType::Float16
+        | arrow::datatypes::DataType::Float32
+        | arrow::datatypes::DataType::Float64 => {
+            json! {{ "type": "number" }}
+        }
+        arrow::datatypes::DataType::Timestamp(_, _) => {
+            json! {{ "type": "string", "format": "date-time" }}
+        }
+        arrow::datatypes::DataType::Date32 | arrow::datatypes::DataType::Date64 => {
+            json! {{ "type": "string", "format": "date" }}
+        }
+        arrow::datatypes::DataType::Time32(_) | arrow::datatypes::DataType::Time64(_) => {
+            json! {{ "type": "string", "format": "time" }}
+        }
+        arrow::datatypes::DataType::Duration(_) | arrow::datatypes::DataType::Interval(_) => {
+            // intervals are std::time::Durations, which serde writes as seconds and nanos
+            json! {{
+                "type": "object",
+                "properties": {
+                    "secs": { "type": "integer" },
+                    "nanos": { "type": "integer" },
+                },
+                "required": ["secs", "nanos"],
+            }}
+        }
+        arrow::datatypes::DataType::Binary
+        | arrow::datatypes::DataType::FixedSizeBinary(_)
+        | arrow::datatypes::DataType::LargeBinary => {
+            json! {{ "type": "array", "items": { "type": "integer" }}}
+        }
+        arrow::datatypes::DataType::Utf8 | arrow::datatypes::DataType::LargeUtf8 => {
+            json! {{ "type": "string" }}
+        }
+        arrow::datatypes::DataType::List(t)
+        | arrow::datatypes::DataType::FixedSizeList(t, _)
+        | arrow::datatypes::DataType::LargeList(t) => {
+            json! {{"type": "array", "items": field_to_json_schema(&*t) }}
+        }
+        arrow::datatypes::DataType::Struct(s) => arrow_to_json_schema(s),
+        arrow::datatypes::DataType::Union(fields, _) => {
+            let variants: Vec<_> = fields
+                .iter()
+                .map(|(_, f)| field_to_json_schema(f))
+                .collect();
+            json! {{ "anyOf": variants }}
+        }
+        arrow::datatypes::DataType::Dictionary(_, value) => field_to_json_schema(&Field::new(
+            field.name(),
+            (**value).clone(),
+            field.is_nullable(),
+        )),
+        arrow::datatypes::DataType::Decimal128(_, _)
+        | arrow::datatypes::DataType::Decimal256(_, _) => {
+            json! {{ "type": "number" }}
+        }
+        arrow::datatypes::DataType::Map(entries, _) => match entries.data_type() {
+            arrow::datatypes::DataType::Struct(kv) if kv.len() == 2 => {
+                json! {{ "type": "object", "additionalProperties": field_to_json_schema(&kv[1]) }}
+            }
+            _ => json! {{ "type": "object" }},
+        },
+        arrow::datatypes::DataType::RunEndEncoded(_, values) => field_to_json_schema(values),
+    }
+}
+
+fn arrow_to_json_schema(fields: &Fields) -> Value {
+    let props: HashMap<String, Value> = fields
+        .iter()
+        .map(|f| (f.name().clone(), field_to_json_schema(f)))
+        .collect();
+
+    let required: Vec<String> = fields
+        .iter()
+        .filter(|f| !f.is_nullable())
+        .map(|f| f.name().clone())
+        .collect();
+    json! {{
+        "type": "object",
+        "properties": props,
+        "required": required,
+    }}
+}
+
+fn field_to_kafka_json(field: &Field) -> Value {
+    use arrow::datatypes::DataType::*;
+
+    let typ = match field.data_type() {
+        Null => todo!(),
+        Boolean => "boolean",
+        Int8 | UInt8 => "int8",
+        Int16 | UInt16 => "int16",
+        Int32 | UInt32 => "int32",
+        Int64 | UInt64 => "int64",
+        Float16 | Float32 => "float",
+        Float64 => "double",
+        Utf8 | LargeUtf8 => "string",
+        Binary | FixedSizeBinary(_) | LargeBinary => "bytes",
+        Time32(_) | Time64(_) | Timestamp(_, _) => {
+            // as far as I can tell, this is the only way to get timestamps from Arroyo into
+            // Kafka connect
+            return json! {{
+                "type": "int64",
+                "field": field.name().clone(),
+                "optional": field.is_nullable(),
+                "name": "org.apache.kafka.connect.data.Timestamp"
+            }};
+        }
+        Date32 | Date64 => {
+            return json! {{
+                "type": "int64",
+                "field": field.name().clone(),
+                "optional": field.is_nullable(),
+                "name": "org.apache.kafka.connect.data.Date"
+            }}
+        }
+        Duration(_) => "int64",
+        Interval(_) => "int64",
+        List(t) | FixedSizeList(t, _) | LargeList(t) => {
+            return json! {{
+                "type": "array",
+                "items": field_to_kafka_json(&*t),
+                "field": field.name().clone(),
+                "optional": field.is_nullable(),
+            }};
+        }
+        Struct(s) => {
+            let fields: Vec<_> = s.iter().map(|f| field_to_kafka_json(&*f)).collect();
+            return json! {{
+                "type": "struct",
+                "fields": fields,
+                "field": field.name().clone(),
+                "optional": field.is_nullable(),
+            }};
+        }
+        Union(_, _) => todo!(),
+        Dictionary(_, _) => todo!(),
+        Decimal128(_, _) => todo!(),
+        Decimal256(_, _) => todo!(),
+        Map(_, _) => todo!(),
+        RunEndEncoded(_, _) => todo!(),
+    };
+
+    json! {{
+        "type": typ,
+        "field": field.name().clone(),
+        "optional": field.is_nullable(),
+    }}
+}
+
+// For some reason Kafka uses it's own bespoke almost-but-not-quite JSON schema format
+// https://www.confluent.io/blog/kafka-connect-deep-dive-converters-serialization-explained/#json-schemas
+fn arrow_to_kafka_json(name: &str, fields: &Fields) -> Value {
+    let fields: Vec<_> = fields.iter().map(|f| field_to_kafka_json(&*f)).collect();
+    json! {{
+        "type": "struct",
+        "name": name,
+        "fields": fields,
+        "optional": false,
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arrow_to_json_schema, deserialize_raw_bytes, DataDeserializer};
+    use arrow::datatypes::{DataType, Field, Fields, TimeUnit};
+    use arroyo_rpc::types::{Format, JsonFormat};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[test]
+    fn test_raw_bytes() {
+        #[derive(Deserialize)]
+        struct Raw {
+            value: Vec<u8>,
+        }
+
+        #[derive(Deserialize)]
+        struct NullableRaw {
+            value: Option<Vec<u8>>,
+        }
+
+        let raw: Raw = deserialize_raw_bytes(b"\x00\xffbytes", &[]).unwrap();
+        assert_eq!(raw.value, b"\x00\xffbytes");
+
+        let raw: NullableRaw = deserialize_raw_bytes(b"", &[]).unwrap();
+        assert_eq!(raw.value, Some(vec![]));
+    }
+
+    #[test]
+    fn test_arrow_to_json_schema() {
+        let fields = Fields::from(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("elapsed", DataType::Duration(TimeUnit::Microsecond), true),
+            Field::new(
+                "tags",
+                DataType::List(Field::new("item", DataType::Utf8, true).into()),
+                true,
+            ),
+            Field::new(
+                "user",
+                DataType::Struct(Fields::from(vec![Field::new(
+                    "name",
+                    DataType::Utf8,
+                    false,
+                )])),
+                false,
+            ),
+        ]);
+
+        let schema = arrow_to_json_schema(&fields);
+        assert_eq!(schema["properties"]["id"], json!({ "type": "integer" }));
+        assert_eq!(
+            schema["properties"]["elapsed"]["required"],
+            json!(["secs", "nanos"])
+        );
+        assert_eq!(
+            schema["properties"]["tags"],
+            json!({ "type": "array", "items": { "type": "string" } })
+        );
+        assert_eq!(
+            schema["properties"]["user"]["properties"]["name"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(schema["required"], json!(["id", "user"]));
+    }
+
+    #[tokio::test]
+    async fn test_projected_json() {
+        // sources that only read some of a table's columns skip the rest of each message
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Projected {
+            a: i32,
+            c: i64,
+        }
+
+        let deserializer: DataDeserializer<Projected> =
+            DataDeserializer::new(Format::Json(JsonFormat::default()));
+        let records = deserializer
+            .deserialize_slice(br#"{"a": 1, "b": {"nested": [1, 2]}, "c": 2, "d": 1.5}"#)
+            .await
+            .unwrap();
+        assert_eq!(records.into_vec(), vec![Projected { a: 1, c: 2 }]);
+    }
+}
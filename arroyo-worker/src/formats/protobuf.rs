@@ -0,0 +1,195 @@
+use arroyo_rpc::protobuf::get_message;
+use arroyo_rpc::types::ProtobufFormat;
+use prost::Message;
+use prost_reflect::{
+    DeserializeOptions, DynamicMessage, FieldDescriptor, Kind, MessageDescriptor, SerializeOptions,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::metadata::{Metadata, WithMetadata};
+
+/// Converts between protobuf messages and the generated structs. As with Avro, this goes by way
+/// of JSON so that the structs can use the same serde annotations as the JSON format.
+///
+/// Repeated and map fields are represented in SQL as JSON-encoded text, so they are converted
+/// to and from strings here.
+pub struct ProtobufCodec {
+    descriptor: MessageDescriptor,
+}
+
+impl ProtobufCodec {
+    pub fn new(format: &ProtobufFormat) -> Result<Self, String> {
+        let compiled = format
+            .compiled_schema
+            .as_ref()
+            .ok_or_else(|| "Protobuf tables must have a compiled schema".to_string())?;
+
+        let descriptor = get_message(compiled, format.message_name.as_deref())?;
+
+        Ok(Self { descriptor })
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(
+        &self,
+        msg: &[u8],
+        metadata: &Metadata,
+    ) -> Result<T, String> {
+        let message = DynamicMessage::decode(self.descriptor.clone(), msg)
+            .map_err(|e| format!("Failed to decode protobuf message: {:?}", e))?;
+
+        let options = SerializeOptions::new()
+            .use_proto_field_name(true)
+            .stringify_64_bit_integers(false)
+            .skip_default_fields(false);
+
+        let mut value = message
+            .serialize_with_options(serde_json::value::Serializer, &options)
+            .map_err(|e| format!("Failed to convert protobuf message: {:?}", e))?;
+
+        stringify_collections(&self.descriptor, &mut value);
+
+        T::deserialize(WithMetadata::new(value, metadata)).map_err(|e| {
+            format!(
+                "Failed to deserialize protobuf message into schema: {:?}",
+                e
+            )
+        })
+    }
+
+    pub fn serialize<T: Serialize>(&self, record: &T) -> Result<Vec<u8>, String> {
+        let mut value = serde_json::to_value(record).unwrap();
+        parse_collections(&self.descriptor, &mut value)?;
+
+        let message = DynamicMessage::deserialize_with_options(
+            self.descriptor.clone(),
+            value,
+            &DeserializeOptions::new().deny_unknown_fields(false),
+        )
+        .map_err(|e| format!("Failed to convert record to protobuf: {:?}", e))?;
+
+        Ok(message.encode_to_vec())
+    }
+}
+
+fn is_collection(field: &FieldDescriptor) -> bool {
+    field.is_list() || field.is_map()
+}
+
+fn stringify_collections(descriptor: &MessageDescriptor, value: &mut Value) {
+    let Value::Object(obj) = value else {
+        return;
+    };
+
+    for field in descriptor.fields() {
+        let Some(v) = obj.get_mut(field.name()) else {
+            continue;
+        };
+
+        if is_collection(&field) {
+            *v = Value::String(v.to_string());
+        } else if let Kind::Message(m) = field.kind() {
+            stringify_collections(&m, v);
+        }
+    }
+}
+
+fn parse_collections(descriptor: &MessageDescriptor, value: &mut Value) -> Result<(), String> {
+    let Value::Object(obj) = value else {
+        return Ok(());
+    };
+
+    for field in descriptor.fields() {
+        let Some(v) = obj.get_mut(field.name()) else {
+            continue;
+        };
+
+        if is_collection(&field) {
+            if let Value::String(s) = v {
+                *v = serde_json::from_str(s).map_err(|e| {
+                    format!(
+                        "Field '{}' does not contain valid JSON: {:?}",
+                        field.name(),
+                        e
+                    )
+                })?;
+            }
+        } else if let Kind::Message(m) = field.kind() {
+            parse_collections(&m, v)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arroyo_rpc::types::ProtobufFormat;
+    use prost::Message;
+    use prost_types::{
+        field_descriptor_proto::{Label, Type},
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    };
+    use serde::{Deserialize, Serialize};
+
+    use super::ProtobufCodec;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestData {
+        id: i64,
+        name: String,
+        tags: String,
+    }
+
+    fn field(name: &str, number: i32, typ: Type, label: Label) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            r#type: Some(typ as i32),
+            label: Some(label as i32),
+            json_name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn descriptor_set() -> Vec<u8> {
+        FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("test.proto".to_string()),
+                package: Some("test".to_string()),
+                syntax: Some("proto3".to_string()),
+                message_type: vec![DescriptorProto {
+                    name: Some("TestData".to_string()),
+                    field: vec![
+                        field("id", 1, Type::Int64, Label::Optional),
+                        field("name", 2, Type::String, Label::Optional),
+                        field("tags", 3, Type::String, Label::Repeated),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+        .encode_to_vec()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let codec = ProtobufCodec::new(&ProtobufFormat {
+            message_name: None,
+            compiled_schema: Some(descriptor_set()),
+        })
+        .unwrap();
+
+        let data = TestData {
+            id: 9_000_000_000,
+            name: "hello".to_string(),
+            tags: r#"["a","b"]"#.to_string(),
+        };
+
+        let bytes = codec.serialize(&data).unwrap();
+        let result: TestData = codec.deserialize(&bytes, &[]).unwrap();
+        assert_eq!(data, result);
+    }
+}
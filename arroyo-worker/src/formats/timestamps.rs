@@ -0,0 +1,316 @@
+use std::{fmt, marker::PhantomData, time::SystemTime};
+
+use arroyo_types::{from_nanos, to_nanos};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+const ISO8601_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// Reads a metadata timestamp, which `WithMetadata` passes to timestamp fields as a newtype
+/// struct holding nanoseconds since the epoch
+pub(crate) fn visit_metadata_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<SystemTime, D::Error> {
+    Ok(from_nanos(u64::deserialize(deserializer)? as u128))
+}
+
+/// The timestamp formats that aren't covered by the `timestamp_as_millis` and
+/// `timestamp_as_rfc3339` modules. Unlike `TimestampFormat`, this can be constructed in a const
+/// context, which allows generated structs to refer to it from their serde attributes via a type
+/// implementing [`TimestampFormatter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampEncoding {
+    UnixSeconds,
+    UnixMicros,
+    UnixNanos,
+    Iso8601,
+    Pattern {
+        pattern: &'static str,
+        timezone: Option<&'static str>,
+    },
+}
+
+impl TimestampEncoding {
+    fn nanos_per_unit(&self) -> Option<i128> {
+        match self {
+            TimestampEncoding::UnixSeconds => Some(1_000_000_000),
+            TimestampEncoding::UnixMicros => Some(1_000),
+            TimestampEncoding::UnixNanos => Some(1),
+            TimestampEncoding::Iso8601 | TimestampEncoding::Pattern { .. } => None,
+        }
+    }
+
+    fn timezone(timezone: Option<&str>) -> Result<Tz, String> {
+        timezone
+            .unwrap_or("UTC")
+            .parse()
+            .map_err(|_| format!("invalid timezone '{}'", timezone.unwrap_or_default()))
+    }
+
+    fn checked_from_nanos(nanos: i128) -> Result<SystemTime, String> {
+        if nanos < 0 {
+            return Err("timestamps before the unix epoch are not supported".to_string());
+        }
+        Ok(from_nanos(nanos as u128))
+    }
+
+    pub fn parse_int(&self, value: i128) -> Result<SystemTime, String> {
+        let n = self
+            .nanos_per_unit()
+            .ok_or_else(|| format!("expected a timestamp string, but found {}", value))?;
+        Self::checked_from_nanos(value * n)
+    }
+
+    pub fn parse_float(&self, value: f64) -> Result<SystemTime, String> {
+        let n = self
+            .nanos_per_unit()
+            .ok_or_else(|| format!("expected a timestamp string, but found {}", value))?;
+        // the whole and fractional parts are converted separately to avoid losing precision
+        Self::checked_from_nanos(
+            value.trunc() as i128 * n + (value.fract() * n as f64).round() as i128,
+        )
+    }
+
+    pub fn parse_str(&self, value: &str) -> Result<SystemTime, String> {
+        match self {
+            TimestampEncoding::UnixSeconds
+            | TimestampEncoding::UnixMicros
+            | TimestampEncoding::UnixNanos => {
+                let value = value.trim();
+                if let Ok(i) = value.parse::<i128>() {
+                    self.parse_int(i)
+                } else if let Ok(f) = value.parse::<f64>() {
+                    self.parse_float(f)
+                } else {
+                    Err(format!("'{}' is not a valid unix timestamp", value))
+                }
+            }
+            TimestampEncoding::Iso8601 => NaiveDateTime::parse_from_str(value, ISO8601_FORMAT)
+                .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f"))
+                .map(|t| Utc.from_utc_datetime(&t).into())
+                .map_err(|e| format!("'{}' is not a valid ISO-8601 timestamp: {}", value, e)),
+            TimestampEncoding::Pattern { pattern, timezone } => {
+                // patterns that include an offset fully determine the time
+                if let Ok(t) = DateTime::parse_from_str(value, pattern) {
+                    return Ok(t.into());
+                }
+
+                let naive = NaiveDateTime::parse_from_str(value, pattern)
+                    .or_else(|e| {
+                        // date-only patterns are interpreted as midnight
+                        NaiveDate::parse_from_str(value, pattern)
+                            .map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+                            .map_err(|_| e)
+                    })
+                    .map_err(|e| {
+                        format!(
+                            "'{}' does not match timestamp pattern '{}': {}",
+                            value, pattern, e
+                        )
+                    })?;
+
+                let tz = Self::timezone(*timezone)?;
+                tz.from_local_datetime(&naive)
+                    .earliest()
+                    .map(|t| t.into())
+                    .ok_or_else(|| format!("'{}' is not a valid time in {}", value, tz))
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        &self,
+        t: SystemTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if let Some(n) = self.nanos_per_unit() {
+            return serializer.serialize_u64((to_nanos(t) / n as u128) as u64);
+        }
+
+        let dt: DateTime<Utc> = t.into();
+        match self {
+            TimestampEncoding::Pattern { pattern, timezone } => {
+                let tz = Self::timezone(*timezone).map_err(serde::ser::Error::custom)?;
+                serializer.collect_str(&dt.with_timezone(&tz).format(pattern))
+            }
+            _ => serializer.collect_str(&dt.naive_utc().format(ISO8601_FORMAT)),
+        }
+    }
+}
+
+struct Encoded(SystemTime, TimestampEncoding);
+
+impl Serialize for Encoded {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.1.serialize(self.0, serializer)
+    }
+}
+
+/// Implemented by the generated code for each struct with non-default timestamp fields
+pub trait TimestampFormatter {
+    const ENCODING: TimestampEncoding;
+}
+
+struct TimestampVisitor(TimestampEncoding);
+
+impl<'de> de::Visitor<'de> for TimestampVisitor {
+    type Value = SystemTime;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a timestamp encoded as {:?}", self.0)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        self.0.parse_int(v as i128).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        self.0.parse_int(v as i128).map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        self.0.parse_float(v).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.0.parse_str(v).map_err(E::custom)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        visit_metadata_timestamp(deserializer)
+    }
+}
+
+struct OptTimestampVisitor(TimestampEncoding);
+
+impl<'de> de::Visitor<'de> for OptTimestampVisitor {
+    type Value = Option<SystemTime>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an optional timestamp encoded as {:?}", self.0)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Ok(Some(
+            deserializer.deserialize_any(TimestampVisitor(self.0))?,
+        ))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+}
+
+/// For use as `#[serde(with = "TimestampAs::<F>")]`, where `F` determines the encoding
+pub struct TimestampAs<F: TimestampFormatter>(PhantomData<F>);
+
+impl<F: TimestampFormatter> TimestampAs<F> {
+    pub fn serialize<S: Serializer>(t: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        F::ENCODING.serialize(*t, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        deserializer.deserialize_any(TimestampVisitor(F::ENCODING))
+    }
+}
+
+/// For use as `#[serde(with = "OptTimestampAs::<F>")]` on nullable fields
+pub struct OptTimestampAs<F: TimestampFormatter>(PhantomData<F>);
+
+impl<F: TimestampFormatter> OptTimestampAs<F> {
+    pub fn serialize<S: Serializer>(
+        t: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match t {
+            Some(t) => serializer.serialize_some(&Encoded(*t, F::ENCODING)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        deserializer.deserialize_option(OptTimestampVisitor(F::ENCODING))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use arroyo_types::{from_micros, from_millis};
+    use serde::{Deserialize, Serialize};
+
+    use super::{OptTimestampAs, TimestampAs, TimestampEncoding, TimestampFormatter};
+
+    struct Seconds;
+    impl TimestampFormatter for Seconds {
+        const ENCODING: TimestampEncoding = TimestampEncoding::UnixSeconds;
+    }
+
+    struct Pattern;
+    impl TimestampFormatter for Pattern {
+        const ENCODING: TimestampEncoding = TimestampEncoding::Pattern {
+            pattern: "%d/%m/%Y %H:%M",
+            timezone: Some("Europe/Berlin"),
+        };
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestData {
+        #[serde(with = "TimestampAs::<Seconds>")]
+        seconds: SystemTime,
+        #[serde(default)]
+        #[serde(with = "OptTimestampAs::<Pattern>")]
+        local: Option<SystemTime>,
+    }
+
+    #[test]
+    fn test_unix() {
+        let data: TestData = serde_json::from_str(r#"{"seconds": 1696000000.25}"#).unwrap();
+        assert_eq!(data.seconds, from_millis(1_696_000_000_250));
+        assert_eq!(data.local, None);
+
+        let data: TestData = serde_json::from_str(r#"{"seconds": "1696000000"}"#).unwrap();
+        assert_eq!(data.seconds, from_millis(1_696_000_000_000));
+
+        assert_eq!(
+            TimestampEncoding::UnixMicros.parse_int(1_696_000_000_000_001),
+            Ok(from_micros(1_696_000_000_000_001))
+        );
+    }
+
+    #[test]
+    fn test_pattern() {
+        let data: TestData =
+            serde_json::from_str(r#"{"seconds": 0, "local": "01/07/2023 14:30"}"#).unwrap();
+        // Berlin is UTC+2 in the summer
+        assert_eq!(data.local, Some(from_millis(1_688_214_600_000)));
+
+        assert_eq!(
+            serde_json::to_string(&data).unwrap(),
+            r#"{"seconds":0,"local":"01/07/2023 14:30"}"#
+        );
+    }
+
+    #[test]
+    fn test_iso8601() {
+        assert_eq!(
+            TimestampEncoding::Iso8601.parse_str("2023-07-01T12:30:00.5"),
+            Ok(from_millis(1_688_214_600_500))
+        );
+        assert_eq!(
+            TimestampEncoding::Iso8601.parse_str("2023-07-01 12:30:00"),
+            Ok(from_millis(1_688_214_600_000))
+        );
+    }
+}
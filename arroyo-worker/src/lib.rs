@@ -70,6 +70,12 @@ pub trait SchemaData: Data + Serialize + DeserializeOwned {
     /// a None value, and should panic if they do not support raw strings (which
     /// indicates a miscompilation).
     fn to_raw_string(&self) -> Option<Vec<u8>>;
+
+    /// Returns the raw bytes of this data, for types with a single binary field
+    ///
+    /// As with `to_raw_string`, implementations should return None for a None value and
+    /// panic if they do not support raw bytes.
+    fn to_raw_bytes(&self) -> Option<Vec<u8>>;
 }
 
 impl<T: SchemaData> SchemaData for Debezium<T> {
@@ -100,6 +106,10 @@ impl<T: SchemaData> SchemaData for Debezium<T> {
     fn to_raw_string(&self) -> Option<Vec<u8>> {
         unimplemented!("debezium data cannot be written as a raw string");
     }
+
+    fn to_raw_bytes(&self) -> Option<Vec<u8>> {
+        unimplemented!("debezium data cannot be written as raw bytes");
+    }
 }
 
 impl SchemaData for RawJson {
@@ -114,6 +124,10 @@ impl SchemaData for RawJson {
     fn to_raw_string(&self) -> Option<Vec<u8>> {
         Some(self.value.as_bytes().to_vec())
     }
+
+    fn to_raw_bytes(&self) -> Option<Vec<u8>> {
+        unimplemented!("raw json cannot be written as raw bytes");
+    }
 }
 
 impl SchemaData for () {
@@ -128,6 +142,10 @@ impl SchemaData for () {
     fn to_raw_string(&self) -> Option<Vec<u8>> {
         None
     }
+
+    fn to_raw_bytes(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 // A custom deserializer for json, that takes a json::Value and reserializes it as a string
@@ -553,11 +571,17 @@ impl WorkerGrpc for WorkerServer {
 
         if req.is_commit {
             info!("committing");
+            // sources take part in commits too, like the kafka source which commits its offsets
             let senders = {
                 let state = self.state.lock().unwrap();
 
                 if let Some(state) = state.as_ref() {
-                    state.sinks.clone()
+                    state
+                        .sinks
+                        .iter()
+                        .chain(state.sources.iter())
+                        .cloned()
+                        .collect::<Vec<_>>()
                 } else {
                     return Err(Status::failed_precondition(
                         "Worker has not yet started execution",
@@ -565,10 +589,14 @@ impl WorkerGrpc for WorkerServer {
                 }
             };
             for sender in &senders {
-                sender
+                // bounded sources may already have finished
+                if sender
                     .send(ControlMessage::Commit { epoch: req.epoch })
                     .await
-                    .unwrap();
+                    .is_err()
+                {
+                    warn!("not sending commit to a task that has finished");
+                }
             }
             return Ok(Response::new(CheckpointResp {}));
         }
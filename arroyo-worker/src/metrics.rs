@@ -1,6 +1,9 @@
 use crate::engine::OutQueue;
 use arroyo_metrics::{counter_for_task, gauge_for_task};
-use arroyo_types::{TaskInfo, BYTES_RECV, BYTES_SENT, MESSAGES_RECV, MESSAGES_SENT};
+use arroyo_types::{
+    TaskInfo, BYTES_RECV, BYTES_SENT, DESERIALIZATION_ERRORS, DROPPED_MESSAGES, MESSAGES_RECV,
+    MESSAGES_SENT,
+};
 use prometheus::{labels, IntCounter, IntGauge};
 use std::collections::HashMap;
 
@@ -43,6 +46,24 @@ pub fn register_counters(task_info: &TaskInfo) -> HashMap<&'static str, IntCount
         counters.insert(BYTES_SENT, c);
     }
 
+    if let Some(c) = counter_for_task(
+        &task_info,
+        DESERIALIZATION_ERRORS,
+        "Count of messages this subtask failed to deserialize",
+        HashMap::new(),
+    ) {
+        counters.insert(DESERIALIZATION_ERRORS, c);
+    }
+
+    if let Some(c) = counter_for_task(
+        &task_info,
+        DROPPED_MESSAGES,
+        "Count of bad messages this subtask dropped",
+        HashMap::new(),
+    ) {
+        counters.insert(DROPPED_MESSAGES, c);
+    }
+
     counters
 }
 